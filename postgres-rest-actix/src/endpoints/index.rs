@@ -1,9 +1,12 @@
-use actix_web::HttpResponse;
+use actix_web::{HttpRequest, HttpResponse};
 use lazy_static::lazy_static;
 use serde_json::{json, Value};
 
+use super::streaming::{body_response, ResponseFormat};
+use crate::Error;
+
 /// Displays a list of available endpoints and their descriptions.
-pub fn index() -> HttpResponse {
+pub fn index(req: HttpRequest) -> Result<HttpResponse, Error> {
     lazy_static! {
         static ref ENDPOINTS_JSON: Value = json!({
             "endpoints": {
@@ -19,8 +22,8 @@ pub fn index() -> HttpResponse {
                     "query_params": {
                         "columns": {
                             "default": null,
-                            "description": "A comma-separated list of column names for which values are retrieved.",
-                            "example": "col1,col2,col_infinity",
+                            "description": "A comma-separated list of column names for which values are retrieved. Rows of tables referencing {table} are embedded as JSON arrays with `table[col1,col2]` (options like `;where=...`, `;order_by=...`, `;limit=...` and `;offset=...` go inside the brackets).",
+                            "example": "col1,col2,child_table[id,name;limit=5]",
                         },
                         "distinct": {
                             "default": null,
@@ -50,6 +53,27 @@ pub fn index() -> HttpResponse {
                             "default": 0,
                             "description": "The number of rows to exclude.",
                         },
+                        "cursor": {
+                            "default": null,
+                            "description": "Paginates rows with a cursor instead of `offset`: rows are sorted by `order_by` and the primary key, and only the rows after the cursor are returned. Pass an empty cursor to get the first page. If the page is full, the cursor of the next page is sent in the `Next-Cursor` header. Can’t be used with `offset` or `group_by`.",
+                        },
+                        "count": {
+                            "default": null,
+                            "options": [null, "exact", "planned", "estimated"],
+                            "description": "Counts the total number of matching rows (ignoring `limit` and `offset`), and sends it in the `Content-Range` header (`0-9/245`). `exact` uses COUNT(*), `planned` uses the query planner’s estimate, and `estimated` uses the estimated number of rows in the whole table.",
+                        },
+                        "nested": {
+                            "default": null,
+                            "description": "Pass in this parameter to return foreign key columns (`parent_id.name`) as nested objects (`{\"parent_id\": {\"name\": ...}}`). Also works with `returning_columns` in PUT and DELETE.",
+                        },
+                        "join": {
+                            "default": null,
+                            "description": "How the tables of foreign key columns (`parent_id.name`) are joined: `inner` or `left`. By default, nullable foreign key columns use LEFT JOIN (rows without a foreign key value are kept) and the others use INNER JOIN. Also works in PUT and DELETE.",
+                        },
+                        "compact": {
+                            "default": null,
+                            "description": "Pass in this parameter to return the column names once, followed by each row as an array of values (instead of one object per row). Also works with `returning_columns` in POST, PUT and DELETE.",
+                        },
                     }
                 },
                 "POST": {
@@ -120,18 +144,78 @@ pub fn index() -> HttpResponse {
                         },
                     }
                 },
+            },
+            "/{table}/{pk}": {
+                "description": "Addresses the row of {table} whose primary key is {pk}. The values of composite primary keys are separated by commas, in the order of the primary key’s columns (`/order_item/1,2`); commas within a value are percent-encoded (`%2C`). Responds with the row as an object, or with a 404 if no such row exists.",
+                "GET": {
+                    "description": "Retrieves the row. Accepts the same `columns`, `nested` and `join` query parameters as `GET /{table}`; all columns are returned by default.",
+                },
+                "PUT": {
+                    "description": "Updates the row (PATCH does the same). Takes the same body and query parameters as `PUT /{table}`, except for `where`. Returns the updated row; `returning_columns` defaults to all columns.",
+                },
+                "DELETE": {
+                    "description": "Deletes the row (without needing `confirm_delete`). Returns the deleted row; `returning_columns` defaults to all columns.",
+                },
+            },
+            "/$batch": {
+                "POST": {
+                    "description": "Runs a list of operations in order, in a single transaction that is rolled back if any of them fails. Returns an array with the result of each operation: its returned rows, or `{\"num_rows\": ...}`. Values returned by earlier operations are referenced as `${operation.column}` (the first returned row) or `${operation[row].column}`, with zero-based positions. References can be used in `where`, and as the whole value of a row (insert) or column (update).",
+                    "body": {
+                        "description": "An array of operations. Each operation has an `operation` (`insert`, `update`, `delete` or `select`), a `table`, the query parameters of the matching `/{table}` request as `params`, and (for inserts and updates) a `body`. Selects can’t use `count` or `cursor`.",
+                        "example": [
+                            {"operation": "insert", "table": "company", "params": {"returning_columns": "id"}, "body": [{"name": "Stark Industries"}]},
+                            {"operation": "insert", "table": "employee", "body": [{"company_id": "${0.id}", "name": "Tony"}]},
+                            {"operation": "select", "table": "employee", "params": {"columns": "id,name", "where": "company_id = ${0.id}"}},
+                        ],
+                    },
+                },
+            },
+            "/$tx": {
+                "POST": {
+                    "description": "Starts a transaction that spans several requests, and returns its ID (`{\"transaction_id\": ...}`). Requests to `/{table}` and `/{table}/{pk}` that send the ID in a `Transaction-Id` header run in the transaction, one request at a time. Rows returned in a transaction can’t be counted, paginated with a cursor, or sent as Arrow or Parquet. Transactions that aren’t used for 60 seconds (configurable) are rolled back.",
+                    "query_params": {
+                        "isolation_level": {
+                            "default": null,
+                            "options": [null, "read_committed", "repeatable_read", "serializable"],
+                            "description": "The isolation level of the transaction. Uses the database’s default isolation level by default.",
+                        },
+                    },
+                },
+            },
+            "/$tx/{transaction_id}/commit": {
+                "POST": "Commits the transaction. If one of its statements failed (and the transaction wasn’t rolled back to a savepoint since), the transaction is rolled back instead and a 409 is returned.",
+            },
+            "/$tx/{transaction_id}/rollback": {
+                "POST": "Rolls back the transaction.",
+            },
+            "/$tx/{transaction_id}/savepoints/{savepoint}": {
+                "POST": "Creates a savepoint named {savepoint} in the transaction.",
+            },
+            "/$tx/{transaction_id}/savepoints/{savepoint}/release": {
+                "POST": "Releases the savepoint, keeping the changes made after it was created.",
+            },
+            "/$tx/{transaction_id}/savepoints/{savepoint}/rollback": {
+                "POST": "Rolls back the changes made after the savepoint was created. Also recovers the transaction after a failed statement.",
             }},
             "/sql": {
                 "POST": {
                     "description": "Runs a custom SQL query. !!DANGER!! Be very careful with how this endpoint is exposed.",
                     "body": {
-                        "description": "An SQL query as a plain-text string (pass a Content-Type header value of `text/plain`).",
-                        "example": "SELECT * FROM a_table;"
+                        "description": "An SQL query as a plain-text string (pass a Content-Type header value of `text/plain`). Alternatively, a JSON object with the query in `sql` and the values of its parameters (`$1`, `$2`, etc.) in `params`.",
+                        "example": "SELECT * FROM a_table;",
+                        "example_with_params": {
+                            "sql": "SELECT * FROM a_table WHERE id = $1;",
+                            "params": [1]
+                        }
                     },
                     "query_params": {
                         "is_return_rows": {
                             "default": null,
                             "description": "Pass in this parameter in order to return row data. Note that this is also needed for SELECT statements to return rows. This is due to a limitation of the parser library we are using.",
+                        },
+                        "compact": {
+                            "default": null,
+                            "description": "Pass in this parameter to return the column names once, followed by each row as an array of values.",
                         }
                     }
                 },
@@ -139,5 +223,5 @@ pub fn index() -> HttpResponse {
         });
     }
 
-    HttpResponse::Ok().json(&*ENDPOINTS_JSON)
+    body_response(&*ENDPOINTS_JSON, ResponseFormat::from_request(&req))
 }
@@ -3,7 +3,18 @@ pub(crate) use index::index;
 
 mod table;
 pub use table::{
-    delete_table, execute_sql, get_all_table_names, get_table, post_table, put_table, reset_caches,
+    delete_table, delete_table_row, execute_sql, get_all_table_names, get_table, get_table_row,
+    post_batch, post_table, put_table, put_table_row, reset_caches,
+};
+
+mod transaction;
+pub use transaction::{
+    post_savepoint, post_savepoint_release, post_savepoint_rollback, post_transaction,
+    post_transaction_commit, post_transaction_rollback,
 };
 
 mod query_params_from_request;
+
+mod request_body;
+
+mod streaming;
@@ -1,15 +1,27 @@
 use crate::error::Error;
-use actix_web::HttpRequest;
-use postgres_rest::queries::{DeleteParams, InsertParams, SelectParams, UpdateParams};
+use postgres_rest::queries::{
+    BatchOperation, CountMode, DeleteParams, EmbeddedRows, ExecuteParams, InsertParams, JoinType,
+    SelectParams, UpdateParams,
+};
 use rayon::prelude::*;
 use serde::Deserialize;
-use serde_json::{Map, Value};
+use serde_json::{json, Map, Value};
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 /// All possible query string parameters in an API request
 pub struct RequestQueryStringParams {
     /// Comma-separated list of column names for which values are retrieved.
     pub columns: Option<String>,
+    /// If `is_some()`, returned rows are sent as arrays of values, after a single list of column
+    /// names (instead of one object per row).
+    pub compact: Option<String>,
+    /// How the total number of matching rows is counted (`exact`, `planned` or `estimated`), in a
+    /// GET/SELECT statement. The count is sent in the `Content-Range` header.
+    pub count: Option<String>,
+    /// Paginates the rows of a GET/SELECT statement with a cursor instead of `offset`. An empty
+    /// cursor starts from the first row. The cursor of the next page is sent in the `Next-Cursor`
+    /// header.
+    pub cursor: Option<String>,
     /// This param is required in order for DELETE operation to process.
     pub confirm_delete: Option<String>,
     /// The `ON CONFLICT` action to perform (`update` or `nothing`) for POSTing to the table
@@ -21,6 +33,12 @@ pub struct RequestQueryStringParams {
     /// A comma-separated list of column names for which rows that have duplicate values are
     /// excluded (in a GET/SELECT statement).
     pub distinct: Option<String>,
+    /// How the tables of foreign key columns (`parent_id.name`) are joined: `inner` or `left`.
+    /// By default, nullable foreign key columns are LEFT JOINed and the others are INNER JOINed.
+    pub join: Option<String>,
+    /// If `is_some()`, foreign key columns (`parent_id.name`) in returned rows are nested into
+    /// objects (`{"parent_id": {"name": ...}}`) instead of being flat keys.
+    pub nested: Option<String>,
     /// The FROM clause of an UPDATE statement. Comma-separated list of columns. Does not accept
     /// sub-queries (use /sql endpoint if more advanced expressions are needed).
     pub from: Option<String>,
@@ -41,15 +59,110 @@ pub struct RequestQueryStringParams {
     /// Used for the custom execute SQL endpoint. If `is_some()`, returns query result rows.
     /// Otherwise, returns the number of rows affected.
     pub is_return_rows: Option<String>,
+    /// The isolation level of a transaction started with `POST /$tx` (`read_committed`,
+    /// `repeatable_read` or `serializable`).
+    pub isolation_level: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+/// An operation in the body of a `POST /$batch` request.
+struct BatchRequestOperation {
+    /// `insert`, `update`, `delete` or `select`.
+    operation: String,
+    table: String,
+    /// The query string parameters of the matching `/{table}` request.
+    #[serde(default)]
+    params: RequestQueryStringParams,
+    /// The request body of the matching `/{table}` request (for inserts and updates).
+    body: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+/// The body of a `POST /sql` request that isn’t sent as plain text.
+struct ExecuteRequestBody {
+    sql: String,
+    /// The values of the statement’s parameters (`$1`, `$2`, etc.), in order.
+    #[serde(default)]
+    params: Vec<Value>,
+}
+
+/// generates the `BatchOperation`s of a `POST /$batch` request from its body: an array of objects
+/// that each describe an operation as it would be sent to the `/{table}` endpoint.
+pub fn generate_batch_operations_from_http_request(
+    body: Value,
+) -> Result<Vec<BatchOperation>, Error> {
+    let operations: Vec<BatchRequestOperation> = match serde_json::from_value(body) {
+        Ok(operations) => operations,
+        Err(e) => return Err(Error::generate_error(
+            "INCORRECT_REQUEST_BODY",
+            format!("The body needs to be an array of operations, each with an `operation`, a `table`, and optionally `params` and a `body`: {}", e),
+        )),
+    };
+
+    operations
+        .into_iter()
+        .map(|operation| {
+            let table = operation.table.as_str();
+            let params = operation.params;
+
+            match operation.operation.trim().to_lowercase().as_str() {
+                "insert" => Ok(BatchOperation::Insert(generate_insert_params_from_http_request(
+                    table,
+                    operation.body.unwrap_or(Value::Null),
+                    params,
+                )?)),
+                "update" => {
+                    let body = operation.body.unwrap_or_else(|| json!({}));
+                    if body == json!({}) {
+                        return Err(Error::generate_error("INCORRECT_REQUEST_BODY", "The body of an update cannot be empty. It must be a JSON object whose key-values represent column names and the values to set.".to_string()));
+                    }
+
+                    Ok(BatchOperation::Update(generate_update_params_from_http_request(
+                        table, body, params,
+                    )?))
+                }
+                "delete" => {
+                    if params.confirm_delete.is_none() {
+                        return Err(Error::generate_error(
+                            "REQUIRED_PARAMETER_MISSING",
+                            "The `confirm_delete` parameter is necessary for table row deletion."
+                                .to_string(),
+                        ));
+                    }
+
+                    Ok(BatchOperation::Delete(generate_delete_params_from_http_request(
+                        table, params,
+                    )?))
+                }
+                "select" => {
+                    let select_params = generate_select_params_from_http_request(table, params)?;
+                    if select_params.columns.is_empty() && select_params.embedded_rows.is_empty() {
+                        return Err(Error::generate_error(
+                            "REQUIRED_PARAMETER_MISSING",
+                            "The `columns` parameter is necessary for selecting table rows in a batch."
+                                .to_string(),
+                        ));
+                    }
+
+                    Ok(BatchOperation::Select(select_params))
+                }
+                _ => Err(Error::generate_error(
+                    "INCORRECT_REQUEST_BODY",
+                    "Valid options for `operation` are: `insert`, `update`, `delete`, `select`."
+                        .to_string(),
+                )),
+            }
+        })
+        .collect()
 }
 
 /// generates a `DeleteParams` from the HttpRequest data.
 pub fn generate_delete_params_from_http_request(
-    req: &HttpRequest,
+    table: &str,
     query_string_params: RequestQueryStringParams,
 ) -> Result<DeleteParams, Error> {
     let params = DeleteParams {
-        table: req.match_info().query("table").trim().to_lowercase(),
+        table: table.trim().to_lowercase(),
         conditions: match query_string_params.r#where {
             Some(where_string) => Some(where_string.trim().to_lowercase()),
             None => None,
@@ -69,29 +182,62 @@ pub fn generate_delete_params_from_http_request(
             }
             None => None,
         },
+        is_nested: query_string_params.nested.is_some(),
+        join_type: parse_join_type(query_string_params.join)?,
     };
 
     Ok(params)
 }
 
+/// generates an `ExecuteParams` from the body of a `POST /sql` request: an object with the `sql`
+/// statement and optionally the values of its `params`.
+pub fn generate_execute_params_from_http_request(
+    body: Value,
+    query_string_params: &RequestQueryStringParams,
+) -> Result<ExecuteParams, Error> {
+    let execute_body: ExecuteRequestBody = match serde_json::from_value(body) {
+        Ok(execute_body) => execute_body,
+        Err(e) => return Err(Error::generate_error(
+            "INCORRECT_REQUEST_BODY",
+            format!("The body needs to be an object with an `sql` statement, and optionally an array of `params` values: {}", e),
+        )),
+    };
+
+    Ok(ExecuteParams {
+        statement: execute_body.sql,
+        params: execute_body.params,
+        is_return_rows: query_string_params.is_return_rows.is_some(),
+    })
+}
+
 /// generates a `SelectParams` from the HttpRequest data.
 pub fn generate_select_params_from_http_request(
-    req: &HttpRequest,
+    table: &str,
     query_string_params: RequestQueryStringParams,
 ) -> Result<SelectParams, Error> {
     let default_limit = 10000;
     let default_offset = 0;
 
+    // embedded rows (`table[...]`) are requested alongside the other columns
+    let (embedded_rows_columns, columns): (Vec<String>, Vec<String>) =
+        match query_string_params.columns {
+            Some(columns_str) => normalize_columns(&columns_str)?
+                .into_iter()
+                .partition(|column| EmbeddedRows::is_embedded_rows_column(column)),
+            None => (vec![], vec![]),
+        };
+    let embedded_rows = embedded_rows_columns
+        .iter()
+        .map(|column| EmbeddedRows::from_column_str(column).map_err(Error::from))
+        .collect::<Result<Vec<EmbeddedRows>, Error>>()?;
+
     let params = SelectParams {
-        columns: match query_string_params.columns {
-            Some(columns_str) => normalize_columns(&columns_str)?,
-            None => vec![],
-        },
+        columns,
         distinct: match query_string_params.distinct {
             Some(distinct_str) => Some(normalize_columns(&distinct_str)?),
             None => None,
         },
-        table: req.match_info().query("table").trim().to_lowercase(),
+        table: table.trim().to_lowercase(),
         conditions: match query_string_params.r#where {
             Some(where_string) => Some(where_string.trim().to_lowercase()),
             None => None,
@@ -112,6 +258,13 @@ pub fn generate_select_params_from_http_request(
             Some(offset) => offset,
             None => default_offset,
         },
+        is_nested: query_string_params.nested.is_some(),
+        embedded_rows,
+        join_type: parse_join_type(query_string_params.join)?,
+        count: parse_count_mode(query_string_params.count)?,
+        cursor: query_string_params
+            .cursor
+            .map(|cursor| cursor.trim().to_string()),
     };
 
     Ok(params)
@@ -119,7 +272,7 @@ pub fn generate_select_params_from_http_request(
 
 /// generates an `InsertParams` from the HttpRequest data.
 pub fn generate_insert_params_from_http_request(
-    req: &HttpRequest,
+    table: &str,
     body: Value,
     query_string_params: RequestQueryStringParams,
 ) -> Result<InsertParams, Error> {
@@ -209,13 +362,13 @@ pub fn generate_insert_params_from_http_request(
         conflict_target,
         returning_columns,
         rows,
-        table: req.match_info().query("table").trim().to_lowercase(),
+        table: table.trim().to_lowercase(),
     })
 }
 
 /// generates an `UpdateParams` from the HttpRequest data.
 pub fn generate_update_params_from_http_request(
-    req: &HttpRequest,
+    table: &str,
     body: Value,
     query_string_params: RequestQueryStringParams,
 ) -> Result<UpdateParams, Error> {
@@ -240,7 +393,7 @@ pub fn generate_update_params_from_http_request(
         }
         None => None,
     };
-    let table = req.match_info().query("table").trim().to_lowercase();
+    let table = table.trim().to_lowercase();
     let conditions = match query_string_params.r#where {
         Some(where_string) => Some(where_string.trim().to_string()),
         None => None,
@@ -249,16 +402,49 @@ pub fn generate_update_params_from_http_request(
     Ok(UpdateParams {
         column_values,
         conditions,
+        is_nested: query_string_params.nested.is_some(),
+        join_type: parse_join_type(query_string_params.join)?,
         returning_columns,
         table,
     })
 }
 
+/// Converts the `count` query string parameter to a `CountMode`.
+fn parse_count_mode(count: Option<String>) -> Result<Option<CountMode>, Error> {
+    match count {
+        Some(count_str) => match count_str.trim().to_lowercase().as_str() {
+            "exact" => Ok(Some(CountMode::Exact)),
+            "planned" => Ok(Some(CountMode::Planned)),
+            "estimated" => Ok(Some(CountMode::Estimated)),
+            _ => Err(Error::generate_error(
+                "INCORRECT_REQUEST_BODY",
+                "Valid options for `count` are: `exact`, `planned`, `estimated`.".to_string(),
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
+/// Converts the `join` query string parameter to a `JoinType`.
+fn parse_join_type(join: Option<String>) -> Result<Option<JoinType>, Error> {
+    match join {
+        Some(join_str) => match join_str.trim().to_lowercase().as_str() {
+            "inner" => Ok(Some(JoinType::Inner)),
+            "left" => Ok(Some(JoinType::Left)),
+            _ => Err(Error::generate_error(
+                "INCORRECT_REQUEST_BODY",
+                "Valid options for `join` are: `inner`, `left`.".to_string(),
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
 /// Given a string of column names separated by commas, convert and return a vector of lowercase
-/// strings.
+/// strings. Commas inside brackets (like in `child_table[id,name]`) don’t separate columns.
 fn normalize_columns(columns_str: &str) -> Result<Vec<String>, Error> {
-    columns_str
-        .split(',')
+    split_columns(columns_str)
+        .into_iter()
         .map(|s| {
             if s == "" {
                 return Err(Error::generate_error(
@@ -270,3 +456,25 @@ fn normalize_columns(columns_str: &str) -> Result<Vec<String>, Error> {
         })
         .collect()
 }
+
+/// Splits a string on the commas that are not inside brackets.
+fn split_columns(columns_str: &str) -> Vec<&str> {
+    let mut columns = vec![];
+    let mut bracket_depth = 0;
+    let mut column_start = 0;
+
+    for (i, c) in columns_str.char_indices() {
+        match c {
+            '[' => bracket_depth += 1,
+            ']' if bracket_depth > 0 => bracket_depth -= 1,
+            ',' if bracket_depth == 0 => {
+                columns.push(&columns_str[column_start..i]);
+                column_start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    columns.push(&columns_str[column_start..]);
+
+    columns
+}
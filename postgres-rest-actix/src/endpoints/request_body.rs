@@ -0,0 +1,23 @@
+use actix_web::{HttpMessage, HttpRequest};
+use postgres_rest::encoding::{self, BinaryFormat};
+use serde_json::Value;
+
+/// Parses a POST/PUT request body into JSON, based on the request’s `Content-Type`: JSON,
+/// MessagePack (`application/msgpack`, `application/x-msgpack`) or CBOR (`application/cbor`).
+/// Returns `None` if the body is empty, can’t be parsed, or has any other content type.
+pub fn parse_request_body(req: &HttpRequest, body: &[u8]) -> Option<Value> {
+    if body.is_empty() {
+        return None;
+    }
+
+    let content_type = req.content_type().to_lowercase();
+    match content_type.as_str() {
+        "application/msgpack" | "application/x-msgpack" => {
+            encoding::from_slice(body, BinaryFormat::MessagePack).ok()
+        }
+        "application/cbor" => encoding::from_slice(body, BinaryFormat::Cbor).ok(),
+        "application/json" => serde_json::from_slice(body).ok(),
+        content_type if content_type.ends_with("+json") => serde_json::from_slice(body).ok(),
+        _ => None,
+    }
+}
@@ -0,0 +1,495 @@
+#[cfg(feature = "arrow-export")]
+use actix_web::http::header::CONTENT_DISPOSITION;
+use actix_web::{
+    dev::HttpResponseBuilder,
+    http::{header::ACCEPT, StatusCode},
+    web::Bytes,
+    HttpRequest, HttpResponse,
+};
+#[cfg(feature = "arrow-export")]
+use futures::stream::iter_ok;
+use futures::{
+    future::{result, Either, Future},
+    stream::Stream,
+    Async, Poll,
+};
+#[cfg(feature = "arrow-export")]
+use postgres_rest::queries::{write_parquet, ArrowIpcStream};
+use postgres_rest::{
+    encoding::{self, BinaryFormat},
+    queries::{NestedRow, QueryResult, RowValues, RowValuesStream, TypedColumnValue},
+    Error as RestError,
+};
+use serde::Serialize;
+use std::mem;
+#[cfg(feature = "arrow-export")]
+use std::{fs::File, io::Read};
+
+use crate::Error;
+
+/// Rows are buffered until their serialized form reaches this size (or until the database has no
+/// more rows ready), and then sent as one chunk of the response body.
+const CHUNK_SIZE: usize = 16 * 1024;
+
+/// The formats that rows can be sent in, chosen using the request’s `Accept` header.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResponseFormat {
+    /// A JSON array of row objects (the default).
+    Json,
+    /// One JSON row object per line.
+    Ndjson,
+    /// A header row of column names, followed by one line per row.
+    Csv,
+    /// MessagePack row maps, one after another (MessagePack has no arrays of unknown length).
+    MessagePack,
+    /// A CBOR (indefinite-length) array of row maps.
+    Cbor,
+    /// An Arrow IPC stream of record batches.
+    #[cfg(feature = "arrow-export")]
+    Arrow,
+    /// A Parquet file.
+    #[cfg(feature = "arrow-export")]
+    Parquet,
+}
+
+impl ResponseFormat {
+    /// Picks the format with the highest `q` value in the request’s `Accept` header. Defaults to
+    /// JSON if the header is missing or contains no supported formats.
+    pub fn from_request(req: &HttpRequest) -> Self {
+        req.headers()
+            .get(ACCEPT)
+            .and_then(|accept| accept.to_str().ok())
+            .map(Self::from_accept_header)
+            .unwrap_or(ResponseFormat::Json)
+    }
+
+    fn from_accept_header(accept: &str) -> Self {
+        let mut best_format = None;
+        let mut best_quality = 0.0;
+
+        for media_range in accept.split(',') {
+            let mut parts = media_range.split(';');
+            let format = match parts.next().unwrap_or("").trim().to_lowercase().as_str() {
+                "application/json" | "application/*" | "*/*" => ResponseFormat::Json,
+                "application/x-ndjson" | "application/ndjson" => ResponseFormat::Ndjson,
+                "text/csv" => ResponseFormat::Csv,
+                "application/msgpack" | "application/x-msgpack" => ResponseFormat::MessagePack,
+                "application/cbor" => ResponseFormat::Cbor,
+                #[cfg(feature = "arrow-export")]
+                "application/vnd.apache.arrow.stream" => ResponseFormat::Arrow,
+                #[cfg(feature = "arrow-export")]
+                "application/vnd.apache.parquet" => ResponseFormat::Parquet,
+                _ => continue,
+            };
+            let quality = parts
+                .filter_map(|param| {
+                    let mut key_value = param.splitn(2, '=');
+                    match (key_value.next(), key_value.next()) {
+                        (Some(key), Some(value)) if key.trim() == "q" => {
+                            value.trim().parse::<f32>().ok()
+                        }
+                        _ => None,
+                    }
+                })
+                .next()
+                .unwrap_or(1.0);
+
+            // earlier media ranges win ties
+            if quality > best_quality {
+                best_format = Some(format);
+                best_quality = quality;
+            }
+        }
+
+        best_format.unwrap_or(ResponseFormat::Json)
+    }
+
+    /// Whether rows are encoded in batches (Arrow and Parquet), which is only possible for rows
+    /// that are streamed straight from the database.
+    pub fn is_columnar(self) -> bool {
+        match self {
+            #[cfg(feature = "arrow-export")]
+            ResponseFormat::Arrow | ResponseFormat::Parquet => true,
+            _ => false,
+        }
+    }
+
+    /// The binary encoding used by MessagePack and CBOR.
+    pub fn binary_format(self) -> Option<BinaryFormat> {
+        match self {
+            ResponseFormat::MessagePack => Some(BinaryFormat::MessagePack),
+            ResponseFormat::Cbor => Some(BinaryFormat::Cbor),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ResponseFormat::Json => "application/json",
+            ResponseFormat::Ndjson => "application/x-ndjson",
+            ResponseFormat::Csv => "text/csv; charset=utf-8",
+            ResponseFormat::MessagePack => "application/msgpack",
+            ResponseFormat::Cbor => "application/cbor",
+            #[cfg(feature = "arrow-export")]
+            ResponseFormat::Arrow => "application/vnd.apache.arrow.stream",
+            #[cfg(feature = "arrow-export")]
+            ResponseFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+}
+
+/// Streams rows into a chunked response body, using the given format. If `is_compact` is set, the
+/// column names are sent once, followed by each row as an array of values (in JSON, NDJSON,
+/// MessagePack and CBOR).
+///
+/// The response waits for the first row, so errors that happen before any rows are returned (like
+/// an invalid statement) result in a regular error response. Once the response has started, its
+/// status can no longer be changed: an error in the middle of the stream aborts the response
+/// instead, closing the connection without finishing the chunked body. Clients receive an
+/// incomplete response rather than a body that silently ends early.
+pub fn rows_response(
+    rows: RowValuesStream,
+    format: ResponseFormat,
+    is_compact: bool,
+) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    match format {
+        #[cfg(feature = "arrow-export")]
+        ResponseFormat::Arrow => Box::new(arrow_response(rows)),
+        #[cfg(feature = "arrow-export")]
+        ResponseFormat::Parquet => Box::new(parquet_response(rows)),
+        _ => Box::new(text_rows_response(rows, format, is_compact)),
+    }
+}
+
+/// Streams rows as JSON, NDJSON, CSV, MessagePack or CBOR.
+fn text_rows_response(
+    rows: RowValuesStream,
+    format: ResponseFormat,
+    is_compact: bool,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    rows.into_future()
+        .map_err(|(e, _rows)| Error::from(e))
+        .and_then(move |(first_row_opt, rows)| {
+            let mut body = RowsBody {
+                buffer: Vec::with_capacity(CHUNK_SIZE),
+                columns: rows.columns().to_vec(),
+                format,
+                is_compact,
+                is_done: false,
+                is_first_row: true,
+                rows,
+            };
+            body.write_start()?;
+            if let Some(first_row) = first_row_opt {
+                body.write_row(&first_row)?;
+            }
+
+            Ok(HttpResponseBuilder::new(StatusCode::OK)
+                .content_type(format.content_type())
+                .streaming(body))
+        })
+}
+
+/// Streams rows as an Arrow IPC stream, one record batch at a time. Like other formats, the
+/// response waits for the first record batch, so that errors before it are sent as regular error
+/// responses.
+#[cfg(feature = "arrow-export")]
+fn arrow_response(rows: RowValuesStream) -> impl Future<Item = HttpResponse, Error = Error> {
+    result(rows.into_record_batches().and_then(ArrowIpcStream::new))
+        .and_then(|ipc_stream| ipc_stream.into_future().map_err(|(e, _ipc_stream)| e))
+        .map_err(Error::from)
+        .map(|(first_chunk_opt, ipc_stream)| {
+            let body = iter_ok(first_chunk_opt)
+                .chain(ipc_stream)
+                .map(Bytes::from)
+                .map_err(Error::from);
+
+            HttpResponseBuilder::new(StatusCode::OK)
+                .content_type(ResponseFormat::Arrow.content_type())
+                .streaming(body)
+        })
+}
+
+/// Sends rows as a Parquet file download. The file is written to a temporary file first (as
+/// Parquet’s footer comes last), so any errors result in regular error responses.
+#[cfg(feature = "arrow-export")]
+fn parquet_response(rows: RowValuesStream) -> impl Future<Item = HttpResponse, Error = Error> {
+    result(rows.into_record_batches())
+        .and_then(write_parquet)
+        .map_err(Error::from)
+        .map(|file| {
+            HttpResponseBuilder::new(StatusCode::OK)
+                .content_type(ResponseFormat::Parquet.content_type())
+                .header(CONTENT_DISPOSITION, "attachment")
+                .streaming(FileChunks(file))
+        })
+}
+
+/// Reads a file in chunks of `CHUNK_SIZE`.
+#[cfg(feature = "arrow-export")]
+struct FileChunks(File);
+
+#[cfg(feature = "arrow-export")]
+impl Stream for FileChunks {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Bytes>, Error> {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let num_bytes = self
+            .0
+            .read(&mut chunk)
+            .map_err(postgres_rest::Error::from)?;
+
+        if num_bytes == 0 {
+            return Ok(Async::Ready(None));
+        }
+
+        chunk.truncate(num_bytes);
+        Ok(Async::Ready(Some(Bytes::from(chunk))))
+    }
+}
+
+/// Converts a `QueryResult` into a response, streaming rows in the given format if needed. The
+/// number of affected rows is sent as MessagePack or CBOR if requested, and as JSON otherwise.
+pub fn query_result_response(
+    query_result: QueryResult,
+    format: ResponseFormat,
+    is_compact: bool,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    match query_result {
+        QueryResult::QueryTableStream(rows) => Either::A(rows_response(rows, format, is_compact)),
+        query_result => Either::B(result(body_response(&query_result, format))),
+    }
+}
+
+/// Sends the first of the given rows as a single row object (as MessagePack or CBOR if requested,
+/// and as JSON otherwise). Responds with a 404 if there are no rows.
+pub fn row_response(
+    rows: RowValuesStream,
+    format: ResponseFormat,
+    offender: String,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    rows.into_future()
+        .map_err(|(e, _rows)| Error::from(e))
+        .and_then(move |(first_row_opt, rows)| match first_row_opt {
+            Some(first_row) => {
+                let row = match rows.nesting() {
+                    Some(nesting) => SerializedRow::Nested(nesting.nest(&first_row)),
+                    None => SerializedRow::Object(&first_row),
+                };
+                body_response(&row, format)
+            }
+            None => Err(Error::from(RestError::generate_error(
+                "ROW_NOT_FOUND",
+                offender,
+            ))),
+        })
+}
+
+/// Sends a (non-row) response body as MessagePack or CBOR if requested, and as JSON otherwise.
+pub fn body_response<T: Serialize>(
+    value: &T,
+    format: ResponseFormat,
+) -> Result<HttpResponse, Error> {
+    match format.binary_format() {
+        Some(binary_format) => Ok(HttpResponseBuilder::new(StatusCode::OK)
+            .content_type(format.content_type())
+            .body(encoding::to_vec(value, binary_format)?)),
+        None => Ok(HttpResponseBuilder::new(StatusCode::OK).json(value)),
+    }
+}
+
+/// Serializes a stream of rows into the chunks of a response body.
+struct RowsBody {
+    /// Serialized rows that haven’t been sent yet.
+    buffer: Vec<u8>,
+    /// The column names, in the order they are written in CSV (and compact rows).
+    columns: Vec<String>,
+    format: ResponseFormat,
+    /// Whether rows are written as arrays of values, after a single list of column names.
+    is_compact: bool,
+    is_done: bool,
+    /// Whether the next row is the first one (and isn’t preceded by a comma in JSON).
+    is_first_row: bool,
+    rows: RowValuesStream,
+}
+
+impl RowsBody {
+    fn write_start(&mut self) -> Result<(), Error> {
+        match self.format {
+            ResponseFormat::Json if self.is_compact => {
+                self.buffer.extend_from_slice(br#"{"columns":"#);
+                serde_json::to_writer(&mut self.buffer, &self.columns)?;
+                self.buffer.extend_from_slice(br#","rows":["#);
+            }
+            ResponseFormat::Json => self.buffer.push(b'['),
+            ResponseFormat::Ndjson if self.is_compact => {
+                serde_json::to_writer(&mut self.buffer, &self.columns)?;
+                self.buffer.push(b'\n');
+            }
+            ResponseFormat::MessagePack if self.is_compact => {
+                encoding::to_writer(&mut self.buffer, &self.columns, BinaryFormat::MessagePack)?
+            }
+            ResponseFormat::Ndjson | ResponseFormat::MessagePack => {}
+            ResponseFormat::Cbor if self.is_compact => {
+                // a map of 2 entries: the column names, and the rows
+                self.buffer.push(0xa2);
+                encoding::to_writer(&mut self.buffer, &"columns", BinaryFormat::Cbor)?;
+                encoding::to_writer(&mut self.buffer, &self.columns, BinaryFormat::Cbor)?;
+                encoding::to_writer(&mut self.buffer, &"rows", BinaryFormat::Cbor)?;
+                self.buffer.push(0x9f);
+            }
+            // start of an indefinite-length array
+            ResponseFormat::Cbor => self.buffer.push(0x9f),
+            ResponseFormat::Csv => {
+                for (i, column) in self.columns.iter().enumerate() {
+                    if i > 0 {
+                        self.buffer.push(b',');
+                    }
+                    write_csv_field(&mut self.buffer, Some(column));
+                }
+                self.buffer.extend_from_slice(b"\r\n");
+            }
+            #[cfg(feature = "arrow-export")]
+            ResponseFormat::Arrow | ResponseFormat::Parquet => {
+                unreachable!("Arrow and Parquet aren’t written row by row.")
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_row(&mut self, row_values: &RowValues) -> Result<(), Error> {
+        let row = if self.is_compact {
+            SerializedRow::Values(
+                self.columns
+                    .iter()
+                    .map(|column| row_values.get(column))
+                    .collect(),
+            )
+        } else if let Some(nesting) = self.rows.nesting() {
+            SerializedRow::Nested(nesting.nest(row_values))
+        } else {
+            SerializedRow::Object(row_values)
+        };
+
+        match self.format {
+            ResponseFormat::Json => {
+                if !self.is_first_row {
+                    self.buffer.push(b',');
+                }
+                serde_json::to_writer(&mut self.buffer, &row)?;
+            }
+            ResponseFormat::Ndjson => {
+                serde_json::to_writer(&mut self.buffer, &row)?;
+                self.buffer.push(b'\n');
+            }
+            ResponseFormat::Csv => {
+                for (i, column) in self.columns.iter().enumerate() {
+                    if i > 0 {
+                        self.buffer.push(b',');
+                    }
+                    let text = match row_values.get(column) {
+                        Some(value) => value.to_text()?,
+                        None => None,
+                    };
+                    write_csv_field(&mut self.buffer, text.as_ref().map(String::as_str));
+                }
+                self.buffer.extend_from_slice(b"\r\n");
+            }
+            ResponseFormat::MessagePack => {
+                encoding::to_writer(&mut self.buffer, &row, BinaryFormat::MessagePack)?
+            }
+            ResponseFormat::Cbor => {
+                encoding::to_writer(&mut self.buffer, &row, BinaryFormat::Cbor)?
+            }
+            #[cfg(feature = "arrow-export")]
+            ResponseFormat::Arrow | ResponseFormat::Parquet => {
+                unreachable!("Arrow and Parquet aren’t written row by row.")
+            }
+        }
+
+        self.is_first_row = false;
+        Ok(())
+    }
+
+    fn write_end(&mut self) {
+        match self.format {
+            ResponseFormat::Json if self.is_compact => self.buffer.extend_from_slice(b"]}"),
+            ResponseFormat::Json => self.buffer.push(b']'),
+            // "break", ending the indefinite-length array (of rows)
+            ResponseFormat::Cbor => self.buffer.push(0xff),
+            _ => {}
+        }
+    }
+
+    fn take_chunk(&mut self) -> Bytes {
+        Bytes::from(mem::replace(
+            &mut self.buffer,
+            Vec::with_capacity(CHUNK_SIZE),
+        ))
+    }
+}
+
+impl Stream for RowsBody {
+    type Item = Bytes;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Bytes>, Error> {
+        if self.is_done {
+            return Ok(Async::Ready(None));
+        }
+
+        loop {
+            match self.rows.poll()? {
+                Async::Ready(Some(row)) => {
+                    self.write_row(&row)?;
+
+                    if self.buffer.len() >= CHUNK_SIZE {
+                        return Ok(Async::Ready(Some(self.take_chunk())));
+                    }
+                }
+                Async::Ready(None) => {
+                    self.write_end();
+                    self.is_done = true;
+                    return Ok(Async::Ready(Some(self.take_chunk())));
+                }
+                Async::NotReady => {
+                    // send what we have while waiting for more rows
+                    if self.buffer.is_empty() {
+                        return Ok(Async::NotReady);
+                    }
+                    return Ok(Async::Ready(Some(self.take_chunk())));
+                }
+            }
+        }
+    }
+}
+
+/// A row as it’s serialized: an object of column names and values (with foreign key columns
+/// optionally nested into objects), or (in the compact layout) an array of values in column order.
+#[derive(Serialize)]
+#[serde(untagged)]
+enum SerializedRow<'a> {
+    Object(&'a RowValues),
+    Nested(NestedRow<'a>),
+    Values(Vec<Option<&'a TypedColumnValue>>),
+}
+
+/// Writes a CSV field, quoting it if needed (RFC 4180). NULL is written as an empty field, and an
+/// empty string as `""`, so that the two can be told apart.
+fn write_csv_field(buffer: &mut Vec<u8>, field: Option<&str>) {
+    let field = match field {
+        Some(field) => field,
+        None => return,
+    };
+
+    if field.is_empty() || field.contains(|c| c == ',' || c == '"' || c == '\r' || c == '\n') {
+        buffer.push(b'"');
+        buffer.extend_from_slice(field.replace('"', "\"\"").as_bytes());
+        buffer.push(b'"');
+    } else {
+        buffer.extend_from_slice(field.as_bytes());
+    }
+}
@@ -1,26 +1,36 @@
 use actix_web::{
     dev::HttpResponseBuilder,
-    http::StatusCode,
-    web::{self, Json},
+    http::{
+        header::{HeaderName, CONTENT_RANGE},
+        HeaderValue, StatusCode,
+    },
+    web::{self, Bytes},
     HttpMessage, HttpRequest, HttpResponse,
 };
 use futures::{
     future::{err, Either},
     Future,
 };
-use serde_json::{json, Value};
+use serde_json::json;
 use tokio_postgres::{
     tls::{MakeTlsConnect, TlsConnect},
     Socket,
 };
 
-use super::query_params_from_request::{
-    generate_delete_params_from_http_request, generate_insert_params_from_http_request,
-    generate_select_params_from_http_request, generate_update_params_from_http_request,
-    RequestQueryStringParams,
+use super::{
+    query_params_from_request::{
+        generate_batch_operations_from_http_request, generate_delete_params_from_http_request,
+        generate_execute_params_from_http_request, generate_insert_params_from_http_request,
+        generate_select_params_from_http_request, generate_update_params_from_http_request,
+        RequestQueryStringParams,
+    },
+    request_body::parse_request_body,
+    streaming::{
+        body_response, query_result_response, row_response, rows_response, ResponseFormat,
+    },
 };
 use crate::{Config, Error};
-use postgres_rest::queries;
+use postgres_rest::{queries, Error as RestError};
 
 /// Deletes table rows and optionally returns the column data in the deleted rows.
 pub fn delete_table<T>(
@@ -34,11 +44,18 @@ where
     <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
     T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
 {
-    let params =
-        match generate_delete_params_from_http_request(&req, query_string_params.into_inner()) {
-            Ok(params) => params,
-            Err(e) => return Either::A(err(e)),
-        };
+    let is_compact = query_string_params.compact.is_some();
+    let mut params = match generate_delete_params_from_http_request(
+        req.match_info().query("table"),
+        query_string_params.into_inner(),
+    ) {
+        Ok(params) => params,
+        Err(e) => return Either::A(err(e)),
+    };
+    params.table = match config.resolve_table_name(&params.table) {
+        Ok(table) => table,
+        Err(e) => return Either::A(err(e)),
+    };
 
     if params.confirm_delete.is_none() {
         return Either::A(err(Error::generate_error(
@@ -47,17 +64,28 @@ where
         )));
     }
 
+    let format = ResponseFormat::from_request(&req);
+    if let Some(transaction_id) = transaction_id(&req) {
+        let operation = queries::BatchOperation::Delete(params);
+        return Either::B(Either::A(transaction_response(
+            config,
+            transaction_id,
+            operation,
+            format,
+            is_compact,
+        )));
+    }
+
     let delete_table_future = queries::delete_table_rows(config.get_ref().inner.clone(), params)
         .map_err(Error::from)
-        .and_then(|rows| Ok(HttpResponseBuilder::new(StatusCode::OK).json(rows)));
+        .and_then(move |result| query_result_response(result, format, is_compact));
 
-    Either::B(delete_table_future)
+    Either::B(Either::B(delete_table_future))
 }
 
-/// Executes the given SQL statement
-pub fn execute_sql<T>(
+/// Deletes the table row with the given primary key, and returns the deleted row.
+pub fn delete_table_row<T>(
     req: HttpRequest,
-    body: String,
     config: web::Data<Config<T>>,
     query_string_params: web::Query<RequestQueryStringParams>,
 ) -> impl Future<Item = HttpResponse, Error = Error>
@@ -67,30 +95,119 @@ where
     <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
     T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
 {
-    let content_type = req.content_type().to_lowercase();
-    if &content_type != "text/plain" {
-        return Either::A(err(Error::generate_error(
-            "INVALID_CONTENT_TYPE",
-            format!("Content type sent was: `{}`.", content_type),
-        )));
+    let mut params = match generate_delete_params_from_http_request(
+        req.match_info().query("table"),
+        query_string_params.into_inner(),
+    ) {
+        Ok(params) => params,
+        Err(e) => return Either::A(err(e)),
+    };
+    params.table = match config.resolve_table_name(&params.table) {
+        Ok(table) => table,
+        Err(e) => return Either::A(err(e)),
+    };
+
+    let format = ResponseFormat::from_request(&req);
+    if format.is_columnar() {
+        return Either::A(err(Error::from(RestError::generate_error(
+            "UNSUPPORTED_RESPONSE_FORMAT",
+            format.content_type().to_string(),
+        ))));
     }
 
-    let params = queries::ExecuteParams {
-        statement: body,
-        is_return_rows: query_string_params.is_return_rows.is_some(),
+    let transaction_id_opt = transaction_id(&req);
+    let pk = primary_key_str(&req);
+    let offender = [params.table.as_str(), "/", &pk].join("");
+    let response =
+        queries::primary_key_conditions(&config.get_ref().inner, params.table.clone(), pk)
+            .map_err(Error::from)
+            .and_then(move |pk_conditions| {
+                // deleting by primary key affects at most one row, so no confirmation is needed
+                params.conditions = Some(pk_conditions.conditions);
+                params.confirm_delete = Some("true".to_string());
+                if params.returning_columns.is_none() {
+                    params.returning_columns = Some(pk_conditions.columns);
+                }
+
+                let result_future = match transaction_id_opt {
+                    Some(transaction_id) => Either::A(queries::execute_in_transaction(
+                        config.get_ref().inner.clone(),
+                        &transaction_id,
+                        queries::BatchOperation::Delete(params),
+                    )),
+                    None => Either::B(queries::delete_table_rows(
+                        config.get_ref().inner.clone(),
+                        params,
+                    )),
+                };
+
+                result_future.map_err(Error::from)
+            })
+            .and_then(move |result| query_result_row_response(result, format, offender));
+
+    Either::B(response)
+}
+
+/// Executes the given SQL statement. The statement is sent either as plain text, or as an object
+/// with the `sql` statement and the values of its `params` (`$1`, `$2`, etc.).
+pub fn execute_sql<T>(
+    req: HttpRequest,
+    body: Bytes,
+    config: web::Data<Config<T>>,
+    query_string_params: web::Query<RequestQueryStringParams>,
+) -> impl Future<Item = HttpResponse, Error = Error>
+where
+    <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
+    <T as MakeTlsConnect<Socket>>::Stream: Send,
+    <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    let content_type = req.content_type().to_lowercase();
+    let params = if &content_type == "text/plain" {
+        match String::from_utf8(body.to_vec()) {
+            Ok(statement) => queries::ExecuteParams {
+                statement,
+                params: vec![],
+                is_return_rows: query_string_params.is_return_rows.is_some(),
+            },
+            Err(e) => {
+                return Either::A(err(Error::generate_error(
+                    "INCORRECT_REQUEST_BODY",
+                    format!("The SQL statement must be UTF-8 text: {}", e),
+                )))
+            }
+        }
+    } else {
+        match parse_request_body(&req, &body) {
+            Some(actual_body) => {
+                match generate_execute_params_from_http_request(actual_body, &query_string_params)
+                {
+                    Ok(params) => params,
+                    Err(e) => return Either::A(err(e)),
+                }
+            }
+            None if body.is_empty() => return Either::A(err(Error::generate_error("INCORRECT_REQUEST_BODY", "Request body is required. Body must be an SQL statement (as `text/plain`), or a JSON (or MessagePack/CBOR) object with an `sql` statement and its `params`.".to_string()))),
+            None => {
+                return Either::A(err(Error::generate_error(
+                    "INVALID_CONTENT_TYPE",
+                    format!("Content type sent was: `{}`.", content_type),
+                )))
+            }
+        }
     };
+    let is_compact = query_string_params.compact.is_some();
 
-    let execute_sql_future = config
-        .connect()
+    let format = ResponseFormat::from_request(&req);
+    let execute_sql_future = queries::execute_sql_query(config.get_ref().inner.clone(), params)
         .map_err(Error::from)
-        .and_then(|client| queries::execute_sql_query(client, params).map_err(Error::from))
-        .and_then(|rows| Ok(HttpResponseBuilder::new(StatusCode::OK).json(rows)));
+        .and_then(move |result| query_result_response(result, format, is_compact));
 
     Either::B(execute_sql_future)
 }
 
 /// Retrieves a list of table names that exist in the DB.
 pub fn get_all_table_names<T>(
+    req: HttpRequest,
     config: web::Data<Config<T>>,
 ) -> impl Future<Item = HttpResponse, Error = Error>
 where
@@ -99,11 +216,14 @@ where
     <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
     T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
 {
+    let format = ResponseFormat::from_request(&req);
     config
         .connect()
         .map_err(Error::from)
-        .and_then(|client| queries::select_all_tables(client).map_err(Error::from))
-        .and_then(|(rows, _client)| Ok(HttpResponseBuilder::new(StatusCode::OK).json(rows)))
+        .and_then(move |client| {
+            queries::select_all_tables(client, &config.get_ref().inner.schemas).map_err(Error::from)
+        })
+        .and_then(move |(rows, _client)| body_response(&rows, format))
 }
 
 /// Queries a table using SELECT.
@@ -118,22 +238,126 @@ where
     <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
     T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
 {
-    let params =
-        match generate_select_params_from_http_request(&req, query_string_params.into_inner()) {
-            Ok(params) => params,
+    let is_compact = query_string_params.compact.is_some();
+    let mut params = match generate_select_params_from_http_request(
+        req.match_info().query("table"),
+        query_string_params.into_inner(),
+    ) {
+        Ok(params) => params,
+        Err(e) => return Either::A(err(e)),
+    };
+    params.table = match config.resolve_table_name(&params.table) {
+        Ok(table) => table,
+        Err(e) => return Either::A(err(e)),
+    };
+    for embedded_rows in params.embedded_rows.iter_mut() {
+        embedded_rows.table = match config.resolve_table_name(&embedded_rows.table) {
+            Ok(table) => table,
             Err(e) => return Either::A(err(e)),
         };
+    }
 
-    if params.columns.is_empty() {
-        Either::B(Either::A(get_table_stats(config, params.table)))
+    let format = ResponseFormat::from_request(&req);
+    if params.columns.is_empty() && params.embedded_rows.is_empty() {
+        Either::B(Either::A(get_table_stats(config, params.table, format)))
+    } else if let Some(transaction_id) = transaction_id(&req) {
+        let operation = queries::BatchOperation::Select(params);
+        Either::B(Either::B(Either::A(transaction_response(
+            config,
+            transaction_id,
+            operation,
+            format,
+            is_compact,
+        ))))
     } else {
-        Either::B(Either::B(get_table_rows(config, params)))
+        Either::B(Either::B(Either::B(get_table_rows(
+            config, params, format, is_compact,
+        ))))
     }
 }
 
+/// Retrieves the table row with the given primary key.
+pub fn get_table_row<T>(
+    req: HttpRequest,
+    config: web::Data<Config<T>>,
+    query_string_params: web::Query<RequestQueryStringParams>,
+) -> impl Future<Item = HttpResponse, Error = Error>
+where
+    <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
+    <T as MakeTlsConnect<Socket>>::Stream: Send,
+    <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    let mut params = match generate_select_params_from_http_request(
+        req.match_info().query("table"),
+        query_string_params.into_inner(),
+    ) {
+        Ok(params) => params,
+        Err(e) => return Either::A(err(e)),
+    };
+    params.table = match config.resolve_table_name(&params.table) {
+        Ok(table) => table,
+        Err(e) => return Either::A(err(e)),
+    };
+    for embedded_rows in params.embedded_rows.iter_mut() {
+        embedded_rows.table = match config.resolve_table_name(&embedded_rows.table) {
+            Ok(table) => table,
+            Err(e) => return Either::A(err(e)),
+        };
+    }
+
+    let format = ResponseFormat::from_request(&req);
+    if format.is_columnar() {
+        return Either::A(err(Error::from(RestError::generate_error(
+            "UNSUPPORTED_RESPONSE_FORMAT",
+            format.content_type().to_string(),
+        ))));
+    }
+
+    let transaction_id_opt = transaction_id(&req);
+    let pk = primary_key_str(&req);
+    let offender = [params.table.as_str(), "/", &pk].join("");
+    let response =
+        queries::primary_key_conditions(&config.get_ref().inner, params.table.clone(), pk)
+            .map_err(Error::from)
+            .and_then(move |pk_conditions| {
+                params.conditions = Some(pk_conditions.conditions);
+                if params.columns.is_empty() {
+                    params.columns = pk_conditions.columns;
+                }
+                params.limit = 1;
+                params.offset = 0;
+                params.count = None;
+                params.cursor = None;
+
+                match transaction_id_opt {
+                    Some(transaction_id) => Either::A(
+                        queries::execute_in_transaction(
+                            config.get_ref().inner.clone(),
+                            &transaction_id,
+                            queries::BatchOperation::Select(params),
+                        )
+                        .map_err(Error::from)
+                        .and_then(move |result| {
+                            query_result_row_response(result, format, offender)
+                        }),
+                    ),
+                    None => Either::B(
+                        queries::select_table_rows(config.get_ref().inner.clone(), params)
+                            .map_err(Error::from)
+                            .and_then(move |rows| row_response(rows, format, offender)),
+                    ),
+                }
+            });
+
+    Either::B(response)
+}
+
 fn get_table_rows<T>(
     config: web::Data<Config<T>>,
     params: queries::SelectParams,
+    format: ResponseFormat,
+    is_compact: bool,
 ) -> impl Future<Item = HttpResponse, Error = Error>
 where
     <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
@@ -141,14 +365,55 @@ where
     <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
     T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
 {
+    let (limit, offset) = (params.limit, params.offset);
     queries::select_table_rows(config.get_ref().inner.clone(), params)
         .map_err(Error::from)
-        .and_then(|rows| Ok(HttpResponseBuilder::new(StatusCode::OK).json(rows)))
+        .and_then(move |rows| {
+            let content_range_opt = rows
+                .total_count()
+                .map(|total_count| content_range(limit, offset, total_count));
+            let next_cursor_opt = rows.next_cursor().map(str::to_string);
+
+            rows_response(rows, format, is_compact).map(move |mut response| {
+                if let Some(content_range) = content_range_opt {
+                    if let Ok(header_value) = HeaderValue::from_str(&content_range) {
+                        response.headers_mut().insert(CONTENT_RANGE, header_value);
+                    }
+                }
+                if let Some(next_cursor) = next_cursor_opt {
+                    if let Ok(header_value) = HeaderValue::from_str(&next_cursor) {
+                        response
+                            .headers_mut()
+                            .insert(HeaderName::from_static(NEXT_CURSOR), header_value);
+                    }
+                }
+                response
+            })
+        })
+}
+
+/// The header that the cursor of the next page of rows is sent in, when rows are paginated with a
+/// cursor.
+static NEXT_CURSOR: &str = "next-cursor";
+
+/// Generates the `Content-Range` header value for the returned rows: `{first}-{last}/{total}`
+/// (zero-based and inclusive), or `*/{total}` if no rows are returned. The range is based on the
+/// total count, so it’s only accurate for exact counts.
+fn content_range(limit: usize, offset: usize, total_count: i64) -> String {
+    let total_count_usize = total_count.max(0) as usize;
+    let num_rows = limit.min(total_count_usize.saturating_sub(offset));
+
+    if num_rows == 0 {
+        format!("*/{}", total_count)
+    } else {
+        format!("{}-{}/{}", offset, offset + num_rows - 1, total_count)
+    }
 }
 
 fn get_table_stats<T>(
     config: web::Data<Config<T>>,
     table: String,
+    format: ResponseFormat,
 ) -> impl Future<Item = HttpResponse, Error = Error>
 where
     // <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
@@ -158,14 +423,72 @@ where
 {
     queries::select_table_stats(&config.get_ref().inner, table)
         .map_err(Error::from)
-        .and_then(|rows| Ok(HttpResponseBuilder::new(StatusCode::OK).json(rows)))
+        .and_then(move |stats| body_response(&stats, format))
+}
+
+/// Runs a batch of insert, update, delete and select operations in a single transaction. Returns
+/// the result of each operation.
+pub fn post_batch<T>(
+    req: HttpRequest,
+    config: web::Data<Config<T>>,
+    body: Bytes,
+) -> impl Future<Item = HttpResponse, Error = Error>
+where
+    <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
+    <T as MakeTlsConnect<Socket>>::Stream: Send,
+    <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    let actual_body = match parse_request_body(&req, &body) {
+        Some(body) => body,
+        None => return Either::A(err(Error::generate_error("INCORRECT_REQUEST_BODY", "Request body is required. Body must be a JSON (or MessagePack/CBOR) array of operations.".to_string())))
+    };
+    let mut operations = match generate_batch_operations_from_http_request(actual_body) {
+        Ok(operations) => operations,
+        Err(e) => return Either::A(err(e)),
+    };
+    for operation in operations.iter_mut() {
+        let table = match operation {
+            queries::BatchOperation::Insert(params) => &mut params.table,
+            queries::BatchOperation::Update(params) => &mut params.table,
+            queries::BatchOperation::Delete(params) => &mut params.table,
+            queries::BatchOperation::Select(params) => {
+                for embedded_rows in params.embedded_rows.iter_mut() {
+                    embedded_rows.table = match config.resolve_table_name(&embedded_rows.table) {
+                        Ok(table) => table,
+                        Err(e) => return Either::A(err(e)),
+                    };
+                }
+                &mut params.table
+            }
+        };
+        *table = match config.resolve_table_name(table) {
+            Ok(table) => table,
+            Err(e) => return Either::A(err(e)),
+        };
+    }
+
+    // rows are returned all at once, along with the results of the other operations
+    let format = ResponseFormat::from_request(&req);
+    if format.is_columnar() {
+        return Either::A(err(Error::from(RestError::generate_error(
+            "UNSUPPORTED_RESPONSE_FORMAT",
+            format.content_type().to_string(),
+        ))));
+    }
+
+    let response = queries::execute_batch(config.get_ref().inner.clone(), operations)
+        .map_err(Error::from)
+        .and_then(move |results| body_response(&results, format));
+
+    Either::B(response)
 }
 
 /// Inserts new rows into a table. Returns the number of rows affected.
 pub fn post_table<T>(
     req: HttpRequest,
     config: web::Data<Config<T>>,
-    body: Option<Json<Value>>,
+    body: Bytes,
     query_string_params: web::Query<RequestQueryStringParams>,
 ) -> impl Future<Item = HttpResponse, Error = Error>
 where
@@ -174,12 +497,13 @@ where
     <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
     T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
 {
-    let actual_body = match body {
-        Some(body) => body.into_inner(),
-        None => return Either::A(err(Error::generate_error("INCORRECT_REQUEST_BODY", "Request body is required. Body must be a JSON array of objects where each object represents a row and whose key-values represent column names and their values.".to_string())))
+    let actual_body = match parse_request_body(&req, &body) {
+        Some(body) => body,
+        None => return Either::A(err(Error::generate_error("INCORRECT_REQUEST_BODY", "Request body is required. Body must be a JSON (or MessagePack/CBOR) array of objects where each object represents a row and whose key-values represent column names and their values.".to_string())))
     };
-    let params = match generate_insert_params_from_http_request(
-        &req,
+    let is_compact = query_string_params.compact.is_some();
+    let mut params = match generate_insert_params_from_http_request(
+        req.match_info().query("table"),
         actual_body,
         query_string_params.into_inner(),
     ) {
@@ -188,23 +512,45 @@ where
             return Either::A(err(e));
         }
     };
+    params.table = match config.resolve_table_name(&params.table) {
+        Ok(table) => table,
+        Err(e) => return Either::A(err(e)),
+    };
+
+    // INSERTed rows are returned all at once instead of being streamed from the database
+    let format = ResponseFormat::from_request(&req);
+    if params.returning_columns.is_some() && format.is_columnar() {
+        return Either::A(err(Error::from(RestError::generate_error(
+            "UNSUPPORTED_RESPONSE_FORMAT",
+            format.content_type().to_string(),
+        ))));
+    }
+
+    if let Some(transaction_id) = transaction_id(&req) {
+        let operation = queries::BatchOperation::Insert(params);
+        return Either::B(Either::A(transaction_response(
+            config,
+            transaction_id,
+            operation,
+            format,
+            is_compact,
+        )));
+    }
 
     let insert_response = config
         .connect()
         .map_err(Error::from)
         .and_then(|client| queries::insert_into_table(client, params).map_err(Error::from))
-        .and_then(|num_rows_affected| {
-            Ok(HttpResponseBuilder::new(StatusCode::OK).json(num_rows_affected))
-        });
+        .and_then(move |result| query_result_response(result, format, is_compact));
 
-    Either::B(insert_response)
+    Either::B(Either::B(insert_response))
 }
 
 /// Runs an UPDATE query and returns either rows affected or row columns if specified.
 pub fn put_table<T>(
     req: HttpRequest,
     config: web::Data<Config<T>>,
-    body: Option<Json<Value>>,
+    body: Bytes,
     query_string_params: web::Query<RequestQueryStringParams>,
 ) -> impl Future<Item = HttpResponse, Error = Error>
 where
@@ -213,17 +559,18 @@ where
     <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
     T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
 {
-    let actual_body = match body {
-        Some(body) => body.into_inner(),
-        None => return Either::A(err(Error::generate_error("INCORRECT_REQUEST_BODY", "Request body is required. Body must be a JSON object whose key-values represent column names and the values to set. String values must contain quotes or else they will be evaluated as expressions and not strings.".to_string())))
+    let actual_body = match parse_request_body(&req, &body) {
+        Some(body) => body,
+        None => return Either::A(err(Error::generate_error("INCORRECT_REQUEST_BODY", "Request body is required. Body must be a JSON (or MessagePack/CBOR) object whose key-values represent column names and the values to set. String values must contain quotes or else they will be evaluated as expressions and not strings.".to_string())))
     };
 
     if actual_body == json!({}) {
         return Either::A(err(Error::generate_error("INCORRECT_REQUEST_BODY", "Request body cannot be empty. Body must be a JSON object whose key-values represent column names and the values to set. String values must contain quotes or else they will be evaluated as expressions and not strings.".to_string())));
     }
 
-    let params = match generate_update_params_from_http_request(
-        &req,
+    let is_compact = query_string_params.compact.is_some();
+    let mut params = match generate_update_params_from_http_request(
+        req.match_info().query("table"),
         actual_body,
         query_string_params.into_inner(),
     ) {
@@ -232,12 +579,170 @@ where
             return Either::A(err(e));
         }
     };
+    params.table = match config.resolve_table_name(&params.table) {
+        Ok(table) => table,
+        Err(e) => return Either::A(err(e)),
+    };
+
+    let format = ResponseFormat::from_request(&req);
+    if let Some(transaction_id) = transaction_id(&req) {
+        let operation = queries::BatchOperation::Update(params);
+        return Either::B(Either::A(transaction_response(
+            config,
+            transaction_id,
+            operation,
+            format,
+            is_compact,
+        )));
+    }
 
     let response = queries::update_table_rows(&config.get_ref().inner, params)
         .map_err(Error::from)
-        .and_then(|num_rows_affected| {
-            Ok(HttpResponseBuilder::new(StatusCode::OK).json(num_rows_affected))
-        });
+        .and_then(move |result| query_result_response(result, format, is_compact));
+
+    Either::B(Either::B(response))
+}
+
+/// Updates the table row with the given primary key, and returns the updated row. Used for both
+/// PUT and PATCH: only the columns in the request body are set.
+pub fn put_table_row<T>(
+    req: HttpRequest,
+    config: web::Data<Config<T>>,
+    body: Bytes,
+    query_string_params: web::Query<RequestQueryStringParams>,
+) -> impl Future<Item = HttpResponse, Error = Error>
+where
+    <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
+    <T as MakeTlsConnect<Socket>>::Stream: Send,
+    <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    let actual_body = match parse_request_body(&req, &body) {
+        Some(body) => body,
+        None => return Either::A(err(Error::generate_error("INCORRECT_REQUEST_BODY", "Request body is required. Body must be a JSON (or MessagePack/CBOR) object whose key-values represent column names and the values to set. String values must contain quotes or else they will be evaluated as expressions and not strings.".to_string())))
+    };
+
+    if actual_body == json!({}) {
+        return Either::A(err(Error::generate_error("INCORRECT_REQUEST_BODY", "Request body cannot be empty. Body must be a JSON object whose key-values represent column names and the values to set. String values must contain quotes or else they will be evaluated as expressions and not strings.".to_string())));
+    }
+
+    let mut params = match generate_update_params_from_http_request(
+        req.match_info().query("table"),
+        actual_body,
+        query_string_params.into_inner(),
+    ) {
+        Ok(params) => params,
+        Err(e) => {
+            return Either::A(err(e));
+        }
+    };
+    params.table = match config.resolve_table_name(&params.table) {
+        Ok(table) => table,
+        Err(e) => return Either::A(err(e)),
+    };
+
+    let format = ResponseFormat::from_request(&req);
+    if format.is_columnar() {
+        return Either::A(err(Error::from(RestError::generate_error(
+            "UNSUPPORTED_RESPONSE_FORMAT",
+            format.content_type().to_string(),
+        ))));
+    }
+
+    let transaction_id_opt = transaction_id(&req);
+    let pk = primary_key_str(&req);
+    let offender = [params.table.as_str(), "/", &pk].join("");
+    let response =
+        queries::primary_key_conditions(&config.get_ref().inner, params.table.clone(), pk)
+            .map_err(Error::from)
+            .and_then(move |pk_conditions| {
+                params.conditions = Some(pk_conditions.conditions);
+                if params.returning_columns.is_none() {
+                    params.returning_columns = Some(pk_conditions.columns);
+                }
+
+                let result_future = match transaction_id_opt {
+                    Some(transaction_id) => Either::A(queries::execute_in_transaction(
+                        config.get_ref().inner.clone(),
+                        &transaction_id,
+                        queries::BatchOperation::Update(params),
+                    )),
+                    None => Either::B(queries::update_table_rows(&config.get_ref().inner, params)),
+                };
+
+                result_future.map_err(Error::from)
+            })
+            .and_then(move |result| query_result_row_response(result, format, offender));
+
+    Either::B(response)
+}
+
+/// Sends the row returned by an UPDATE or DELETE of a single row.
+fn query_result_row_response(
+    query_result: queries::QueryResult,
+    format: ResponseFormat,
+    offender: String,
+) -> impl Future<Item = HttpResponse, Error = Error> {
+    match query_result {
+        queries::QueryResult::QueryTableStream(rows) => {
+            Either::A(row_response(rows, format, offender))
+        }
+        queries::QueryResult::RowsAffected { .. } => Either::B(err(Error::from(
+            RestError::generate_error("ROW_NOT_RETURNED", offender),
+        ))),
+    }
+}
+
+/// Returns the `{pk}` segment of the request’s path as it was sent. The router decodes
+/// percent-encoded commas (`%2C`), which would then be mistaken for the delimiter between the
+/// values of a composite primary key.
+fn primary_key_str(req: &HttpRequest) -> String {
+    req.uri()
+        .path()
+        .rsplit('/')
+        .next()
+        .unwrap_or("")
+        .to_string()
+}
+
+/// The header that runs a request in a long-lived transaction (started with `POST /$tx`), using the
+/// transaction’s ID.
+static TRANSACTION_ID: &str = "transaction-id";
+
+/// Returns the ID of the long-lived transaction that the request is run in, if any.
+fn transaction_id(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(TRANSACTION_ID)
+        .and_then(|header_value| header_value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Runs an operation in a long-lived transaction, and sends its result. The returned rows are
+/// retrieved all at once, so they can’t be sent as Arrow or Parquet.
+fn transaction_response<T>(
+    config: web::Data<Config<T>>,
+    transaction_id: String,
+    operation: queries::BatchOperation,
+    format: ResponseFormat,
+    is_compact: bool,
+) -> impl Future<Item = HttpResponse, Error = Error>
+where
+    <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
+    <T as MakeTlsConnect<Socket>>::Stream: Send,
+    <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    if format.is_columnar() {
+        return Either::A(err(Error::from(RestError::generate_error(
+            "UNSUPPORTED_RESPONSE_FORMAT",
+            format.content_type().to_string(),
+        ))));
+    }
+
+    let response =
+        queries::execute_in_transaction(config.get_ref().inner.clone(), &transaction_id, operation)
+            .map_err(Error::from)
+            .and_then(move |result| query_result_response(result, format, is_compact));
 
     Either::B(response)
 }
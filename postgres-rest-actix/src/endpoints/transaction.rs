@@ -0,0 +1,128 @@
+use actix_web::{dev::HttpResponseBuilder, http::StatusCode, web, HttpRequest, HttpResponse};
+use futures::{
+    future::{err, Either},
+    Future,
+};
+use serde_json::json;
+use tokio_postgres::{tls::MakeTlsConnect, Socket};
+
+use super::{
+    query_params_from_request::RequestQueryStringParams,
+    streaming::{body_response, ResponseFormat},
+};
+use crate::{Config, Error};
+use postgres_rest::queries::{self, IsolationLevel};
+
+/// Starts a long-lived transaction, and returns its ID (`{"transaction_id": ...}`). Requests to the
+/// `/{table}` endpoints that send the ID in their `Transaction-Id` header are run in the
+/// transaction.
+pub fn post_transaction<T>(
+    req: HttpRequest,
+    config: web::Data<Config<T>>,
+    query_string_params: web::Query<RequestQueryStringParams>,
+) -> impl Future<Item = HttpResponse, Error = Error>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    let isolation_level = match &query_string_params.isolation_level {
+        Some(isolation_level_str) => match isolation_level_str.parse::<IsolationLevel>() {
+            Ok(isolation_level) => Some(isolation_level),
+            Err(e) => return Either::A(err(Error::from(e))),
+        },
+        None => None,
+    };
+
+    let format = ResponseFormat::from_request(&req);
+    let response = queries::begin_transaction(&config.get_ref().inner, isolation_level)
+        .map_err(Error::from)
+        .and_then(move |transaction_id| {
+            body_response(&json!({ "transaction_id": transaction_id }), format)
+        });
+
+    Either::B(response)
+}
+
+/// Commits a long-lived transaction.
+pub fn post_transaction_commit<T>(
+    req: HttpRequest,
+    config: web::Data<Config<T>>,
+) -> impl Future<Item = HttpResponse, Error = Error>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    queries::commit_transaction(
+        &config.get_ref().inner,
+        req.match_info().query("transaction_id"),
+    )
+    .then(empty_response)
+}
+
+/// Rolls back a long-lived transaction.
+pub fn post_transaction_rollback<T>(
+    req: HttpRequest,
+    config: web::Data<Config<T>>,
+) -> impl Future<Item = HttpResponse, Error = Error>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    queries::rollback_transaction(
+        &config.get_ref().inner,
+        req.match_info().query("transaction_id"),
+    )
+    .then(empty_response)
+}
+
+/// Creates a savepoint in a long-lived transaction.
+pub fn post_savepoint<T>(
+    req: HttpRequest,
+    config: web::Data<Config<T>>,
+) -> impl Future<Item = HttpResponse, Error = Error>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    queries::create_savepoint(
+        &config.get_ref().inner,
+        req.match_info().query("transaction_id"),
+        req.match_info().query("savepoint"),
+    )
+    .then(empty_response)
+}
+
+/// Releases a savepoint of a long-lived transaction.
+pub fn post_savepoint_release<T>(
+    req: HttpRequest,
+    config: web::Data<Config<T>>,
+) -> impl Future<Item = HttpResponse, Error = Error>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    queries::release_savepoint(
+        &config.get_ref().inner,
+        req.match_info().query("transaction_id"),
+        req.match_info().query("savepoint"),
+    )
+    .then(empty_response)
+}
+
+/// Rolls back a long-lived transaction to a savepoint.
+pub fn post_savepoint_rollback<T>(
+    req: HttpRequest,
+    config: web::Data<Config<T>>,
+) -> impl Future<Item = HttpResponse, Error = Error>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    queries::rollback_to_savepoint(
+        &config.get_ref().inner,
+        req.match_info().query("transaction_id"),
+        req.match_info().query("savepoint"),
+    )
+    .then(empty_response)
+}
+
+fn empty_response(result: Result<(), postgres_rest::Error>) -> Result<HttpResponse, Error> {
+    match result {
+        Ok(_) => Ok(HttpResponseBuilder::new(StatusCode::OK).finish()),
+        Err(e) => Err(Error::from(e)),
+    }
+}
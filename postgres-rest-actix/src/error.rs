@@ -123,9 +123,9 @@ impl Error {
 
             "INVALID_CONTENT_TYPE" => Error::UserError {
                 code: err_id,
-                details: "The `Content-Type` header value is not valid for this request".to_string(),
+                details: "The `Content-Type` header value is not valid for this request, or the body could not be parsed as that type.".to_string(),
                 http_status: 400,
-                message: "The `Content-Type` must be `text/plain`.",
+                message: "The `Content-Type` must be `text/plain`, or JSON, MessagePack or CBOR.",
                 offender,
             },
 
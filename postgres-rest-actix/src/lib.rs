@@ -1,7 +1,7 @@
 // used for dev/tests
 #![deny(clippy::complexity, clippy::correctness, clippy::perf, clippy::style)]
 // to serialize large json (like the index)
-#![recursion_limit = "128"]
+#![recursion_limit = "256"]
 
 //! Use `actix-web` to serve a REST API for your PostgreSQL database.
 //!
@@ -16,14 +16,15 @@
 //! fn main() {
 //!     let ip_address = "127.0.0.1:3000";
 //!
+//!     // created once and shared by every server, so that they use the same connection pool and
+//!     // transactions
+//!     let config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+//!
 //!     // start 1 server on each cpu thread
 //!     # thread::spawn(move || {
 //!     HttpServer::new(move || {
-//!         App::new().service(
-//!             // appends an actix-web Scope under the "/api" endpoint to app.
-//!             Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls)
-//!                 .generate_scope("/api"),
-//!         )
+//!         // appends an actix-web Scope under the "/api" endpoint to app.
+//!         App::new().service(config.generate_scope("/api"))
 //!     })
 //!     .bind(ip_address)
 //!     .expect("Can not bind to port 3000")
@@ -49,15 +50,14 @@
 ///
 /// fn main() {
 ///     let ip_address = "127.0.0.1:3000";
+///     let config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
 ///
 ///     // start 1 server on each cpu thread
 ///     # std::thread::spawn(move || {
 ///     HttpServer::new(move || {
-///         let config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
-///
 ///         App::new().service(
 ///             web::scope("/custom_api_endpoint")
-///                 .data(config)
+///                 .data(config.clone())
 ///                 .route("/table", web::get().to_async(endpoints::get_all_table_names::<NoTls>))
 ///                 .service(
 ///                     web::resource("/{table}")
@@ -80,18 +80,21 @@ pub mod endpoints;
 mod error;
 
 use endpoints::{
-    delete_table, execute_sql, get_all_table_names, get_table, index, post_table, put_table,
+    delete_table, delete_table_row, execute_sql, get_all_table_names, get_table, get_table_row,
+    index, post_batch, post_savepoint, post_savepoint_release, post_savepoint_rollback, post_table,
+    post_transaction, post_transaction_commit, post_transaction_rollback, put_table, put_table_row,
     reset_caches,
 };
 
 pub use error::Error;
-use postgres_rest::Config as InnerConfig;
+pub use postgres_rest::queries::SqlStatementKind;
+use postgres_rest::{Config as InnerConfig, PooledClient};
 
 use actix_web::{web, Scope};
 use futures::future::Future;
 use tokio_postgres::{
     tls::{MakeTlsConnect, TlsConnect},
-    Client, Socket,
+    Socket,
 };
 
 /// Configures and creates the REST API `Scope`.
@@ -147,8 +150,8 @@ where
         self
     }
 
-    /// A convenience wrapper around `tokio_postgres::connect`. Returns a future that evaluates to
-    /// the database client connection.
+    /// Checks out a database client connection from the connection pool. The connection is
+    /// returned to the pool when the client is dropped.
     ///
     /// # Example
     ///
@@ -165,10 +168,17 @@ where
     ///         ok(())
     ///     }));
     /// ```
-    pub fn connect(&self) -> impl Future<Item = Client, Error = Error> {
+    pub fn connect(&self) -> impl Future<Item = PooledClient, Error = Error> {
         self.inner.connect().map_err(Error::from)
     }
 
+    /// Turns on health checks for pooled connections. Idle connections are tested with `SELECT 1`
+    /// before being checked out, and are replaced if the test fails.
+    pub fn enable_pool_health_check(&mut self) -> &mut Self {
+        self.inner.enable_pool_health_check();
+        self
+    }
+
     /// Enables an additional API endpoint at `{scope_name}/reset_table_stats_cache`, which allows
     /// for manual resetting of the Table Stats cache.
     pub fn enable_cache_reset_url(&mut self) -> &mut Self {
@@ -183,7 +193,16 @@ where
         self
     }
 
+    /// Runs the queries of the `{scope_name}/sql` endpoint in a `READ ONLY` transaction, so that
+    /// they can’t write to the database.
+    pub fn enable_custom_sql_read_only(&mut self) -> &mut Self {
+        self.inner.enable_custom_sql_read_only();
+        self
+    }
+
     /// Creates the Actix scope url at `scope_name`, which contains all of the other API endpoints.
+    /// Scopes generated from the same config (or its clones) share its connection pool and
+    /// transactions, so create the config once and generate a scope for each worker from it.
     /// ```no_run
     /// use postgres_rest_actix::Config;
     /// use tokio_postgres::NoTls;
@@ -211,6 +230,29 @@ where
             .route("", web::get().to(index))
             .route("/", web::get().to(index))
             .route("/table", web::get().to_async(get_all_table_names::<T>))
+            // registered before `/{table}`, so that these aren’t mistaken for tables
+            .route("/$batch", web::post().to_async(post_batch::<T>))
+            .route("/$tx", web::post().to_async(post_transaction::<T>))
+            .route(
+                "/$tx/{transaction_id}/commit",
+                web::post().to_async(post_transaction_commit::<T>),
+            )
+            .route(
+                "/$tx/{transaction_id}/rollback",
+                web::post().to_async(post_transaction_rollback::<T>),
+            )
+            .route(
+                "/$tx/{transaction_id}/savepoints/{savepoint}",
+                web::post().to_async(post_savepoint::<T>),
+            )
+            .route(
+                "/$tx/{transaction_id}/savepoints/{savepoint}/release",
+                web::post().to_async(post_savepoint_release::<T>),
+            )
+            .route(
+                "/$tx/{transaction_id}/savepoints/{savepoint}/rollback",
+                web::post().to_async(post_savepoint_rollback::<T>),
+            )
             .service(
                 web::resource("/{table}")
                     .route(web::delete().to_async(delete_table::<T>))
@@ -218,6 +260,83 @@ where
                     .route(web::post().to_async(post_table::<T>))
                     .route(web::put().to_async(put_table::<T>)),
             )
+            .service(
+                web::resource("/{table}/{pk}")
+                    .route(web::delete().to_async(delete_table_row::<T>))
+                    .route(web::get().to_async(get_table_row::<T>))
+                    .route(web::patch().to_async(put_table_row::<T>))
+                    .route(web::put().to_async(put_table_row::<T>)),
+            )
+    }
+
+    /// Resolves a table name (`table` or `schema.table`) from an API request into the name used by
+    /// the `postgres_rest::queries` functions. Unqualified table names belong to the first exposed
+    /// schema.
+    pub fn resolve_table_name(&self, table: &str) -> Result<String, Error> {
+        self.inner.resolve_table_name(table).map_err(Error::from)
+    }
+
+    /// Sets the schemas whose tables are exposed by the API. Tables in the first schema can be
+    /// referred to without a schema prefix (`/api/{table}`); all other tables are referred to with
+    /// one (`/api/{schema}.{table}`). Default: `["public"]`. If the Table Stats cache is used, call
+    /// this before `cache_table_stats()`.
+    /// ```
+    /// use postgres_rest_actix::Config;
+    /// use tokio_postgres::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.set_schemas(&["public", "billing", "auth"]);
+    /// ```
+    pub fn set_schemas(&mut self, schemas: &[&str]) -> &mut Self {
+        self.inner.set_schemas(schemas);
+        self
+    }
+
+    /// Sets the maximum number of rows that the queries of the `{scope_name}/sql` endpoint can
+    /// return when they are run in a `READ ONLY` transaction or with a timeout (as their rows are
+    /// then held in memory). `0` allows any number of rows. Default: `10000`.
+    /// ```
+    /// use postgres_rest_actix::Config;
+    /// use tokio_postgres::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.enable_custom_sql_url().set_custom_sql_max_rows(1000);
+    /// ```
+    pub fn set_custom_sql_max_rows(&mut self, max_rows: u32) -> &mut Self {
+        self.inner.set_custom_sql_max_rows(max_rows);
+        self
+    }
+
+    /// Restricts the queries of the `{scope_name}/sql` endpoint to the given kinds of statements.
+    /// Other statements are rejected with a `SQL_STATEMENT_NOT_ALLOWED` error. Allowing only
+    /// `SELECT`s also runs the queries in a `READ ONLY` transaction, as a `SELECT` can still write
+    /// by calling functions.
+    /// ```
+    /// use postgres_rest_actix::{Config, SqlStatementKind};
+    /// use tokio_postgres::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config
+    ///     .enable_custom_sql_url()
+    ///     .set_custom_sql_statement_kinds(&[SqlStatementKind::Select]);
+    /// ```
+    pub fn set_custom_sql_statement_kinds(&mut self, kinds: &[SqlStatementKind]) -> &mut Self {
+        self.inner.set_custom_sql_statement_kinds(kinds);
+        self
+    }
+
+    /// Sets the number of milliseconds after which the queries of the `{scope_name}/sql` endpoint
+    /// are cancelled. `0` lets queries run indefinitely. Default: `0`.
+    /// ```
+    /// use postgres_rest_actix::Config;
+    /// use tokio_postgres::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.enable_custom_sql_url().set_custom_sql_statement_timeout(5000);
+    /// ```
+    pub fn set_custom_sql_statement_timeout(&mut self, milliseconds: u32) -> &mut Self {
+        self.inner.set_custom_sql_statement_timeout(milliseconds);
+        self
     }
 
     /// Set the timer to automatically reset the table stats cache on a recurring interval. If this
@@ -233,4 +352,77 @@ where
         self.inner.set_cache_reset_timer(seconds);
         self
     }
+
+    /// Sets the number of seconds to wait for a connection to free up when the connection pool is
+    /// at its maximum size, or for a new connection to open. `0` waits indefinitely. Default: `30`.
+    /// ```
+    /// use postgres_rest_actix::Config;
+    /// use tokio_postgres::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.set_pool_checkout_timeout(5);
+    /// ```
+    pub fn set_pool_checkout_timeout(&mut self, seconds: u32) -> &mut Self {
+        self.inner.set_pool_checkout_timeout(seconds);
+        self
+    }
+
+    /// Sets the number of seconds that a pooled connection has to be idle for before it is tested
+    /// with `SELECT 1` on checkout. `0` only tests connections if health checks are enabled.
+    /// Default: `30`.
+    /// ```
+    /// use postgres_rest_actix::Config;
+    /// use tokio_postgres::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.set_pool_health_check_idle_time(10);
+    /// ```
+    pub fn set_pool_health_check_idle_time(&mut self, seconds: u32) -> &mut Self {
+        self.inner.set_pool_health_check_idle_time(seconds);
+        self
+    }
+
+    /// Sets the number of seconds after which idle pooled connections are closed. `0` keeps idle
+    /// connections open indefinitely. Default: `300`.
+    /// ```
+    /// use postgres_rest_actix::Config;
+    /// use tokio_postgres::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.set_pool_idle_timeout(60);
+    /// ```
+    pub fn set_pool_idle_timeout(&mut self, seconds: u32) -> &mut Self {
+        self.inner.set_pool_idle_timeout(seconds);
+        self
+    }
+
+    /// Sets the minimum and maximum number of connections in the connection pool. Missing
+    /// connections are opened in the background whenever a connection is checked out. Default: `0`
+    /// and `10`.
+    /// ```
+    /// use postgres_rest_actix::Config;
+    /// use tokio_postgres::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.set_pool_size(2, 20);
+    /// ```
+    pub fn set_pool_size(&mut self, min_size: usize, max_size: usize) -> &mut Self {
+        self.inner.set_pool_size(min_size, max_size);
+        self
+    }
+
+    /// Sets the number of seconds after which transactions started with `{scope_name}/$tx` are
+    /// rolled back if they haven’t been used. `0` keeps idle transactions open indefinitely.
+    /// Default: `60`.
+    /// ```
+    /// use postgres_rest_actix::Config;
+    /// use tokio_postgres::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.set_transaction_idle_timeout(300);
+    /// ```
+    pub fn set_transaction_idle_timeout(&mut self, seconds: u32) -> &mut Self {
+        self.inner.set_transaction_idle_timeout(seconds);
+        self
+    }
 }
@@ -7,9 +7,12 @@ use lazy_static::lazy_static;
 use pretty_assertions::assert_eq;
 use reqwest::{self, header, Client, Method, StatusCode};
 use serde_json::{self, json, Value};
-use std::sync::{
-    atomic::{AtomicBool, Ordering},
-    Once,
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Once,
+    },
 };
 
 lazy_static! {
@@ -35,6 +38,16 @@ fn run_setup() {
     }
 }
 
+/// Removes the row count and size estimates (which depend on when Postgres last analyzed the
+/// table) from table stats, after checking that they are there.
+fn remove_size_estimates(stats: &mut Value) {
+    let stats = stats.as_object_mut().unwrap();
+    for key in ["estimated_row_count", "total_size_bytes"].iter() {
+        let estimate = stats.remove(*key).and_then(|estimate| estimate.as_i64());
+        assert!(estimate.unwrap() >= 0);
+    }
+}
+
 #[test]
 fn index() {
     run_setup();
@@ -53,6 +66,26 @@ fn index_no_slash() {
     assert_eq!(res.status(), StatusCode::OK);
 }
 
+#[test]
+fn index_cbor() {
+    run_setup();
+
+    let url = ["http://", &SERVER_IP, ":", &NO_CACHE_PORT, "/api/"].join("");
+    let mut res = Client::new()
+        .get(&url)
+        .header(header::ACCEPT, "application/cbor")
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "application/cbor");
+
+    let mut body = vec![];
+    res.copy_to(&mut body).unwrap();
+    let response_body: Value = serde_cbor::from_slice(&body).unwrap();
+    assert!(response_body["endpoints"]["/table"].is_object());
+}
+
 #[test]
 fn get_table_names() {
     run_setup();
@@ -66,19 +99,34 @@ fn get_table_names() {
         response_body,
         json!([
             "adult",
+            "author",
+            "billing.invoice",
+            "billing.line_item",
+            "book",
             "child",
             "coach",
             "company",
             "delete_a",
             "delete_b",
             "delete_simple",
+            "department",
+            "employee",
+            "order_item",
             "player",
+            "post",
+            "post_tag",
             "school",
             "sibling",
+            "tag",
             "team",
+            "tenant_order",
+            "test_arrays",
             "test_batch_insert",
+            "test_enums",
             "test_fields",
+            "test_hstore",
             "test_insert",
+            "test_ranges",
         ])
     );
 }
@@ -87,12 +135,13 @@ fn get_table_names() {
 fn get_table_stats() {
     run_setup();
 
-    let expected_response_body = json!({"columns":[{"char_max_length":null,"char_octet_length":null,"column_name":"school_id","column_type":"int8","default_value":null,"foreign_key_column":"id","foreign_key_column_type":"int8","foreign_key_table":"school","is_foreign_key":true,"is_nullable":true},{"char_max_length":null,"char_octet_length":null,"column_name":"parent_id","column_type":"int8","default_value":null,"foreign_key_column":"id","foreign_key_column_type":"int8","foreign_key_table":"adult","is_foreign_key":true,"is_nullable":true},{"char_max_length":null,"char_octet_length":1_073_741_824,"column_name":"name","column_type":"text","default_value":null,"foreign_key_column":null,"foreign_key_column_type":null,"foreign_key_table":null,"is_foreign_key":false,"is_nullable":true},{"char_max_length":null,"char_octet_length":null,"column_name":"id","column_type":"int8","default_value":null,"foreign_key_column":null,"foreign_key_column_type":null,"foreign_key_table":null,"is_foreign_key":false,"is_nullable":false}],"constraints":[{"columns":["id"],"constraint_type":"primary_key","definition":"PRIMARY KEY (id)","fk_columns":null,"fk_table":null,"name":"child_id_key","table":"child"},{"columns":["parent_id"],"constraint_type":"foreign_key","definition":"FOREIGN KEY (parent_id) REFERENCES adult(id)","fk_columns":["id"],"fk_table":"adult","name":"child_parent_id","table":"child"},{"columns":["school_id"],"constraint_type":"foreign_key","definition":"FOREIGN KEY (school_id) REFERENCES school(id)","fk_columns":["id"],"fk_table":"school","name":"child_school_id","table":"child"},{"columns":["id","parent_id"],"constraint_type":"unique","definition":"UNIQUE (id, parent_id)","fk_columns":null,"fk_table":null,"name":"child_unique_id_parent_id","table":"child"},{"columns":["parent_id","sibling_id"],"constraint_type":"foreign_key","definition":"FOREIGN KEY (parent_id, sibling_id) REFERENCES child(parent_id, id)","fk_columns":["parent_id","id"],"fk_table":"child","name":"sibling_reference","table":"sibling"}],"indexes":[{"access_method":"btree","columns":["id"],"is_exclusion":false,"is_primary_key":true,"is_unique":true,"name":"child_id_key"},{"access_method":"btree","columns":["id","parent_id"],"is_exclusion":false,"is_primary_key":false,"is_unique":true,"name":"child_unique_id_parent_id"}],"primary_key":["id"],"referenced_by":[{"columns_referenced":["parent_id","id"],"referencing_columns":["parent_id","sibling_id"],"referencing_table":"sibling"}]});
+    let expected_response_body = json!({"columns":[{"char_max_length":null,"char_octet_length":null,"column_name":"id","column_type":"int8","default_value":null,"foreign_key_column":null,"foreign_key_column_type":null,"foreign_key_table":null,"is_foreign_key":false,"is_nullable":false},{"char_max_length":null,"char_octet_length":1_073_741_824,"column_name":"name","column_type":"text","default_value":null,"foreign_key_column":null,"foreign_key_column_type":null,"foreign_key_table":null,"is_foreign_key":false,"is_nullable":true},{"char_max_length":null,"char_octet_length":null,"column_name":"parent_id","column_type":"int8","default_value":null,"foreign_key_column":"id","foreign_key_column_type":"int8","foreign_key_table":"adult","is_foreign_key":true,"is_nullable":true},{"char_max_length":null,"char_octet_length":null,"column_name":"school_id","column_type":"int8","default_value":null,"foreign_key_column":"id","foreign_key_column_type":"int8","foreign_key_table":"school","is_foreign_key":true,"is_nullable":true}],"constraints":[{"columns":["id"],"constraint_type":"primary_key","definition":"PRIMARY KEY (id)","fk_columns":null,"fk_table":null,"name":"child_id_key","table":"child"},{"columns":["parent_id"],"constraint_type":"foreign_key","definition":"FOREIGN KEY (parent_id) REFERENCES adult(id)","fk_columns":["id"],"fk_table":"adult","name":"child_parent_id","table":"child"},{"columns":["school_id"],"constraint_type":"foreign_key","definition":"FOREIGN KEY (school_id) REFERENCES school(id)","fk_columns":["id"],"fk_table":"school","name":"child_school_id","table":"child"},{"columns":["id","parent_id"],"constraint_type":"unique","definition":"UNIQUE (id, parent_id)","fk_columns":null,"fk_table":null,"name":"child_unique_id_parent_id","table":"child"},{"columns":["parent_id","sibling_id"],"constraint_type":"foreign_key","definition":"FOREIGN KEY (parent_id, sibling_id) REFERENCES child(parent_id, id)","fk_columns":["parent_id","id"],"fk_table":"child","name":"sibling_reference","table":"sibling"}],"indexes":[{"access_method":"btree","columns":["id"],"is_exclusion":false,"is_primary_key":true,"is_unique":true,"name":"child_id_key"},{"access_method":"btree","columns":["id","parent_id"],"is_exclusion":false,"is_primary_key":false,"is_unique":true,"name":"child_unique_id_parent_id"}],"primary_key":["id"],"referenced_by":[{"columns_referenced":["parent_id","id"],"referencing_columns":["parent_id","sibling_id"],"referencing_table":"sibling"}]});
 
     // test the non-cached path
     let url = ["http://", &SERVER_IP, ":", &NO_CACHE_PORT, "/api/child"].join("");
     let mut res = reqwest::get(&url).unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut response_body: Value = res.json().unwrap();
+    remove_size_estimates(&mut response_body);
 
     assert_eq!(res.status(), StatusCode::OK);
     assert_eq!(response_body, expected_response_body);
@@ -100,7 +149,8 @@ fn get_table_stats() {
     // test the cached path
     let url = ["http://", &SERVER_IP, ":", &CACHE_PORT, "/api/child"].join("");
     let mut res = reqwest::get(&url).unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut response_body: Value = res.json().unwrap();
+    remove_size_estimates(&mut response_body);
 
     assert_eq!(res.status(), StatusCode::OK);
     assert_eq!(response_body, expected_response_body);
@@ -110,12 +160,13 @@ fn get_table_stats() {
 fn get_table_stats_multi_column_fk() {
     run_setup();
 
-    let expected_response_body = json!({"columns":[{"char_max_length":null,"char_octet_length":null,"column_name":"sibling_id","column_type":"int8","default_value":null,"foreign_key_column":"id","foreign_key_column_type":"int8","foreign_key_table":"child","is_foreign_key":true,"is_nullable":true},{"char_max_length":null,"char_octet_length":null,"column_name":"parent_id","column_type":"int8","default_value":null,"foreign_key_column":"parent_id","foreign_key_column_type":"int8","foreign_key_table":"child","is_foreign_key":true,"is_nullable":true},{"char_max_length":null,"char_octet_length":1_073_741_824,"column_name":"name","column_type":"text","default_value":null,"foreign_key_column":null,"foreign_key_column_type":null,"foreign_key_table":null,"is_foreign_key":false,"is_nullable":true},{"char_max_length":null,"char_octet_length":null,"column_name":"id","column_type":"int8","default_value":null,"foreign_key_column":null,"foreign_key_column_type":null,"foreign_key_table":null,"is_foreign_key":false,"is_nullable":false}],"constraints":[{"columns":["id"],"constraint_type":"primary_key","definition":"PRIMARY KEY (id)","fk_columns":null,"fk_table":null,"name":"sibling_id_key","table":"sibling"},{"columns":["parent_id","sibling_id"],"constraint_type":"foreign_key","definition":"FOREIGN KEY (parent_id, sibling_id) REFERENCES child(parent_id, id)","fk_columns":["parent_id","id"],"fk_table":"child","name":"sibling_reference","table":"sibling"}],"indexes":[{"access_method":"btree","columns":["id"],"is_exclusion":false,"is_primary_key":true,"is_unique":true,"name":"sibling_id_key"}],"primary_key":["id"],"referenced_by":[]});
+    let expected_response_body = json!({"columns":[{"char_max_length":null,"char_octet_length":null,"column_name":"id","column_type":"int8","default_value":null,"foreign_key_column":null,"foreign_key_column_type":null,"foreign_key_table":null,"is_foreign_key":false,"is_nullable":false},{"char_max_length":null,"char_octet_length":1_073_741_824,"column_name":"name","column_type":"text","default_value":null,"foreign_key_column":null,"foreign_key_column_type":null,"foreign_key_table":null,"is_foreign_key":false,"is_nullable":true},{"char_max_length":null,"char_octet_length":null,"column_name":"parent_id","column_type":"int8","composite_foreign_key":[["parent_id","parent_id"],["sibling_id","id"]],"default_value":null,"foreign_key_column":"parent_id","foreign_key_column_type":"int8","foreign_key_table":"child","is_foreign_key":true,"is_nullable":true},{"char_max_length":null,"char_octet_length":null,"column_name":"sibling_id","column_type":"int8","composite_foreign_key":[["parent_id","parent_id"],["sibling_id","id"]],"default_value":null,"foreign_key_column":"id","foreign_key_column_type":"int8","foreign_key_table":"child","is_foreign_key":true,"is_nullable":true}],"constraints":[{"columns":["id"],"constraint_type":"primary_key","definition":"PRIMARY KEY (id)","fk_columns":null,"fk_table":null,"name":"sibling_id_key","table":"sibling"},{"columns":["parent_id","sibling_id"],"constraint_type":"foreign_key","definition":"FOREIGN KEY (parent_id, sibling_id) REFERENCES child(parent_id, id)","fk_columns":["parent_id","id"],"fk_table":"child","name":"sibling_reference","table":"sibling"}],"indexes":[{"access_method":"btree","columns":["id"],"is_exclusion":false,"is_primary_key":true,"is_unique":true,"name":"sibling_id_key"}],"primary_key":["id"],"referenced_by":[]});
 
     // test the non-cached path
     let url = ["http://", &SERVER_IP, ":", &NO_CACHE_PORT, "/api/sibling"].join("");
     let mut res = reqwest::get(&url).unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut response_body: Value = res.json().unwrap();
+    remove_size_estimates(&mut response_body);
 
     assert_eq!(res.status(), StatusCode::OK);
     assert_eq!(response_body, expected_response_body);
@@ -123,41 +174,35 @@ fn get_table_stats_multi_column_fk() {
     // test the cached path
     let url = ["http://", &SERVER_IP, ":", &CACHE_PORT, "/api/sibling"].join("");
     let mut res = reqwest::get(&url).unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut response_body: Value = res.json().unwrap();
+    remove_size_estimates(&mut response_body);
 
     assert_eq!(res.status(), StatusCode::OK);
     assert_eq!(response_body, expected_response_body);
 }
 
 #[test]
-fn get_table_records_unsupported_type() {
+fn get_table_records_bit_strings() {
     run_setup();
 
-    // test the non-cached path
-    let url = [
-        "http://",
-        &SERVER_IP,
-        ":",
-        &NO_CACHE_PORT,
-        "/api/test_fields?columns=id,test_bit,test_varbit",
-    ]
-    .join("");
-    let res = reqwest::get(&url).unwrap();
-
-    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
-
-    // test the cached path
-    let url = [
-        "http://",
-        &SERVER_IP,
-        ":",
-        &CACHE_PORT,
-        "/api/test_fields?columns=id,test_bit,test_varbit",
-    ]
-    .join("");
-    let res = reqwest::get(&url).unwrap();
-
-    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    for port in [*NO_CACHE_PORT, *CACHE_PORT].iter() {
+        let url = [
+            "http://",
+            &SERVER_IP,
+            ":",
+            port,
+            "/api/test_fields?columns=id,test_bit,test_varbit",
+        ]
+        .join("");
+        let mut res = reqwest::get(&url).unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = res.json().unwrap();
+        assert_eq!(
+            body,
+            json!([{ "id": 46_327_143_679_919_107i64, "test_bit": "1", "test_varbit": "101" }])
+        );
+    }
 }
 
 #[test]
@@ -390,6 +435,78 @@ fn get_table_records_foreign_keys() {
     assert_eq!(response_body, expected_response_body);
 }
 
+#[test]
+fn get_table_records_foreign_keys_nullable() {
+    run_setup();
+
+    // rows without a foreign key value are kept (LEFT JOIN)
+    let expected_response_body = json!([
+        {"id": 1, "name": "Jon", "department_id.name": "Night's Watch"},
+        {"id": 2, "name": "Hodor", "department_id.name": null},
+    ]);
+
+    // test the non-cached path
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/employee?columns=id,name,department_id.name&order_by=id",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(response_body, expected_response_body);
+
+    // test the cached path
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &CACHE_PORT,
+        "/api/employee?columns=id,name,department_id.name&order_by=id",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(response_body, expected_response_body);
+
+    // `join=inner` excludes them
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/employee?columns=id,name,department_id.name&order_by=id&join=inner",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        response_body,
+        json!([{"id": 1, "name": "Jon", "department_id.name": "Night's Watch"}])
+    );
+
+    // invalid join types are rejected
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/employee?columns=id,department_id.name&join=outer",
+    ]
+    .join("");
+    let res = reqwest::get(&url).unwrap();
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
 #[test]
 fn get_table_records_foreign_keys_dot_misuse() {
     run_setup();
@@ -462,6 +579,55 @@ fn get_table_records_foreign_keys_nested() {
     assert_eq!(res.status(), StatusCode::OK);
 }
 
+#[test]
+fn get_table_records_foreign_keys_nested_objects() {
+    run_setup();
+    let expected_response_body = json!([{
+        "id": 1000,
+        "name": "Robb",
+        "parent_id": {
+            "name": "Ned",
+            "company_id": { "name": "Stark Corporation" },
+        },
+        "company_name": "Stark Corporation",
+    }]);
+
+    for port in [*NO_CACHE_PORT, *CACHE_PORT].iter() {
+        let url = [
+            "http://",
+            &SERVER_IP,
+            ":",
+            port,
+            "/api/child?columns=id,name,parent_id.name,parent_id.company_id.name,parent_id.company_id.name as company_name&nested",
+        ]
+        .join("");
+        let mut res = reqwest::get(&url).unwrap();
+        let response_body: Value = res.json().unwrap();
+
+        assert_eq!(response_body, expected_response_body);
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
+
+#[test]
+fn get_table_records_foreign_keys_nested_objects_conflict() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/child?columns=id,parent_id,parent_id.name&nested",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response_body["code"], "NESTED_COLUMN_CONFLICT");
+}
+
 #[test]
 fn get_table_records_foreign_keys_nested_aliases() {
     run_setup();
@@ -596,6 +762,47 @@ fn get_table_records_gt_1_fk_to_same_table() {
     assert_eq!(res.status(), StatusCode::OK);
 }
 
+#[test]
+fn get_table_records_multi_column_foreign_key() {
+    run_setup();
+
+    // both columns of the foreign key are used to join (order 1 exists for both tenants)
+    let expected_response_body = json!([
+        {"id": 1, "name": "sword", "order_id.note": "first tenant"},
+        {"id": 2, "name": "shield", "order_id.note": "second tenant"},
+    ]);
+
+    // test the non-cached path
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/order_item?columns=id,name,order_id.note&where=id%3C3&order_by=id",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(response_body, expected_response_body);
+
+    // test the cached path
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &CACHE_PORT,
+        "/api/order_item?columns=id,name,order_id.note&where=id%3C3&order_by=id",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(response_body, expected_response_body);
+}
+
 #[test]
 fn get_table_records_foreign_key_wildcards() {
     run_setup();
@@ -628,30 +835,51 @@ fn get_table_records_foreign_key_wildcards() {
 }
 
 #[test]
-fn post_table_record() {
+fn get_table_records_network_time_money_xml_types() {
     run_setup();
 
+    let expected = json!([{
+        "test_inet": "192.168.100.128",
+        "test_cidr": "10.1.0.0/16",
+        "test_interval": "P1Y2M3DT4H5M6.5S",
+        "test_timetz": "04:05:06.789-08:00",
+        "test_money": "-1234.56",
+        "test_xml": "<winterfell><lord>Ned</lord></winterfell>"
+    }]);
+
+    // test the non-cached path
     let url = [
         "http://",
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/test_insert",
+        "/api/test_fields?columns=test_inet,test_cidr,test_interval,test_timetz,test_money,test_xml",
     ]
     .join("");
-    let mut res = Client::new()
-        .request(Method::POST, &url)
-        .json(&json!([{"id": 1}]))
-        .send()
-        .unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut res = reqwest::get(&url).unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, expected);
+
+    // test the cached path
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &CACHE_PORT,
+        "/api/test_fields?columns=test_inet,test_cidr,test_interval,test_timetz,test_money,test_xml",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
 
-    assert_eq!(response_body, json!({ "num_rows": 1 }));
     assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, expected);
 }
 
 #[test]
-fn post_table_records() {
+fn get_table_records_network_time_money_where() {
     run_setup();
 
     let url = [
@@ -659,39 +887,68 @@ fn post_table_records() {
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/test_insert",
+        "/api/test_fields?columns=id&where=test_interval > INTERVAL '1' YEAR AND test_inet = '192.168.100.128' AND test_cidr = '10.1.0.0/16' AND test_timetz = '04:05:06.789-08:00' AND test_money = '-1234.56'",
     ]
     .join("");
-    let mut res = Client::new()
-        .request(Method::POST, &url)
-        .json(&json!([{"id": 2}, {"id": 3}]))
-        .send()
-        .unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut res = reqwest::get(&url).unwrap();
 
-    assert_eq!(response_body, json!({ "num_rows": 2 }));
     assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, json!([{ "id": 46_327_143_679_919_107i64 }]));
 }
 
 #[test]
-fn post_table_records_no_body() {
+fn get_table_records_arrays() {
     run_setup();
 
+    let expected = json!([
+        {
+            "id": 1,
+            "tags": ["winter", null, "is coming"],
+            "matrix": [[1, 2], [3, 4]],
+            "uuids": ["0e49a9e6-9aa9-4c6c-9bb4-0f1d2f3a8d6b"]
+        },
+        {
+            "id": 2,
+            "tags": [],
+            "matrix": null,
+            "uuids": null
+        }
+    ]);
+
+    // test the non-cached path
     let url = [
         "http://",
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/test_insert",
+        "/api/test_arrays?columns=id,tags,matrix,uuids&order_by=id",
     ]
     .join("");
-    let res = Client::new().request(Method::POST, &url).send().unwrap();
+    let mut res = reqwest::get(&url).unwrap();
 
-    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, expected);
+
+    // test the cached path
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &CACHE_PORT,
+        "/api/test_arrays?columns=id,tags,matrix,uuids&order_by=id",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, expected);
 }
 
 #[test]
-fn post_table_records_returning_columns() {
+fn get_table_records_arrays_where() {
     run_setup();
 
     let url = [
@@ -699,67 +956,58 @@ fn post_table_records_returning_columns() {
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/test_insert?returning_columns=id, name",
+        "/api/test_arrays?columns=id&where=matrix = '{{1,2},{3,4}}'",
     ]
     .join("");
-    let mut res = Client::new()
-        .request(Method::POST, &url)
-        .json(&json!([{"id": 4, "name": "A"}, {"id": 5, "name": "b"}]))
-        .send()
-        .unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut res = reqwest::get(&url).unwrap();
 
-    assert_eq!(
-        response_body,
-        json!([{ "id": 4, "name": "A" }, { "id": 5, "name": "b" }])
-    );
     assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, json!([{ "id": 1 }]));
 }
 
 #[test]
-fn post_table_records_on_conflict_do_nothing() {
+fn get_table_records_enums_and_domains() {
     run_setup();
 
+    let expected = json!([
+        { "id": 1, "status": "ruling", "bannermen": 12 },
+        { "id": 2, "status": "extinct", "bannermen": null }
+    ]);
+
+    // test the non-cached path
     let url = [
         "http://",
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/test_insert",
+        "/api/test_enums?columns=id,status,bannermen&order_by=id",
     ]
     .join("");
-    let mut res = Client::new()
-        .request(Method::POST, &url)
-        .json(&json!([{"id": 14, "name": "A"}]))
-        .send()
-        .unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut res = reqwest::get(&url).unwrap();
 
-    assert_eq!(response_body, json!({ "num_rows": 1 }));
     assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, expected);
 
-    // now attempt to send same request but with different name
+    // test the cached path
     let url = [
         "http://",
         &SERVER_IP,
         ":",
-        &NO_CACHE_PORT,
-        "/api/test_insert?conflict_action=nothing&conflict_target=id",
+        &CACHE_PORT,
+        "/api/test_enums?columns=id,status,bannermen&order_by=id",
     ]
     .join("");
-    let mut res = Client::new()
-        .request(Method::POST, &url)
-        .json(&json!([{"id": 14, "name": "B"}]))
-        .send()
-        .unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut res = reqwest::get(&url).unwrap();
 
-    assert_eq!(response_body, json!({ "num_rows": 0 }));
     assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, expected);
 }
 
 #[test]
-fn post_table_records_on_conflict_do_nothing_returning_columns() {
+fn get_table_records_enums_where() {
     run_setup();
 
     let url = [
@@ -767,86 +1015,322 @@ fn post_table_records_on_conflict_do_nothing_returning_columns() {
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/test_insert?returning_columns=id, name",
+        "/api/test_enums?columns=id&where=status = 'extinct'",
     ]
     .join("");
-    let mut res = Client::new()
-        .request(Method::POST, &url)
-        .json(&json!([{"id": 10, "name": "A"}]))
-        .send()
-        .unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut res = reqwest::get(&url).unwrap();
 
-    assert_eq!(response_body, json!([{ "id": 10, "name": "A" }]));
     assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, json!([{ "id": 2 }]));
+}
 
-    // now attempt to send same request but with different name
-    let url = [
-        "http://",
-        &SERVER_IP,
-        ":",
-        &NO_CACHE_PORT,
-        "/api/test_insert?returning_columns=id, name&conflict_action=nothing&conflict_target=id",
-    ]
-    .join("");
-    let mut res = Client::new()
-        .request(Method::POST, &url)
-        .json(&json!([{"id": 10, "name": "B"}, {"id": 11, "name": "C"}]))
-        .send()
-        .unwrap();
-    let response_body: Value = res.json().unwrap();
+#[test]
+fn get_table_records_hstore() {
+    run_setup();
 
-    assert_eq!(response_body, json!([{ "id": 11, "name": "C" }]));
-    assert_eq!(res.status(), StatusCode::OK);
+    for port in [*NO_CACHE_PORT, *CACHE_PORT].iter() {
+        let url = [
+            "http://",
+            &SERVER_IP,
+            ":",
+            port,
+            "/api/test_hstore?columns=id,tags,flags,mask&order_by=id",
+        ]
+        .join("");
+        let mut res = reqwest::get(&url).unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = res.json().unwrap();
+        assert_eq!(
+            body,
+            json!([
+                {
+                    "id": 1,
+                    "tags": { "color": "red", "house words": "Winter is coming", "sigil": null },
+                    "flags": "0101",
+                    "mask": "1"
+                },
+                { "id": 2, "tags": { "color": "gold" }, "flags": "1100", "mask": "10110" }
+            ])
+        );
+    }
 }
 
 #[test]
-fn post_table_records_on_conflict_update() {
+fn get_table_records_hstore_and_bit_string_arrays() {
+    run_setup();
+
+    for port in [*NO_CACHE_PORT, *CACHE_PORT].iter() {
+        let url = [
+            "http://",
+            &SERVER_IP,
+            ":",
+            port,
+            "/api/test_hstore?columns=id,tag_history,flag_history,masks&order_by=id",
+        ]
+        .join("");
+        let mut res = reqwest::get(&url).unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = res.json().unwrap();
+        assert_eq!(
+            body,
+            json!([
+                {
+                    "id": 1,
+                    "tag_history": [{ "color": "grey" }, null],
+                    "flag_history": ["0001", null],
+                    "masks": ["1", "10"]
+                },
+                { "id": 2, "tag_history": null, "flag_history": null, "masks": [] }
+            ])
+        );
+    }
+}
+
+#[test]
+fn get_table_records_hstore_and_bit_string_where() {
+    run_setup();
+
+    let where_clauses = [
+        // `?`
+        "tags %3F 'sigil'",
+        // `@>`
+        "tags @> 'color=>red'",
+        "flags = B'0101'",
+        "mask = b'1'",
+    ];
+
+    for where_clause in where_clauses.iter() {
+        let url = [
+            "http://",
+            &SERVER_IP,
+            ":",
+            &NO_CACHE_PORT,
+            "/api/test_hstore?columns=id&where=",
+            where_clause,
+        ]
+        .join("");
+        let mut res = reqwest::get(&url).unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = res.json().unwrap();
+        assert_eq!(body, json!([{ "id": 1 }]));
+    }
+}
+
+#[test]
+fn get_table_stats_enums_and_domains() {
+    run_setup();
+
+    for port in [*NO_CACHE_PORT, *CACHE_PORT].iter() {
+        let url = ["http://", &SERVER_IP, ":", port, "/api/test_enums"].join("");
+        let mut res = reqwest::get(&url).unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        let body: Value = res.json().unwrap();
+        let columns = body["columns"].as_array().unwrap();
+
+        let bannermen = &columns[0];
+        assert_eq!(bannermen["column_name"], json!("bannermen"));
+        assert_eq!(bannermen["column_type"], json!("int4"));
+        assert_eq!(bannermen.get("enum_labels"), None);
+
+        let status = &columns[2];
+        assert_eq!(status["column_name"], json!("status"));
+        assert_eq!(status["column_type"], json!("enum"));
+        assert_eq!(status["enum_labels"], json!(["ruling", "sworn", "extinct"]));
+    }
+}
+
+#[test]
+fn get_table_records_ranges() {
     run_setup();
 
+    let expected = json!([
+        {
+            "id": 1,
+            "during": {
+                "lower": "2019-01-01T10:00:00Z",
+                "upper": "2019-01-01T11:00:00Z",
+                "lower_inclusive": true,
+                "upper_inclusive": false
+            },
+            "seats": { "lower": 1, "upper": 5, "lower_inclusive": true, "upper_inclusive": false },
+            "closed": [
+                { "lower": "2019-01-05", "upper": "2019-01-07", "lower_inclusive": true, "upper_inclusive": false },
+                { "lower": "2019-02-01", "upper": "2019-02-02", "lower_inclusive": true, "upper_inclusive": false }
+            ]
+        },
+        {
+            "id": 2,
+            "during": {
+                "lower": "2019-01-02T10:00:00Z",
+                "upper": null,
+                "lower_inclusive": true,
+                "upper_inclusive": false
+            },
+            "seats": { "empty": true },
+            "closed": null
+        }
+    ]);
+
+    // test the non-cached path
     let url = [
         "http://",
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/test_insert?returning_columns=id, name",
+        "/api/test_ranges?columns=id,during,seats,closed&order_by=id",
     ]
     .join("");
-    let mut res = Client::new()
-        .request(Method::POST, &url)
-        .json(&json!([{"id": 12, "name": "A"}]))
-        .send()
-        .unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut res = reqwest::get(&url).unwrap();
 
-    assert_eq!(response_body, json!([{ "id": 12, "name": "A" }]));
     assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, expected);
 
-    // now attempt to send same request but with different name
+    // test the cached path
     let url = [
         "http://",
         &SERVER_IP,
         ":",
-        &NO_CACHE_PORT,
-        "/api/test_insert?returning_columns=id, name&conflict_action=update&conflict_target=id",
+        &CACHE_PORT,
+        "/api/test_ranges?columns=id,during,seats,closed&order_by=id",
     ]
     .join("");
-    let mut res = Client::new()
-        .request(Method::POST, &url)
-        .json(&json!([{"id": 12, "name": "B"}, {"id": 13, "name": "C"}]))
-        .send()
-        .unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut res = reqwest::get(&url).unwrap();
 
-    assert_eq!(
-        response_body,
-        json!([{"id": 12, "name": "B"}, {"id": 13, "name": "C"}])
-    );
     assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, expected);
 }
 
 #[test]
-fn put_table_records_simple() {
+fn get_table_records_error_before_first_row() {
+    run_setup();
+
+    for port in [*NO_CACHE_PORT, *CACHE_PORT].iter() {
+        // division by zero happens on the first row, so the error can still be sent as a response
+        let url = [
+            "http://",
+            &SERVER_IP,
+            ":",
+            port,
+            "/api/test_ranges?columns=id&where=1 / (id - 1) < 100",
+        ]
+        .join("");
+        let mut res = reqwest::get(&url).unwrap();
+
+        assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body: Value = res.json().unwrap();
+        assert_eq!(body["code"], json!("DATABASE_ERROR"));
+    }
+}
+
+#[test]
+fn get_table_records_error_mid_stream() {
+    run_setup();
+
+    for port in [*NO_CACHE_PORT, *CACHE_PORT].iter() {
+        // division by zero happens on the second row, after the response has already started
+        let url = [
+            "http://",
+            &SERVER_IP,
+            ":",
+            port,
+            "/api/test_ranges?columns=id&where=1 / (id - 2) < 100",
+        ]
+        .join("");
+        let mut res = reqwest::get(&url).unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        // the response is aborted instead of being completed
+        assert!(res.text().is_err());
+    }
+}
+
+#[test]
+fn get_table_records_csv() {
+    run_setup();
+
+    for port in [*NO_CACHE_PORT, *CACHE_PORT].iter() {
+        let url = [
+            "http://",
+            &SERVER_IP,
+            ":",
+            port,
+            "/api/test_fields?columns=id,test_text AS label,test_bytea,test_json,test_timestamp,test_timestamptz,test_oid",
+        ]
+        .join("");
+        let mut res = Client::new()
+            .get(&url)
+            .header(header::ACCEPT, "text/csv")
+            .send()
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers()[header::CONTENT_TYPE],
+            "text/csv; charset=utf-8"
+        );
+        assert_eq!(
+            res.text().unwrap(),
+            [
+                "id,label,test_bytea,test_json,test_timestamp,test_timestamptz,test_oid\r\n",
+                r#"46327143679919107,some text,\x5c783563333133333334,"{""a"":123,""b"":""cde""}",2004-10-19T10:23:54,2004-10-19T08:23:54Z,"#,
+                "\r\n",
+            ]
+            .join("")
+        );
+    }
+}
+
+#[test]
+fn get_table_records_ndjson() {
+    run_setup();
+
+    for accept in [
+        "application/x-ndjson",
+        "text/csv;q=0.5, application/x-ndjson",
+    ]
+    .iter()
+    {
+        let url = [
+            "http://",
+            &SERVER_IP,
+            ":",
+            &NO_CACHE_PORT,
+            "/api/test_ranges?columns=id,seats&where=id < 3&order_by=id",
+        ]
+        .join("");
+        let mut res = Client::new()
+            .get(&url)
+            .header(header::ACCEPT, *accept)
+            .send()
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[header::CONTENT_TYPE], "application/x-ndjson");
+
+        let body = res.text().unwrap();
+        let rows: Vec<Value> = body
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                json!({ "id": 1, "seats": { "lower": 1, "upper": 5, "lower_inclusive": true, "upper_inclusive": false } }),
+                json!({ "id": 2, "seats": { "empty": true } }),
+            ]
+        );
+    }
+}
+
+#[test]
+fn get_table_records_column_order() {
     run_setup();
 
     let url = [
@@ -854,22 +1338,20 @@ fn put_table_records_simple() {
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/player?where=id%3D5",
+        "/api/test_fields?columns=test_text,id,test_bool",
     ]
     .join("");
-    let mut res = Client::new()
-        .request(Method::PUT, &url)
-        .json(&json!({"team_id": 5}))
-        .send()
-        .unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut res = reqwest::get(&url).unwrap();
 
-    assert_eq!(response_body, json!({ "num_rows": 1 }));
     assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.text().unwrap(),
+        r#"[{"test_text":"some text","id":46327143679919107,"test_bool":true}]"#
+    );
 }
 
 #[test]
-fn put_table_records_simple_returning_columns() {
+fn get_table_records_compact() {
     run_setup();
 
     let url = [
@@ -877,22 +1359,20 @@ fn put_table_records_simple_returning_columns() {
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/player?where=id%3D5&returning_columns=team_id",
+        "/api/test_fields?columns=test_text AS label,id,test_oid&compact",
     ]
     .join("");
-    let mut res = Client::new()
-        .request(Method::PUT, &url)
-        .json(&json!({"team_id": 5}))
-        .send()
-        .unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut res = reqwest::get(&url).unwrap();
 
-    assert_eq!(response_body, json!([{ "team_id": 5 }]));
     assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.text().unwrap(),
+        r#"{"columns":["label","id","test_oid"],"rows":[["some text",46327143679919107,null]]}"#
+    );
 }
 
 #[test]
-fn put_table_records_string_value() {
+fn get_table_records_compact_ndjson() {
     run_setup();
 
     let url = [
@@ -900,22 +1380,34 @@ fn put_table_records_string_value() {
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/player?where=name%3D'Russell Westbrook'&returning_columns=name",
+        "/api/test_ranges?columns=id,seats&where=id < 3&order_by=id&compact",
     ]
     .join("");
     let mut res = Client::new()
-        .request(Method::PUT, &url)
-        .json(&json!({"name": "'Chris Paul'"}))
+        .get(&url)
+        .header(header::ACCEPT, "application/x-ndjson")
         .send()
         .unwrap();
-    let response_body: Value = res.json().unwrap();
 
-    assert_eq!(response_body, json!([{ "name": "Chris Paul" }]));
     assert_eq!(res.status(), StatusCode::OK);
+
+    let body = res.text().unwrap();
+    let lines: Vec<Value> = body
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        lines,
+        vec![
+            json!(["id", "seats"]),
+            json!([1, { "lower": 1, "upper": 5, "lower_inclusive": true, "upper_inclusive": false }]),
+            json!([2, { "empty": true }]),
+        ]
+    );
 }
 
 #[test]
-fn put_table_records_fk_in_where() {
+fn get_table_records_compact_cbor() {
     run_setup();
 
     let url = [
@@ -923,22 +1415,28 @@ fn put_table_records_fk_in_where() {
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/player?where=team_id.name%3D'LA Clippers'",
+        "/api/test_ranges?columns=id&where=id < 3&order_by=id&compact",
     ]
     .join("");
     let mut res = Client::new()
-        .request(Method::PUT, &url)
-        .json(&json!({"team_id": 3}))
+        .get(&url)
+        .header(header::ACCEPT, "application/cbor")
         .send()
         .unwrap();
-    let response_body: Value = res.json().unwrap();
 
-    assert_eq!(response_body, json!({ "num_rows": 2 }));
     assert_eq!(res.status(), StatusCode::OK);
+
+    let mut body = vec![];
+    res.copy_to(&mut body).unwrap();
+    let response_body: Value = serde_cbor::from_slice(&body).unwrap();
+    assert_eq!(
+        response_body,
+        json!({ "columns": ["id"], "rows": [[1], [2]] })
+    );
 }
 
 #[test]
-fn put_table_records_fk_in_body() {
+fn get_table_records_cbor() {
     run_setup();
 
     let url = [
@@ -946,25 +1444,61 @@ fn put_table_records_fk_in_body() {
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/player?where=id%3D1&returning_columns=id, name",
+        "/api/test_fields?columns=id,test_bytea,test_decimal,test_timestamptz,test_oid",
     ]
     .join("");
     let mut res = Client::new()
-        .request(Method::PUT, &url)
-        .json(&json!({"name": "team_id.name"}))
+        .get(&url)
+        .header(header::ACCEPT, "application/cbor")
         .send()
         .unwrap();
-    let response_body: Value = res.json().unwrap();
 
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "application/cbor");
+
+    let mut body = vec![];
+    res.copy_to(&mut body).unwrap();
+    // indefinite-length array
+    assert_eq!(body[0], 0x9f);
+    assert_eq!(body[body.len() - 1], 0xff);
+
+    let text = |s: &str| serde_cbor::Value::Text(s.to_string());
+    let mut row = BTreeMap::new();
+    row.insert(
+        text("id"),
+        serde_cbor::Value::Integer(46_327_143_679_919_107),
+    );
+    row.insert(
+        text("test_bytea"),
+        serde_cbor::Value::Bytes(b"\\x5c313334".to_vec()),
+    );
+    // decimal fraction: 10001 * 10^-2
+    row.insert(
+        text("test_decimal"),
+        serde_cbor::Value::Tag(
+            4,
+            Box::new(serde_cbor::Value::Array(vec![
+                serde_cbor::Value::Integer(-2),
+                serde_cbor::Value::Integer(10001),
+            ])),
+        ),
+    );
+    // date/time string
+    row.insert(
+        text("test_timestamptz"),
+        serde_cbor::Value::Tag(0, Box::new(text("2004-10-19T08:23:54Z"))),
+    );
+    row.insert(text("test_oid"), serde_cbor::Value::Null);
+
+    let rows: serde_cbor::Value = serde_cbor::from_slice(&body).unwrap();
     assert_eq!(
-        response_body,
-        json!([{ "id": 1, "name": "Golden State Warriors" }])
+        rows,
+        serde_cbor::Value::Array(vec![serde_cbor::Value::Map(row)])
     );
-    assert_eq!(res.status(), StatusCode::OK);
 }
 
 #[test]
-fn put_table_records_nested_fk_in_returning_columns() {
+fn get_table_records_msgpack() {
     run_setup();
 
     let url = [
@@ -972,25 +1506,58 @@ fn put_table_records_nested_fk_in_returning_columns() {
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/player?where=id%3D2&returning_columns=id, name, team_id.name, team_id.coach_id.name",
+        "/api/test_fields?columns=id,test_bytea,test_decimal,test_timestamptz",
     ]
     .join("");
     let mut res = Client::new()
-        .request(Method::PUT, &url)
-        .json(&json!({"name": "team_id.coach_id.name"}))
+        .get(&url)
+        .header(header::ACCEPT, "application/x-msgpack")
         .send()
         .unwrap();
-    let response_body: Value = res.json().unwrap();
 
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_TYPE], "application/msgpack");
+
+    let mut body = vec![];
+    res.copy_to(&mut body).unwrap();
+
+    // one map per row, with nothing around them
+    let mut remaining = &body[..];
+    let row = rmpv::decode::read_value(&mut remaining).unwrap();
+    assert!(remaining.is_empty());
+
+    let field = |name: &str| {
+        row.as_map()
+            .unwrap()
+            .iter()
+            .find(|(key, _)| key.as_str() == Some(name))
+            .map(|(_, value)| value.clone())
+            .unwrap()
+    };
+
+    // timestamp extension: 32-bit nanoseconds, 64-bit seconds
+    let mut timestamp = vec![0, 0, 0, 0];
+    timestamp.extend_from_slice(&1_098_174_234i64.to_be_bytes());
+
+    assert_eq!(field("id"), rmpv::Value::from(46_327_143_679_919_107i64));
     assert_eq!(
-        response_body,
-        json!([{ "id": 2, "name": "Steve Kerr", "team_id.name": "Golden State Warriors", "team_id.coach_id.name": "Steve Kerr" }])
+        field("test_bytea"),
+        rmpv::Value::Binary(b"\\x5c313334".to_vec())
     );
-    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(field("test_decimal"), rmpv::Value::from("100.01"));
+    assert_eq!(field("test_timestamptz"), rmpv::Value::Ext(-1, timestamp));
 }
 
+#[cfg(feature = "arrow-export")]
 #[test]
-fn put_table_records_nested_fk_in_returning_column_aliases() {
+fn get_table_records_arrow() {
+    use arrow::{
+        array::{Date32Array, Int64Array, StringArray, TimestampMicrosecondArray},
+        datatypes::{DataType, DateUnit, TimeUnit},
+        ipc::reader::StreamReader,
+    };
+    use std::{io::Cursor, sync::Arc};
+
     run_setup();
 
     let url = [
@@ -998,25 +1565,76 @@ fn put_table_records_nested_fk_in_returning_column_aliases() {
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/player?where=id%3D2&returning_columns=id, team_id.name as team_name",
+        "/api/test_fields?columns=id,test_date,test_text,test_timestamptz,test_numeric,test_oid",
     ]
     .join("");
     let mut res = Client::new()
-        .request(Method::PUT, &url)
-        .json(&json!({"name": "team_id.coach_id.name"}))
+        .get(&url)
+        .header(header::ACCEPT, "application/vnd.apache.arrow.stream")
         .send()
         .unwrap();
-    let response_body: Value = res.json().unwrap();
 
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.headers()[header::CONTENT_TYPE],
+        "application/vnd.apache.arrow.stream"
+    );
+
+    let mut body = vec![];
+    res.copy_to(&mut body).unwrap();
+    let mut reader = StreamReader::try_new(Cursor::new(body)).unwrap();
+
+    let schema = reader.schema();
+    let data_types: Vec<DataType> = schema
+        .fields()
+        .iter()
+        .map(|field| field.data_type().clone())
+        .collect();
     assert_eq!(
-        response_body,
-        json!([{ "id": 2, "team_name": "Golden State Warriors" }])
+        data_types,
+        vec![
+            DataType::Int64,
+            DataType::Date32(DateUnit::Day),
+            DataType::Utf8,
+            DataType::Timestamp(TimeUnit::Microsecond, Some(Arc::new("UTC".to_string()))),
+            DataType::Utf8,
+            DataType::UInt32,
+        ]
     );
-    assert_eq!(res.status(), StatusCode::OK);
+
+    let batch = reader.next().unwrap().unwrap();
+    assert_eq!(batch.num_rows(), 1);
+
+    let column = |i: usize| batch.column(i).as_any();
+    assert_eq!(
+        column(0).downcast_ref::<Int64Array>().unwrap().value(0),
+        46_327_143_679_919_107
+    );
+    assert_eq!(
+        column(1).downcast_ref::<Date32Array>().unwrap().value(0),
+        17896
+    );
+    assert_eq!(
+        column(2).downcast_ref::<StringArray>().unwrap().value(0),
+        "some text"
+    );
+    assert_eq!(
+        column(3)
+            .downcast_ref::<TimestampMicrosecondArray>()
+            .unwrap()
+            .value(0),
+        1_098_174_234_000_000
+    );
+    assert_eq!(
+        column(4).downcast_ref::<StringArray>().unwrap().value(0),
+        "100.02"
+    );
+    assert!(batch.column(5).is_null(0));
 }
 
+#[cfg(feature = "arrow-export")]
 #[test]
-fn put_table_records_nested_fk_in_returning_column_aliases_shortened() {
+fn get_table_records_parquet() {
     run_setup();
 
     let url = [
@@ -1024,25 +1642,32 @@ fn put_table_records_nested_fk_in_returning_column_aliases_shortened() {
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/player?where=id%3D2&returning_columns=id, team_id.name team_name",
+        "/api/test_fields?columns=id,test_bool,test_bytea,test_float8,test_text,test_time,test_timestamp",
     ]
     .join("");
     let mut res = Client::new()
-        .request(Method::PUT, &url)
-        .json(&json!({"name": "team_id.coach_id.name"}))
+        .get(&url)
+        .header(header::ACCEPT, "application/vnd.apache.parquet")
         .send()
         .unwrap();
-    let response_body: Value = res.json().unwrap();
 
+    assert_eq!(res.status(), StatusCode::OK);
     assert_eq!(
-        response_body,
-        json!([{ "id": 2, "team_name": "Golden State Warriors" }])
+        res.headers()[header::CONTENT_TYPE],
+        "application/vnd.apache.parquet"
     );
-    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_DISPOSITION], "attachment");
+
+    // Parquet files start and end with the `PAR1` magic number
+    let mut body = vec![];
+    res.copy_to(&mut body).unwrap();
+    assert!(body.starts_with(b"PAR1"));
+    assert!(body.ends_with(b"PAR1"));
 }
 
+#[cfg(feature = "arrow-export")]
 #[test]
-fn delete_table_records_no_confirm() {
+fn post_table_records_returning_columns_arrow() {
     run_setup();
 
     let url = [
@@ -1050,54 +1675,122 @@ fn delete_table_records_no_confirm() {
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/delete_simple",
+        "/api/test_insert?returning_columns=id",
     ]
     .join("");
-    let res = Client::new().request(Method::DELETE, &url).send().unwrap();
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .header(header::ACCEPT, "application/vnd.apache.arrow.stream")
+        .json(&json!([{"id": 30}]))
+        .send()
+        .unwrap();
 
-    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(res.status(), StatusCode::NOT_ACCEPTABLE);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body["code"], json!("UNSUPPORTED_RESPONSE_FORMAT"));
 }
 
 #[test]
-fn delete_table_records_simple() {
+fn get_table_records_range_operators() {
+    run_setup();
+
+    let get_ids = |port: &str, conditions: &str| -> Value {
+        let url = [
+            "http://",
+            &SERVER_IP,
+            ":",
+            port,
+            "/api/test_ranges?columns=id&order_by=id&where=",
+            conditions,
+        ]
+        .join("");
+        let mut res = reqwest::get(&url).unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        res.json().unwrap()
+    };
+
+    for port in [*NO_CACHE_PORT, *CACHE_PORT].iter() {
+        // range @> element
+        assert_eq!(
+            get_ids(port, "during @> '2019-01-01T10:30:00Z'"),
+            json!([{ "id": 1 }])
+        );
+        // range && range (`&&` is URL-encoded)
+        assert_eq!(
+            get_ids(
+                port,
+                "during %26%26 '[2019-01-01T00:00:00Z,2019-01-03T00:00:00Z)'"
+            ),
+            json!([{ "id": 1 }, { "id": 2 }])
+        );
+        // range <@ range
+        assert_eq!(
+            get_ids(port, "seats <@ '[0,10)'"),
+            json!([{ "id": 1 }, { "id": 2 }])
+        );
+        // multirange @> element
+        assert_eq!(
+            get_ids(port, "closed @> '2019-01-06'"),
+            json!([{ "id": 1 }])
+        );
+        // element <@ range
+        assert_eq!(get_ids(port, "id <@ '[2,3)'"), json!([{ "id": 2 }]));
+    }
+}
+
+#[test]
+fn get_table_records_schema_qualified() {
     run_setup();
 
+    let expected = json!([
+        {
+            "id": 1,
+            "description": "Swords",
+            "invoice_id.total": 5000,
+            "invoice_id.company_id.name": "Stark Corporation"
+        },
+        {
+            "id": 2,
+            "description": "Direwolf food",
+            "invoice_id.total": 5000,
+            "invoice_id.company_id.name": "Stark Corporation"
+        }
+    ]);
+
+    // test the non-cached path
     let url = [
         "http://",
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/delete_simple?confirm_delete",
+        "/api/billing.line_item?columns=id,description,invoice_id.total,invoice_id.company_id.name&order_by=id",
     ]
     .join("");
-    let mut res = Client::new().request(Method::DELETE, &url).send().unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut res = reqwest::get(&url).unwrap();
 
-    assert_eq!(response_body, json!({ "num_rows": 3 }));
     assert_eq!(res.status(), StatusCode::OK);
-}
-
-#[test]
-fn delete_table_records_conditions() {
-    run_setup();
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, expected);
 
+    // test the cached path
     let url = [
         "http://",
         &SERVER_IP,
         ":",
-        &NO_CACHE_PORT,
-        "/api/delete_a?confirm_delete&where=id%3D1",
+        &CACHE_PORT,
+        "/api/billing.line_item?columns=id,description,invoice_id.total,invoice_id.company_id.name&order_by=id",
     ]
     .join("");
-    let mut res = Client::new().request(Method::DELETE, &url).send().unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut res = reqwest::get(&url).unwrap();
 
-    assert_eq!(response_body, json!({ "num_rows": 1 }));
     assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, expected);
 }
 
 #[test]
-fn delete_table_records_conditions_return_fk_columns() {
+fn get_table_records_unexposed_schema() {
     run_setup();
 
     let url = [
@@ -1105,51 +1798,2009 @@ fn delete_table_records_conditions_return_fk_columns() {
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/delete_a?confirm_delete&where=id%3D3&returning_columns=b_id.id",
+        "/api/pg_catalog.pg_class?columns=relname",
     ]
     .join("");
-    let mut res = Client::new().request(Method::DELETE, &url).send().unwrap();
-    let response_body: Value = res.json().unwrap();
+    let res = reqwest::get(&url).unwrap();
 
-    assert_eq!(response_body, json!([{ "b_id.id": 2 }]));
-    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
 }
 
 #[test]
-fn delete_table_records_return_fk_column_alias() {
+fn get_table_records_embedded_rows() {
     run_setup();
 
+    let expected = json!([{
+        "id": 100,
+        "name": "Stark Corporation",
+        "adult": [{ "id": 1, "name": "Ned" }],
+    }]);
+
+    // test the non-cached path
     let url = [
         "http://",
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/delete_a?confirm_delete&where=id%3D5&returning_columns=b_id.id as b_id",
+        "/api/company?columns=id,name,adult[id,name]",
     ]
     .join("");
-    let mut res = Client::new().request(Method::DELETE, &url).send().unwrap();
-    let response_body: Value = res.json().unwrap();
+    let mut res = reqwest::get(&url).unwrap();
 
-    assert_eq!(response_body, json!([{ "b_id": 4 }]));
     assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, expected);
+
+    // test the cached path
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &CACHE_PORT,
+        "/api/company?columns=id,name,adult[id,name]",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, expected);
+}
+
+#[test]
+fn get_table_records_embedded_rows_options() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &CACHE_PORT,
+        "/api/billing.invoice?columns=id,billing.line_item[id,description;where=id > 0;order_by=id desc;limit=1] as last_item,billing.line_item[id;where=id < 2] first_item",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(
+        body,
+        json!([{
+            "id": 1,
+            "last_item": [{ "id": 2, "description": "Direwolf food" }],
+            "first_item": [{ "id": 1 }],
+        }])
+    );
+}
+
+#[test]
+fn get_table_records_embedded_rows_multi_column_foreign_key() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/child?columns=id,sibling[name],school_id.name&nested",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(
+        body,
+        json!([{
+            "id": 1000,
+            "school_id": { "name": "Winterfell Tower" },
+            "sibling": [{ "name": "Sansa" }],
+        }])
+    );
+}
+
+#[test]
+fn get_table_records_embedded_rows_not_referencing() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/school?columns=id,adult[id]",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body["code"], "REFERENCING_TABLE_NOT_FOUND");
+}
+
+#[test]
+fn get_table_records_many_to_many() {
+    run_setup();
+
+    let expected = json!([
+        {
+            "title": "Winter is coming",
+            "tag.name": ["stark", "weather"],
+            "tag_ids": [1, 2],
+        },
+        {
+            "title": "Hear me roar",
+            "tag.name": [],
+            "tag_ids": [],
+        },
+    ]);
+
+    // test the non-cached path
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/post?columns=title,tag.name,tag.id as tag_ids&order_by=id",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, expected);
+
+    // test the cached path
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &CACHE_PORT,
+        "/api/post?columns=title,tag.name,tag.id as tag_ids&order_by=id",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    let body: Value = res.json().unwrap();
+    assert_eq!(body, expected);
+}
+
+#[test]
+fn get_table_records_count_exact() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/coach?columns=id&order_by=id&limit=2&offset=1&count=exact",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!([{ "id": 2 }, { "id": 3 }]));
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers()[header::CONTENT_RANGE], "1-2/5");
+}
+
+#[test]
+fn get_table_records_count_exact_where() {
+    run_setup();
+
+    for (query, expected_content_range) in [
+        ("&where=id%3E3", "0-1/2"),
+        ("&where=id%3E3&offset=1", "1-1/2"),
+        ("&offset=10", "*/5"),
+    ]
+    .iter()
+    {
+        let url = [
+            "http://",
+            &SERVER_IP,
+            ":",
+            &NO_CACHE_PORT,
+            "/api/coach?columns=id&count=exact",
+            query,
+        ]
+        .join("");
+        let res = reqwest::get(&url).unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers()[header::CONTENT_RANGE],
+            *expected_content_range
+        );
+    }
+}
+
+#[test]
+fn get_table_records_count_planned_and_estimated() {
+    run_setup();
+
+    for count_mode in ["planned", "estimated"].iter() {
+        let url = [
+            "http://",
+            &SERVER_IP,
+            ":",
+            &NO_CACHE_PORT,
+            "/api/coach?columns=id&where=id%3E3&count=",
+            count_mode,
+        ]
+        .join("");
+        let res = reqwest::get(&url).unwrap();
+
+        assert_eq!(res.status(), StatusCode::OK);
+        let content_range = res.headers()[header::CONTENT_RANGE].to_str().unwrap();
+        let total_count = content_range.rsplit('/').next().unwrap();
+        assert!(total_count.parse::<i64>().unwrap() >= 0);
+    }
+}
+
+#[test]
+fn get_table_records_count_invalid() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/coach?columns=id&count=all",
+    ]
+    .join("");
+    let res = reqwest::get(&url).unwrap();
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn get_table_records_no_count() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/coach?columns=id",
+    ]
+    .join("");
+    let res = reqwest::get(&url).unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(res.headers().get(header::CONTENT_RANGE), None);
+}
+
+/// Requests every page of rows using cursors, starting with an empty cursor.
+fn get_cursor_pages(path: &str) -> Vec<Value> {
+    let mut pages = vec![];
+    let mut cursor = String::new();
+
+    loop {
+        let url = [
+            "http://",
+            &SERVER_IP,
+            ":",
+            &NO_CACHE_PORT,
+            path,
+            "&cursor=",
+            &cursor,
+        ]
+        .join("");
+        let mut res = reqwest::get(&url).unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        pages.push(res.json().unwrap());
+
+        match res.headers().get("next-cursor") {
+            Some(next_cursor) => cursor = next_cursor.to_str().unwrap().to_string(),
+            None => return pages,
+        }
+    }
+}
+
+#[test]
+fn get_table_records_cursor() {
+    run_setup();
+
+    assert_eq!(
+        get_cursor_pages("/api/coach?columns=id&limit=2"),
+        vec![
+            json!([{ "id": 1 }, { "id": 2 }]),
+            json!([{ "id": 3 }, { "id": 4 }]),
+            json!([{ "id": 5 }]),
+        ]
+    );
+}
+
+#[test]
+fn get_table_records_cursor_order_by() {
+    run_setup();
+
+    assert_eq!(
+        get_cursor_pages("/api/coach?columns=id,name&order_by=name%20desc&limit=2"),
+        vec![
+            json!([
+                { "id": 1, "name": "Steve Kerr" },
+                { "id": 5, "name": "Mike D'Antoni" }
+            ]),
+            json!([
+                { "id": 3, "name": "Kenny Atkinson" },
+                { "id": 2, "name": "Doc Rivers" }
+            ]),
+            json!([{ "id": 4, "name": "Bill Donovan" }]),
+        ]
+    );
+}
+
+#[test]
+fn get_table_records_cursor_foreign_key_order_by() {
+    run_setup();
+
+    // Hodor has no department, so it’s sorted last. The last page is full, so it has a cursor
+    // (after NULL), whose page is empty
+    assert_eq!(
+        get_cursor_pages(
+            "/api/employee?columns=id,department_id.name&order_by=department_id.name&limit=1"
+        ),
+        vec![
+            json!([{ "id": 1, "department_id.name": "Night's Watch" }]),
+            json!([{ "id": 2, "department_id.name": null }]),
+            json!([]),
+        ]
+    );
+}
+
+#[test]
+fn get_table_records_cursor_invalid() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/coach?columns=id&order_by=name&limit=2&cursor=",
+    ]
+    .join("");
+    let res = reqwest::get(&url).unwrap();
+    let cursor = res.headers()["next-cursor"].to_str().unwrap();
+    // a cursor can’t be used with a different sort order
+    let other_order_by_query = ["&order_by=name%20desc&cursor=", cursor].join("");
+
+    for query in [
+        "&order_by=name&cursor=&offset=1",
+        "&order_by=name&cursor=not_a_cursor",
+        other_order_by_query.as_str(),
+    ]
+    .iter()
+    {
+        let url = [
+            "http://",
+            &SERVER_IP,
+            ":",
+            &NO_CACHE_PORT,
+            "/api/coach?columns=id",
+            query,
+        ]
+        .join("");
+        let res = reqwest::get(&url).unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+#[test]
+fn get_table_row() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/employee/1",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(
+        response_body,
+        json!({ "id": 1, "department_id": 1, "name": "Jon" })
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn get_table_row_columns() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/employee/1?columns=name,department_id.name",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(
+        response_body,
+        json!({ "name": "Jon", "department_id.name": "Night's Watch" })
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn get_table_row_not_found() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/employee/3",
+    ]
+    .join("");
+    let res = reqwest::get(&url).unwrap();
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn get_table_row_composite_primary_key() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/tenant_order/2,1",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(
+        response_body,
+        json!({ "tenant_id": 2, "id": 1, "note": "second tenant" })
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn get_table_row_composite_primary_key_missing_value() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/tenant_order/2",
+    ]
+    .join("");
+    let res = reqwest::get(&url).unwrap();
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn get_table_row_composite_primary_key_encoded_comma() {
+    run_setup();
+
+    // a percent-encoded comma is part of a value, not a delimiter
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/tenant_order/2%2C1",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body["code"], json!("INVALID_PRIMARY_KEY"));
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn get_table_row_invalid_primary_key() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/employee/abc",
+    ]
+    .join("");
+    let res = reqwest::get(&url).unwrap();
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn post_table_record() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_insert",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{"id": 1}]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!({ "num_rows": 1 }));
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn post_table_records() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_insert",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{"id": 2}, {"id": 3}]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!({ "num_rows": 2 }));
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn post_table_records_no_body() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_insert",
+    ]
+    .join("");
+    let res = Client::new().request(Method::POST, &url).send().unwrap();
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn post_table_records_returning_columns() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_insert?returning_columns=id, name",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{"id": 4, "name": "A"}, {"id": 5, "name": "b"}]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(
+        response_body,
+        json!([{ "id": 4, "name": "A" }, { "id": 5, "name": "b" }])
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn post_table_records_returning_columns_csv() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_insert?returning_columns=id, name",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .header(header::ACCEPT, "text/csv")
+        .json(&json!([
+            {"id": 20, "name": "a, \"b\""},
+            {"id": 21, "name": ""},
+            {"id": 22, "name": null}
+        ]))
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.text().unwrap(),
+        "id,name\r\n20,\"a, \"\"b\"\"\"\r\n21,\"\"\r\n22,\r\n"
+    );
+}
+
+#[test]
+fn post_table_records_returning_columns_compact() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_insert?returning_columns=name, id&compact",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{"id": 41, "name": "compact"}]))
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.text().unwrap(),
+        r#"{"columns":["name","id"],"rows":[["compact",41]]}"#
+    );
+}
+
+#[test]
+fn post_table_records_msgpack_body() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_insert?returning_columns=id, name",
+    ]
+    .join("");
+
+    let mut body = vec![];
+    rmpv::encode::write_value(
+        &mut body,
+        &rmpv::Value::Array(vec![rmpv::Value::Map(vec![
+            (rmpv::Value::from("id"), rmpv::Value::from(40)),
+            (rmpv::Value::from("name"), rmpv::Value::from("msgpack")),
+        ])]),
+    )
+    .unwrap();
+
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .header(header::CONTENT_TYPE, "application/msgpack")
+        .body(body)
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(
+        res.json::<Value>().unwrap(),
+        json!([{ "id": 40, "name": "msgpack" }])
+    );
+}
+
+#[test]
+fn post_table_records_arrays() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_arrays?returning_columns=id,tags,matrix",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{"id": 3, "tags": ["a", null], "matrix": [[1], [2], [3]]}]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(
+        response_body,
+        json!([{ "id": 3, "tags": ["a", null], "matrix": [[1], [2], [3]] }])
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn post_table_records_arrays_mismatched_dimensions() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_arrays",
+    ]
+    .join("");
+    let res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{"id": 4, "matrix": [[1, 2], [3]]}]))
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn post_table_records_enums_and_domains() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_enums?returning_columns=id,status,bannermen",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{"id": 3, "status": "sworn", "bannermen": 5}]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(
+        response_body,
+        json!([{ "id": 3, "status": "sworn", "bannermen": 5 }])
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn post_table_records_invalid_enum_label() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_enums",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{"id": 4, "status": "kneeling"}]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response_body["code"], json!("INVALID_ENUM_LABEL"));
+}
+
+#[test]
+fn post_table_records_hstore_and_bit_strings() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_hstore?returning_columns=id,tags,flags,mask",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{
+            "id": 3,
+            "tags": { "color": "black", "sigil": null },
+            "flags": "0011",
+            "mask": "111"
+        }]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(
+        response_body,
+        json!([{
+            "id": 3,
+            "tags": { "color": "black", "sigil": null },
+            "flags": "0011",
+            "mask": "111"
+        }])
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn post_table_records_ranges() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_ranges?returning_columns=id,during,seats,closed",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{
+            "id": 3,
+            "during": { "lower": "2019-03-01T10:00:00Z", "upper": "2019-03-01T12:00:00Z", "upper_inclusive": true },
+            "seats": "(0,2]",
+            "closed": [{ "lower": "2019-03-02", "upper": null }]
+        }]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(
+        response_body,
+        json!([{
+            "id": 3,
+            "during": {
+                "lower": "2019-03-01T10:00:00Z",
+                "upper": "2019-03-01T12:00:00Z",
+                "lower_inclusive": true,
+                "upper_inclusive": true
+            },
+            // discrete ranges are normalized by postgres
+            "seats": { "lower": 1, "upper": 3, "lower_inclusive": true, "upper_inclusive": false },
+            "closed": [{ "lower": "2019-03-02", "upper": null, "lower_inclusive": true, "upper_inclusive": false }]
+        }])
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn post_table_records_ranges_exclusion_violation() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_ranges",
+    ]
+    .join("");
+    let res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{
+            "id": 4,
+            "during": "[2019-01-01T10:30:00Z,2019-01-01T10:45:00Z)"
+        }]))
+        .send()
+        .unwrap();
+
+    assert_ne!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn post_table_records_on_conflict_do_nothing() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_insert",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{"id": 14, "name": "A"}]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!({ "num_rows": 1 }));
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // now attempt to send same request but with different name
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_insert?conflict_action=nothing&conflict_target=id",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{"id": 14, "name": "B"}]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!({ "num_rows": 0 }));
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn post_table_records_on_conflict_do_nothing_returning_columns() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_insert?returning_columns=id, name",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{"id": 10, "name": "A"}]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!([{ "id": 10, "name": "A" }]));
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // now attempt to send same request but with different name
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_insert?returning_columns=id, name&conflict_action=nothing&conflict_target=id",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{"id": 10, "name": "B"}, {"id": 11, "name": "C"}]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!([{ "id": 11, "name": "C" }]));
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn post_table_records_on_conflict_update() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_insert?returning_columns=id, name",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{"id": 12, "name": "A"}]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!([{ "id": 12, "name": "A" }]));
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // now attempt to send same request but with different name
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/test_insert?returning_columns=id, name&conflict_action=update&conflict_target=id",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([{"id": 12, "name": "B"}, {"id": 13, "name": "C"}]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(
+        response_body,
+        json!([{"id": 12, "name": "B"}, {"id": 13, "name": "C"}])
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn put_table_records_simple() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/player?where=id%3D5",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::PUT, &url)
+        .json(&json!({"team_id": 5}))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!({ "num_rows": 1 }));
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn put_table_records_simple_returning_columns() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/player?where=id%3D5&returning_columns=team_id",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::PUT, &url)
+        .json(&json!({"team_id": 5}))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!([{ "team_id": 5 }]));
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn put_table_records_string_value() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/player?where=name%3D'Russell Westbrook'&returning_columns=name",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::PUT, &url)
+        .json(&json!({"name": "'Chris Paul'"}))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!([{ "name": "Chris Paul" }]));
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn put_table_records_fk_in_where() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/player?where=team_id.name%3D'LA Clippers'",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::PUT, &url)
+        .json(&json!({"team_id": 3}))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!({ "num_rows": 2 }));
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn put_table_records_fk_in_body() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/player?where=id%3D1&returning_columns=id, name",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::PUT, &url)
+        .json(&json!({"name": "team_id.name"}))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(
+        response_body,
+        json!([{ "id": 1, "name": "Golden State Warriors" }])
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn put_table_records_multi_column_fk_in_body() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/order_item?where=id%3D3&returning_columns=id, name, tenant_id.note",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::PUT, &url)
+        .json(&json!({"name": "order_id.note"}))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(
+        response_body,
+        json!([{ "id": 3, "name": "second tenant", "tenant_id.note": "second tenant" }])
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn put_table_records_nested_fk_in_returning_columns() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/player?where=id%3D2&returning_columns=id, name, team_id.name, team_id.coach_id.name",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::PUT, &url)
+        .json(&json!({"name": "team_id.coach_id.name"}))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(
+        response_body,
+        json!([{ "id": 2, "name": "Steve Kerr", "team_id.name": "Golden State Warriors", "team_id.coach_id.name": "Steve Kerr" }])
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn put_table_records_nested_fk_in_returning_columns_nested_objects() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/player?where=id%3D2&returning_columns=id, team_id.name, team_id.coach_id.name&nested",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::PUT, &url)
+        .json(&json!({"name": "team_id.coach_id.name"}))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(
+        response_body,
+        json!([{ "id": 2, "team_id": { "name": "Golden State Warriors", "coach_id": { "name": "Steve Kerr" } } }])
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn put_table_records_nested_fk_in_returning_column_aliases() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/player?where=id%3D2&returning_columns=id, team_id.name as team_name",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::PUT, &url)
+        .json(&json!({"name": "team_id.coach_id.name"}))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(
+        response_body,
+        json!([{ "id": 2, "team_name": "Golden State Warriors" }])
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn put_table_records_nested_fk_in_returning_column_aliases_shortened() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/player?where=id%3D2&returning_columns=id, team_id.name team_name",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::PUT, &url)
+        .json(&json!({"name": "team_id.coach_id.name"}))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(
+        response_body,
+        json!([{ "id": 2, "team_name": "Golden State Warriors" }])
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn put_table_row() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/delete_a/7",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::PUT, &url)
+        .json(&json!({"b_id": 2}))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!({ "id": 7, "b_id": 2 }));
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn patch_table_row_returning_columns() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/delete_a/8?returning_columns=b_id.id",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::PATCH, &url)
+        .json(&json!({"b_id": 3}))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!({ "b_id.id": 3 }));
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn put_table_row_not_found() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/delete_a/100",
+    ]
+    .join("");
+    let res = Client::new()
+        .request(Method::PUT, &url)
+        .json(&json!({"b_id": 2}))
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn delete_table_records_no_confirm() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/delete_simple",
+    ]
+    .join("");
+    let res = Client::new().request(Method::DELETE, &url).send().unwrap();
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn delete_table_records_simple() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/delete_simple?confirm_delete",
+    ]
+    .join("");
+    let mut res = Client::new().request(Method::DELETE, &url).send().unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!({ "num_rows": 3 }));
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn delete_table_records_conditions() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/delete_a?confirm_delete&where=id%3D1",
+    ]
+    .join("");
+    let mut res = Client::new().request(Method::DELETE, &url).send().unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!({ "num_rows": 1 }));
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn delete_table_records_conditions_return_fk_columns() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/delete_a?confirm_delete&where=id%3D3&returning_columns=b_id.id",
+    ]
+    .join("");
+    let mut res = Client::new().request(Method::DELETE, &url).send().unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!([{ "b_id.id": 2 }]));
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn delete_table_records_return_fk_columns_nested_objects() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/delete_a?confirm_delete&where=id%3D4&returning_columns=id,b_id.id&nested",
+    ]
+    .join("");
+    let mut res = Client::new().request(Method::DELETE, &url).send().unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!([{ "id": 4, "b_id": { "id": 3 } }]));
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn delete_table_records_return_fk_column_alias() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/delete_a?confirm_delete&where=id%3D5&returning_columns=b_id.id as b_id",
+    ]
+    .join("");
+    let mut res = Client::new().request(Method::DELETE, &url).send().unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!([{ "b_id": 4 }]));
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn delete_table_records_return_fk_column_alias_shortened() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/delete_a?confirm_delete&where=id%3D6&returning_columns=b_id.id b_id",
+    ]
+    .join("");
+    let mut res = Client::new().request(Method::DELETE, &url).send().unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!([{ "b_id": 4 }]));
+    assert_eq!(res.status(), StatusCode::OK);
+}
+
+#[test]
+fn delete_table_row() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/delete_a/9",
+    ]
+    .join("");
+    let mut res = Client::new().request(Method::DELETE, &url).send().unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!({ "id": 9, "b_id": 4 }));
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // the row is gone
+    let res = Client::new().request(Method::DELETE, &url).send().unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn post_batch() {
+    run_setup();
+
+    let url = ["http://", &SERVER_IP, ":", &NO_CACHE_PORT, "/api/$batch"].join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([
+            {
+                "operation": "insert",
+                "table": "author",
+                "params": {"returning_columns": "id, name"},
+                "body": [{"name": "Arya"}]
+            },
+            {
+                "operation": "insert",
+                "table": "book",
+                "params": {"returning_columns": "id"},
+                "body": [
+                    {"author_id": "${0.id}", "title": "Needle"},
+                    {"author_id": "${0.id}", "title": "Faceless"}
+                ]
+            },
+            {
+                "operation": "update",
+                "table": "book",
+                "params": {"where": "id = ${1[1].id}"},
+                "body": {"title": "'No One'"}
+            },
+            {
+                "operation": "select",
+                "table": "book",
+                "params": {
+                    "columns": "title, author_id.name",
+                    "where": "author_id = ${0.id}",
+                    "order_by": "id"
+                }
+            }
+        ]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(response_body[0][0]["name"], json!("Arya"));
+    assert_eq!(response_body[1].as_array().unwrap().len(), 2);
+    assert_eq!(response_body[2], json!({ "num_rows": 1 }));
+    assert_eq!(
+        response_body[3],
+        json!([
+            { "title": "Needle", "author_id.name": "Arya" },
+            { "title": "No One", "author_id.name": "Arya" }
+        ])
+    );
+}
+
+#[test]
+fn post_batch_rollback() {
+    run_setup();
+
+    let url = ["http://", &SERVER_IP, ":", &NO_CACHE_PORT, "/api/$batch"].join("");
+    let res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!([
+            {
+                "operation": "insert",
+                "table": "author",
+                "params": {"returning_columns": "id"},
+                "body": [{"name": "Jaqen"}]
+            },
+            {
+                // `title` can’t be NULL
+                "operation": "insert",
+                "table": "book",
+                "body": [{"author_id": "${0.id}"}]
+            }
+        ]))
+        .send()
+        .unwrap();
+
+    assert!(!res.status().is_success());
+
+    // the first insert was rolled back too
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/author?columns=id&where=name%3D'Jaqen'",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!([]));
+}
+
+#[test]
+fn post_batch_invalid() {
+    run_setup();
+
+    let url = ["http://", &SERVER_IP, ":", &NO_CACHE_PORT, "/api/$batch"].join("");
+
+    for body in [
+        json!({"operation": "select", "table": "author"}),
+        json!([{"operation": "upsert", "table": "author", "body": [{"name": "Syrio"}]}]),
+        json!([{"operation": "delete", "table": "author"}]),
+        json!([{"operation": "select", "table": "author", "params": {"columns": "id", "count": "exact"}}]),
+        // operations can only reference earlier operations
+        json!([
+            {"operation": "select", "table": "author", "params": {"columns": "id", "where": "id = ${1.id}"}},
+            {"operation": "select", "table": "author", "params": {"columns": "id"}}
+        ]),
+        // the referenced operation has to return rows
+        json!([
+            {"operation": "insert", "table": "author", "body": [{"name": "Syrio"}]},
+            {"operation": "select", "table": "author", "params": {"columns": "id", "where": "id = ${0.id}"}}
+        ]),
+    ]
+    .iter()
+    {
+        let res = Client::new()
+            .request(Method::POST, &url)
+            .json(body)
+            .send()
+            .unwrap();
+
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+    }
+}
+
+/// Starts a transaction with `POST /$tx`, and returns its ID. The requests of a transaction test
+/// share one client.
+fn begin_transaction(client: &Client, query_string: &str) -> String {
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/$tx",
+        query_string,
+    ]
+    .join("");
+    let mut res = client.request(Method::POST, &url).send().unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    response_body["transaction_id"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn post_transaction_commit() {
+    run_setup();
+    let client = Client::new();
+
+    let transaction_id = begin_transaction(&client, "");
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/author?returning_columns=name",
+    ]
+    .join("");
+    let mut res = client
+        .request(Method::POST, &url)
+        .header("Transaction-Id", transaction_id.as_str())
+        .json(&json!([{"name": "Brienne"}]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(res.status(), StatusCode::OK);
+    assert_eq!(response_body, json!([{ "name": "Brienne" }]));
+
+    // the row is only visible in the transaction until it is committed
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/author?columns=name&where=name%3D'Brienne'",
+    ]
+    .join("");
+    let mut res = client
+        .get(&url)
+        .header("Transaction-Id", transaction_id.as_str())
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+    assert_eq!(response_body, json!([{ "name": "Brienne" }]));
+
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+    assert_eq!(response_body, json!([]));
+
+    let commit_url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/$tx/",
+        &transaction_id,
+        "/commit",
+    ]
+    .join("");
+    let res = client.request(Method::POST, &commit_url).send().unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+    assert_eq!(response_body, json!([{ "name": "Brienne" }]));
+
+    // the transaction has ended
+    let res = client
+        .get(&url)
+        .header("Transaction-Id", transaction_id.as_str())
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn post_transaction_rollback() {
+    run_setup();
+    let client = Client::new();
+
+    let transaction_id = begin_transaction(&client, "?isolation_level=repeatable_read");
+
+    let url = ["http://", &SERVER_IP, ":", &NO_CACHE_PORT, "/api/author"].join("");
+    let res = client
+        .request(Method::POST, &url)
+        .header("Transaction-Id", transaction_id.as_str())
+        .json(&json!([{"name": "Gendry"}]))
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let rollback_url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/$tx/",
+        &transaction_id,
+        "/rollback",
+    ]
+    .join("");
+    let res = client.request(Method::POST, &rollback_url).send().unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/author?columns=id&where=name%3D'Gendry'",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+    assert_eq!(response_body, json!([]));
+}
+
+#[test]
+fn post_transaction_savepoints() {
+    run_setup();
+    let client = Client::new();
+
+    let transaction_id = begin_transaction(&client, "?isolation_level=serializable");
+    let tx_url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/$tx/",
+        &transaction_id,
+    ]
+    .join("");
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/author?returning_columns=id",
+    ]
+    .join("");
+    let mut res = client
+        .request(Method::POST, &url)
+        .header("Transaction-Id", transaction_id.as_str())
+        .json(&json!([{"name": "Podrick"}]))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+    let author_id = response_body[0]["id"].clone();
+
+    let res = client
+        .request(
+            Method::POST,
+            &[&tx_url, "/savepoints/before_books"].join(""),
+        )
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // `title` can’t be NULL
+    let url = ["http://", &SERVER_IP, ":", &NO_CACHE_PORT, "/api/book"].join("");
+    let res = client
+        .request(Method::POST, &url)
+        .header("Transaction-Id", transaction_id.as_str())
+        .json(&json!([{ "author_id": author_id }]))
+        .send()
+        .unwrap();
+    assert!(!res.status().is_success());
+
+    let res = client
+        .request(
+            Method::POST,
+            &[&tx_url, "/savepoints/before_books/rollback"].join(""),
+        )
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client
+        .request(Method::POST, &url)
+        .header("Transaction-Id", transaction_id.as_str())
+        .json(&json!([{ "author_id": author_id, "title": "Oathkeeper" }]))
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client
+        .request(
+            Method::POST,
+            &[&tx_url, "/savepoints/before_books/release"].join(""),
+        )
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let res = client
+        .request(Method::POST, &[&tx_url, "/commit"].join(""))
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/book?columns=title,author_id.name&where=title%3D'Oathkeeper'",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+    assert_eq!(
+        response_body,
+        json!([{ "title": "Oathkeeper", "author_id.name": "Podrick" }])
+    );
 }
 
 #[test]
-fn delete_table_records_return_fk_column_alias_shortened() {
+fn post_transaction_commit_failed() {
     run_setup();
+    let client = Client::new();
+
+    let transaction_id = begin_transaction(&client, "");
 
     let url = [
         "http://",
         &SERVER_IP,
         ":",
         &NO_CACHE_PORT,
-        "/api/delete_a?confirm_delete&where=id%3D6&returning_columns=b_id.id b_id",
+        "/api/author?returning_columns=id",
     ]
     .join("");
-    let mut res = Client::new().request(Method::DELETE, &url).send().unwrap();
+    let mut res = client
+        .request(Method::POST, &url)
+        .header("Transaction-Id", transaction_id.as_str())
+        .json(&json!([{"name": "Hot Pie"}]))
+        .send()
+        .unwrap();
     let response_body: Value = res.json().unwrap();
+    let author_id = response_body[0]["id"].clone();
 
-    assert_eq!(response_body, json!([{ "b_id": 4 }]));
+    // `title` can’t be NULL
+    let url = ["http://", &SERVER_IP, ":", &NO_CACHE_PORT, "/api/book"].join("");
+    let res = client
+        .request(Method::POST, &url)
+        .header("Transaction-Id", transaction_id.as_str())
+        .json(&json!([{ "author_id": author_id }]))
+        .send()
+        .unwrap();
+    assert!(!res.status().is_success());
+
+    let commit_url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/$tx/",
+        &transaction_id,
+        "/commit",
+    ]
+    .join("");
+    let res = client.request(Method::POST, &commit_url).send().unwrap();
+    assert_eq!(res.status(), StatusCode::CONFLICT);
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/author?columns=id&where=name%3D'Hot%20Pie'",
+    ]
+    .join("");
+    let mut res = reqwest::get(&url).unwrap();
+    let response_body: Value = res.json().unwrap();
+    assert_eq!(response_body, json!([]));
+}
+
+#[test]
+fn post_transaction_invalid() {
+    run_setup();
+    let client = Client::new();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/$tx?isolation_level=chaotic",
+    ]
+    .join("");
+    let res = client.request(Method::POST, &url).send().unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/author?columns=id",
+    ]
+    .join("");
+    let res = client
+        .get(&url)
+        .header("Transaction-Id", "not-a-transaction")
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+    // rows can’t be counted in a transaction
+    let transaction_id = begin_transaction(&client, "");
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/author?columns=id&count=exact",
+    ]
+    .join("");
+    let res = client
+        .get(&url)
+        .header("Transaction-Id", transaction_id.as_str())
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let tx_url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/$tx/",
+        &transaction_id,
+    ]
+    .join("");
+    let res = client
+        .request(Method::POST, &[&tx_url, "/savepoints/Not-Valid"].join(""))
+        .send()
+        .unwrap();
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    let res = client
+        .request(Method::POST, &[&tx_url, "/rollback"].join(""))
+        .send()
+        .unwrap();
     assert_eq!(res.status(), StatusCode::OK);
 }
 
@@ -1167,6 +3818,199 @@ fn execute_sql_incorrect_content_type() {
     assert_eq!(res.status(), StatusCode::BAD_REQUEST);
 }
 
+#[test]
+fn execute_sql_params() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/api/sql?is_return_rows",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!({
+            "sql": "SELECT id, name FROM adult WHERE company_id = $1 AND name = $2 AND $3::TEXT IS NULL;",
+            "params": [100, "Ned", null]
+        }))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!([{ "id": 1, "name": "Ned" }]));
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // the number of values has to match the number of parameters
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!({
+            "sql": "SELECT id FROM adult WHERE company_id = $1 AND name = $2;",
+            "params": [100]
+        }))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body["code"], json!("INVALID_SQL_PARAMETERS"));
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // values are converted to the types of the parameters
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!({
+            "sql": "SELECT id FROM adult WHERE company_id = $1;",
+            "params": ["one hundred"]
+        }))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body["code"], json!("INVALID_JSON_TYPE_CONVERSION"));
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // the statement has to be in `sql`
+    let res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!({ "statement": "SELECT 1;" }))
+        .send()
+        .unwrap();
+
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn execute_sql_read_only() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/read_only_api/sql?is_return_rows",
+    ]
+    .join("");
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .json(&json!({
+            "sql": "SELECT id, name FROM company WHERE id = $1;",
+            "params": [100]
+        }))
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(
+        response_body,
+        json!([{ "id": 100, "name": "Stark Corporation" }])
+    );
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // only SELECTs are allowed
+    for statement in &[
+        "DROP TABLE company;",
+        "DELETE FROM company;",
+        "TRUNCATE company;",
+    ] {
+        let mut res = Client::new()
+            .request(Method::POST, &url)
+            .header(header::CONTENT_TYPE, "text/plain")
+            .body(*statement)
+            .send()
+            .unwrap();
+        let response_body: Value = res.json().unwrap();
+
+        assert_eq!(response_body["code"], json!("SQL_STATEMENT_NOT_ALLOWED"));
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+    }
+
+    // SELECTs can't write either
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body("SELECT nextval('author_id_seq');")
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body["code"], json!("SQL_READ_ONLY_VIOLATION"));
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body("SELECT pg_sleep(2);")
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body["code"], json!("SQL_STATEMENT_TIMEOUT"));
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+    // the company table is still there
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body("SELECT name FROM company;")
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!([{ "name": "Stark Corporation" }]));
+}
+
+#[test]
+fn execute_sql_select_only() {
+    run_setup();
+
+    let url = [
+        "http://",
+        &SERVER_IP,
+        ":",
+        &NO_CACHE_PORT,
+        "/select_only_api/sql?is_return_rows",
+    ]
+    .join("");
+
+    // the transaction is READ ONLY without enabling it
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body("SELECT nextval('author_id_seq');")
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body["code"], json!("SQL_READ_ONLY_VIOLATION"));
+    assert_eq!(res.status(), StatusCode::FORBIDDEN);
+
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body("SELECT generate_series(1, 2) AS n;")
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body, json!([{ "n": 1 }, { "n": 2 }]));
+    assert_eq!(res.status(), StatusCode::OK);
+
+    // at most 2 rows can be returned
+    let mut res = Client::new()
+        .request(Method::POST, &url)
+        .header(header::CONTENT_TYPE, "text/plain")
+        .body("SELECT generate_series(1, 3) AS n;")
+        .send()
+        .unwrap();
+    let response_body: Value = res.json().unwrap();
+
+    assert_eq!(response_body["code"], json!("SQL_RESULT_TOO_LARGE"));
+    assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+}
+
 #[test]
 fn execute_sql_simple() {
     run_setup();
@@ -1,7 +1,7 @@
 use actix::spawn as actix_spawn;
 use actix_web::{test::block_fn, App, HttpServer};
 use futures::{stream::Stream, Future};
-use postgres_rest_actix::Config;
+use postgres_rest_actix::{Config, SqlStatementKind};
 use std::{fs::read_to_string, thread::spawn as thread_spawn};
 use tokio_postgres::{connect, NoTls};
 
@@ -24,12 +24,32 @@ pub fn start_web_server(db_url: &'static str, address: &'static str) {
     thread_spawn(move || {
         let address_no_cache = [address, no_cache_port].join(":");
 
+        // the configs are shared by every worker, so that they share connections and transactions
+        let mut api_config = Config::new(db_url, NoTls);
+        api_config
+            .set_schemas(&["public", "billing"])
+            .enable_custom_sql_url();
+
+        // custom SQL queries are restricted to SELECTs, in a READ ONLY transaction
+        let mut read_only_api_config = Config::new(db_url, NoTls);
+        read_only_api_config
+            .enable_custom_sql_url()
+            .set_custom_sql_statement_kinds(&[SqlStatementKind::Select])
+            .enable_custom_sql_read_only()
+            .set_custom_sql_statement_timeout(1000);
+
+        // allowing only SELECTs implies a READ ONLY transaction
+        let mut select_only_api_config = Config::new(db_url, NoTls);
+        select_only_api_config
+            .enable_custom_sql_url()
+            .set_custom_sql_statement_kinds(&[SqlStatementKind::Select])
+            .set_custom_sql_max_rows(2);
+
         HttpServer::new(move || {
-            App::new().service(
-                Config::new(db_url, NoTls)
-                    .enable_custom_sql_url()
-                    .generate_scope("/api"),
-            )
+            App::new()
+                .service(api_config.generate_scope("/api"))
+                .service(read_only_api_config.generate_scope("/read_only_api"))
+                .service(select_only_api_config.generate_scope("/select_only_api"))
         })
         .bind(&address_no_cache)
         .expect("Can not bind to port.")
@@ -42,17 +62,16 @@ pub fn start_web_server(db_url: &'static str, address: &'static str) {
     thread_spawn(move || {
         let address_cache = [address, cache_port].join(":");
 
-        HttpServer::new(move || {
-            App::new().service(
-                Config::new(db_url, NoTls)
-                    .enable_custom_sql_url()
-                    .generate_scope("/api"),
-            )
-        })
-        .bind(&address_cache)
-        .expect("Can not bind to port.")
-        .run()
-        .unwrap();
+        let mut api_config = Config::new(db_url, NoTls);
+        api_config
+            .set_schemas(&["public", "billing"])
+            .enable_custom_sql_url();
+
+        HttpServer::new(move || App::new().service(api_config.generate_scope("/api")))
+            .bind(&address_cache)
+            .expect("Can not bind to port.")
+            .run()
+            .unwrap();
 
         println!("Running server on {}", &address_cache);
     });
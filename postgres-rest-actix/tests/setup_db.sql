@@ -9,8 +9,12 @@ SET client_min_messages = warning;
 SET row_security = off;
 
 CREATE EXTENSION IF NOT EXISTS citext WITH SCHEMA public;
+CREATE EXTENSION IF NOT EXISTS hstore WITH SCHEMA public;
 CREATE EXTENSION IF NOT EXISTS pgcrypto WITH SCHEMA public;
 
+-- Dropped first, since its tables reference tables in the public schema
+DROP SCHEMA IF EXISTS billing CASCADE;
+
 -- For testing fields
 
 DROP TABLE IF EXISTS public.test_fields;
@@ -44,7 +48,13 @@ CREATE TABLE IF NOT EXISTS public.test_fields (
     test_varchar character varying,
     test_f64 double precision,
     test_decimal numeric(5,2),
-    test_numeric numeric(5,2)
+    test_numeric numeric(5,2),
+    test_inet inet,
+    test_cidr cidr,
+    test_interval interval,
+    test_timetz time with time zone,
+    test_money money,
+    test_xml xml
 );
 
 ALTER TABLE ONLY public.test_fields
@@ -80,7 +90,13 @@ INSERT INTO public.test_fields (
   test_varchar,
   test_f64,
   test_decimal,
-  test_numeric
+  test_numeric,
+  test_inet,
+  test_cidr,
+  test_interval,
+  test_timetz,
+  test_money,
+  test_xml
 ) VALUES (
   46327143679919107,
   -9001,
@@ -111,7 +127,13 @@ INSERT INTO public.test_fields (
   'a varchar',
   1.31479999999999997,
   100.01,
-  100.02
+  100.02,
+  '192.168.100.128',
+  '10.1.0.0/16',
+  '1 year 2 mons 3 days 04:05:06.5',
+  '04:05:06.789-08',
+  -1234.56,
+  '<winterfell><lord>Ned</lord></winterfell>'
 );
 
 -- For testing foreign keys
@@ -163,6 +185,71 @@ INSERT INTO public.adult (id, company_id, name) VALUES (1, 100, 'Ned');
 INSERT INTO public.child (id, name, parent_id, school_id) VALUES (1000, 'Robb', 1, 10);
 INSERT INTO public.sibling(id, name, parent_id, sibling_id) VALUES(2, 'Sansa', 1, 1000);
 
+-- For testing array fields
+
+DROP TABLE IF EXISTS public.test_arrays;
+CREATE TABLE IF NOT EXISTS public.test_arrays (
+  id BIGINT CONSTRAINT test_arrays_id_key PRIMARY KEY,
+  tags TEXT[],
+  matrix INTEGER[][],
+  uuids UUID[]
+);
+
+INSERT INTO public.test_arrays (id, tags, matrix, uuids) VALUES
+  (1, '{"winter", NULL, "is coming"}', '{{1, 2}, {3, 4}}', '{"0e49a9e6-9aa9-4c6c-9bb4-0f1d2f3a8d6b"}'),
+  (2, '{}', NULL, NULL);
+
+-- For testing enum and domain fields
+
+DROP TABLE IF EXISTS public.test_enums;
+DROP TYPE IF EXISTS public.house_status;
+DROP DOMAIN IF EXISTS public.positive_int;
+
+CREATE TYPE public.house_status AS ENUM ('ruling', 'sworn', 'extinct');
+CREATE DOMAIN public.positive_int AS INTEGER CHECK (VALUE > 0);
+
+CREATE TABLE IF NOT EXISTS public.test_enums (
+  id BIGINT CONSTRAINT test_enums_id_key PRIMARY KEY,
+  status public.house_status,
+  bannermen public.positive_int
+);
+
+INSERT INTO public.test_enums (id, status, bannermen) VALUES
+  (1, 'ruling', 12),
+  (2, 'extinct', NULL);
+
+-- For testing range fields
+
+DROP TABLE IF EXISTS public.test_ranges;
+CREATE TABLE IF NOT EXISTS public.test_ranges (
+  id BIGINT CONSTRAINT test_ranges_id_key PRIMARY KEY,
+  during TSTZRANGE,
+  seats INT4RANGE,
+  closed DATEMULTIRANGE,
+  CONSTRAINT test_ranges_no_overlap EXCLUDE USING gist (during WITH &&)
+);
+
+INSERT INTO public.test_ranges (id, during, seats, closed) VALUES
+  (1, '[2019-01-01 10:00:00+00,2019-01-01 11:00:00+00)', '[1,5)', '{[2019-01-05,2019-01-07), [2019-02-01,2019-02-02)}'),
+  (2, '[2019-01-02 10:00:00+00,)', 'empty', NULL);
+
+-- For testing hstore and bit string fields
+
+DROP TABLE IF EXISTS public.test_hstore;
+CREATE TABLE IF NOT EXISTS public.test_hstore (
+  id BIGINT CONSTRAINT test_hstore_id_key PRIMARY KEY,
+  tags public.hstore,
+  flags BIT(4),
+  mask BIT VARYING,
+  tag_history public.hstore[],
+  flag_history BIT(4)[],
+  masks BIT VARYING[]
+);
+
+INSERT INTO public.test_hstore (id, tags, flags, mask, tag_history, flag_history, masks) VALUES
+  (1, 'color=>red, "house words"=>"Winter is coming", sigil=>NULL', B'0101', B'1', ARRAY['color=>grey', NULL]::public.hstore[], '{0001,NULL}', '{1,10}'),
+  (2, 'color=>gold', B'1100', B'10110', NULL, NULL, '{}');
+
 -- For testing INSERTs
 
 DROP TABLE IF EXISTS public.test_insert;
@@ -254,6 +341,117 @@ INSERT INTO public.delete_a
   (3, 2),
   (4, 3),
   (5, 4),
-  (6, 4);
+  (6, 4),
+  (7, 1),
+  (8, 1),
+  (9, 4);
 
 INSERT INTO public.delete_simple (id) VALUES (1), (2), (3);
+
+-- For testing many-to-many references through junction tables
+
+DROP TABLE IF EXISTS public.post_tag;
+DROP TABLE IF EXISTS public.post;
+DROP TABLE IF EXISTS public.tag;
+
+CREATE TABLE IF NOT EXISTS public.post (
+  id BIGINT CONSTRAINT post_id_key PRIMARY KEY,
+  title TEXT
+);
+CREATE TABLE IF NOT EXISTS public.tag (
+  id BIGINT CONSTRAINT tag_id_key PRIMARY KEY,
+  name TEXT
+);
+CREATE TABLE IF NOT EXISTS public.post_tag (
+  post_id BIGINT,
+  tag_id BIGINT,
+  created_at TIMESTAMPTZ DEFAULT now(),
+  CONSTRAINT post_tag_key PRIMARY KEY (post_id, tag_id)
+);
+ALTER TABLE public.post_tag ADD CONSTRAINT post_tag_post_id FOREIGN KEY (post_id) REFERENCES public.post(id);
+ALTER TABLE public.post_tag ADD CONSTRAINT post_tag_tag_id FOREIGN KEY (tag_id) REFERENCES public.tag(id);
+
+INSERT INTO public.post (id, title) VALUES (1, 'Winter is coming'), (2, 'Hear me roar');
+INSERT INTO public.tag (id, name) VALUES (1, 'stark'), (2, 'weather'), (3, 'lannister');
+INSERT INTO public.post_tag (post_id, tag_id) VALUES (1, 2), (1, 1);
+
+-- For testing nullable foreign keys (LEFT JOINs)
+
+DROP TABLE IF EXISTS public.employee;
+DROP TABLE IF EXISTS public.department;
+
+CREATE TABLE IF NOT EXISTS public.department (
+  id BIGINT CONSTRAINT department_id_key PRIMARY KEY,
+  name TEXT
+);
+CREATE TABLE IF NOT EXISTS public.employee (
+  id BIGINT CONSTRAINT employee_id_key PRIMARY KEY,
+  department_id BIGINT,
+  name TEXT
+);
+ALTER TABLE public.employee ADD CONSTRAINT employee_department_id FOREIGN KEY (department_id) REFERENCES public.department(id);
+
+INSERT INTO public.department (id, name) VALUES (1, 'Night''s Watch');
+INSERT INTO public.employee (id, department_id, name) VALUES (1, 1, 'Jon'), (2, NULL, 'Hodor');
+
+-- For testing multi-column foreign keys in dot syntax
+
+DROP TABLE IF EXISTS public.order_item;
+DROP TABLE IF EXISTS public.tenant_order;
+
+CREATE TABLE IF NOT EXISTS public.tenant_order (
+  tenant_id BIGINT,
+  id BIGINT,
+  note TEXT,
+  CONSTRAINT tenant_order_key PRIMARY KEY (tenant_id, id)
+);
+CREATE TABLE IF NOT EXISTS public.order_item (
+  id BIGINT CONSTRAINT order_item_id_key PRIMARY KEY,
+  tenant_id BIGINT,
+  order_id BIGINT,
+  name TEXT
+);
+ALTER TABLE public.order_item ADD CONSTRAINT order_item_order FOREIGN KEY (tenant_id, order_id) REFERENCES public.tenant_order(tenant_id, id);
+
+INSERT INTO public.tenant_order (tenant_id, id, note) VALUES (1, 1, 'first tenant'), (2, 1, 'second tenant');
+INSERT INTO public.order_item (id, tenant_id, order_id, name) VALUES
+  (1, 1, 1, 'sword'),
+  (2, 2, 1, 'shield'),
+  (3, 2, 1, 'helmet');
+
+-- For testing batches
+
+DROP TABLE IF EXISTS public.book;
+DROP TABLE IF EXISTS public.author;
+
+CREATE TABLE IF NOT EXISTS public.author (
+  id BIGSERIAL CONSTRAINT author_id_key PRIMARY KEY,
+  name TEXT
+);
+CREATE TABLE IF NOT EXISTS public.book (
+  id BIGSERIAL CONSTRAINT book_id_key PRIMARY KEY,
+  author_id BIGINT,
+  title TEXT NOT NULL
+);
+ALTER TABLE public.book ADD CONSTRAINT book_author_id FOREIGN KEY (author_id) REFERENCES public.author(id);
+
+-- For testing multiple schemas
+
+CREATE SCHEMA billing;
+
+CREATE TABLE IF NOT EXISTS billing.invoice (
+  id BIGINT CONSTRAINT invoice_id_key PRIMARY KEY,
+  company_id BIGINT,
+  total BIGINT
+);
+ALTER TABLE billing.invoice ADD CONSTRAINT invoice_company_id FOREIGN KEY (company_id) REFERENCES public.company(id);
+
+CREATE TABLE IF NOT EXISTS billing.line_item (
+  id BIGINT CONSTRAINT line_item_id_key PRIMARY KEY,
+  invoice_id BIGINT,
+  description TEXT
+);
+ALTER TABLE billing.line_item ADD CONSTRAINT line_item_invoice_id FOREIGN KEY (invoice_id) REFERENCES billing.invoice(id);
+
+INSERT INTO billing.invoice (id, company_id, total) VALUES (1, 100, 5000);
+INSERT INTO billing.line_item (id, invoice_id, description) VALUES (1, 1, 'Swords'), (2, 1, 'Direwolf food');
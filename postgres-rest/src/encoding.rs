@@ -0,0 +1,374 @@
+use crate::Error;
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use rust_decimal::Decimal;
+use serde::{Serialize, Serializer};
+use serde_bytes::ByteBuf;
+use serde_cbor::{tags::Tagged, Value as CborValue};
+use serde_json::{Map, Number, Value as JsonValue};
+use std::{
+    cell::Cell,
+    convert::{TryFrom, TryInto},
+    io::Write,
+};
+
+/// CBOR tag for RFC 3339 date/time strings.
+const CBOR_DATE_TIME_TAG: u64 = 0;
+/// CBOR tag for numbers of seconds since the Unix epoch.
+const CBOR_EPOCH_TIME_TAG: u64 = 1;
+/// CBOR tag for decimal fractions (`[exponent, mantissa]`).
+const CBOR_DECIMAL_FRACTION_TAG: u64 = 4;
+
+/// The name `rmp-serde` uses to recognize MessagePack extension types.
+const MSGPACK_EXT_STRUCT_NAME: &str = "_ExtStruct";
+/// The MessagePack extension type reserved for timestamps.
+const MSGPACK_TIMESTAMP_EXT_TYPE: i8 = -1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// A binary encoding that query results can be serialized into (and request bodies parsed from).
+pub enum BinaryFormat {
+    Cbor,
+    MessagePack,
+}
+
+thread_local! {
+    /// The binary format that is currently being serialized into, if any. Column values whose
+    /// encoding depends on the format (timestamps, decimals) look it up while serializing.
+    static CURRENT_BINARY_FORMAT: Cell<Option<BinaryFormat>> = Cell::new(None);
+}
+
+/// Serializes a value into MessagePack or CBOR bytes.
+pub fn to_vec<T: Serialize>(value: &T, format: BinaryFormat) -> Result<Vec<u8>, Error> {
+    let mut bytes = vec![];
+    to_writer(&mut bytes, value, format)?;
+    Ok(bytes)
+}
+
+/// Serializes a value into a writer as MessagePack or CBOR. Maps are written with their keys
+/// (MessagePack structs are written as maps rather than arrays).
+pub fn to_writer<W: Write, T: Serialize>(
+    mut writer: W,
+    value: &T,
+    format: BinaryFormat,
+) -> Result<(), Error> {
+    let previous_format = CURRENT_BINARY_FORMAT.with(|current| current.replace(Some(format)));
+
+    let result = match format {
+        BinaryFormat::Cbor => serde_cbor::to_writer(writer, value).map_err(Error::from),
+        BinaryFormat::MessagePack => {
+            rmp_serde::encode::write_named(&mut writer, value).map_err(Error::from)
+        }
+    };
+
+    CURRENT_BINARY_FORMAT.with(|current| current.set(previous_format));
+    result
+}
+
+/// Parses MessagePack or CBOR bytes into JSON, so that they can be handled the same way as JSON
+/// request bodies. Byte strings become arrays of numbers, and tagged timestamps and decimals become
+/// strings.
+pub fn from_slice(bytes: &[u8], format: BinaryFormat) -> Result<JsonValue, Error> {
+    match format {
+        BinaryFormat::Cbor => Ok(cbor_to_json(serde_cbor::from_slice(bytes)?)),
+        BinaryFormat::MessagePack => {
+            let mut bytes = bytes;
+            Ok(msgpack_to_json(rmpv::decode::read_value(&mut bytes)?))
+        }
+    }
+}
+
+/// Serializes a `timestamptz` as a tagged date/time string (CBOR), a timestamp extension type
+/// (MessagePack), or a string (JSON).
+pub(crate) fn serialize_timestamptz<S: Serializer>(
+    timestamp: &DateTime<Utc>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match CURRENT_BINARY_FORMAT.with(Cell::get) {
+        Some(BinaryFormat::Cbor) => Tagged::new(
+            Some(CBOR_DATE_TIME_TAG),
+            timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        )
+        .serialize(serializer),
+        Some(BinaryFormat::MessagePack) => {
+            // timestamp 96: 32-bit nanoseconds followed by 64-bit seconds, both big-endian
+            let mut data = Vec::with_capacity(12);
+            data.extend_from_slice(&timestamp.timestamp_subsec_nanos().to_be_bytes());
+            data.extend_from_slice(&timestamp.timestamp().to_be_bytes());
+
+            serializer.serialize_newtype_struct(
+                MSGPACK_EXT_STRUCT_NAME,
+                &(MSGPACK_TIMESTAMP_EXT_TYPE, ByteBuf::from(data)),
+            )
+        }
+        None => timestamp.serialize(serializer),
+    }
+}
+
+/// Serializes a decimal as a tagged decimal fraction (CBOR) or a string (MessagePack, JSON).
+/// Decimals whose mantissa doesn’t fit into 64 bits are written as strings in CBOR as well.
+pub(crate) fn serialize_decimal<S: Serializer>(
+    decimal: &Decimal,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match (
+        CURRENT_BINARY_FORMAT.with(Cell::get),
+        decimal_fraction(decimal),
+    ) {
+        (Some(BinaryFormat::Cbor), Some(exponent_and_mantissa)) => {
+            Tagged::new(Some(CBOR_DECIMAL_FRACTION_TAG), exponent_and_mantissa)
+                .serialize(serializer)
+        }
+        _ => decimal.serialize(serializer),
+    }
+}
+
+/// Splits a decimal into a base-10 exponent and an integer mantissa, e.g. `100.01` into `(-2,
+/// 10001)`.
+fn decimal_fraction(decimal: &Decimal) -> Option<(i64, i64)> {
+    let decimal_str = decimal.to_string();
+    let (integer, fraction) = match decimal_str.find('.') {
+        Some(dot_index) => (&decimal_str[..dot_index], &decimal_str[dot_index + 1..]),
+        None => (decimal_str.as_str(), ""),
+    };
+    let mantissa = format!("{}{}", integer, fraction).parse::<i64>().ok()?;
+
+    Some((-(fraction.len() as i64), mantissa))
+}
+
+/// Formats a decimal fraction (`mantissa * 10^exponent`) as a decimal string.
+fn decimal_fraction_to_string(exponent: i128, mantissa: i128) -> Option<String> {
+    // rust_decimal supports at most 28 decimal places
+    if exponent.abs() > 28 {
+        return None;
+    }
+
+    if exponent >= 0 {
+        let zeros = "0".repeat(exponent as usize);
+        return Some(format!("{}{}", mantissa, zeros));
+    }
+
+    let scale = (-exponent) as u32;
+
+    Some(Decimal::new(mantissa.try_into().ok()?, scale).to_string())
+}
+
+fn cbor_to_json(value: CborValue) -> JsonValue {
+    match value {
+        CborValue::Null => JsonValue::Null,
+        CborValue::Bool(b) => JsonValue::Bool(b),
+        CborValue::Integer(i) => integer_to_json(i),
+        CborValue::Float(f) => float_to_json(f),
+        CborValue::Bytes(bytes) => bytes_to_json(bytes),
+        CborValue::Text(s) => JsonValue::String(s),
+        CborValue::Array(values) => {
+            JsonValue::Array(values.into_iter().map(cbor_to_json).collect())
+        }
+        CborValue::Map(entries) => JsonValue::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        CborValue::Text(key) => key,
+                        key => cbor_to_json(key).to_string(),
+                    };
+                    (key, cbor_to_json(value))
+                })
+                .collect::<Map<String, JsonValue>>(),
+        ),
+        CborValue::Tag(tag, tagged_value) => cbor_tag_to_json(tag, *tagged_value),
+        _ => JsonValue::Null,
+    }
+}
+
+fn cbor_tag_to_json(tag: u64, value: CborValue) -> JsonValue {
+    match (tag, value) {
+        (CBOR_EPOCH_TIME_TAG, CborValue::Integer(seconds)) => {
+            match seconds
+                .try_into()
+                .ok()
+                .and_then(|s| Utc.timestamp_opt(s, 0).single())
+            {
+                Some(timestamp) => JsonValue::String(timestamp.to_rfc3339()),
+                None => integer_to_json(seconds),
+            }
+        }
+        (CBOR_EPOCH_TIME_TAG, CborValue::Float(seconds)) => {
+            let nanos = (seconds.fract() * 1_000_000_000.0).round() as i64;
+            match Utc.timestamp_opt(seconds.trunc() as i64, 0).single() {
+                Some(timestamp) => JsonValue::String(
+                    (timestamp + chrono::Duration::nanoseconds(nanos)).to_rfc3339(),
+                ),
+                None => float_to_json(seconds),
+            }
+        }
+        (CBOR_DECIMAL_FRACTION_TAG, CborValue::Array(exponent_and_mantissa)) => {
+            let decimal_str = match exponent_and_mantissa.as_slice() {
+                [CborValue::Integer(exponent), CborValue::Integer(mantissa)] => {
+                    decimal_fraction_to_string(*exponent, *mantissa)
+                }
+                _ => None,
+            };
+
+            match decimal_str {
+                Some(decimal_str) => JsonValue::String(decimal_str),
+                None => cbor_to_json(CborValue::Array(exponent_and_mantissa)),
+            }
+        }
+        // the date/time string (tag 0) and unknown tags are passed through as their tagged value
+        (_, value) => cbor_to_json(value),
+    }
+}
+
+fn msgpack_to_json(value: rmpv::Value) -> JsonValue {
+    match value {
+        rmpv::Value::Nil => JsonValue::Null,
+        rmpv::Value::Boolean(b) => JsonValue::Bool(b),
+        rmpv::Value::Integer(i) => match (i.as_i64(), i.as_u64()) {
+            (Some(i), _) => JsonValue::Number(i.into()),
+            (None, Some(u)) => JsonValue::Number(u.into()),
+            (None, None) => JsonValue::Null,
+        },
+        rmpv::Value::F32(f) => float_to_json(f64::from(f)),
+        rmpv::Value::F64(f) => float_to_json(f),
+        rmpv::Value::String(s) => match s.into_str() {
+            Some(s) => JsonValue::String(s),
+            None => JsonValue::Null,
+        },
+        rmpv::Value::Binary(bytes) => bytes_to_json(bytes),
+        rmpv::Value::Array(values) => {
+            JsonValue::Array(values.into_iter().map(msgpack_to_json).collect())
+        }
+        rmpv::Value::Map(entries) => JsonValue::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = match key {
+                        rmpv::Value::String(key) => key.into_str().unwrap_or_default(),
+                        key => msgpack_to_json(key).to_string(),
+                    };
+                    (key, msgpack_to_json(value))
+                })
+                .collect::<Map<String, JsonValue>>(),
+        ),
+        rmpv::Value::Ext(MSGPACK_TIMESTAMP_EXT_TYPE, data) => match msgpack_timestamp(&data) {
+            Some(timestamp) => {
+                JsonValue::String(timestamp.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+            }
+            None => bytes_to_json(data),
+        },
+        rmpv::Value::Ext(_, data) => bytes_to_json(data),
+    }
+}
+
+/// Parses the data of a MessagePack timestamp extension (timestamp 32, 64 or 96).
+fn msgpack_timestamp(data: &[u8]) -> Option<DateTime<Utc>> {
+    let (seconds, nanos) = match data.len() {
+        4 => (i64::from(u32::from_be_bytes(data.try_into().ok()?)), 0),
+        8 => {
+            let value = u64::from_be_bytes(data.try_into().ok()?);
+            ((value & 0x3_ffff_ffff) as i64, (value >> 34) as u32)
+        }
+        12 => (
+            i64::from_be_bytes(data[4..].try_into().ok()?),
+            u32::from_be_bytes(data[..4].try_into().ok()?),
+        ),
+        _ => return None,
+    };
+
+    Utc.timestamp_opt(seconds, nanos).single()
+}
+
+fn integer_to_json(i: i128) -> JsonValue {
+    if let Ok(i) = i64::try_from(i) {
+        JsonValue::Number(Number::from(i))
+    } else if let Ok(u) = u64::try_from(i) {
+        JsonValue::Number(Number::from(u))
+    } else {
+        JsonValue::String(i.to_string())
+    }
+}
+
+fn float_to_json(f: f64) -> JsonValue {
+    Number::from_f64(f).map_or(JsonValue::Null, JsonValue::Number)
+}
+
+fn bytes_to_json(bytes: Vec<u8>) -> JsonValue {
+    JsonValue::Array(bytes.into_iter().map(JsonValue::from).collect())
+}
+
+#[cfg(test)]
+mod encoding_tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn decimal_fractions() {
+        assert_eq!(
+            decimal_fraction(&"100.01".parse().unwrap()),
+            Some((-2, 10001))
+        );
+        assert_eq!(decimal_fraction(&"-0.5".parse().unwrap()), Some((-1, -5)));
+        assert_eq!(decimal_fraction(&"42".parse().unwrap()), Some((0, 42)));
+
+        assert_eq!(
+            decimal_fraction_to_string(-2, 10001),
+            Some("100.01".to_string())
+        );
+        assert_eq!(
+            decimal_fraction_to_string(3, -12),
+            Some("-12000".to_string())
+        );
+    }
+
+    #[test]
+    fn cbor_tags_to_json() {
+        let decimal = Tagged::new(Some(CBOR_DECIMAL_FRACTION_TAG), (-2, 10001));
+        let timestamp = Tagged::new(Some(CBOR_DATE_TIME_TAG), "2019-01-01T00:00:00Z");
+        let bytes = to_vec(
+            &(decimal, timestamp, ByteBuf::from(vec![0, 10])),
+            BinaryFormat::Cbor,
+        )
+        .unwrap();
+
+        assert_eq!(
+            from_slice(&bytes, BinaryFormat::Cbor).unwrap(),
+            json!(["100.01", "2019-01-01T00:00:00Z", [0, 10]])
+        );
+    }
+
+    #[test]
+    fn msgpack_timestamps_to_json() {
+        let timestamp = Utc.ymd(2019, 1, 1).and_hms_milli(0, 0, 0, 500);
+
+        let mut timestamp_32 = vec![0xd6, 0xff];
+        timestamp_32.extend_from_slice(&(timestamp.timestamp() as u32).to_be_bytes());
+        assert_eq!(
+            from_slice(&timestamp_32, BinaryFormat::MessagePack).unwrap(),
+            json!("2019-01-01T00:00:00Z")
+        );
+
+        let serialized = to_vec(&SerializeWith(timestamp), BinaryFormat::MessagePack).unwrap();
+        assert_eq!(
+            from_slice(&serialized, BinaryFormat::MessagePack).unwrap(),
+            json!("2019-01-01T00:00:00.500Z")
+        );
+    }
+
+    #[test]
+    fn json_is_unchanged() {
+        let timestamp = Utc.ymd(2019, 1, 1).and_hms(0, 0, 0);
+        assert_eq!(
+            serde_json::to_value(SerializeWith(timestamp)).unwrap(),
+            serde_json::to_value(timestamp).unwrap()
+        );
+    }
+
+    /// Serializes a timestamp with `serialize_timestamptz`.
+    struct SerializeWith(DateTime<Utc>);
+
+    impl Serialize for SerializeWith {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize_timestamptz(&self.0, serializer)
+        }
+    }
+}
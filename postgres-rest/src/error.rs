@@ -1,7 +1,8 @@
-use failure::Fail;
+use failure::{Compat, Fail};
 use serde::Serialize;
+use std::error::Error as StdError;
 
-#[derive(Debug, Fail, Serialize)]
+#[derive(Clone, Debug, Fail, Serialize)]
 #[serde(untagged)]
 /// A wrapper around all the errors we can run into.
 pub enum Error {
@@ -38,6 +39,17 @@ impl From<actix::MailboxError> for Error {
         }
     }
 }
+#[cfg(feature = "arrow-export")]
+impl From<arrow::error::ArrowError> for Error {
+    fn from(err: arrow::error::ArrowError) -> Self {
+        Error::InternalError {
+            code: "ARROW_ERROR",
+            details: format!("{:?}", err),
+            message: "An error occurred when encoding rows as Arrow record batches.",
+            http_status: 500,
+        }
+    }
+}
 impl From<chrono::format::ParseError> for Error {
     fn from(err: chrono::format::ParseError) -> Self {
         Error::UserError {
@@ -60,6 +72,38 @@ impl From<eui48::ParseError> for Error {
         }
     }
 }
+#[cfg(feature = "arrow-export")]
+impl From<parquet::errors::ParquetError> for Error {
+    fn from(err: parquet::errors::ParquetError) -> Self {
+        Error::InternalError {
+            code: "PARQUET_ERROR",
+            details: format!("{}", err),
+            message: "An error occurred when writing rows to a Parquet file.",
+            http_status: 500,
+        }
+    }
+}
+impl From<rmp_serde::encode::Error> for Error {
+    fn from(err: rmp_serde::encode::Error) -> Self {
+        Error::InternalError {
+            code: "MSGPACK_ERROR",
+            details: format!("{}", err),
+            message: "An error occurred when encoding MessagePack.",
+            http_status: 500,
+        }
+    }
+}
+impl From<rmpv::decode::Error> for Error {
+    fn from(err: rmpv::decode::Error) -> Self {
+        Error::UserError {
+            code: "MSGPACK_ERROR",
+            details: format!("{}", err),
+            message: "An error occurred when parsing MessagePack.",
+            offender: "".to_string(),
+            http_status: 400,
+        }
+    }
+}
 impl From<rust_decimal::Error> for Error {
     fn from(err: rust_decimal::Error) -> Self {
         Error::UserError {
@@ -71,6 +115,17 @@ impl From<rust_decimal::Error> for Error {
         }
     }
 }
+impl From<serde_cbor::Error> for Error {
+    fn from(err: serde_cbor::Error) -> Self {
+        Error::UserError {
+            code: "CBOR_ERROR",
+            details: format!("{}", err),
+            message: "An error occurred when encoding or parsing CBOR.",
+            offender: "".to_string(),
+            http_status: 400,
+        }
+    }
+}
 impl From<serde_json::error::Error> for Error {
     fn from(err: serde_json::error::Error) -> Self {
         Error::UserError {
@@ -98,6 +153,16 @@ impl From<sqlparser::parser::ParserError> for Error {
         }
     }
 }
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::InternalError {
+            code: "IO_ERROR",
+            details: format!("{}", err),
+            message: "An I/O error occurred.",
+            http_status: 500,
+        }
+    }
+}
 impl<T> From<std::sync::PoisonError<T>> for Error {
     fn from(err: std::sync::PoisonError<T>) -> Self {
         Error::InternalError {
@@ -110,6 +175,14 @@ impl<T> From<std::sync::PoisonError<T>> for Error {
 }
 impl From<tokio_postgres::Error> for Error {
     fn from(err: tokio_postgres::Error) -> Self {
+        // errors raised by our own ToSql/FromSql implementations are passed through as-is
+        if let Some(compat_err) = err
+            .source()
+            .and_then(|source| source.downcast_ref::<Compat<Error>>())
+        {
+            return compat_err.get_ref().clone();
+        }
+
         Error::InternalError {
             code: "DATABASE_ERROR",
             details: format!("{}", err),
@@ -143,6 +216,21 @@ impl Error {
     /// Used to generate an Error
     pub fn generate_error(err_id: &'static str, offender: String) -> Self {
         match err_id {
+            "AMBIGUOUS_JUNCTION_TABLE" => Error::UserError {
+                code: err_id,
+                details: "Columns like `tags.name` are looked up through the junction table that links the selected table to `tags`. More than one table links them, so the column can’t be looked up.".to_string(),
+                http_status: 400,
+                message: "More than one junction table links the tables.",
+                offender,
+            },
+
+            "DB_POOL_CHECKOUT_TIMEOUT" => Error::InternalError {
+                code: err_id,
+                details: format!("No database connection became available within {}. Consider increasing the pool’s maximum size or checkout timeout.", offender),
+                http_status: 503,
+                message: "Timed out while waiting for a database connection from the connection pool.",
+            },
+
             "INCORRECT_REQUEST_BODY" => Error::UserError {
                 code: err_id,
                 details: "".to_string(),
@@ -151,6 +239,46 @@ impl Error {
                 offender,
             },
 
+            "INVALID_BATCH_REFERENCE" => Error::UserError {
+                code: err_id,
+                details: "Values returned by earlier operations of a batch are referenced as `${operation.column}` (the first returned row) or `${operation[row].column}`, where `operation` and `row` are zero-based. The operation must return rows (using `returning_columns` for inserts, updates and deletes).".to_string(),
+                http_status: 400,
+                message: "The referenced value was not returned by an earlier operation of the batch.",
+                offender,
+            },
+
+            "INVALID_CURSOR" => Error::UserError {
+                code: err_id,
+                details: "Cursors can only be used with the same `order_by` (and without `offset` or `group_by`) as the request that returned them. An empty cursor starts from the first row.".to_string(),
+                http_status: 400,
+                message: "The pagination cursor is invalid.",
+                offender,
+            },
+
+            "INVALID_EMBEDDED_ROWS" => Error::UserError {
+                code: err_id,
+                details: "Embedded rows are requested as `table[column, ...]`. The brackets can also hold `;where=...`, `;order_by=...`, `;limit=...` and `;offset=...` options.".to_string(),
+                http_status: 400,
+                message: "The embedded rows could not be parsed.",
+                offender,
+            },
+
+            "INVALID_ENUM_LABEL" => Error::UserError {
+                code: err_id,
+                details: "".to_string(),
+                http_status: 400,
+                message: "The value is not one of the labels allowed by the enum column.",
+                offender,
+            },
+
+            "INVALID_ISOLATION_LEVEL" => Error::UserError {
+                code: err_id,
+                details: "Transactions can be started with the `read_committed`, `repeatable_read` or `serializable` isolation level.".to_string(),
+                http_status: 400,
+                message: "The isolation level is not supported.",
+                offender,
+            },
+
             "INVALID_JSON_TYPE_CONVERSION" => Error::UserError {
                 code: err_id,
                 details: "The type of the JSON data does not match the type of the database column.".to_string(),
@@ -159,6 +287,21 @@ impl Error {
                 offender
             },
 
+            "INVALID_PRIMARY_KEY" => Error::UserError {
+                code: err_id,
+                details: "Composite primary keys are given as comma-separated values, in the order of the primary key’s columns (`/table/1,2`). Commas within a value are percent-encoded (`%2C`).".to_string(),
+                http_status: 400,
+                message: "The primary key value does not match the table’s primary key columns.",
+                offender,
+            },
+
+            "INVALID_QUERY_PLAN" => Error::InternalError {
+                code: err_id,
+                details: format!("The number of rows could not be read from the query plan: {}", offender),
+                http_status: 500,
+                message: "The planned row count could not be determined.",
+            },
+
             "INVALID_SQL_IDENTIFIER" => Error::UserError {
                 code: err_id,
                 details: "Valid identifiers must only contain alphanumeric and underscore (_) characters. The first character must also be a letter or underscore. Wildcards (*) are not allowed.".to_string(),
@@ -167,6 +310,22 @@ impl Error {
                 offender,
             },
 
+            "INVALID_SQL_PARAMETERS" => Error::UserError {
+                code: err_id,
+                details: "Each parameter of the statement (`$1`, `$2`, etc.) needs exactly one value in `params`, in order.".to_string(),
+                http_status: 400,
+                message: "The number of parameter values does not match the statement’s parameters.",
+                offender,
+            },
+
+            "INVALID_SQL_VALUE" => Error::UserError {
+                code: err_id,
+                details: "The value could not be converted to the type of the database column it is compared to or assigned to.".to_string(),
+                http_status: 400,
+                message: "There was a value in the SQL expression that did not match the type of its column.",
+                offender,
+            },
+
             "INVALID_SQL_SYNTAX" => Error::UserError {
                 code: err_id,
                 details: "The SQL expression could not be parsed by PostgreSQL.".to_string(),
@@ -175,6 +334,53 @@ impl Error {
                 offender,
             },
 
+            "NESTED_COLUMN_CONFLICT" => Error::UserError {
+                code: err_id,
+                details: "A key of a nested row object can either hold a column value or the columns of a foreign key table, not both. Use an alias (`column AS name`) for one of them.".to_string(),
+                http_status: 400,
+                message: "The column conflicts with another column when nesting foreign key columns.",
+                offender,
+            },
+
+            "PRIMARY_KEY_NOT_FOUND" => Error::UserError {
+                code: err_id,
+                details: "Rows can only be addressed by their primary key (`/table/{id}`) in tables that have a primary key.".to_string(),
+                http_status: 400,
+                message: "The table has no primary key.",
+                offender,
+            },
+
+            "REFERENCING_TABLE_NOT_FOUND" => Error::UserError {
+                code: err_id,
+                details: "Only tables with a foreign key referencing the selected table can be embedded. If there is more than one such foreign key, pick one with `table!column[...]`.".to_string(),
+                http_status: 400,
+                message: "The embedded table has no single foreign key referencing the selected table.",
+                offender,
+            },
+
+            "ROW_NOT_FOUND" => Error::UserError {
+                code: err_id,
+                details: "".to_string(),
+                http_status: 404,
+                message: "No row with the given primary key exists.",
+                offender,
+            },
+
+            "ROW_NOT_RETURNED" => Error::InternalError {
+                code: err_id,
+                details: format!("The query on `{}` returned the number of affected rows instead of the row. Please submit a bug report, as this really shouldn’t be happening.", offender),
+                http_status: 500,
+                message: "The affected row could not be returned.",
+            },
+
+            "SCHEMA_NOT_EXPOSED" => Error::UserError {
+                code: err_id,
+                details: "Only tables in the schemas configured via the config’s `set_schemas()` can be queried.".to_string(),
+                http_status: 404,
+                message: "The requested table belongs to a schema that is not exposed by the API.",
+                offender,
+            },
+
             "SQL_IDENTIFIER_KEYWORD" => Error::UserError {
                 code: err_id,
                 details: "`table` is a reserved keyword and cannot be used to name SQL identifiers".to_string(),
@@ -183,6 +389,38 @@ impl Error {
                 offender,
             },
 
+            "SQL_READ_ONLY_VIOLATION" => Error::UserError {
+                code: err_id,
+                details: "Custom SQL queries are run in a `READ ONLY` transaction, so they can’t write to the database.".to_string(),
+                http_status: 403,
+                message: "The statement tried to write to the database.",
+                offender,
+            },
+
+            "SQL_RESULT_TOO_LARGE" => Error::UserError {
+                code: err_id,
+                details: format!("Custom SQL queries run in a `READ ONLY` transaction or with a statement timeout can return at most {} rows. Add a `LIMIT`, or raise the limit with the config’s `set_custom_sql_max_rows()`.", offender),
+                http_status: 400,
+                message: "The statement returned too many rows.",
+                offender,
+            },
+
+            "SQL_STATEMENT_NOT_ALLOWED" => Error::UserError {
+                code: err_id,
+                details: "Custom SQL queries are restricted to certain kinds of statements (`select`, `insert`, `update`, `delete`, `ddl` or `other`). Statements that can’t be parsed are of the `other` kind.".to_string(),
+                http_status: 403,
+                message: "This kind of statement is not allowed.",
+                offender,
+            },
+
+            "SQL_STATEMENT_TIMEOUT" => Error::UserError {
+                code: err_id,
+                details: "Custom SQL queries are cancelled once they run for longer than the statement timeout.".to_string(),
+                http_status: 400,
+                message: "The statement took too long to run.",
+                offender,
+            },
+
             "TABLE_COLUMN_TYPE_NOT_FOUND" => Error::InternalError {
                 code: err_id,
                 details: format!("The column type for column `{}` could not be generated from the Table Stats query. Please submit a bug report, as this really shouldn’t be happening.", offender),
@@ -206,6 +444,62 @@ impl Error {
                 offender,
             },
 
+            "TOO_MANY_TRANSACTIONS" => Error::UserError {
+                code: err_id,
+                details: format!("At most {} transactions can be open at a time (one less than the connection pool’s maximum size, so that requests outside of transactions can still get a connection). Commit or roll back an open transaction first.", offender),
+                http_status: 503,
+                message: "Too many transactions are open.",
+                offender,
+            },
+
+            "TRANSACTION_ABORTED" => Error::UserError {
+                code: err_id,
+                details: "A statement of the transaction failed, so none of its changes can be committed. Roll back to a savepoint after a failed statement to keep the transaction going.".to_string(),
+                http_status: 409,
+                message: "The transaction was rolled back instead of being committed.",
+                offender,
+            },
+
+            "TRANSACTION_IN_USE" => Error::UserError {
+                code: err_id,
+                details: "A transaction runs one request at a time. Wait for the previous request to finish before sending the next one.".to_string(),
+                http_status: 409,
+                message: "The transaction is being used by another request.",
+                offender,
+            },
+
+            "TRANSACTION_NOT_FOUND" => Error::UserError {
+                code: err_id,
+                details: "The transaction has already been committed or rolled back, or was rolled back after being idle for longer than the transaction idle timeout.".to_string(),
+                http_status: 404,
+                message: "No open transaction has the given ID.",
+                offender,
+            },
+
+            "UNSUPPORTED_BATCH_OPERATION" => Error::UserError {
+                code: err_id,
+                details: "Rows selected in a batch can’t be counted (`count`) or paginated with a cursor (`cursor`).".to_string(),
+                http_status: 400,
+                message: "The operation is not supported in a batch.",
+                offender,
+            },
+
+            "UNSUPPORTED_TRANSACTION_OPERATION" => Error::UserError {
+                code: err_id,
+                details: "Rows selected in a transaction can’t be counted (`count`) or paginated with a cursor (`cursor`).".to_string(),
+                http_status: 400,
+                message: "The operation is not supported in a transaction.",
+                offender,
+            },
+
+            "UNSUPPORTED_RESPONSE_FORMAT" => Error::UserError {
+                code: err_id,
+                details: "Rows returned by this endpoint can only be sent as JSON, NDJSON, CSV, MessagePack or CBOR.".to_string(),
+                http_status: 406,
+                message: "The requested response format is not supported by this endpoint.",
+                offender,
+            },
+
             "UNSUPPORTED_DATA_TYPE" => Error::UserError {
                 code: err_id,
                 details: "".to_string(),
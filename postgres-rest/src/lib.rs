@@ -3,15 +3,29 @@
 // to serialize large json (like the index)
 #![recursion_limit = "128"]
 
+/// Encodes query results as MessagePack or CBOR, and parses request bodies sent in those formats.
+pub mod encoding;
+
 mod error;
 
+mod pool;
+pub use pool::PooledClient;
+use pool::{PinnedClient, Pool, PoolSettings};
+
 /// Contains the functions used to query the database.
 pub mod queries;
 
 mod stats_cache;
 use stats_cache::{get_stats_cache_addr, StatsCacheMessage};
 
+mod transactions;
+use transactions::Transactions;
+
 pub use error::Error;
+use queries::{
+    utils::{split_table_name, validate_table_name},
+    SqlStatementKind,
+};
 
 use actix::{spawn as actix_spawn, System};
 use futures::future::{err, ok, Either, Future};
@@ -32,6 +46,54 @@ where
     /// When set to a positive integer `n`, automatically refresh the Table Stats cache every `n`
     /// seconds. Default: `0` (cache is never automatically reset).
     pub cache_reset_interval_seconds: u32,
+    /// The kinds of statements that custom SQL queries (`queries::execute_sql_query()`) can run.
+    /// Allowing only `SELECT`s also runs the queries in a `READ ONLY` transaction. Default: `None`
+    /// (any statement).
+    pub custom_sql_statement_kinds: Option<Vec<SqlStatementKind>>,
+    /// When set to `true`, custom SQL queries are run in a `READ ONLY` transaction. Default:
+    /// `false`.
+    pub is_custom_sql_read_only: bool,
+    /// When set to a positive integer `n`, custom SQL queries are cancelled after running for `n`
+    /// milliseconds (using `statement_timeout`). Default: `0` (no timeout).
+    pub custom_sql_statement_timeout_ms: u32,
+    /// When set to a positive integer `n`, custom SQL queries that are run in a `READ ONLY`
+    /// transaction or with a timeout (and whose rows are therefore held in memory) fail if they
+    /// return more than `n` rows. Default: `10000` (`0` allows any number of rows).
+    pub custom_sql_max_rows: u32,
+    /// The number of connections that are kept open, even when idle. Missing connections are
+    /// opened in the background whenever a connection is checked out, so the pool is filled up to
+    /// this size by the first checkout. Default: `0`.
+    pub pool_min_size: usize,
+    /// The maximum number of connections that can be open at any one time. Default: `10`.
+    pub pool_max_size: usize,
+    /// When set to a positive integer `n`, idle connections are closed after `n` seconds (down to
+    /// `pool_min_size`). Default: `300`.
+    pub pool_idle_timeout_seconds: u32,
+    /// When set to a positive integer `n`, checking out a connection fails after waiting `n`
+    /// seconds for one to free up or to open. Default: `30` (`0` waits indefinitely).
+    pub pool_checkout_timeout_seconds: u32,
+    /// When set to `true`, idle connections are tested with `SELECT 1` before being checked out.
+    /// Default: `false`.
+    pub is_pool_health_check_enabled: bool,
+    /// When set to a positive integer `n`, connections that have been idle for at least `n`
+    /// seconds are tested with `SELECT 1` before being checked out, even if
+    /// `is_pool_health_check_enabled` is `false`. Default: `30` (`0` turns this off).
+    pub pool_health_check_idle_seconds: u32,
+    /// The database schemas whose tables are exposed by the API. Tables in the first schema can be
+    /// referred to without a schema prefix; all other tables are referred to as `schema.table`.
+    /// Default: `["public"]`.
+    pub schemas: Vec<String>,
+    /// When set to a positive integer `n`, long-lived transactions (started with
+    /// `queries::begin_transaction()`) are rolled back after not being used for `n` seconds.
+    /// Default: `60` (`0` keeps idle transactions open indefinitely).
+    pub transaction_idle_timeout_seconds: u32,
+    /// The connections shared between all clones of this config.
+    pool: Pool,
+    /// Set on clones of this config that share a connection which is already checked out by a
+    /// request (see `pin_client()`). `connect()` then checks out that connection instead.
+    pinned_client: Option<PinnedClient>,
+    /// The long-lived transactions shared between all clones of this config.
+    transactions: Transactions,
     /// A Tls connection that can be passed into `tokio_postgres::connect`.
     tls: T,
 }
@@ -49,6 +111,21 @@ impl<T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static> Config<T> {
             db_url,
             is_cache_table_stats: false,
             cache_reset_interval_seconds: 0,
+            custom_sql_statement_kinds: None,
+            is_custom_sql_read_only: false,
+            custom_sql_statement_timeout_ms: 0,
+            custom_sql_max_rows: 10_000,
+            pool_min_size: 0,
+            pool_max_size: 10,
+            pool_idle_timeout_seconds: 300,
+            pool_checkout_timeout_seconds: 30,
+            is_pool_health_check_enabled: false,
+            pool_health_check_idle_seconds: 30,
+            schemas: vec!["public".to_string()],
+            transaction_idle_timeout_seconds: 60,
+            pool: Pool::default(),
+            pinned_client: None,
+            transactions: Transactions::default(),
             tls,
         }
     }
@@ -61,8 +138,9 @@ impl<T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static> Config<T> {
         self
     }
 
-    /// A convenience wrapper around `tokio_postgres::connect`. Returns a future that evaluates to
-    /// the database client connection.
+    /// Checks out a database client connection from the connection pool, opening a new
+    /// connection (via `tokio_postgres::connect`) if there are no idle ones. The connection is
+    /// returned to the pool when the client is dropped.
     ///
     /// # Example
     ///
@@ -83,23 +161,31 @@ impl<T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static> Config<T> {
     ///
     /// tokio::run(fut);
     /// ```
-    pub fn connect(&self) -> impl Future<Item = Client, Error = Error> {
-        pg_connect(self.db_url, self.tls.clone())
-            .map_err(Error::from)
-            .and_then(|(client, connection)| {
-                let is_actix_result = std::panic::catch_unwind(|| {
-                    System::current();
-                });
+    pub fn connect(&self) -> impl Future<Item = PooledClient, Error = Error> {
+        if let Some(pinned_client) = &self.pinned_client {
+            return Either::A(pinned_client.checkout());
+        }
 
-                if is_actix_result.is_ok() {
-                    actix_spawn(connection.map_err(|e| panic!("{}", e)));
-                } else {
-                    let _spawn_result = TaskExecutor::current()
-                        .spawn_local(Box::new(connection.map_err(|e| panic!("{}", e))));
-                }
+        let db_url = self.db_url;
+        let tls = self.tls.clone();
 
-                Ok(client)
-            })
+        Either::B(self.pool.checkout(self.pool_settings(), move || {
+            connect_client(db_url, tls.clone())
+        }))
+    }
+
+    /// Runs custom SQL queries in a `READ ONLY` transaction, so that they can’t write to the
+    /// database (even through functions called by a `SELECT`).
+    pub fn enable_custom_sql_read_only(&mut self) -> &mut Self {
+        self.is_custom_sql_read_only = true;
+        self
+    }
+
+    /// Turns on health checks for pooled connections. Idle connections are tested with `SELECT 1`
+    /// before being checked out, and are replaced if the test fails.
+    pub fn enable_pool_health_check(&mut self) -> &mut Self {
+        self.is_pool_health_check_enabled = true;
+        self
     }
 
     /// Forces the Table Stats cache to reset/refresh new data.
@@ -142,6 +228,253 @@ impl<T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static> Config<T> {
         self.cache_reset_interval_seconds = seconds;
         self
     }
+
+    /// Sets the maximum number of rows that custom SQL queries run in a `READ ONLY` transaction or
+    /// with a timeout can return, as their rows are held in memory until the transaction is
+    /// committed. `0` allows any number of rows.
+    /// ```
+    /// use postgres_rest::Config;
+    /// use tokio_postgres::tls::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.set_custom_sql_max_rows(1000);
+    /// ```
+    pub fn set_custom_sql_max_rows(&mut self, max_rows: u32) -> &mut Self {
+        self.custom_sql_max_rows = max_rows;
+        self
+    }
+
+    /// Restricts custom SQL queries to the given kinds of statements. Statements are classified
+    /// using the SQL parser; statements that it doesn’t support are of the `Other` kind.
+    ///
+    /// Classifying statements doesn’t stop a `SELECT` from writing (by calling functions like
+    /// `nextval()` or `set_config()`, or locking rows with `FOR UPDATE`), so allowing only
+    /// `SELECT`s also runs the queries in a `READ ONLY` transaction, like
+    /// `enable_custom_sql_read_only()`. Even then, functions that act outside of the transaction
+    /// (like `pg_terminate_backend()`) can still be called; use a database role with limited
+    /// privileges to prevent that.
+    /// ```
+    /// use postgres_rest::{queries::SqlStatementKind, Config};
+    /// use tokio_postgres::tls::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// // no DDL
+    /// config.set_custom_sql_statement_kinds(&[
+    ///     SqlStatementKind::Select,
+    ///     SqlStatementKind::Insert,
+    ///     SqlStatementKind::Update,
+    ///     SqlStatementKind::Delete,
+    /// ]);
+    /// ```
+    pub fn set_custom_sql_statement_kinds(&mut self, kinds: &[SqlStatementKind]) -> &mut Self {
+        self.custom_sql_statement_kinds = Some(kinds.to_vec());
+        self
+    }
+
+    /// Sets the number of milliseconds after which custom SQL queries are cancelled. `0` lets
+    /// queries run indefinitely.
+    /// ```
+    /// use postgres_rest::Config;
+    /// use tokio_postgres::tls::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.set_custom_sql_statement_timeout(5000);
+    /// ```
+    pub fn set_custom_sql_statement_timeout(&mut self, milliseconds: u32) -> &mut Self {
+        self.custom_sql_statement_timeout_ms = milliseconds;
+        self
+    }
+
+    /// Resolves a table name (`table` or `schema.table`) from an API request into the name used by
+    /// the `queries` functions. Unqualified table names belong to the first exposed schema, and
+    /// tables in schemas that are not exposed result in an error.
+    /// ```
+    /// use postgres_rest::Config;
+    /// use tokio_postgres::tls::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.set_schemas(&["billing", "public"]);
+    ///
+    /// assert_eq!(config.resolve_table_name("invoice").unwrap(), "billing.invoice");
+    /// assert_eq!(config.resolve_table_name("public.user").unwrap(), "user");
+    /// assert!(config.resolve_table_name("auth.user").is_err());
+    /// ```
+    pub fn resolve_table_name(&self, table: &str) -> Result<String, Error> {
+        validate_table_name(table)?;
+
+        let (schema, table_name) = if table.contains('.') {
+            split_table_name(table)
+        } else {
+            (self.schemas[0].as_str(), table)
+        };
+
+        if !self
+            .schemas
+            .iter()
+            .any(|exposed_schema| exposed_schema == schema)
+        {
+            return Err(Error::generate_error(
+                "SCHEMA_NOT_EXPOSED",
+                schema.to_string(),
+            ));
+        }
+
+        if schema == "public" {
+            Ok(table_name.to_string())
+        } else {
+            Ok([schema, ".", table_name].join(""))
+        }
+    }
+
+    /// Sets the schemas whose tables are exposed by the API. Tables in the first schema can be
+    /// referred to without a schema prefix. Ignored if `schemas` is empty. If the Table Stats cache
+    /// is used, call this before `cache_table_stats()`.
+    /// ```
+    /// use postgres_rest::Config;
+    /// use tokio_postgres::tls::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.set_schemas(&["public", "billing", "auth"]);
+    /// ```
+    pub fn set_schemas(&mut self, schemas: &[&str]) -> &mut Self {
+        if !schemas.is_empty() {
+            self.schemas = schemas.iter().map(|schema| schema.to_string()).collect();
+        }
+        self
+    }
+
+    /// Sets the number of seconds to wait for a connection to free up when the pool is at its
+    /// maximum size, or for a new connection to open. `0` waits indefinitely.
+    /// ```
+    /// use postgres_rest::Config;
+    /// use tokio_postgres::tls::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.set_pool_checkout_timeout(5);
+    /// ```
+    pub fn set_pool_checkout_timeout(&mut self, seconds: u32) -> &mut Self {
+        self.pool_checkout_timeout_seconds = seconds;
+        self
+    }
+
+    /// Sets the number of seconds that a connection has to be idle for before it is tested with
+    /// `SELECT 1` on checkout. `0` only tests connections if health checks are enabled.
+    /// ```
+    /// use postgres_rest::Config;
+    /// use tokio_postgres::tls::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.set_pool_health_check_idle_time(10);
+    /// ```
+    pub fn set_pool_health_check_idle_time(&mut self, seconds: u32) -> &mut Self {
+        self.pool_health_check_idle_seconds = seconds;
+        self
+    }
+
+    /// Sets the number of seconds after which idle connections are closed. `0` keeps idle
+    /// connections open indefinitely.
+    /// ```
+    /// use postgres_rest::Config;
+    /// use tokio_postgres::tls::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.set_pool_idle_timeout(60);
+    /// ```
+    pub fn set_pool_idle_timeout(&mut self, seconds: u32) -> &mut Self {
+        self.pool_idle_timeout_seconds = seconds;
+        self
+    }
+
+    /// Sets the minimum and maximum number of connections in the connection pool. The maximum is
+    /// at least 1, and the minimum is never larger than the maximum.
+    /// ```
+    /// use postgres_rest::Config;
+    /// use tokio_postgres::tls::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.set_pool_size(2, 20);
+    /// ```
+    pub fn set_pool_size(&mut self, min_size: usize, max_size: usize) -> &mut Self {
+        self.pool_max_size = max_size.max(1);
+        self.pool_min_size = min_size.min(self.pool_max_size);
+        self
+    }
+
+    /// Sets the number of seconds after which long-lived transactions that haven’t been used are
+    /// rolled back. `0` keeps idle transactions open indefinitely.
+    /// ```
+    /// use postgres_rest::Config;
+    /// use tokio_postgres::tls::NoTls;
+    ///
+    /// let mut config = Config::new("postgresql://postgres@0.0.0.0:5432/postgres", NoTls);
+    /// config.set_transaction_idle_timeout(300);
+    /// ```
+    pub fn set_transaction_idle_timeout(&mut self, seconds: u32) -> &mut Self {
+        self.transaction_idle_timeout_seconds = seconds;
+        self
+    }
+
+    /// Returns a clone of this config whose `connect()` shares the given connection (which the
+    /// caller has checked out) instead of checking out another one. Lookups made with the clone
+    /// wait for each other rather than for the pool, so that they can’t deadlock a full pool.
+    /// Use `unpin_client()` to take back the connection.
+    pub(crate) fn pin_client(&self, client: PooledClient) -> Self {
+        let mut config = self.clone();
+        config.pinned_client = Some(PinnedClient::new(client));
+        config
+    }
+
+    /// Takes back the connection of a config returned by `pin_client()`, once the lookups made
+    /// with it are done.
+    pub(crate) fn unpin_client<E>(&self) -> impl Future<Item = PooledClient, Error = E> {
+        self.pinned_client
+            .as_ref()
+            .expect("Only configs returned by `pin_client()` have a pinned client.")
+            .unpin()
+    }
+
+    fn pool_settings(&self) -> PoolSettings {
+        PoolSettings {
+            min_size: self.pool_min_size,
+            max_size: self.pool_max_size,
+            idle_timeout_seconds: self.pool_idle_timeout_seconds,
+            checkout_timeout_seconds: self.pool_checkout_timeout_seconds,
+            is_health_check_enabled: self.is_pool_health_check_enabled,
+            health_check_idle_seconds: self.pool_health_check_idle_seconds,
+        }
+    }
+}
+
+/// A convenience wrapper around `tokio_postgres::connect` that spawns the connection onto the
+/// current actix system (or tokio executor, when not running inside actix).
+fn connect_client<T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static>(
+    db_url: &'static str,
+    tls: T,
+) -> impl Future<Item = Client, Error = Error> {
+    pg_connect(db_url, tls)
+        .map_err(Error::from)
+        .and_then(|(client, connection)| {
+            // the pool discards the client once the connection has closed
+            spawn_future(connection.map_err(|e| eprintln!("{}", e)));
+            Ok(client)
+        })
+}
+
+/// Spawns a future onto the current actix system (or tokio executor, when not running inside
+/// actix). Returns `false` if there is no executor to spawn the future onto.
+pub(crate) fn spawn_future<F: Future<Item = (), Error = ()> + 'static>(future: F) -> bool {
+    let is_actix_result = std::panic::catch_unwind(|| {
+        System::current();
+    });
+
+    if is_actix_result.is_ok() {
+        actix_spawn(future);
+        true
+    } else {
+        TaskExecutor::current()
+            .spawn_local(Box::new(future))
+            .is_ok()
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,509 @@
+use futures::{
+    future::{err, loop_fn, ok, Either, Future, Loop},
+    stream::Stream,
+};
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use serde::{
+    ser::{SerializeMap, SerializeSeq},
+    Serialize, Serializer,
+};
+use serde_json::Value as JsonValue;
+use tokio_postgres::{
+    tls::{MakeTlsConnect, TlsConnect},
+    types::ToSql,
+    Socket,
+};
+
+use super::{
+    column_nesting::ColumnNesting,
+    delete_table_rows::{delete_statement, DeleteParams},
+    insert_into_table::{insert_into_table_in_transaction, InsertParams, InsertResult},
+    postgres_types::{row_to_row_values, RowValues},
+    row_values_stream::column_names,
+    select_table_rows::{select_statement, SelectParams},
+    update_table_rows::{update_statement, UpdateParams},
+    utils::TableStatement,
+    QueryResult, RowValuesStream,
+};
+use crate::{Config, Error, PooledClient};
+
+lazy_static! {
+    // `${operation.column}` or `${operation[row].column}`
+    static ref REFERENCE_RE: Regex = Regex::new(r"\$\{(\d+)(?:\[(\d+)\])?\.([^}]+)\}").unwrap();
+}
+
+#[derive(Debug)]
+/// A query that is run as part of a batch.
+///
+/// Operations can reference the values returned by earlier operations of the batch:
+/// `${operation.column}` is the value of `column` in the first row returned by `operation` (a
+/// zero-based position in the batch), and `${operation[row].column}` is its value in another row.
+/// References can be used in `conditions` (where they are replaced with SQL literals), and as
+/// INSERT row values or UPDATE column values (which must consist of only the reference).
+pub enum BatchOperation {
+    Insert(InsertParams),
+    Update(UpdateParams),
+    Delete(DeleteParams),
+    Select(SelectParams),
+}
+
+/// The result of an operation in a batch. Rows are retrieved all at once, as the next operation
+/// can only start once they are.
+pub enum BatchResult {
+    /// The returned columns and rows, and how their foreign key columns are nested (if requested).
+    Rows {
+        columns: Vec<String>,
+        rows: Vec<RowValues>,
+        nesting: Option<ColumnNesting>,
+    },
+    RowsAffected {
+        num_rows: u64,
+    },
+}
+
+/// Rows are serialized as an array of row objects, and the number of rows affected as
+/// `{"num_rows": ...}` (like the results of single queries).
+impl Serialize for BatchResult {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            BatchResult::Rows { rows, nesting, .. } => {
+                let mut seq = serializer.serialize_seq(Some(rows.len()))?;
+                for row in rows {
+                    match nesting {
+                        Some(nesting) => seq.serialize_element(&nesting.nest(row))?,
+                        None => seq.serialize_element(row)?,
+                    }
+                }
+                seq.end()
+            }
+            BatchResult::RowsAffected { num_rows } => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry("num_rows", num_rows)?;
+                map.end()
+            }
+        }
+    }
+}
+
+/// Converts the result of an operation into the result of a single query, whose rows are streamed
+/// from memory.
+impl From<BatchResult> for QueryResult {
+    fn from(result: BatchResult) -> Self {
+        match result {
+            BatchResult::Rows {
+                columns,
+                rows,
+                nesting,
+            } => QueryResult::QueryTableStream(
+                RowValuesStream::from_rows(columns, rows).with_nesting(nesting),
+            ),
+            BatchResult::RowsAffected { num_rows } => QueryResult::from_num_rows_affected(num_rows),
+        }
+    }
+}
+
+/// Runs the operations one after another, in a single transaction. If any of them fails, the
+/// transaction is rolled back and the error is returned.
+pub fn execute_batch<T>(
+    config: Config<T>,
+    operations: Vec<BatchOperation>,
+) -> impl Future<Item = Vec<BatchResult>, Error = Error>
+where
+    <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
+    <T as MakeTlsConnect<Socket>>::Stream: Send,
+    <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    // counting and cursor pagination run more than one statement per SELECT
+    for operation in &operations {
+        if let BatchOperation::Select(params) = operation {
+            if params.count.is_some() || params.cursor.is_some() {
+                return Either::A(err(Error::generate_error(
+                    "UNSUPPORTED_BATCH_OPERATION",
+                    params.table.clone(),
+                )));
+            }
+        }
+    }
+
+    let batch_future = config
+        .connect()
+        .map_err(Error::from)
+        .and_then(move |mut conn| {
+            conn.simple_query("BEGIN")
+                .collect()
+                .then(|r| match r {
+                    Ok(_) => Ok(conn),
+                    Err(e) => Err((Error::from(e), conn)),
+                })
+                .and_then(move |conn| {
+                    loop_fn(
+                        (vec![], operations.into_iter(), conn),
+                        move |(mut results, mut operations, conn)| {
+                            let operation = match operations.next() {
+                                Some(operation) => operation,
+                                None => return Either::A(ok(Loop::Break((results, conn)))),
+                            };
+                            let operation = match resolve_references(operation, &results) {
+                                Ok(operation) => operation,
+                                Err(e) => return Either::A(err((e, conn))),
+                            };
+
+                            Either::B(execute_operation(&config, conn, operation).map(
+                                move |(result, conn)| {
+                                    results.push(result);
+                                    Loop::Continue((results, operations, conn))
+                                },
+                            ))
+                        },
+                    )
+                })
+                .and_then(|(results, mut conn)| {
+                    conn.simple_query("COMMIT")
+                        .for_each(|_| Ok(()))
+                        .then(|r| match r {
+                            Ok(_) => Ok(results),
+                            Err(e) => Err((Error::from(e), conn)),
+                        })
+                })
+                .or_else(|(e, mut conn)| {
+                    // keep the connection checked out until the rollback finishes, so that it
+                    // isn't returned to the pool mid-transaction
+                    conn.simple_query("ROLLBACK")
+                        .for_each(|_| Ok(()))
+                        .then(move |_| {
+                            drop(conn);
+                            Err(e)
+                        })
+                })
+        });
+
+    Either::B(batch_future)
+}
+
+/// Runs a single operation of a batch (or of a long-lived transaction) on the transaction’s
+/// connection. The table stats needed to generate the statements of UPDATEs, DELETEs and SELECTs
+/// are looked up on the transaction’s connection as well, so that a batch never waits on a second
+/// connection while holding one.
+pub(crate) fn execute_operation<T>(
+    config: &Config<T>,
+    conn: PooledClient,
+    operation: BatchOperation,
+) -> impl Future<Item = (BatchResult, PooledClient), Error = (Error, PooledClient)>
+where
+    <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
+    <T as MakeTlsConnect<Socket>>::Stream: Send,
+    <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    let pinned_config = config.pin_client(conn);
+    let statement_future = match operation {
+        BatchOperation::Insert(params) => {
+            // INSERTs look up the table stats on the connection themselves
+            let insert_future = pinned_config
+                .unpin_client()
+                .and_then(|conn| insert_into_table_in_transaction(conn, params))
+                .map(|(insert_result, conn)| {
+                    let result = match insert_result {
+                        InsertResult::Rows(columns, rows) => BatchResult::Rows {
+                            columns,
+                            rows,
+                            nesting: None,
+                        },
+                        InsertResult::NumRowsAffected(num_rows) => {
+                            BatchResult::RowsAffected { num_rows }
+                        }
+                    };
+                    (result, conn)
+                });
+
+            return Either::A(insert_future);
+        }
+        BatchOperation::Update(params) => Either::A(update_statement(&pinned_config, params)),
+        BatchOperation::Delete(params) => {
+            Either::B(Either::A(delete_statement(pinned_config.clone(), params)))
+        }
+        BatchOperation::Select(params) => Either::B(Either::B(
+            select_statement(pinned_config.clone(), params)
+                .map(|(statement, _count, _keyset)| statement),
+        )),
+    };
+
+    let operation_future = statement_future.then(move |result| {
+        pinned_config
+            .unpin_client()
+            .and_then(move |conn| match result {
+                Ok(statement) => Either::A(execute_statement(conn, statement)),
+                Err(e) => Either::B(err((e, conn))),
+            })
+    });
+
+    Either::B(operation_future)
+}
+
+/// Executes a statement on the batch’s connection, retrieving all of the returned rows.
+fn execute_statement(
+    mut conn: PooledClient,
+    statement: TableStatement,
+) -> impl Future<Item = (BatchResult, PooledClient), Error = (Error, PooledClient)> {
+    let TableStatement {
+        statement: statement_str,
+        prepared_values,
+        is_return_rows,
+        nesting,
+    } = statement;
+
+    conn.prepare(&statement_str)
+        .then(move |result| match result {
+            Ok(statement) => Ok((statement, conn)),
+            Err(e) => Err((Error::from(e), conn)),
+        })
+        .and_then(move |(statement, mut conn)| {
+            let prep_values: Vec<&dyn ToSql> = prepared_values.iter().map(|v| v as _).collect();
+
+            if is_return_rows {
+                let columns = column_names(&statement);
+                let return_rows_future =
+                    conn.query(&statement, &prep_values)
+                        .collect()
+                        .then(move |result| {
+                            match result.map_err(Error::from).and_then(|rows| {
+                                rows.iter()
+                                    .map(row_to_row_values)
+                                    .collect::<Result<Vec<RowValues>, Error>>()
+                            }) {
+                                Ok(rows) => Ok((
+                                    BatchResult::Rows {
+                                        columns,
+                                        rows,
+                                        nesting,
+                                    },
+                                    conn,
+                                )),
+                                Err(e) => Err((e, conn)),
+                            }
+                        });
+
+                Either::A(return_rows_future)
+            } else {
+                let return_row_count_future =
+                    conn.execute(&statement, &prep_values)
+                        .then(move |result| match result {
+                            Ok(num_rows) => Ok((BatchResult::RowsAffected { num_rows }, conn)),
+                            Err(e) => Err((Error::from(e), conn)),
+                        });
+
+                Either::B(return_row_count_future)
+            }
+        })
+}
+
+/// Replaces the references to values returned by earlier operations (`results`) in an operation.
+fn resolve_references(
+    operation: BatchOperation,
+    results: &[BatchResult],
+) -> Result<BatchOperation, Error> {
+    match operation {
+        BatchOperation::Insert(mut params) => {
+            for row in params.rows.iter_mut() {
+                for (_column, value) in row.iter_mut() {
+                    resolve_value_reference(value, results, false)?;
+                }
+            }
+            Ok(BatchOperation::Insert(params))
+        }
+        BatchOperation::Update(mut params) => {
+            // UPDATE column values that are strings are expressions, unless they are quoted
+            for (_column, value) in params.column_values.iter_mut() {
+                resolve_value_reference(value, results, true)?;
+            }
+            params.conditions = resolve_conditions_references(params.conditions, results)?;
+            Ok(BatchOperation::Update(params))
+        }
+        BatchOperation::Delete(mut params) => {
+            params.conditions = resolve_conditions_references(params.conditions, results)?;
+            Ok(BatchOperation::Delete(params))
+        }
+        BatchOperation::Select(mut params) => {
+            params.conditions = resolve_conditions_references(params.conditions, results)?;
+            Ok(BatchOperation::Select(params))
+        }
+    }
+}
+
+/// Replaces a value that consists of only a reference with the referenced value. Strings are
+/// turned into SQL literals (quoted and escaped) if `is_quote_strings` is set.
+fn resolve_value_reference(
+    value: &mut JsonValue,
+    results: &[BatchResult],
+    is_quote_strings: bool,
+) -> Result<(), Error> {
+    let referenced_value = match value.as_str().and_then(|value_str| {
+        REFERENCE_RE
+            .captures(value_str)
+            .filter(|captures| &captures[0] == value_str)
+    }) {
+        Some(captures) => referenced_value(&captures, results)?,
+        None => return Ok(()),
+    };
+
+    *value = if is_quote_strings && referenced_value.is_string() {
+        JsonValue::String(sql_literal(&referenced_value))
+    } else {
+        referenced_value
+    };
+
+    Ok(())
+}
+
+/// Replaces each reference in a WHERE clause with the referenced value, as an SQL literal.
+fn resolve_conditions_references(
+    conditions: Option<String>,
+    results: &[BatchResult],
+) -> Result<Option<String>, Error> {
+    let conditions = match conditions {
+        Some(conditions) => conditions,
+        None => return Ok(None),
+    };
+
+    let mut resolved_conditions = String::with_capacity(conditions.len());
+    let mut last_end = 0;
+    for captures in REFERENCE_RE.captures_iter(&conditions) {
+        let reference = captures.get(0).unwrap();
+        resolved_conditions.push_str(&conditions[last_end..reference.start()]);
+        resolved_conditions.push_str(&sql_literal(&referenced_value(&captures, results)?));
+        last_end = reference.end();
+    }
+    resolved_conditions.push_str(&conditions[last_end..]);
+
+    Ok(Some(resolved_conditions))
+}
+
+/// Returns the value that a reference points to, as JSON.
+fn referenced_value(captures: &Captures, results: &[BatchResult]) -> Result<JsonValue, Error> {
+    let invalid_reference =
+        || Error::generate_error("INVALID_BATCH_REFERENCE", captures[0].to_string());
+
+    let operation_index: usize = captures[1].parse().map_err(|_| invalid_reference())?;
+    let row_index: usize = match captures.get(2) {
+        Some(row_index) => row_index
+            .as_str()
+            .parse()
+            .map_err(|_| invalid_reference())?,
+        None => 0,
+    };
+
+    // only earlier operations have results
+    let rows = match results.get(operation_index) {
+        Some(BatchResult::Rows { rows, .. }) => rows,
+        _ => return Err(invalid_reference()),
+    };
+    let value = rows
+        .get(row_index)
+        .and_then(|row| row.get(&captures[3]))
+        .ok_or_else(invalid_reference)?;
+
+    Ok(serde_json::to_value(value)?)
+}
+
+/// Converts a referenced value into an SQL literal. Like primary key values, numbers and booleans
+/// are used as-is; other values are quoted, and converted to the column’s type along with the rest
+/// of the WHERE clause.
+fn sql_literal(value: &JsonValue) -> String {
+    match value {
+        JsonValue::Null => "NULL".to_string(),
+        JsonValue::Bool(_) | JsonValue::Number(_) => value.to_string(),
+        JsonValue::String(value_str) => ["'", &value_str.replace('\'', "''"), "'"].join(""),
+        _ => ["'", &value.to_string().replace('\'', "''"), "'"].join(""),
+    }
+}
+
+#[cfg(test)]
+mod resolve_references_tests {
+    use super::*;
+    use crate::queries::postgres_types::{IsNullColumnValue, TypedColumnValue};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn results() -> Vec<BatchResult> {
+        let mut first_row = RowValues::new();
+        first_row.insert(
+            "id".to_string(),
+            TypedColumnValue::Int(IsNullColumnValue::NotNullable(1)),
+        );
+        first_row.insert(
+            "name".to_string(),
+            TypedColumnValue::Text(IsNullColumnValue::NotNullable("O'Brien".to_string())),
+        );
+        let mut second_row = RowValues::new();
+        second_row.insert(
+            "id".to_string(),
+            TypedColumnValue::Int(IsNullColumnValue::NotNullable(2)),
+        );
+        second_row.insert(
+            "name".to_string(),
+            TypedColumnValue::Text(IsNullColumnValue::Nullable(None)),
+        );
+
+        vec![
+            BatchResult::Rows {
+                columns: vec!["id".to_string(), "name".to_string()],
+                rows: vec![first_row, second_row],
+                nesting: None,
+            },
+            BatchResult::RowsAffected { num_rows: 2 },
+        ]
+    }
+
+    #[test]
+    fn conditions() {
+        assert_eq!(
+            resolve_conditions_references(
+                Some("id = ${0.id} OR (id = ${0[1].id} AND name = ${0.name})".to_string()),
+                &results()
+            )
+            .unwrap(),
+            Some("id = 1 OR (id = 2 AND name = 'O''Brien')".to_string())
+        );
+        assert_eq!(
+            resolve_conditions_references(Some("name IS ${0[1].name}".to_string()), &results())
+                .unwrap(),
+            Some("name IS NULL".to_string())
+        );
+        assert_eq!(
+            resolve_conditions_references(None, &results()).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn values() {
+        let mut insert_value = json!("${0.name}");
+        resolve_value_reference(&mut insert_value, &results(), false).unwrap();
+        assert_eq!(insert_value, json!("O'Brien"));
+
+        let mut update_value = json!("${0.name}");
+        resolve_value_reference(&mut update_value, &results(), true).unwrap();
+        assert_eq!(update_value, json!("'O''Brien'"));
+
+        let mut number_value = json!("${0[1].id}");
+        resolve_value_reference(&mut number_value, &results(), true).unwrap();
+        assert_eq!(number_value, json!(2));
+
+        // only values that consist of a reference are replaced
+        let mut expression_value = json!("id + ${0.id}");
+        resolve_value_reference(&mut expression_value, &results(), true).unwrap();
+        assert_eq!(expression_value, json!("id + ${0.id}"));
+    }
+
+    #[test]
+    fn invalid_references() {
+        for reference in &["${0.missing}", "${0[2].id}", "${1.id}", "${2.id}"] {
+            match resolve_conditions_references(Some(["id = ", reference].join("")), &results()) {
+                Err(Error::UserError { code, .. }) => assert_eq!(code, "INVALID_BATCH_REFERENCE"),
+                _ => panic!("`{}` should not be a valid reference.", reference),
+            }
+        }
+    }
+}
@@ -0,0 +1,219 @@
+use super::{foreign_keys::ForeignKeyReference, utils::validate_alias_identifier, RowValues};
+use crate::Error;
+use serde::{ser::SerializeMap, Serialize, Serializer};
+
+/// Describes how the (flat) columns of returned rows are nested into objects, following the
+/// foreign key references in their names: `parent_id.company_id.name` is returned as
+/// `{"parent_id": {"company_id": {"name": ...}}}`. Aliased columns and columns that aren’t foreign
+/// key references keep their names.
+#[derive(Debug, PartialEq)]
+pub struct ColumnNesting {
+    fields: Vec<(String, NestedField)>,
+}
+
+#[derive(Debug, PartialEq)]
+enum NestedField {
+    /// The name of a returned column.
+    Column(String),
+    /// The fields of a referenced table’s row.
+    Object(Vec<(String, NestedField)>),
+}
+
+impl ColumnNesting {
+    /// Builds the nesting of the given (requested) columns. Returns an error if a column would
+    /// have to be both a value and an object (like `parent_id` and `parent_id.name`).
+    pub(crate) fn new(
+        columns: &[String],
+        table: &str,
+        fks: &[ForeignKeyReference],
+    ) -> Result<Self, Error> {
+        let mut fields = vec![];
+
+        for column in columns {
+            let (path, column_name): (Vec<&str>, &str) = match validate_alias_identifier(column)? {
+                Some((_actual_column_ref, alias)) => (vec![alias], alias),
+                None if ForeignKeyReference::find(fks, table, column).is_some() => {
+                    (column.split('.').collect(), column)
+                }
+                None => (vec![column], column),
+            };
+
+            Self::insert(&mut fields, &path, column_name, column)?;
+        }
+
+        Ok(ColumnNesting { fields })
+    }
+
+    fn insert(
+        fields: &mut Vec<(String, NestedField)>,
+        path: &[&str],
+        column_name: &str,
+        column: &str,
+    ) -> Result<(), Error> {
+        let (key, sub_path) = match path.split_first() {
+            Some(key_and_sub_path) => key_and_sub_path,
+            None => return Ok(()),
+        };
+
+        let field_index = match fields.iter().position(|(field_key, _)| field_key == key) {
+            Some(field_index) => field_index,
+            None => {
+                let field = if sub_path.is_empty() {
+                    NestedField::Column(column_name.to_string())
+                } else {
+                    let mut sub_fields = vec![];
+                    Self::insert(&mut sub_fields, sub_path, column_name, column)?;
+                    NestedField::Object(sub_fields)
+                };
+
+                fields.push((key.to_string(), field));
+                return Ok(());
+            }
+        };
+
+        match (&mut fields[field_index].1, sub_path.is_empty()) {
+            (NestedField::Object(sub_fields), false) => {
+                Self::insert(sub_fields, sub_path, column_name, column)
+            }
+            // the same column, requested more than once
+            (NestedField::Column(existing_column_name), true)
+                if existing_column_name == column_name =>
+            {
+                Ok(())
+            }
+            _ => Err(Error::generate_error(
+                "NESTED_COLUMN_CONFLICT",
+                column.to_string(),
+            )),
+        }
+    }
+
+    /// Returns a serializable view of a row, with its columns nested.
+    pub fn nest<'a>(&'a self, row: &'a RowValues) -> NestedRow<'a> {
+        NestedRow {
+            fields: &self.fields,
+            row,
+        }
+    }
+}
+
+/// A row whose columns are nested according to a `ColumnNesting`.
+pub struct NestedRow<'a> {
+    fields: &'a [(String, NestedField)],
+    row: &'a RowValues,
+}
+
+impl<'a> Serialize for NestedRow<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.fields.len()))?;
+
+        for (key, field) in self.fields {
+            match field {
+                NestedField::Column(column_name) => {
+                    map.serialize_entry(key, &self.row.get(column_name))?
+                }
+                NestedField::Object(fields) => map.serialize_entry(
+                    key,
+                    &NestedRow {
+                        fields,
+                        row: self.row,
+                    },
+                )?,
+            }
+        }
+
+        map.end()
+    }
+}
+
+#[cfg(test)]
+mod column_nesting_tests {
+    use super::*;
+    use crate::queries::postgres_types::{IsNullColumnValue, TypedColumnValue};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn child_fks() -> Vec<ForeignKeyReference> {
+        vec![ForeignKeyReference {
+            original_refs: vec![
+                "parent_id.name".to_string(),
+                "parent_id.company_id.name".to_string(),
+            ],
+            referring_table: "child".to_string(),
+            referring_column: "parent_id".to_string(),
+            referring_column_type: "int8",
+            referring_column_is_nullable: false,
+            foreign_key_table: "adult".to_string(),
+            foreign_key_table_stats: vec![],
+            foreign_key_column: "id".to_string(),
+            foreign_key_column_type: "int8",
+            additional_columns: vec![],
+            nested_fks: vec![ForeignKeyReference {
+                original_refs: vec!["company_id.name".to_string()],
+                referring_table: "adult".to_string(),
+                referring_column: "company_id".to_string(),
+                referring_column_type: "int8",
+                referring_column_is_nullable: false,
+                foreign_key_table: "company".to_string(),
+                foreign_key_table_stats: vec![],
+                foreign_key_column: "id".to_string(),
+                foreign_key_column_type: "int8",
+                additional_columns: vec![],
+                nested_fks: vec![],
+            }],
+        }]
+    }
+
+    fn text(s: &str) -> TypedColumnValue {
+        TypedColumnValue::Text(IsNullColumnValue::Nullable(Some(s.to_string())))
+    }
+
+    #[test]
+    fn nested_foreign_keys() {
+        let columns = vec![
+            "id".to_string(),
+            "parent_id.name".to_string(),
+            "parent_id.company_id.name".to_string(),
+            "parent_id.company_id.name as company_name".to_string(),
+        ];
+        let nesting = ColumnNesting::new(&columns, "child", &child_fks()).unwrap();
+
+        let mut row = RowValues::new();
+        row.insert(
+            "id".to_string(),
+            TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(1000)),
+        );
+        row.insert("parent_id.name".to_string(), text("Ned"));
+        row.insert(
+            "parent_id.company_id.name".to_string(),
+            text("Stark Corporation"),
+        );
+        row.insert("company_name".to_string(), text("Stark Corporation"));
+
+        assert_eq!(
+            serde_json::to_value(nesting.nest(&row)).unwrap(),
+            json!({
+                "id": 1000,
+                "parent_id": {
+                    "name": "Ned",
+                    "company_id": { "name": "Stark Corporation" },
+                },
+                "company_name": "Stark Corporation",
+            })
+        );
+    }
+
+    #[test]
+    fn conflicting_columns() {
+        let columns = vec!["parent_id".to_string(), "parent_id.name".to_string()];
+        let error = ColumnNesting::new(&columns, "child", &child_fks()).unwrap_err();
+
+        match error {
+            Error::UserError { code, offender, .. } => {
+                assert_eq!(code, "NESTED_COLUMN_CONFLICT");
+                assert_eq!(offender, "parent_id.name");
+            }
+            _ => panic!("Expected a user error."),
+        }
+    }
+}
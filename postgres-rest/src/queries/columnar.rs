@@ -0,0 +1,522 @@
+use arrow::{
+    array::{
+        ArrayRef, BinaryArray, BinaryBuilder, BooleanArray, BooleanBuilder, Date32Array,
+        Date32Builder, Float32Array, Float32Builder, Float64Array, Float64Builder, Int16Array,
+        Int16Builder, Int32Array, Int32Builder, Int64Array, Int64Builder, StringArray,
+        StringBuilder, Time64MicrosecondArray, Time64MicrosecondBuilder, TimestampMicrosecondArray,
+        TimestampMicrosecondBuilder, UInt32Array, UInt32Builder,
+    },
+    datatypes::{DataType, DateUnit, Field, Schema, SchemaRef, TimeUnit},
+    ipc::writer::StreamWriter,
+    record_batch::RecordBatch,
+};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
+use futures::{
+    future::{loop_fn, result, Future, Loop},
+    stream::Stream,
+    Async, Poll,
+};
+use parquet::{
+    basic::{LogicalType, Repetition, Type as PhysicalType},
+    column::writer::ColumnWriter,
+    data_type::ByteArray,
+    file::{
+        properties::WriterProperties,
+        writer::{FileWriter, RowGroupWriter, SerializedFileWriter},
+    },
+    schema::types::Type as ParquetType,
+};
+use std::{
+    cell::RefCell,
+    fs::File,
+    io::{self, Seek, SeekFrom, Write},
+    mem,
+    rc::Rc,
+    sync::Arc,
+};
+use tokio_postgres::{impls::Query, row::Row, types::Type};
+
+use super::postgres_types::{resolve_domain, TypedColumnValue};
+use crate::{Error, PooledClient};
+
+/// The maximum number of rows in each record batch (and Parquet row group).
+pub const RECORD_BATCH_SIZE: usize = 8192;
+
+/// Returns the Arrow type used for a postgres column type. Types without an Arrow equivalent
+/// (`numeric`, `uuid`, `json`, arrays, ranges, etc.) are stored as text, formatted the same way as
+/// in CSV responses (see `TypedColumnValue::to_text()`).
+fn arrow_data_type(column_type: &Type) -> DataType {
+    match resolve_domain(column_type).name() {
+        "bool" => DataType::Boolean,
+        "bytea" => DataType::Binary,
+        "date" => DataType::Date32(DateUnit::Day),
+        "float4" => DataType::Float32,
+        "float8" => DataType::Float64,
+        "int2" => DataType::Int16,
+        "int4" => DataType::Int32,
+        "int8" => DataType::Int64,
+        "oid" => DataType::UInt32,
+        "time" => DataType::Time64(TimeUnit::Microsecond),
+        "timestamp" => DataType::Timestamp(TimeUnit::Microsecond, None),
+        "timestamptz" => {
+            DataType::Timestamp(TimeUnit::Microsecond, Some(Arc::new("UTC".to_string())))
+        }
+        _ => DataType::Utf8,
+    }
+}
+
+/// Returns the Arrow schema of the rows returned by a query.
+fn arrow_schema(columns: &[String], column_types: &[Type]) -> Schema {
+    Schema::new(
+        columns
+            .iter()
+            .zip(column_types)
+            .map(|(column, column_type)| Field::new(column, arrow_data_type(column_type), true))
+            .collect(),
+    )
+}
+
+/// Builds the values of a single column, directly from postgres rows.
+enum ColumnBuilder {
+    Binary(BinaryBuilder),
+    Boolean(BooleanBuilder),
+    Date32(Date32Builder),
+    Float32(Float32Builder),
+    Float64(Float64Builder),
+    Int16(Int16Builder),
+    Int32(Int32Builder),
+    Int64(Int64Builder),
+    Text(StringBuilder),
+    Time64(Time64MicrosecondBuilder),
+    Timestamp(TimestampMicrosecondBuilder),
+    TimestampTz(TimestampMicrosecondBuilder),
+    UInt32(UInt32Builder),
+}
+
+impl ColumnBuilder {
+    fn new(data_type: &DataType) -> Self {
+        match data_type {
+            DataType::Binary => ColumnBuilder::Binary(BinaryBuilder::new(RECORD_BATCH_SIZE)),
+            DataType::Boolean => ColumnBuilder::Boolean(BooleanBuilder::new(RECORD_BATCH_SIZE)),
+            DataType::Date32(_) => ColumnBuilder::Date32(Date32Builder::new(RECORD_BATCH_SIZE)),
+            DataType::Float32 => ColumnBuilder::Float32(Float32Builder::new(RECORD_BATCH_SIZE)),
+            DataType::Float64 => ColumnBuilder::Float64(Float64Builder::new(RECORD_BATCH_SIZE)),
+            DataType::Int16 => ColumnBuilder::Int16(Int16Builder::new(RECORD_BATCH_SIZE)),
+            DataType::Int32 => ColumnBuilder::Int32(Int32Builder::new(RECORD_BATCH_SIZE)),
+            DataType::Int64 => ColumnBuilder::Int64(Int64Builder::new(RECORD_BATCH_SIZE)),
+            DataType::Time64(_) => {
+                ColumnBuilder::Time64(Time64MicrosecondBuilder::new(RECORD_BATCH_SIZE))
+            }
+            DataType::Timestamp(_, None) => {
+                ColumnBuilder::Timestamp(TimestampMicrosecondBuilder::new(RECORD_BATCH_SIZE))
+            }
+            DataType::Timestamp(_, Some(_)) => {
+                ColumnBuilder::TimestampTz(TimestampMicrosecondBuilder::new(RECORD_BATCH_SIZE))
+            }
+            DataType::UInt32 => ColumnBuilder::UInt32(UInt32Builder::new(RECORD_BATCH_SIZE)),
+            _ => ColumnBuilder::Text(StringBuilder::new(RECORD_BATCH_SIZE)),
+        }
+    }
+
+    /// Appends the value of column `i` of the row.
+    fn append(&mut self, row: &Row, i: usize) -> Result<(), Error> {
+        match self {
+            ColumnBuilder::Binary(builder) => match row.try_get::<_, Option<Vec<u8>>>(i)? {
+                Some(bytes) => builder.append_value(&bytes)?,
+                None => builder.append_null()?,
+            },
+            ColumnBuilder::Boolean(builder) => builder.append_option(row.try_get(i)?)?,
+            ColumnBuilder::Date32(builder) => builder.append_option(
+                row.try_get::<_, Option<NaiveDate>>(i)?
+                    .map(|date| (date - NaiveDate::from_ymd(1970, 1, 1)).num_days() as i32),
+            )?,
+            ColumnBuilder::Float32(builder) => builder.append_option(row.try_get(i)?)?,
+            ColumnBuilder::Float64(builder) => builder.append_option(row.try_get(i)?)?,
+            ColumnBuilder::Int16(builder) => builder.append_option(row.try_get(i)?)?,
+            ColumnBuilder::Int32(builder) => builder.append_option(row.try_get(i)?)?,
+            ColumnBuilder::Int64(builder) => builder.append_option(row.try_get(i)?)?,
+            ColumnBuilder::Text(builder) => {
+                match row.try_get::<_, TypedColumnValue>(i)?.to_text()? {
+                    Some(text) => builder.append_value(&text)?,
+                    None => builder.append_null()?,
+                }
+            }
+            ColumnBuilder::Time64(builder) => {
+                builder.append_option(row.try_get::<_, Option<NaiveTime>>(i)?.map(|time| {
+                    i64::from(time.num_seconds_from_midnight()) * 1_000_000
+                        + i64::from(time.nanosecond() / 1_000)
+                }))?
+            }
+            ColumnBuilder::Timestamp(builder) => builder.append_option(
+                row.try_get::<_, Option<NaiveDateTime>>(i)?
+                    .map(|timestamp| timestamp_micros(&timestamp)),
+            )?,
+            ColumnBuilder::TimestampTz(builder) => builder.append_option(
+                row.try_get::<_, Option<DateTime<Utc>>>(i)?
+                    .map(|timestamp| timestamp_micros(&timestamp.naive_utc())),
+            )?,
+            ColumnBuilder::UInt32(builder) => builder.append_option(row.try_get(i)?)?,
+        };
+
+        Ok(())
+    }
+
+    /// Returns the built column, and resets the builder.
+    fn finish(&mut self) -> ArrayRef {
+        match self {
+            ColumnBuilder::Binary(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Boolean(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Date32(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Float32(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Float64(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Int16(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Int32(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Int64(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Text(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Time64(builder) => Arc::new(builder.finish()),
+            ColumnBuilder::Timestamp(builder) | ColumnBuilder::TimestampTz(builder) => {
+                Arc::new(builder.finish())
+            }
+            ColumnBuilder::UInt32(builder) => Arc::new(builder.finish()),
+        }
+    }
+}
+
+fn timestamp_micros(timestamp: &NaiveDateTime) -> i64 {
+    timestamp.timestamp() * 1_000_000 + i64::from(timestamp.timestamp_subsec_micros())
+}
+
+/// A stream of query results, converted into Arrow record batches of up to `RECORD_BATCH_SIZE`
+/// rows. Rows are appended to column builders as they arrive, without being converted into
+/// `RowValues` first.
+pub struct RecordBatchStream {
+    builders: Vec<ColumnBuilder>,
+    is_done: bool,
+    num_rows: usize,
+    rows: Query,
+    schema: SchemaRef,
+    _client: PooledClient,
+}
+
+impl RecordBatchStream {
+    pub(crate) fn new(
+        columns: &[String],
+        column_types: &[Type],
+        rows: Query,
+        client: PooledClient,
+    ) -> Self {
+        let schema = Arc::new(arrow_schema(columns, column_types));
+        let builders = schema
+            .fields()
+            .iter()
+            .map(|field| ColumnBuilder::new(field.data_type()))
+            .collect();
+
+        RecordBatchStream {
+            builders,
+            is_done: false,
+            num_rows: 0,
+            rows,
+            schema,
+            _client: client,
+        }
+    }
+
+    /// The Arrow schema of the record batches.
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn finish_batch(&mut self) -> Result<RecordBatch, Error> {
+        self.num_rows = 0;
+        let columns = self
+            .builders
+            .iter_mut()
+            .map(ColumnBuilder::finish)
+            .collect();
+        Ok(RecordBatch::try_new(self.schema.clone(), columns)?)
+    }
+}
+
+impl Stream for RecordBatchStream {
+    type Item = RecordBatch;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<RecordBatch>, Error> {
+        if self.is_done {
+            return Ok(Async::Ready(None));
+        }
+
+        loop {
+            match self.rows.poll().map_err(Error::from)? {
+                Async::Ready(Some(row)) => {
+                    for (i, builder) in self.builders.iter_mut().enumerate() {
+                        builder.append(&row, i)?;
+                    }
+                    self.num_rows += 1;
+
+                    if self.num_rows == RECORD_BATCH_SIZE {
+                        return Ok(Async::Ready(Some(self.finish_batch()?)));
+                    }
+                }
+                Async::Ready(None) => {
+                    self.is_done = true;
+                    if self.num_rows == 0 {
+                        return Ok(Async::Ready(None));
+                    }
+                    return Ok(Async::Ready(Some(self.finish_batch()?)));
+                }
+                Async::NotReady => return Ok(Async::NotReady),
+            }
+        }
+    }
+}
+
+/// A `Write` whose contents can be taken out while an Arrow writer still holds on to it.
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    fn take(&self) -> Vec<u8> {
+        mem::replace(&mut *self.0.borrow_mut(), vec![])
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Encodes record batches as an [Arrow IPC stream](https://arrow.apache.org/docs/format/Columnar.html#ipc-streaming-format).
+/// Each item is the encoded form of one record batch (the first one also contains the schema, and
+/// the last one the end-of-stream marker).
+pub struct ArrowIpcStream {
+    batches: RecordBatchStream,
+    buffer: SharedBuffer,
+    is_done: bool,
+    writer: StreamWriter<SharedBuffer>,
+}
+
+impl ArrowIpcStream {
+    pub fn new(batches: RecordBatchStream) -> Result<Self, Error> {
+        let buffer = SharedBuffer::default();
+        let writer = StreamWriter::try_new(buffer.clone(), &batches.schema())?;
+
+        Ok(ArrowIpcStream {
+            batches,
+            buffer,
+            is_done: false,
+            writer,
+        })
+    }
+}
+
+impl Stream for ArrowIpcStream {
+    type Item = Vec<u8>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<Vec<u8>>, Error> {
+        if self.is_done {
+            return Ok(Async::Ready(None));
+        }
+
+        match self.batches.poll()? {
+            Async::Ready(Some(batch)) => self.writer.write(&batch)?,
+            Async::Ready(None) => {
+                self.writer.finish()?;
+                self.is_done = true;
+            }
+            Async::NotReady => return Ok(Async::NotReady),
+        };
+
+        Ok(Async::Ready(Some(self.buffer.take())))
+    }
+}
+
+/// Writes record batches into a temporary Parquet file, one row group per batch. Parquet files
+/// can only be read once their footer is written, so the file is returned (rewound to the start)
+/// once all rows are written.
+pub fn write_parquet(batches: RecordBatchStream) -> impl Future<Item = File, Error = Error> {
+    let schema = batches.schema();
+    let writer_result = tempfile::tempfile().map_err(Error::from).and_then(|file| {
+        let parquet_schema = Rc::new(parquet_schema(&schema)?);
+        let properties = Rc::new(WriterProperties::builder().build());
+        let writer = SerializedFileWriter::new(file.try_clone()?, parquet_schema, properties)?;
+        Ok((file, writer))
+    });
+
+    result(writer_result).and_then(|(file, writer)| {
+        loop_fn((batches, writer), |(batches, mut writer)| {
+            batches.into_future().map_err(|(e, _batches)| e).and_then(
+                move |(batch_opt, batches)| match batch_opt {
+                    Some(batch) => {
+                        let mut row_group_writer = writer.next_row_group()?;
+                        write_row_group(&batch, &mut row_group_writer)?;
+                        writer.close_row_group(row_group_writer)?;
+                        Ok(Loop::Continue((batches, writer)))
+                    }
+                    None => Ok(Loop::Break(writer)),
+                },
+            )
+        })
+        .and_then(move |mut writer| {
+            writer.close()?;
+            let mut file = file;
+            file.seek(SeekFrom::Start(0))?;
+            Ok(file)
+        })
+    })
+}
+
+/// Returns the Parquet schema equivalent of an Arrow schema.
+fn parquet_schema(schema: &Schema) -> Result<ParquetType, Error> {
+    let mut fields = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let (physical_type, logical_type) = match field.data_type() {
+                DataType::Binary => (PhysicalType::BYTE_ARRAY, LogicalType::NONE),
+                DataType::Boolean => (PhysicalType::BOOLEAN, LogicalType::NONE),
+                DataType::Date32(_) => (PhysicalType::INT32, LogicalType::DATE),
+                DataType::Float32 => (PhysicalType::FLOAT, LogicalType::NONE),
+                DataType::Float64 => (PhysicalType::DOUBLE, LogicalType::NONE),
+                DataType::Int16 => (PhysicalType::INT32, LogicalType::INT_16),
+                DataType::Int32 => (PhysicalType::INT32, LogicalType::NONE),
+                DataType::Int64 => (PhysicalType::INT64, LogicalType::NONE),
+                DataType::Time64(_) => (PhysicalType::INT64, LogicalType::TIME_MICROS),
+                DataType::Timestamp(..) => (PhysicalType::INT64, LogicalType::TIMESTAMP_MICROS),
+                DataType::UInt32 => (PhysicalType::INT32, LogicalType::UINT_32),
+                _ => (PhysicalType::BYTE_ARRAY, LogicalType::UTF8),
+            };
+
+            ParquetType::primitive_type_builder(field.name(), physical_type)
+                .with_logical_type(logical_type)
+                .with_repetition(Repetition::OPTIONAL)
+                .build()
+                .map(Rc::new)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(ParquetType::group_type_builder("schema")
+        .with_fields(&mut fields)
+        .build()?)
+}
+
+/// Writes the columns of a record batch into a Parquet row group.
+fn write_row_group(
+    batch: &RecordBatch,
+    row_group_writer: &mut Box<dyn RowGroupWriter>,
+) -> Result<(), Error> {
+    for column in batch.columns() {
+        let mut column_writer = match row_group_writer.next_column()? {
+            Some(column_writer) => column_writer,
+            None => break,
+        };
+        let def_levels: Vec<i16> = (0..column.len())
+            .map(|i| if column.is_null(i) { 0 } else { 1 })
+            .collect();
+        let is_valid = |i: &usize| !column.is_null(*i);
+
+        // only non-null values are written; nulls are encoded in the definition levels
+        let any = column.as_any();
+        match &mut column_writer {
+            ColumnWriter::BoolColumnWriter(writer) => {
+                let array = any.downcast_ref::<BooleanArray>().unwrap();
+                let values: Vec<bool> = (0..array.len())
+                    .filter(is_valid)
+                    .map(|i| array.value(i))
+                    .collect();
+                writer.write_batch(&values, Some(&def_levels), None)?;
+            }
+            ColumnWriter::Int32ColumnWriter(writer) => {
+                let values: Vec<i32> = if let Some(array) = any.downcast_ref::<Int16Array>() {
+                    (0..array.len())
+                        .filter(is_valid)
+                        .map(|i| i32::from(array.value(i)))
+                        .collect()
+                } else if let Some(array) = any.downcast_ref::<Date32Array>() {
+                    (0..array.len())
+                        .filter(is_valid)
+                        .map(|i| array.value(i))
+                        .collect()
+                } else if let Some(array) = any.downcast_ref::<UInt32Array>() {
+                    // stored as the same bits, annotated with `UINT_32`
+                    (0..array.len())
+                        .filter(is_valid)
+                        .map(|i| array.value(i) as i32)
+                        .collect()
+                } else {
+                    let array = any.downcast_ref::<Int32Array>().unwrap();
+                    (0..array.len())
+                        .filter(is_valid)
+                        .map(|i| array.value(i))
+                        .collect()
+                };
+                writer.write_batch(&values, Some(&def_levels), None)?;
+            }
+            ColumnWriter::Int64ColumnWriter(writer) => {
+                let values: Vec<i64> = if let Some(array) = any.downcast_ref::<Int64Array>() {
+                    (0..array.len())
+                        .filter(is_valid)
+                        .map(|i| array.value(i))
+                        .collect()
+                } else if let Some(array) = any.downcast_ref::<Time64MicrosecondArray>() {
+                    (0..array.len())
+                        .filter(is_valid)
+                        .map(|i| array.value(i))
+                        .collect()
+                } else {
+                    let array = any.downcast_ref::<TimestampMicrosecondArray>().unwrap();
+                    (0..array.len())
+                        .filter(is_valid)
+                        .map(|i| array.value(i))
+                        .collect()
+                };
+                writer.write_batch(&values, Some(&def_levels), None)?;
+            }
+            ColumnWriter::FloatColumnWriter(writer) => {
+                let array = any.downcast_ref::<Float32Array>().unwrap();
+                let values: Vec<f32> = (0..array.len())
+                    .filter(is_valid)
+                    .map(|i| array.value(i))
+                    .collect();
+                writer.write_batch(&values, Some(&def_levels), None)?;
+            }
+            ColumnWriter::DoubleColumnWriter(writer) => {
+                let array = any.downcast_ref::<Float64Array>().unwrap();
+                let values: Vec<f64> = (0..array.len())
+                    .filter(is_valid)
+                    .map(|i| array.value(i))
+                    .collect();
+                writer.write_batch(&values, Some(&def_levels), None)?;
+            }
+            ColumnWriter::ByteArrayColumnWriter(writer) => {
+                let values: Vec<ByteArray> = if let Some(array) = any.downcast_ref::<BinaryArray>()
+                {
+                    (0..array.len())
+                        .filter(is_valid)
+                        .map(|i| ByteArray::from(array.value(i).to_vec()))
+                        .collect()
+                } else {
+                    let array = any.downcast_ref::<StringArray>().unwrap();
+                    (0..array.len())
+                        .filter(is_valid)
+                        .map(|i| ByteArray::from(array.value(i)))
+                        .collect()
+                };
+                writer.write_batch(&values, Some(&def_levels), None)?;
+            }
+            _ => unreachable!("The Parquet schema only contains the types written above."),
+        };
+
+        row_group_writer.close_column(column_writer)?;
+    }
+
+    Ok(())
+}
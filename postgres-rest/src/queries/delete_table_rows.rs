@@ -8,12 +8,13 @@ use tokio_postgres::{
 };
 
 use super::{
-    foreign_keys::{fk_columns_from_where_ast, ForeignKeyReference},
+    column_nesting::ColumnNesting,
+    foreign_keys::{fk_columns_from_where_ast, ForeignKeyReference, JoinType, JOINED_TABLE_ALIAS},
     postgres_types::TypedColumnValue,
     select_table_stats::{select_column_stats, select_column_stats_statement, TableColumnStat},
     utils::{
         conditions_params_to_ast, generate_query_result_from_db, get_columns_str, get_where_string,
-        validate_alias_identifier, validate_table_name,
+        validate_alias_identifier, validate_table_name, TableStatement,
     },
     QueryResult,
 };
@@ -26,6 +27,11 @@ pub struct DeleteParams {
     pub conditions: Option<String>,
     pub confirm_delete: Option<String>,
     pub returning_columns: Option<Vec<String>>,
+    /// Whether foreign key columns in `returning_columns` are returned as nested objects.
+    pub is_nested: bool,
+    /// How the tables of foreign key columns are joined. If `None`, nullable foreign key columns
+    /// use LEFT JOIN and the others use INNER JOIN.
+    pub join_type: Option<JoinType>,
 }
 
 /// Returns the results of a `DELETE FROM {table} WHERE [conditions] [RETURNING [columns]]` query.
@@ -33,6 +39,30 @@ pub fn delete_table_rows<T>(
     config: Config<T>,
     params: DeleteParams,
 ) -> impl Future<Item = QueryResult, Error = Error>
+where
+    <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
+    <T as MakeTlsConnect<Socket>>::Stream: Send,
+    <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    let config_clone = config.clone();
+    delete_statement(config, params).and_then(move |statement| {
+        generate_query_result_from_db(
+            config_clone,
+            statement.statement,
+            statement.prepared_values,
+            statement.is_return_rows,
+            statement.nesting,
+        )
+    })
+}
+
+/// Generates the DELETE statement (looking up the table’s column stats and foreign keys), without
+/// executing it.
+pub(crate) fn delete_statement<T>(
+    config: Config<T>,
+    params: DeleteParams,
+) -> impl Future<Item = TableStatement, Error = Error>
 where
     <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
     <T as MakeTlsConnect<Socket>>::Stream: Send,
@@ -90,8 +120,6 @@ where
         });
 
     // parse column_expr_strings for foreign key usage
-    let config_clone = config.clone();
-
     let fk_future = stats_future
         .join(ForeignKeyReference::from_query_columns(
             config,
@@ -100,20 +128,22 @@ where
             column_expr_strings,
         ))
         .and_then(move |(stats, fk_columns)| {
-            let (statement_str, prepared_values) =
-                match build_delete_statement(params, stats, fk_columns, where_ast) {
-                    Ok((st, vals)) => (st, vals),
-                    Err(e) => return Either::A(err(e)),
-                };
-
-            let delete_rows_future = generate_query_result_from_db(
-                config_clone,
-                statement_str,
+            let nesting = match (&params.returning_columns, params.is_nested) {
+                (Some(columns), true) => {
+                    Some(ColumnNesting::new(columns, &params.table, &fk_columns)?)
+                }
+                _ => None,
+            };
+
+            let (statement, prepared_values) =
+                build_delete_statement(params, stats, fk_columns, where_ast)?;
+
+            Ok(TableStatement {
+                statement,
                 prepared_values,
                 is_return_rows,
-            );
-
-            Either::B(delete_rows_future)
+                nesting,
+            })
         });
 
     Either::B(fk_future)
@@ -132,8 +162,36 @@ fn build_delete_statement(
     let fk_using_clause;
     let mut fk_where_filter = String::from("");
 
-    // build USING and WHERE foreign-key clauses
-    if !fks.is_empty() {
+    // build USING and WHERE foreign-key clauses. LEFT JOINs need the table to be joined again (by
+    // `ctid`), because the tables listed in USING can’t refer to it
+    if ForeignKeyReference::has_left_join(&fks, params.join_type) {
+        query_str_arr.push("\nUSING\n  ");
+
+        fk_using_clause = [
+            params.table.as_str(),
+            " AS ",
+            JOINED_TABLE_ALIAS,
+            "\n  ",
+            &ForeignKeyReference::join_clauses(
+                &fks,
+                params.join_type,
+                &params.table,
+                Some(JOINED_TABLE_ALIAS),
+            )
+            .replace("\n", "\n  "),
+        ]
+        .join("");
+
+        fk_where_filter = [
+            params.table.as_str(),
+            ".ctid = ",
+            JOINED_TABLE_ALIAS,
+            ".ctid",
+        ]
+        .join("");
+
+        query_str_arr.push(&fk_using_clause);
+    } else if !fks.is_empty() {
         query_str_arr.push("\nUSING\n  ");
 
         fk_using_clause = ForeignKeyReference::join_foreign_key_references(
@@ -158,23 +216,28 @@ fn build_delete_statement(
 
     let (mut where_string, where_column_types) =
         get_where_string(&mut where_ast, &params.table, &stats, &fks);
-    if &where_string != "" {
+    if &where_string != "" || &fk_where_filter != "" {
         query_str_arr.push("\nWHERE (\n  ");
 
-        let (where_string_with_prepared_positions, prepared_values_vec) =
-            TypedColumnValue::generate_prepared_statement_from_ast_expr(
-                &where_ast,
-                &params.table,
-                &where_column_types,
-                None,
-            )?;
-        where_string = where_string_with_prepared_positions;
-        prepared_statement_values.par_extend(prepared_values_vec);
-
-        query_str_arr.push(&where_string);
+        if &where_string != "" {
+            let (where_string_with_prepared_positions, prepared_values_vec) =
+                TypedColumnValue::generate_prepared_statement_from_ast_expr(
+                    &where_ast,
+                    &params.table,
+                    &where_column_types,
+                    None,
+                )?;
+            where_string = where_string_with_prepared_positions;
+            prepared_statement_values.par_extend(prepared_values_vec);
+
+            query_str_arr.push(&where_string);
+
+            if &fk_where_filter != "" {
+                query_str_arr.push(" AND\n  ");
+            }
+        }
 
         if &fk_where_filter != "" {
-            query_str_arr.push(" AND\n  ");
             query_str_arr.push(&fk_where_filter);
         }
 
@@ -206,6 +269,8 @@ mod build_delete_statement_tests {
             confirm_delete: Some("true".to_string()),
             conditions: None,
             returning_columns: None,
+            is_nested: false,
+            join_type: None,
             table: "a_table".to_string(),
         };
         let stats = vec![TableColumnStat {
@@ -219,6 +284,10 @@ mod build_delete_statement_tests {
             foreign_key_column_type: None,
             char_max_length: None,
             char_octet_length: None,
+            enum_labels: None,
+            geometry_srid: None,
+            geometry_type: None,
+            composite_foreign_key: None,
         }];
         let fks = vec![];
 
@@ -235,6 +304,8 @@ mod build_delete_statement_tests {
             confirm_delete: Some("true".to_string()),
             conditions: None,
             returning_columns: None,
+            is_nested: false,
+            join_type: None,
             table: "a_table".to_string(),
         };
         let stats = vec![
@@ -249,6 +320,10 @@ mod build_delete_statement_tests {
                 foreign_key_column_type: None,
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
             TableColumnStat {
                 column_name: "b_id".to_string(),
@@ -261,6 +336,10 @@ mod build_delete_statement_tests {
                 foreign_key_column_type: Some("int8"),
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
         ];
         let fks = vec![];
@@ -278,6 +357,8 @@ mod build_delete_statement_tests {
             confirm_delete: Some("true".to_string()),
             conditions: None,
             returning_columns: Some(vec!["b_id.id".to_string()]),
+            is_nested: false,
+            join_type: None,
             table: "a_table".to_string(),
         };
         let stats = vec![
@@ -292,6 +373,10 @@ mod build_delete_statement_tests {
                 foreign_key_column_type: None,
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
             TableColumnStat {
                 column_name: "b_id".to_string(),
@@ -304,6 +389,10 @@ mod build_delete_statement_tests {
                 foreign_key_column_type: Some("int8"),
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
         ];
         let fks = vec![ForeignKeyReference {
@@ -311,6 +400,7 @@ mod build_delete_statement_tests {
             referring_table: "a_table".to_string(),
             referring_column: "b_id".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "b_table".to_string(),
             foreign_key_table_stats: vec![
                 TableColumnStat {
@@ -324,6 +414,10 @@ mod build_delete_statement_tests {
                     foreign_key_column_type: None,
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
                 TableColumnStat {
                     column_name: "name".to_string(),
@@ -336,10 +430,15 @@ mod build_delete_statement_tests {
                     foreign_key_column_type: None,
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
             ],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![],
         }];
 
@@ -348,7 +447,7 @@ mod build_delete_statement_tests {
 
         assert_eq!(
             &sql_str,
-            "DELETE FROM\n  a_table\nUSING\n  b_table\nRETURNING\n  b_table.id AS \"b_id.id\";"
+            "DELETE FROM\n  a_table\nUSING\n  b_table\nWHERE (\n  a_table.b_id = b_table.id\n)\nRETURNING\n  b_table.id AS \"b_id.id\";"
         );
         assert_eq!(prepared_values, vec![]);
     }
@@ -361,6 +460,8 @@ mod build_delete_statement_tests {
             confirm_delete: Some("true".to_string()),
             conditions: Some(conditions.to_string()),
             returning_columns: None,
+            is_nested: false,
+            join_type: None,
             table: "a_table".to_string(),
         };
         let stats = vec![
@@ -375,6 +476,10 @@ mod build_delete_statement_tests {
                 foreign_key_column_type: None,
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
             TableColumnStat {
                 column_name: "name".to_string(),
@@ -387,6 +492,10 @@ mod build_delete_statement_tests {
                 foreign_key_column_type: None,
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
             TableColumnStat {
                 column_name: "b_id".to_string(),
@@ -399,6 +508,10 @@ mod build_delete_statement_tests {
                 foreign_key_column_type: Some("int8"),
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
         ];
         let fks = vec![ForeignKeyReference {
@@ -406,6 +519,7 @@ mod build_delete_statement_tests {
             referring_table: "a_table".to_string(),
             referring_column: "b_id".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "b_table".to_string(),
             foreign_key_table_stats: vec![
                 TableColumnStat {
@@ -419,6 +533,10 @@ mod build_delete_statement_tests {
                     foreign_key_column_type: None,
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
                 TableColumnStat {
                     column_name: "name".to_string(),
@@ -431,10 +549,15 @@ mod build_delete_statement_tests {
                     foreign_key_column_type: None,
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
             ],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![],
         }];
 
@@ -459,6 +582,8 @@ mod build_delete_statement_tests {
             confirm_delete: Some("true".to_string()),
             conditions: Some(conditions.to_string()),
             returning_columns: None,
+            is_nested: false,
+            join_type: None,
             table: "a_table".to_string(),
         };
         let stats = vec![
@@ -473,6 +598,10 @@ mod build_delete_statement_tests {
                 foreign_key_column_type: None,
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
             TableColumnStat {
                 column_name: "name".to_string(),
@@ -485,6 +614,10 @@ mod build_delete_statement_tests {
                 foreign_key_column_type: None,
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
             TableColumnStat {
                 column_name: "b_id".to_string(),
@@ -497,6 +630,10 @@ mod build_delete_statement_tests {
                 foreign_key_column_type: Some("int8"),
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
         ];
         let fks = vec![ForeignKeyReference {
@@ -504,6 +641,7 @@ mod build_delete_statement_tests {
             referring_table: "a_table".to_string(),
             referring_column: "b_id".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "b_table".to_string(),
             foreign_key_table_stats: vec![
                 TableColumnStat {
@@ -517,6 +655,10 @@ mod build_delete_statement_tests {
                     foreign_key_column_type: None,
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
                 TableColumnStat {
                     column_name: "name".to_string(),
@@ -529,10 +671,15 @@ mod build_delete_statement_tests {
                     foreign_key_column_type: None,
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
             ],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![],
         }];
 
@@ -545,4 +692,56 @@ mod build_delete_statement_tests {
             vec![TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(1))]
         );
     }
+
+    #[test]
+    fn nullable_fks_left_join() {
+        let conditions = "id = 1";
+        let where_ast = conditions_params_to_ast(&Some(conditions.to_string())).unwrap();
+        let params = DeleteParams {
+            confirm_delete: Some("true".to_string()),
+            conditions: Some(conditions.to_string()),
+            returning_columns: Some(vec!["b_id.name".to_string()]),
+            is_nested: false,
+            join_type: None,
+            table: "a_table".to_string(),
+        };
+        let stats = vec![TableColumnStat {
+            column_name: "id".to_string(),
+            column_type: "int8",
+            default_value: None,
+            is_nullable: false,
+            is_foreign_key: false,
+            foreign_key_table: None,
+            foreign_key_column: None,
+            foreign_key_column_type: None,
+            char_max_length: None,
+            char_octet_length: None,
+            enum_labels: None,
+            geometry_srid: None,
+            geometry_type: None,
+            composite_foreign_key: None,
+        }];
+        let fks = vec![ForeignKeyReference {
+            original_refs: vec!["b_id.name".to_string()],
+            referring_table: "a_table".to_string(),
+            referring_column: "b_id".to_string(),
+            referring_column_type: "int8",
+            referring_column_is_nullable: true,
+            foreign_key_table: "b_table".to_string(),
+            foreign_key_table_stats: vec![],
+            foreign_key_column: "id".to_string(),
+            foreign_key_column_type: "int8",
+            additional_columns: vec![],
+            nested_fks: vec![],
+        }];
+
+        let (sql_str, prepared_values) =
+            build_delete_statement(params, stats, fks, where_ast).unwrap();
+
+        assert_eq!(&sql_str, "DELETE FROM\n  a_table\nUSING\n  a_table AS joined_table\n  LEFT JOIN b_table ON joined_table.b_id = b_table.id\nWHERE (\n  a_table.id = $1 AND\n  a_table.ctid = joined_table.ctid\n)\nRETURNING\n  b_table.name AS \"b_id.name\";");
+        assert_eq!(
+            prepared_values,
+            vec![TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(1))]
+        );
+    }
 }
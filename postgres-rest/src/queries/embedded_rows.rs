@@ -0,0 +1,472 @@
+use futures::future::{join_all, ok, Either, Future};
+use tokio_postgres::{tls::MakeTlsConnect, Socket};
+
+use super::{
+    postgres_types::TypedColumnValue,
+    select_table_stats::{select_table_stats, TableColumnStat, TableReferencedBy},
+    utils::{
+        conditions_params_to_ast, get_columns_str, get_order_by_str, get_where_string,
+        split_table_name, validate_alias_identifier, validate_table_name,
+    },
+};
+use crate::{Config, Error};
+
+/// Rows of another table whose foreign key references the selected table (like the `orders` of a
+/// `customer`), embedded into every selected row as a JSON array.
+///
+/// Requested as a column: `orders[id, total]`. The brackets can also hold options for the embedded
+/// rows, separated by semicolons: `orders[id, total;where=total > 10;order_by=total desc;limit=5]`.
+/// If the table has more than one foreign key referencing the selected table, the referencing
+/// column is picked with `!`: `orders!billing_customer_id[id]`. The rows are returned under the
+/// table name, or under an alias: `orders[id] AS recent_orders`.
+#[derive(Debug, Default, PartialEq)]
+pub struct EmbeddedRows {
+    /// The table whose rows are embedded.
+    pub table: String,
+    /// The column (of the embedded table) of the foreign key referencing the selected table. Only
+    /// needed if there is more than one such foreign key.
+    pub referencing_column: Option<String>,
+    /// The key that the rows are returned under. Defaults to the (unqualified) table name.
+    pub alias: Option<String>,
+    pub columns: Vec<String>,
+    pub conditions: Option<String>,
+    pub order_by: Option<Vec<String>>,
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+/// The table stats needed to generate the sub-queries of embedded rows.
+#[derive(Default)]
+pub(crate) struct EmbeddedRowsStats {
+    /// The foreign keys referencing the selected table.
+    pub referenced_by: Vec<TableReferencedBy>,
+    /// The column stats of each embedded table, in the order the embedded rows were requested.
+    pub columns: Vec<Vec<TableColumnStat>>,
+}
+
+impl EmbeddedRows {
+    /// Returns true if the column requests embedded rows (`table[column, ...]`).
+    pub fn is_embedded_rows_column(column: &str) -> bool {
+        column.contains('[')
+    }
+
+    /// Parses a column like `orders!customer_id[id, total;where=total > 10;limit=5] AS recent`.
+    pub fn from_column_str(column: &str) -> Result<Self, Error> {
+        let invalid = || Error::generate_error("INVALID_EMBEDDED_ROWS", column.to_string());
+
+        let (open_index, close_index) = match (column.find('['), column.rfind(']')) {
+            (Some(open_index), Some(close_index)) if open_index < close_index => {
+                (open_index, close_index)
+            }
+            _ => return Err(invalid()),
+        };
+
+        // table and referencing column
+        let mut table_and_column = column[..open_index].trim().splitn(2, '!');
+        let table = table_and_column
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        validate_table_name(&table)?;
+        let referencing_column = match table_and_column.next() {
+            Some(referencing_column) => {
+                let referencing_column = referencing_column.trim();
+                validate_table_name(referencing_column)?;
+                if referencing_column.contains('.') {
+                    return Err(invalid());
+                }
+                Some(referencing_column.to_string())
+            }
+            None => None,
+        };
+
+        // alias
+        let alias_str = column[close_index + 1..].trim();
+        let alias_str = match alias_str.get(..3) {
+            Some(as_str) if as_str.eq_ignore_ascii_case("as ") => alias_str[3..].trim(),
+            _ => alias_str,
+        };
+        let alias = if alias_str.is_empty() {
+            None
+        } else {
+            validate_table_name(alias_str)?;
+            if alias_str.contains('.') {
+                return Err(invalid());
+            }
+            Some(alias_str.to_string())
+        };
+
+        let mut embedded_rows = EmbeddedRows {
+            table,
+            referencing_column,
+            alias,
+            ..Default::default()
+        };
+
+        // columns, followed by options
+        let mut sections = column[open_index + 1..close_index].split(';');
+        embedded_rows.columns =
+            split_columns(sections.next().unwrap_or_default()).ok_or_else(invalid)?;
+        for column in &embedded_rows.columns {
+            validate_alias_identifier(column)?;
+        }
+
+        for option in sections {
+            let option = option.trim();
+            if option.is_empty() {
+                continue;
+            }
+
+            let equals_index = option.find('=').ok_or_else(invalid)?;
+            let value = option[equals_index + 1..].trim();
+            match option[..equals_index].trim() {
+                "where" => embedded_rows.conditions = Some(value.to_string()),
+                "order_by" => {
+                    embedded_rows.order_by = Some(split_columns(value).ok_or_else(invalid)?)
+                }
+                "limit" => embedded_rows.limit = Some(value.parse().map_err(|_| invalid())?),
+                "offset" => embedded_rows.offset = value.parse().map_err(|_| invalid())?,
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(embedded_rows)
+    }
+
+    /// The key that the rows are returned under.
+    pub fn key(&self) -> &str {
+        match &self.alias {
+            Some(alias) => alias,
+            None => split_table_name(&self.table).1,
+        }
+    }
+
+    /// Generates the sub-query (correlated with the selected `table`) that aggregates the embedded
+    /// rows into a JSON array. The prepared statement parameters of the WHERE clause start at
+    /// `prepared_param_pos`, which is advanced past them.
+    pub(crate) fn to_sql(
+        &self,
+        table: &str,
+        referenced_by: &[TableReferencedBy],
+        stats: &[TableColumnStat],
+        prepared_param_pos: &mut usize,
+    ) -> Result<(String, Vec<TypedColumnValue>), Error> {
+        let key = self.key();
+        // the embedded table is aliased as the key, so that it can be told apart from the
+        // selected table if it references itself
+        if key == split_table_name(table).1 {
+            return Err(Error::generate_error(
+                "INVALID_EMBEDDED_ROWS",
+                format!(
+                    "`{}` has the same name as the selected table and needs an alias.",
+                    self.table
+                ),
+            ));
+        }
+
+        let reference = self.find_reference(referenced_by)?;
+        let mut conditions = reference
+            .referencing_columns
+            .iter()
+            .zip(&reference.columns_referenced)
+            .map(|(referencing_column, column_referenced)| {
+                [
+                    key,
+                    ".",
+                    referencing_column,
+                    " = ",
+                    table,
+                    ".",
+                    column_referenced,
+                ]
+                .join("")
+            })
+            .collect::<Vec<String>>();
+
+        let mut where_ast = conditions_params_to_ast(&self.conditions)?;
+        let (where_string, column_types) = get_where_string(&mut where_ast, key, stats, &[]);
+        let mut prepared_values = vec![];
+        if !where_string.is_empty() {
+            let (where_string_with_prepared_positions, prepared_values_vec) =
+                TypedColumnValue::generate_prepared_statement_from_ast_expr(
+                    &where_ast,
+                    key,
+                    &column_types,
+                    Some(prepared_param_pos),
+                )?;
+            conditions.push(["(", &where_string_with_prepared_positions, ")"].join(""));
+            prepared_values = prepared_values_vec;
+        }
+
+        let mut statement = vec![
+            "(SELECT COALESCE(json_agg(".to_string(),
+            key.to_string(),
+            ".*), '[]') FROM (SELECT ".to_string(),
+            get_columns_str(&self.columns, key, &[])?.join(""),
+            " FROM ".to_string(),
+            self.table.clone(),
+            " AS ".to_string(),
+            key.to_string(),
+            " WHERE ".to_string(),
+            conditions.join(" AND "),
+        ];
+
+        if let Some(order_by_columns) = &self.order_by {
+            statement.push(" ORDER BY ".to_string());
+            statement.push(get_order_by_str(order_by_columns, key, &[])?.join(""));
+        }
+        if let Some(limit) = self.limit {
+            statement.push(format!(" LIMIT {}", limit));
+        }
+        if self.offset > 0 {
+            statement.push(format!(" OFFSET {}", self.offset));
+        }
+
+        statement.push(format!(") AS {0}) AS \"{0}\"", key));
+
+        Ok((statement.join(""), prepared_values))
+    }
+
+    /// Finds the foreign key (of the embedded table) that references the selected table.
+    fn find_reference<'a>(
+        &self,
+        referenced_by: &'a [TableReferencedBy],
+    ) -> Result<&'a TableReferencedBy, Error> {
+        let table = split_table_name(&self.table);
+        let references: Vec<&TableReferencedBy> = referenced_by
+            .iter()
+            .filter(|reference| {
+                split_table_name(&reference.referencing_table) == table
+                    && match &self.referencing_column {
+                        Some(column) => reference.referencing_columns.contains(column),
+                        None => true,
+                    }
+            })
+            .collect();
+
+        match references.as_slice() {
+            [reference] => Ok(reference),
+            _ => Err(Error::generate_error(
+                "REFERENCING_TABLE_NOT_FOUND",
+                match &self.referencing_column {
+                    Some(column) => [&self.table, "!", column].join(""),
+                    None => self.table.clone(),
+                },
+            )),
+        }
+    }
+}
+
+/// Splits a comma-separated list of columns. Returns `None` if a column is empty.
+fn split_columns(columns_str: &str) -> Option<Vec<String>> {
+    columns_str
+        .split(',')
+        .map(|column| match column.trim() {
+            "" => None,
+            column => Some(column.to_string()),
+        })
+        .collect()
+}
+
+/// Retrieves the stats needed for the sub-queries of the embedded rows of the selected `table`.
+pub(crate) fn select_embedded_rows_stats<T>(
+    config: &Config<T>,
+    table: &str,
+    embedded_rows: &[EmbeddedRows],
+) -> impl Future<Item = EmbeddedRowsStats, Error = Error>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    if embedded_rows.is_empty() {
+        return Either::A(ok(EmbeddedRowsStats::default()));
+    }
+
+    let embedded_tables_stats_future = join_all(
+        embedded_rows
+            .iter()
+            .map(|embedded_rows| select_table_stats(config, embedded_rows.table.clone()))
+            .collect::<Vec<_>>(),
+    );
+
+    Either::B(
+        select_table_stats(config, table.to_string())
+            .join(embedded_tables_stats_future)
+            .map(|(stats, embedded_tables_stats)| EmbeddedRowsStats {
+                referenced_by: stats.referenced_by,
+                columns: embedded_tables_stats
+                    .into_iter()
+                    .map(|stats| stats.columns)
+                    .collect(),
+            }),
+    )
+}
+
+#[cfg(test)]
+mod embedded_rows_tests {
+    use super::*;
+    use crate::queries::postgres_types::IsNullColumnValue;
+    use pretty_assertions::assert_eq;
+
+    fn orders_referenced_by() -> Vec<TableReferencedBy> {
+        vec![
+            TableReferencedBy {
+                referencing_table: "orders".to_string(),
+                referencing_columns: vec!["customer_id".to_string()],
+                columns_referenced: vec!["id".to_string()],
+            },
+            TableReferencedBy {
+                referencing_table: "invoices".to_string(),
+                referencing_columns: vec!["customer_id".to_string()],
+                columns_referenced: vec!["id".to_string()],
+            },
+            TableReferencedBy {
+                referencing_table: "invoices".to_string(),
+                referencing_columns: vec!["billing_customer_id".to_string()],
+                columns_referenced: vec!["id".to_string()],
+            },
+        ]
+    }
+
+    #[test]
+    fn parse_columns() {
+        assert_eq!(
+            EmbeddedRows::from_column_str("orders[id, total]").unwrap(),
+            EmbeddedRows {
+                table: "orders".to_string(),
+                columns: vec!["id".to_string(), "total".to_string()],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parse_options_and_alias() {
+        assert_eq!(
+            EmbeddedRows::from_column_str(
+                "invoices!billing_customer_id[id;where=total > 10 and id in (1, 2);order_by=total desc,id;limit=5;offset=2] AS billed"
+            )
+            .unwrap(),
+            EmbeddedRows {
+                table: "invoices".to_string(),
+                referencing_column: Some("billing_customer_id".to_string()),
+                alias: Some("billed".to_string()),
+                columns: vec!["id".to_string()],
+                conditions: Some("total > 10 and id in (1, 2)".to_string()),
+                order_by: Some(vec!["total desc".to_string(), "id".to_string()]),
+                limit: Some(5),
+                offset: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        for column in &[
+            "orders[]",
+            "orders[id,]",
+            "orders[id",
+            "orders[id;limit=five]",
+            "orders[id;having=true]",
+            "orders[id] AS a.b",
+        ] {
+            match EmbeddedRows::from_column_str(column) {
+                Err(Error::UserError { code, .. }) => assert_eq!(code, "INVALID_EMBEDDED_ROWS"),
+                _ => panic!("`{}` should not be parsed.", column),
+            }
+        }
+    }
+
+    #[test]
+    fn sub_query() {
+        let embedded_rows = EmbeddedRows::from_column_str(
+            "orders[id, total AS amount;order_by=total desc;limit=5]",
+        )
+        .unwrap();
+        let mut prepared_param_pos = 1;
+
+        assert_eq!(
+            embedded_rows
+                .to_sql(
+                    "customers",
+                    &orders_referenced_by(),
+                    &[],
+                    &mut prepared_param_pos
+                )
+                .unwrap()
+                .0,
+            "(SELECT COALESCE(json_agg(orders.*), '[]') FROM (SELECT id, total AS amount FROM orders AS orders WHERE orders.customer_id = customers.id ORDER BY total DESC LIMIT 5) AS orders) AS \"orders\""
+        );
+    }
+
+    #[test]
+    fn sub_query_with_order_by() {
+        let embedded_rows =
+            EmbeddedRows::from_column_str("orders[id, total;order_by=total desc,id]").unwrap();
+
+        assert_eq!(
+            embedded_rows
+                .to_sql("customers", &orders_referenced_by(), &[], &mut 1)
+                .unwrap()
+                .0,
+            "(SELECT COALESCE(json_agg(orders.*), '[]') FROM (SELECT id, total FROM orders AS orders WHERE orders.customer_id = customers.id ORDER BY total DESC, id ASC) AS orders) AS \"orders\""
+        );
+    }
+
+    #[test]
+    fn sub_query_with_where_clause() {
+        let embedded_rows = EmbeddedRows::from_column_str(
+            "invoices!billing_customer_id[id;where=id > 2] AS billed",
+        )
+        .unwrap();
+        let mut prepared_param_pos = 2;
+
+        let stats = vec![TableColumnStat {
+            column_name: "id".to_string(),
+            column_type: "int8",
+            default_value: None,
+            is_nullable: false,
+            is_foreign_key: false,
+            foreign_key_table: None,
+            foreign_key_column: None,
+            foreign_key_column_type: None,
+            char_max_length: None,
+            char_octet_length: None,
+            enum_labels: None,
+            geometry_srid: None,
+            geometry_type: None,
+            composite_foreign_key: None,
+        }];
+
+        let (sql, prepared_values) = embedded_rows
+            .to_sql(
+                "customers",
+                &orders_referenced_by(),
+                &stats,
+                &mut prepared_param_pos,
+            )
+            .unwrap();
+        assert_eq!(
+            sql,
+            "(SELECT COALESCE(json_agg(billed.*), '[]') FROM (SELECT id FROM invoices AS billed WHERE billed.billing_customer_id = customers.id AND (billed.id > $2)) AS billed) AS \"billed\""
+        );
+        assert_eq!(
+            prepared_values,
+            vec![TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(2))]
+        );
+        assert_eq!(prepared_param_pos, 3);
+    }
+
+    #[test]
+    fn ambiguous_or_missing_reference() {
+        for column in &["invoices[id]", "payments[id]", "orders!id[id]"] {
+            let embedded_rows = EmbeddedRows::from_column_str(column).unwrap();
+            match embedded_rows.to_sql("customers", &orders_referenced_by(), &[], &mut 1) {
+                Err(Error::UserError { code, .. }) => {
+                    assert_eq!(code, "REFERENCING_TABLE_NOT_FOUND")
+                }
+                _ => panic!("`{}` should not have a single reference.", column),
+            }
+        }
+    }
+}
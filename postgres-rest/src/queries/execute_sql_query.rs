@@ -1,48 +1,170 @@
 use super::{
-    postgres_types::{row_to_row_values, RowValues},
-    QueryResult,
+    postgres_types::{resolve_domain, row_to_row_values, RowValues, TypedColumnValue},
+    row_values_stream::column_names,
+    utils::replace_operators_with_placeholders,
+    QueryResult, RowValuesStream,
 };
-use crate::Error;
+use crate::{Config, Error, PooledClient};
 use futures::{
-    future::{Either, Future},
+    future::{err, ok, Either, Future},
     stream::Stream,
 };
-use rayon::prelude::*;
-use tokio_postgres::Client;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_json::Value as JsonValue;
+use sqlparser::{ast::Statement as SqlStatement, dialect::PostgreSqlDialect, parser::Parser};
+use tokio_postgres::{
+    error::SqlState,
+    tls::MakeTlsConnect,
+    types::{Kind, ToSql, Type},
+    Socket, Statement,
+};
 
 #[derive(Debug)]
 /// Options used to execute a custom SQL query.
 pub struct ExecuteParams {
     pub statement: String,
+    /// The values of the statement’s parameters (`$1`, `$2`, etc.), in order. Values are
+    /// converted to the parameter types that postgres infers for the statement. `null` values are
+    /// sent as NULL.
+    pub params: Vec<JsonValue>,
     pub is_return_rows: bool,
 }
 
-/// Executes an SQL query statement.
-pub fn execute_sql_query(
-    mut client: Client,
+/// The kinds of statements that custom SQL queries can be restricted to (see
+/// `Config::set_custom_sql_statement_kinds()`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SqlStatementKind {
+    /// `SELECT` (and `VALUES`) queries, including queries with `WITH` clauses.
+    Select,
+    Insert,
+    Update,
+    Delete,
+    /// `CREATE TABLE`, `CREATE VIEW`, `ALTER TABLE` and `DROP`.
+    Ddl,
+    /// Any other statement (like `COPY`, `SET` or `TRUNCATE`), including statements that the SQL
+    /// parser doesn’t support.
+    Other,
+}
+
+impl SqlStatementKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            SqlStatementKind::Select => "select",
+            SqlStatementKind::Insert => "insert",
+            SqlStatementKind::Update => "update",
+            SqlStatementKind::Delete => "delete",
+            SqlStatementKind::Ddl => "ddl",
+            SqlStatementKind::Other => "other",
+        }
+    }
+
+    /// Determines the kind of each statement in an SQL string. Strings that can’t be parsed are a
+    /// single statement of the `Other` kind.
+    fn from_sql(sql: &str) -> Vec<Self> {
+        lazy_static! {
+            // the SQL parser doesn’t support parameters, so they are replaced with NULLs (which
+            // doesn’t change the kind of the statement)
+            static ref PARAM_RE: Regex = Regex::new(r"\$\d+").unwrap();
+        }
+
+        let sql = PARAM_RE.replace_all(&replace_operators_with_placeholders(sql), "NULL");
+        let dialect = PostgreSqlDialect {};
+        let statements = match Parser::parse_sql(&dialect, sql.to_string()) {
+            Ok(statements) => statements,
+            Err(_) => return vec![SqlStatementKind::Other],
+        };
+
+        statements
+            .iter()
+            .map(|statement| match statement {
+                SqlStatement::Query(_) => SqlStatementKind::Select,
+                SqlStatement::Insert { .. } => SqlStatementKind::Insert,
+                SqlStatement::Update { .. } => SqlStatementKind::Update,
+                SqlStatement::Delete { .. } => SqlStatementKind::Delete,
+                SqlStatement::CreateTable { .. }
+                | SqlStatement::CreateView { .. }
+                | SqlStatement::AlterTable { .. }
+                | SqlStatement::Drop { .. } => SqlStatementKind::Ddl,
+                _ => SqlStatementKind::Other,
+            })
+            .collect()
+    }
+}
+
+/// Executes an SQL query statement, subject to the config’s restrictions on custom SQL queries:
+/// the kinds of statements that are allowed, and whether the statement is run in a `READ ONLY`
+/// transaction and/or with a `statement_timeout`. Statements are classified by their syntax only
+/// (a `SELECT` can still write by calling functions, or lock rows with `FOR UPDATE`), so allowing
+/// only `SELECT`s also runs the statement in a `READ ONLY` transaction.
+pub fn execute_sql_query<T>(
+    config: Config<T>,
+    params: ExecuteParams,
+) -> impl Future<Item = QueryResult, Error = Error>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    if let Some(allowed_kinds) = &config.custom_sql_statement_kinds {
+        for kind in SqlStatementKind::from_sql(&params.statement) {
+            if !allowed_kinds.contains(&kind) {
+                return Either::A(err(Error::generate_error(
+                    "SQL_STATEMENT_NOT_ALLOWED",
+                    kind.as_str().to_string(),
+                )));
+            }
+        }
+    }
+
+    let is_select_only = match &config.custom_sql_statement_kinds {
+        Some(allowed_kinds) => allowed_kinds
+            .iter()
+            .all(|kind| *kind == SqlStatementKind::Select),
+        None => false,
+    };
+    let is_read_only = config.is_custom_sql_read_only || is_select_only;
+    let statement_timeout_ms = config.custom_sql_statement_timeout_ms;
+    let max_rows = config.custom_sql_max_rows;
+    let execute_future = config.connect().and_then(move |client| {
+        if is_read_only || statement_timeout_ms > 0 {
+            Either::A(execute_in_restricted_transaction(
+                client,
+                params,
+                is_read_only,
+                statement_timeout_ms,
+                max_rows,
+            ))
+        } else {
+            Either::B(execute_statement(client, params))
+        }
+    });
+
+    Either::B(execute_future)
+}
+
+/// Executes the statement on its own, streaming the returned rows from the database.
+fn execute_statement(
+    mut client: PooledClient,
     params: ExecuteParams,
 ) -> impl Future<Item = QueryResult, Error = Error> {
     client
         .prepare(&params.statement)
         .map_err(Error::from)
         .and_then(move |statement| {
-            if params.is_return_rows {
-                let rows_future = client
-                    .query(&statement, &[])
-                    .map_err(Error::from)
-                    .collect()
-                    .and_then(|rows| {
-                        let convert_row_result: Result<Vec<RowValues>, Error> =
-                            rows.par_iter().map(row_to_row_values).collect();
+            let param_values = match statement_param_values(&statement, &params.params) {
+                Ok(param_values) => param_values,
+                Err(e) => return Either::A(err(e)),
+            };
+            let prep_values: Vec<&dyn ToSql> = param_values.iter().map(|v| v as _).collect();
 
-                        convert_row_result
-                    })
-                    .map(QueryResult::QueryTableResult);
+            if params.is_return_rows {
+                let rows = client.query(&statement, &prep_values);
 
-                Either::A(rows_future)
+                Either::A(ok(QueryResult::QueryTableStream(RowValuesStream::new(
+                    &statement, rows, client,
+                ))))
             } else {
                 let num_rows_future = client
-                    .execute(&statement, &[])
+                    .execute(&statement, &prep_values)
                     .map_err(Error::from)
                     .map(QueryResult::from_num_rows_affected);
 
@@ -50,3 +172,202 @@ pub fn execute_sql_query(
             }
         })
 }
+
+/// Executes the statement in a transaction that is `READ ONLY` and/or has a `statement_timeout`
+/// (in milliseconds, if positive). Rows are retrieved all at once, as the transaction is committed
+/// before they are sent; statements returning more than `max_rows` rows (if positive) fail.
+fn execute_in_restricted_transaction(
+    mut client: PooledClient,
+    params: ExecuteParams,
+    is_read_only: bool,
+    statement_timeout_ms: u32,
+    max_rows: u32,
+) -> impl Future<Item = QueryResult, Error = Error> {
+    let mut begin_statement = if is_read_only {
+        "BEGIN READ ONLY;".to_string()
+    } else {
+        "BEGIN;".to_string()
+    };
+    if statement_timeout_ms > 0 {
+        begin_statement.push_str(&format!(
+            " SET LOCAL statement_timeout = {};",
+            statement_timeout_ms
+        ));
+    }
+
+    client
+        .simple_query(&begin_statement)
+        .for_each(|_| Ok(()))
+        .then(|result| match result {
+            Ok(_) => Ok(client),
+            Err(e) => Err((Error::from(e), client)),
+        })
+        .and_then(move |mut client| {
+            client
+                .prepare(&params.statement)
+                .then(move |result| match result {
+                    Ok(statement) => Ok((statement, client)),
+                    Err(e) => Err((Error::from(e), client)),
+                })
+                .and_then(move |(statement, mut client)| {
+                    let param_values = match statement_param_values(&statement, &params.params) {
+                        Ok(param_values) => param_values,
+                        Err(e) => return Either::A(err((e, client))),
+                    };
+                    let prep_values: Vec<&dyn ToSql> =
+                        param_values.iter().map(|v| v as _).collect();
+
+                    if params.is_return_rows {
+                        let columns = column_names(&statement);
+                        let rows_future = client
+                            .query(&statement, &prep_values)
+                            .map_err(restricted_statement_error)
+                            .fold(vec![], move |mut rows: Vec<RowValues>, row| {
+                                // the rows are held in memory, so stop reading them once there
+                                // are too many
+                                if max_rows > 0 && rows.len() >= max_rows as usize {
+                                    return Err(Error::generate_error(
+                                        "SQL_RESULT_TOO_LARGE",
+                                        max_rows.to_string(),
+                                    ));
+                                }
+
+                                rows.push(row_to_row_values(&row)?);
+                                Ok(rows)
+                            })
+                            .then(move |result| match result {
+                                Ok(rows) => Ok((
+                                    QueryResult::QueryTableStream(RowValuesStream::from_rows(
+                                        columns, rows,
+                                    )),
+                                    client,
+                                )),
+                                Err(e) => Err((e, client)),
+                            });
+
+                        Either::B(Either::A(rows_future))
+                    } else {
+                        let num_rows_future = client.execute(&statement, &prep_values).then(
+                            move |result| match result {
+                                Ok(num_rows) => {
+                                    Ok((QueryResult::from_num_rows_affected(num_rows), client))
+                                }
+                                Err(e) => Err((restricted_statement_error(e), client)),
+                            },
+                        );
+
+                        Either::B(Either::B(num_rows_future))
+                    }
+                })
+        })
+        .and_then(|(result, mut client)| {
+            client
+                .simple_query("COMMIT")
+                .for_each(|_| Ok(()))
+                .then(|r| match r {
+                    Ok(_) => Ok(result),
+                    Err(e) => Err((Error::from(e), client)),
+                })
+        })
+        .or_else(|(e, mut client)| {
+            // keep the connection checked out until the rollback finishes, so that it isn't
+            // returned to the pool mid-transaction
+            client
+                .simple_query("ROLLBACK")
+                .for_each(|_| Ok(()))
+                .then(move |_| {
+                    drop(client);
+                    Err(e)
+                })
+        })
+}
+
+/// Converts the database errors caused by the restrictions on custom SQL queries (writing in a
+/// `READ ONLY` transaction, or exceeding the `statement_timeout`) into user errors.
+fn restricted_statement_error(e: tokio_postgres::Error) -> Error {
+    match e.code() {
+        Some(code) if *code == SqlState::READ_ONLY_SQL_TRANSACTION => {
+            Error::generate_error("SQL_READ_ONLY_VIOLATION", e.to_string())
+        }
+        Some(code) if *code == SqlState::QUERY_CANCELED => {
+            Error::generate_error("SQL_STATEMENT_TIMEOUT", e.to_string())
+        }
+        _ => Error::from(e),
+    }
+}
+
+/// Converts the JSON values of a prepared statement’s parameters to the parameters’ types.
+fn statement_param_values(
+    statement: &Statement,
+    params: &[JsonValue],
+) -> Result<Vec<Option<TypedColumnValue>>, Error> {
+    if statement.params().len() != params.len() {
+        return Err(Error::generate_error(
+            "INVALID_SQL_PARAMETERS",
+            format!(
+                "The statement has {} parameter(s), but {} value(s) were given.",
+                statement.params().len(),
+                params.len()
+            ),
+        ));
+    }
+
+    statement
+        .params()
+        .iter()
+        .zip(params)
+        .map(|(param_type, value)| match value {
+            JsonValue::Null => Ok(None),
+            value => TypedColumnValue::from_json(param_type_name(param_type), value).map(Some),
+        })
+        .collect()
+}
+
+/// Returns the type name that `TypedColumnValue::from_json()` expects for a parameter type.
+/// Domains are resolved to their base type, and enums are named `enum` (like in table stats).
+fn param_type_name(param_type: &Type) -> &str {
+    let param_type = resolve_domain(param_type);
+
+    match param_type.kind() {
+        Kind::Enum(_) => "enum",
+        _ => param_type.name(),
+    }
+}
+
+#[cfg(test)]
+mod sql_statement_kind_tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn from_sql() {
+        assert_eq!(
+            SqlStatementKind::from_sql("SELECT * FROM adult WHERE id = $1 AND tags @> 'a=>b';"),
+            vec![SqlStatementKind::Select]
+        );
+        assert_eq!(
+            SqlStatementKind::from_sql("WITH a AS (SELECT 1) SELECT * FROM a"),
+            vec![SqlStatementKind::Select]
+        );
+        assert_eq!(
+            SqlStatementKind::from_sql("INSERT INTO company (id, name) VALUES ($1, $2)"),
+            vec![SqlStatementKind::Insert]
+        );
+        assert_eq!(
+            SqlStatementKind::from_sql("UPDATE company SET name = 'a'; DELETE FROM company"),
+            vec![SqlStatementKind::Update, SqlStatementKind::Delete]
+        );
+        assert_eq!(
+            SqlStatementKind::from_sql("DROP TABLE company"),
+            vec![SqlStatementKind::Ddl]
+        );
+        assert_eq!(
+            SqlStatementKind::from_sql("CREATE TABLE motto (id BIGINT, motto TEXT)"),
+            vec![SqlStatementKind::Ddl]
+        );
+        assert_eq!(
+            SqlStatementKind::from_sql("TRUNCATE company"),
+            vec![SqlStatementKind::Other]
+        );
+    }
+}
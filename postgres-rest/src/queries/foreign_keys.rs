@@ -1,5 +1,6 @@
 use actix::Addr;
 use futures::future::{err, join_all, ok, Either, Future};
+use itertools::Itertools;
 use rayon::prelude::*;
 use sqlparser::ast::{Expr, Function};
 use std::{
@@ -245,6 +246,30 @@ pub fn fk_columns_from_where_ast(ast: &Expr) -> Vec<String> {
     fks
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// How the tables of foreign key references (dot-syntax columns) are joined.
+pub enum JoinType {
+    /// Rows without a matching foreign key row are excluded.
+    Inner,
+    /// Rows without a matching foreign key row are kept (with `NULL` foreign key column values).
+    Left,
+}
+
+impl JoinType {
+    /// Returns the SQL keyword(s) of the join.
+    pub fn to_sql(self) -> &'static str {
+        match self {
+            JoinType::Inner => "INNER JOIN",
+            JoinType::Left => "LEFT JOIN",
+        }
+    }
+}
+
+/// Alias of the table being updated or deleted from, when it is joined again with the tables of its
+/// foreign keys. Postgres doesn’t allow JOINing the target table of an UPDATE or DELETE directly,
+/// so the joined copy is matched with the target rows by `ctid`.
+pub(crate) const JOINED_TABLE_ALIAS: &str = "joined_table";
+
 type ChildColumns = Vec<String>;
 type OriginalColumnReferences = Vec<String>;
 
@@ -263,6 +288,10 @@ pub(crate) struct ForeignKeyReference {
     // The Postgres type of the referring column.
     pub referring_column_type: &'static str,
 
+    /// Whether the referring column can be NULL (in which case the tables are LEFT JOINed by
+    /// default, so that rows without a foreign key value are kept).
+    pub referring_column_is_nullable: bool,
+
     /// The table being referred by the foreign key.
     pub foreign_key_table: String,
 
@@ -275,6 +304,10 @@ pub(crate) struct ForeignKeyReference {
     // The Postgres type of the column of the table being referred by the foreign key.
     pub foreign_key_column_type: &'static str,
 
+    /// The other (referring column, column referred) pairs of the foreign key, if it has more than
+    /// 1 column.
+    pub additional_columns: Vec<(String, String)>,
+
     /// Any child foreign key columns that are part of the original_ref string.
     pub nested_fks: Vec<ForeignKeyReference>,
 }
@@ -443,11 +476,18 @@ impl ForeignKeyReference {
         fk_columns_grouped: HashMap<String, (ChildColumns, OriginalColumnReferences)>,
     ) -> (
         Vec<TableColumnStat>,
-        Vec<(String, ChildColumns, OriginalColumnReferences)>,
+        Vec<(String, ChildColumns, OriginalColumnReferences, bool)>,
     ) {
-        // contains a tuple representing the (matched parent column name, child columns, and
-        // original column strings)
-        let mut matched_columns: Vec<(String, Vec<String>, Vec<String>)> = vec![];
+        // contains a tuple representing the (matched parent column name, child columns, original
+        // column strings, and whether the foreign key can be null)
+        let mut matched_columns: Vec<(String, Vec<String>, Vec<String>, bool)> = vec![];
+
+        // a multi-column foreign key can be null if any of its columns can be null
+        let nullable_columns: Vec<String> = stats
+            .iter()
+            .filter(|stat| stat.is_nullable)
+            .map(|stat| stat.column_name.clone())
+            .collect();
 
         // filter the table column stats to just the foreign key columns that match the
         // given columns
@@ -466,6 +506,13 @@ impl ForeignKeyReference {
                         matched_parent_fk_column,
                         (matched_child_col_vec, matched_orig_refs),
                     )) => {
+                        let is_nullable = match &stat.composite_foreign_key {
+                            Some(composite_fk) => composite_fk
+                                .iter()
+                                .any(|(column, _)| nullable_columns.contains(column)),
+                            None => stat.is_nullable,
+                        };
+
                         matched_columns.push((
                             matched_parent_fk_column.to_string(),
                             matched_child_col_vec
@@ -473,6 +520,7 @@ impl ForeignKeyReference {
                                 .map(|s| s.to_string())
                                 .collect(),
                             matched_orig_refs.clone(),
+                            is_nullable,
                         ));
                         true
                     }
@@ -491,7 +539,7 @@ impl ForeignKeyReference {
         stats_cache_addr: Arc<Option<Addr<StatsCache>>>,
         table: String,
         stats: Vec<TableColumnStat>,
-        matched_columns: Vec<(String, Vec<String>, Vec<String>)>,
+        matched_columns: Vec<(String, Vec<String>, Vec<String>, bool)>,
     ) -> impl Future<Item = Vec<Self>, Error = Error>
     where
         <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
@@ -503,7 +551,9 @@ impl ForeignKeyReference {
 
         for (i, stat) in stats.into_iter().enumerate() {
             // stats.into_iter().enumerate().map(move |(i, stat)| {
-            let (_parent_col_match, child_columns_match, original_refs_match) = &matched_columns[i];
+            let (_parent_col_match, child_columns_match, original_refs_match, is_nullable) =
+                &matched_columns[i];
+            let is_nullable = *is_nullable;
 
             let original_refs = original_refs_match
                 .par_iter()
@@ -528,6 +578,14 @@ impl ForeignKeyReference {
             let stat_column_name_clone = stat.column_name.clone();
             let stat_fk_column = stat.foreign_key_column.clone().unwrap_or_else(String::new);
             let stat_fk_column_type = stat.foreign_key_column_type.unwrap_or_else(|| "");
+            let additional_columns: Vec<(String, String)> = match &stat.composite_foreign_key {
+                Some(composite_fk) => composite_fk
+                    .iter()
+                    .filter(|(column, _)| column != &stat.column_name)
+                    .cloned()
+                    .collect(),
+                None => vec![],
+            };
             let config_clone = config.clone();
             let config_clone_2 = config.clone();
 
@@ -572,11 +630,13 @@ impl ForeignKeyReference {
                     fk_column_stats_future.map(move |stats| ForeignKeyReference {
                         referring_column: stat_column_name_clone,
                         referring_column_type: stat.column_type,
+                        referring_column_is_nullable: is_nullable,
                         referring_table: table_clone,
                         foreign_key_table,
                         foreign_key_table_stats: stats,
                         foreign_key_column: stat_fk_column,
                         foreign_key_column_type: stat_fk_column_type,
+                        additional_columns,
                         nested_fks: vec![],
                         original_refs,
                     });
@@ -597,11 +657,13 @@ impl ForeignKeyReference {
                 Ok(ForeignKeyReference {
                     referring_column: stat_column_name_clone,
                     referring_column_type: stat.column_type,
+                    referring_column_is_nullable: is_nullable,
                     referring_table: table_clone,
                     foreign_key_table,
                     foreign_key_table_stats,
                     foreign_key_column: stat_fk_column,
                     foreign_key_column_type: stat_fk_column_type,
+                    additional_columns,
                     nested_fks,
                     original_refs,
                 })
@@ -674,9 +736,11 @@ impl ForeignKeyReference {
         // to equate, fk table name to join with, fk table column to equate
         let join_data = Self::fk_tables_columns_from_foreign_key_references(fk_refs);
 
+        // strings can repeat (like the table of a multi-column foreign key)
         join_data
             .into_iter()
             .map(str_conversion_fn)
+            .unique()
             .collect::<Vec<String>>()
             .join(join_str)
     }
@@ -696,6 +760,15 @@ impl ForeignKeyReference {
                 &fk.foreign_key_column,
             ));
 
+            for (referring_column, foreign_key_column) in &fk.additional_columns {
+                join_data.push((
+                    &fk.referring_table,
+                    referring_column,
+                    &fk.foreign_key_table,
+                    foreign_key_column,
+                ));
+            }
+
             join_data.par_extend(Self::fk_tables_columns_from_foreign_key_references(
                 &fk.nested_fks,
             ));
@@ -703,6 +776,135 @@ impl ForeignKeyReference {
 
         join_data
     }
+
+    /// Like `fk_tables_columns_from_foreign_key_references`, but each tuple is paired with the
+    /// type of join to use. If `join_type` is `None`, nullable foreign key columns are LEFT JOINed
+    /// and the others are INNER JOINed. Tables joined through a LEFT JOINed table are LEFT JOINed
+    /// as well (an INNER JOIN would drop the rows kept by the LEFT JOIN).
+    pub fn fk_joins_from_foreign_key_references(
+        fk_refs: &[Self],
+        join_type: Option<JoinType>,
+    ) -> Vec<(JoinType, (&str, &str, &str, &str))> {
+        let mut join_data = vec![];
+
+        for fk in fk_refs {
+            let fk_join_type = match join_type {
+                Some(join_type) => join_type,
+                None if fk.referring_column_is_nullable => JoinType::Left,
+                None => JoinType::Inner,
+            };
+
+            join_data.push((
+                fk_join_type,
+                (
+                    fk.referring_table.as_str(),
+                    fk.referring_column.as_str(),
+                    fk.foreign_key_table.as_str(),
+                    fk.foreign_key_column.as_str(),
+                ),
+            ));
+            for (referring_column, foreign_key_column) in &fk.additional_columns {
+                join_data.push((
+                    fk_join_type,
+                    (
+                        fk.referring_table.as_str(),
+                        referring_column.as_str(),
+                        fk.foreign_key_table.as_str(),
+                        foreign_key_column.as_str(),
+                    ),
+                ));
+            }
+
+            let nested_join_type = match fk_join_type {
+                JoinType::Left => Some(JoinType::Left),
+                JoinType::Inner => join_type,
+            };
+            join_data.extend(Self::fk_joins_from_foreign_key_references(
+                &fk.nested_fks,
+                nested_join_type,
+            ));
+        }
+
+        join_data
+    }
+
+    /// Returns whether any of the foreign key references is LEFT JOINed.
+    pub fn has_left_join(fk_refs: &[Self], join_type: Option<JoinType>) -> bool {
+        Self::fk_joins_from_foreign_key_references(fk_refs, join_type)
+            .iter()
+            .any(|(fk_join_type, _)| *fk_join_type == JoinType::Left)
+    }
+
+    /// Generates the JOIN clauses of a list of foreign key references (like `LEFT JOIN b_table ON
+    /// a_table.b_id = b_table.id`), separated by new lines. Foreign keys having more than 1 column
+    /// are joined in a single clause, with every column pair. If `table_alias` is provided, it
+    /// replaces `table` in the join conditions.
+    pub fn join_clauses(
+        fk_refs: &[Self],
+        join_type: Option<JoinType>,
+        table: &str,
+        table_alias: Option<&str>,
+    ) -> String {
+        let fk_joins = Self::fk_joins_from_foreign_key_references(fk_refs, join_type);
+
+        // More than 1 fk that has the same referred_table and referring table (like the columns of
+        // a multi-column foreign key) should first be joined with "AND". Groups keep the position
+        // of their first fk, so that tables are joined before the tables joined through them
+        let mut table_ref_groups: Vec<((&str, &str), JoinType, Vec<String>)> = vec![];
+        for (fk_join_type, (referring_table, referring_column, referred_table, referred_column)) in
+            fk_joins
+        {
+            let aliased_referring_table = match table_alias {
+                Some(alias) if referring_table == table => alias,
+                _ => referring_table,
+            };
+            let equals_str = [
+                aliased_referring_table,
+                ".",
+                referring_column,
+                " = ",
+                referred_table,
+                ".",
+                referred_column,
+            ]
+            .join("");
+
+            match table_ref_groups
+                .iter_mut()
+                .find(|(key, _, _)| *key == (referring_table, referred_table))
+            {
+                Some((_, group_join_type, equals_strs)) => {
+                    // a single nullable column is enough for the foreign key to not match
+                    if fk_join_type == JoinType::Left {
+                        *group_join_type = JoinType::Left;
+                    }
+                    if !equals_strs.contains(&equals_str) {
+                        equals_strs.push(equals_str);
+                    }
+                }
+                None => table_ref_groups.push((
+                    (referring_table, referred_table),
+                    fk_join_type,
+                    vec![equals_str],
+                )),
+            }
+        }
+
+        table_ref_groups
+            .into_iter()
+            .map(|((_, referred_table), group_join_type, equals_strs)| {
+                [
+                    group_join_type.to_sql(),
+                    " ",
+                    referred_table,
+                    " ON ",
+                    &equals_strs.join(" AND "),
+                ]
+                .join("")
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
 }
 
 #[cfg(test)]
@@ -776,10 +978,12 @@ mod fkr_find {
             referring_table: "a_table".to_string(),
             referring_column: "a_foreign_key".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "b_table".to_string(),
             foreign_key_table_stats: vec![],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![],
         }];
 
@@ -797,19 +1001,23 @@ mod fkr_find {
             referring_table: "a_table".to_string(),
             referring_column: "another_foreign_key".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "b_table".to_string(),
             foreign_key_table_stats: vec![],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![ForeignKeyReference {
                 original_refs: vec!["nested_fk.some_str".to_string()],
                 referring_table: "b_table".to_string(),
                 referring_column: "nested_fk".to_string(),
                 referring_column_type: "int8",
+                referring_column_is_nullable: false,
                 foreign_key_table: "c_table".to_string(),
                 foreign_key_table_stats: vec![],
                 foreign_key_column: "id".to_string(),
                 foreign_key_column_type: "int8",
+                additional_columns: vec![],
                 nested_fks: vec![],
             }],
         }];
@@ -838,19 +1046,23 @@ mod fkr_join_foreign_key_references {
                 referring_table: "a_table".to_string(),
                 referring_column: "another_foreign_key".to_string(),
                 referring_column_type: "int8",
+                referring_column_is_nullable: false,
                 foreign_key_table: "b_table".to_string(),
                 foreign_key_table_stats: vec![],
                 foreign_key_column: "id".to_string(),
                 foreign_key_column_type: "int8",
+                additional_columns: vec![],
                 nested_fks: vec![ForeignKeyReference {
                     original_refs: vec!["nested_fk.some_str".to_string()],
                     referring_table: "b_table".to_string(),
                     referring_column: "nested_fk".to_string(),
                     referring_column_type: "int8",
+                    referring_column_is_nullable: false,
                     foreign_key_table: "d_table".to_string(),
                     foreign_key_table_stats: vec![],
                     foreign_key_column: "id".to_string(),
                     foreign_key_column_type: "int8",
+                    additional_columns: vec![],
                     nested_fks: vec![],
                 }],
             },
@@ -859,10 +1071,12 @@ mod fkr_join_foreign_key_references {
                 referring_table: "b_table".to_string(),
                 referring_column: "b_table_fk".to_string(),
                 referring_column_type: "int8",
+                referring_column_is_nullable: false,
                 foreign_key_table: "e_table".to_string(),
                 foreign_key_table_stats: vec![],
                 foreign_key_column: "id".to_string(),
                 foreign_key_column_type: "int8",
+                additional_columns: vec![],
                 nested_fks: vec![],
             },
         ];
@@ -884,3 +1098,124 @@ mod fkr_join_foreign_key_references {
         }, "\nINNER JOIN "), "b_table ON a_table.another_foreign_key = b_table.id\nINNER JOIN d_table ON b_table.nested_fk = d_table.id\nINNER JOIN e_table ON b_table.b_table_fk = e_table.id".to_string());
     }
 }
+
+#[cfg(test)]
+mod fkr_join_clauses {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn multi_column_fk_with_nullable_column() {
+        let fk =
+            |referring_column: &str, foreign_key_column: &str, is_nullable| ForeignKeyReference {
+                original_refs: vec!["sibling_id.name".to_string()],
+                referring_table: "sibling".to_string(),
+                referring_column: referring_column.to_string(),
+                referring_column_type: "int8",
+                referring_column_is_nullable: is_nullable,
+                foreign_key_table: "child".to_string(),
+                foreign_key_table_stats: vec![],
+                foreign_key_column: foreign_key_column.to_string(),
+                foreign_key_column_type: "int8",
+                additional_columns: vec![],
+                nested_fks: vec![],
+            };
+        let refs = vec![
+            fk("sibling_id", "id", true),
+            fk("parent_id", "parent_id", false),
+        ];
+
+        assert_eq!(
+            ForeignKeyReference::join_clauses(&refs, None, "sibling", None),
+            "LEFT JOIN child ON sibling.sibling_id = child.id AND sibling.parent_id = child.parent_id"
+        );
+        assert_eq!(
+            ForeignKeyReference::join_clauses(&refs, Some(JoinType::Inner), "sibling", Some("s")),
+            "INNER JOIN child ON s.sibling_id = child.id AND s.parent_id = child.parent_id"
+        );
+    }
+
+    #[test]
+    fn composite_fk() {
+        let refs = vec![ForeignKeyReference {
+            original_refs: vec!["order_id.total".to_string()],
+            referring_table: "order_item".to_string(),
+            referring_column: "order_id".to_string(),
+            referring_column_type: "int8",
+            referring_column_is_nullable: false,
+            foreign_key_table: "orders".to_string(),
+            foreign_key_table_stats: vec![],
+            foreign_key_column: "id".to_string(),
+            foreign_key_column_type: "int8",
+            additional_columns: vec![("tenant_id".to_string(), "tenant_id".to_string())],
+            nested_fks: vec![],
+        }];
+
+        assert_eq!(
+            ForeignKeyReference::join_clauses(&refs, None, "order_item", None),
+            "INNER JOIN orders ON order_item.order_id = orders.id AND order_item.tenant_id = orders.tenant_id"
+        );
+        assert_eq!(
+            ForeignKeyReference::join_foreign_key_references(
+                &refs,
+                |(_, _, referred_table, _)| referred_table.to_string(),
+                ", "
+            ),
+            "orders"
+        );
+    }
+}
+
+#[cfg(test)]
+mod fkr_match_fk_column_stats {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn composite_fk_is_nullable_if_any_column_is_nullable() {
+        let stat = |column_name: &str, is_nullable| TableColumnStat {
+            column_name: column_name.to_string(),
+            column_type: "int8",
+            default_value: None,
+            is_nullable,
+            is_foreign_key: true,
+            foreign_key_table: Some("orders".to_string()),
+            foreign_key_column: Some(column_name.replace("order_", "")),
+            foreign_key_column_type: Some("int8"),
+            char_max_length: None,
+            char_octet_length: None,
+            enum_labels: None,
+            geometry_srid: None,
+            geometry_type: None,
+            composite_foreign_key: Some(vec![
+                ("tenant_id".to_string(), "tenant_id".to_string()),
+                ("order_id".to_string(), "id".to_string()),
+            ]),
+        };
+        let mut fk_columns_grouped = HashMap::new();
+        fk_columns_grouped.insert(
+            "order_id".to_string(),
+            (
+                vec![".total".to_string()],
+                vec!["order_id.total".to_string()],
+            ),
+        );
+
+        let (filtered_stats, matched_columns) = ForeignKeyReference::match_fk_column_stats(
+            vec![stat("order_id", false), stat("tenant_id", true)],
+            fk_columns_grouped,
+        );
+
+        assert_eq!(filtered_stats.len(), 1);
+        assert_eq!(filtered_stats[0].column_name, "order_id");
+        assert_eq!(
+            matched_columns,
+            vec![(
+                "order_id".to_string(),
+                vec![".total".to_string()],
+                vec!["order_id.total".to_string()],
+                true
+            )]
+        );
+    }
+}
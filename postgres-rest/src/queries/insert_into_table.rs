@@ -1,19 +1,20 @@
 use futures::{
-    future::{err, loop_fn, Either, Future, Loop},
+    future::{err, loop_fn, ok, Either, Future, Loop},
     stream::Stream,
 };
 use rayon::prelude::*;
 use serde_json::{Map, Value};
 use std::collections::HashMap;
-use tokio_postgres::{types::ToSql, Client};
+use tokio_postgres::types::ToSql;
 
 use super::{
     postgres_types::{row_to_row_values, RowValues, TypedColumnValue},
+    row_values_stream::column_names,
     select_table_stats::{select_column_stats, select_column_stats_statement, TableColumnStat},
     utils::{get_columns_str, validate_where_column},
-    QueryResult,
+    QueryResult, RowValuesStream,
 };
-use crate::Error;
+use crate::{Error, PooledClient};
 
 static INSERT_ROWS_BATCH_COUNT: usize = 100;
 
@@ -28,14 +29,15 @@ pub struct InsertParams {
 }
 
 /// Used for returning either number of rows or actual row values in INSERT/UPDATE statements.
-enum InsertResult {
-    Rows(Vec<RowValues>),
+pub(crate) enum InsertResult {
+    /// The returned column names and rows.
+    Rows(Vec<String>, Vec<RowValues>),
     NumRowsAffected(u64),
 }
 
 /// Runs an `INSERT INTO <table>` query.
 pub fn insert_into_table(
-    mut conn: Client,
+    mut conn: PooledClient,
     params: InsertParams,
 ) -> impl Future<Item = QueryResult, Error = Error> {
     // serde_json::Values can't automatically convert to non-JSON/JSONB columns.
@@ -81,53 +83,25 @@ pub fn insert_into_table(
                         Err(e) => Err((Error::from(e), conn)),
                     })
                     .and_then(|conn| {
-                        loop_fn(
-                            (0, 0, vec![], column_types, insert_batches, params, conn),
-                            |(
-                                i,
-                                mut total_num_rows_affected,
-                                mut total_rows_returned,
-                                column_types,
-                                insert_batches,
-                                params,
-                                conn,
-                            )| {
-                                execute_insert(conn, params, column_types, &insert_batches[i])
-                                    .and_then(move |(conn, params, column_types, insert_result)| {
-                                        match insert_result {
-                                            InsertResult::NumRowsAffected(num_rows_affected) => {
-                                                total_num_rows_affected += num_rows_affected;
-                                            }
-                                            InsertResult::Rows(rows) => {
-                                                total_rows_returned.par_extend(rows);
-                                            }
-                                        };
-
-                                        if i == insert_batches.len() - 1 {
-                                            Ok(Loop::Break((
-                                                total_num_rows_affected,
-                                                total_rows_returned,
-                                                params,
-                                                conn,
-                                            )))
-                                        } else {
-                                            Ok(Loop::Continue((
-                                                i + 1,
-                                                total_num_rows_affected,
-                                                total_rows_returned,
-                                                column_types,
-                                                insert_batches,
-                                                params,
-                                                conn,
-                                            )))
-                                        }
-                                    })
-                            },
-                        )
+                        execute_insert_batches(conn, params, column_types, insert_batches)
                     })
                     .map(
-                        |(total_num_rows_affected, total_rows_returned, params, conn)| {
-                            ((total_num_rows_affected, total_rows_returned, params), conn)
+                        |(
+                            total_num_rows_affected,
+                            returned_columns,
+                            total_rows_returned,
+                            params,
+                            conn,
+                        )| {
+                            (
+                                (
+                                    total_num_rows_affected,
+                                    returned_columns,
+                                    total_rows_returned,
+                                    params,
+                                ),
+                                conn,
+                            )
                         },
                     )
                     .and_then(|(results, mut conn)| {
@@ -139,17 +113,32 @@ pub fn insert_into_table(
                             })
                     })
                     .or_else(|(e, mut conn)| {
+                        // keep the connection checked out until the rollback finishes, so that
+                        // it isn't returned to the pool mid-transaction
                         conn.simple_query("ROLLBACK")
                             .for_each(|_| Ok(()))
-                            .then(|_| Err(e))
+                            .then(move |_| {
+                                drop(conn);
+                                Err(e)
+                            })
                     })
-                    .and_then(|(total_num_rows_affected, total_rows_returned, params)| {
-                        if params.returning_columns.is_some() {
-                            Ok(QueryResult::QueryTableResult(total_rows_returned))
-                        } else {
-                            Ok(QueryResult::from_num_rows_affected(total_num_rows_affected))
-                        }
-                    });
+                    .and_then(
+                        |(
+                            total_num_rows_affected,
+                            returned_columns,
+                            total_rows_returned,
+                            params,
+                        )| {
+                            if params.returning_columns.is_some() {
+                                Ok(QueryResult::QueryTableStream(RowValuesStream::from_rows(
+                                    returned_columns,
+                                    total_rows_returned,
+                                )))
+                            } else {
+                                Ok(QueryResult::from_num_rows_affected(total_num_rows_affected))
+                            }
+                        },
+                    );
 
                 Either::A(batch_insert_future)
             } else {
@@ -161,7 +150,9 @@ pub fn insert_into_table(
                             InsertResult::NumRowsAffected(num_rows_affected) => {
                                 Ok(QueryResult::from_num_rows_affected(num_rows_affected))
                             }
-                            InsertResult::Rows(rows) => Ok(QueryResult::QueryTableResult(rows)),
+                            InsertResult::Rows(columns, rows) => Ok(QueryResult::QueryTableStream(
+                                RowValuesStream::from_rows(columns, rows),
+                            )),
                         },
                         Err((e, _client)) => Err(e),
                     });
@@ -172,20 +163,149 @@ pub fn insert_into_table(
         })
 }
 
+/// Runs an `INSERT INTO <table>` query on a connection that is already in a transaction (like
+/// the one of a batch of queries). Rows are still inserted in batches of 100. The connection is
+/// returned along with the result (or the error), so that it can be used for other queries.
+pub(crate) fn insert_into_table_in_transaction(
+    mut conn: PooledClient,
+    params: InsertParams,
+) -> impl Future<Item = (InsertResult, PooledClient), Error = (Error, PooledClient)> {
+    let table = params.table.clone();
+
+    select_column_stats_statement(&mut conn, &table)
+        .then(|result| match result {
+            Ok(statement) => Ok((statement, conn)),
+            Err(e) => Err((Error::from(e), conn)),
+        })
+        .and_then(|(statement, mut conn)| {
+            let q = conn.query(&statement, &[]);
+            select_column_stats(q).then(|result| match result {
+                Ok(stats) => Ok((stats, conn)),
+                Err(e) => Err((e, conn)),
+            })
+        })
+        .and_then(move |(stats, conn)| {
+            if params.rows.is_empty() {
+                let insert_result = match params.returning_columns {
+                    Some(_) => InsertResult::Rows(vec![], vec![]),
+                    None => InsertResult::NumRowsAffected(0),
+                };
+                return Either::A(ok((insert_result, conn)));
+            }
+
+            let column_types: HashMap<String, &'static str> =
+                TableColumnStat::stats_to_column_types(stats);
+            let insert_batches = params
+                .rows
+                .chunks(INSERT_ROWS_BATCH_COUNT)
+                .map(<[Map<String, Value>]>::to_vec)
+                .collect();
+
+            Either::B(
+                execute_insert_batches(conn, params, column_types, insert_batches).map(
+                    |(
+                        total_num_rows_affected,
+                        returned_columns,
+                        total_rows_returned,
+                        params,
+                        conn,
+                    )| {
+                        let insert_result = if params.returning_columns.is_some() {
+                            InsertResult::Rows(returned_columns, total_rows_returned)
+                        } else {
+                            InsertResult::NumRowsAffected(total_num_rows_affected)
+                        };
+                        (insert_result, conn)
+                    },
+                ),
+            )
+        })
+}
+
+/// Inserts each batch of rows (on a connection that is already in a transaction), adding up the
+/// number of rows affected and collecting the returned rows.
+fn execute_insert_batches(
+    conn: PooledClient,
+    params: InsertParams,
+    column_types: HashMap<String, &'static str>,
+    insert_batches: Vec<Vec<Map<String, Value>>>,
+) -> impl Future<
+    Item = (u64, Vec<String>, Vec<RowValues>, InsertParams, PooledClient),
+    Error = (Error, PooledClient),
+> {
+    loop_fn(
+        (
+            0,
+            0,
+            vec![],
+            vec![],
+            column_types,
+            insert_batches,
+            params,
+            conn,
+        ),
+        |(
+            i,
+            mut total_num_rows_affected,
+            mut returned_columns,
+            mut total_rows_returned,
+            column_types,
+            insert_batches,
+            params,
+            conn,
+        )| {
+            execute_insert(conn, params, column_types, &insert_batches[i]).and_then(
+                move |(conn, params, column_types, insert_result)| {
+                    match insert_result {
+                        InsertResult::NumRowsAffected(num_rows_affected) => {
+                            total_num_rows_affected += num_rows_affected;
+                        }
+                        InsertResult::Rows(columns, rows) => {
+                            returned_columns = columns;
+                            total_rows_returned.par_extend(rows);
+                        }
+                    };
+
+                    if i == insert_batches.len() - 1 {
+                        Ok(Loop::Break((
+                            total_num_rows_affected,
+                            returned_columns,
+                            total_rows_returned,
+                            params,
+                            conn,
+                        )))
+                    } else {
+                        Ok(Loop::Continue((
+                            i + 1,
+                            total_num_rows_affected,
+                            returned_columns,
+                            total_rows_returned,
+                            column_types,
+                            insert_batches,
+                            params,
+                            conn,
+                        )))
+                    }
+                },
+            )
+        },
+    )
+}
+
 /// Runs the actual setting up + execution of the INSERT query
 fn execute_insert<'a>(
-    mut conn: Client,
+    mut conn: PooledClient,
     params: InsertParams,
     column_types: HashMap<String, &'static str>,
     rows: &'a [Map<String, Value>],
 ) -> impl Future<
     Item = (
-        Client,
+        PooledClient,
         InsertParams,
         HashMap<String, &'static str>,
         InsertResult,
     ),
-    Error = (Error, Client),
+    Error = (Error, PooledClient),
 > {
     let mut is_return_rows = false;
     let mut insert_statement_tokens = vec!["INSERT INTO ", &params.table];
@@ -261,9 +381,12 @@ fn execute_insert<'a>(
                                 .map(|row| row_to_row_values(&row))
                                 .collect::<Result<Vec<RowValues>, Error>>()
                             {
-                                Ok(row_values) => {
-                                    Ok((conn, params, column_types, InsertResult::Rows(row_values)))
-                                }
+                                Ok(row_values) => Ok((
+                                    conn,
+                                    params,
+                                    column_types,
+                                    InsertResult::Rows(column_names(&statement), row_values),
+                                )),
                                 Err(e) => Err((e, conn)),
                             }
                         }
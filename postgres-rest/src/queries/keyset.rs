@@ -0,0 +1,526 @@
+use futures::{future::Future, stream::Stream};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+use super::{
+    foreign_keys::ForeignKeyReference, postgres_types::TypedColumnValue,
+    select_table_stats::TableColumnStat, utils::split_order_by_column, RowValuesStream,
+};
+use crate::Error;
+
+/// The sort key of each row is selected (after the requested columns) under this alias, followed
+/// by the position of the column in the sort key. The columns are removed before rows are returned.
+static CURSOR_COLUMN_ALIAS: &str = "__cursor_";
+
+/// A column that rows are sorted by when they are paginated with a cursor.
+#[derive(Debug, PartialEq)]
+struct KeysetColumn {
+    /// The column as requested (`name`, `team_id.name`).
+    column: String,
+    /// The column as used in the statement, prefixed with its table.
+    sql_column: String,
+    column_type: &'static str,
+    /// Foreign key columns are always treated as nullable, as their tables may be LEFT JOINed.
+    is_nullable: bool,
+    is_descending: bool,
+}
+
+/// The columns that rows are sorted by when they are paginated with a cursor (keyset pagination):
+/// the `order_by` columns, followed by the primary key columns that rows aren’t already sorted by
+/// (so that every row has a unique position).
+///
+/// A cursor holds the values of these columns for the last row of a page. The next page starts
+/// right after that row, no matter how many rows were inserted or deleted in the meantime.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Keyset {
+    columns: Vec<KeysetColumn>,
+}
+
+/// The contents of a cursor: the sort key columns (with their direction), and their values for the
+/// last row of a page.
+#[derive(Deserialize, Serialize)]
+struct CursorContents {
+    columns: Vec<String>,
+    values: Vec<JsonValue>,
+}
+
+impl Keyset {
+    pub(crate) fn new(
+        order_by_columns: &[String],
+        primary_key: &[String],
+        table: &str,
+        stats: &[TableColumnStat],
+        fks: &[ForeignKeyReference],
+    ) -> Result<Self, Error> {
+        if primary_key.is_empty() {
+            return Err(Error::generate_error(
+                "PRIMARY_KEY_NOT_FOUND",
+                table.to_string(),
+            ));
+        }
+
+        let mut columns = vec![];
+        for column in order_by_columns {
+            let (column, is_descending) = split_order_by_column(column)?;
+            columns.push(Self::column(column, is_descending, table, stats, fks)?);
+        }
+        for column in primary_key {
+            if columns
+                .iter()
+                .all(|keyset_column| &keyset_column.column != column)
+            {
+                columns.push(Self::column(column, false, table, stats, fks)?);
+            }
+        }
+
+        Ok(Keyset { columns })
+    }
+
+    fn column(
+        column: &str,
+        is_descending: bool,
+        table: &str,
+        stats: &[TableColumnStat],
+        fks: &[ForeignKeyReference],
+    ) -> Result<KeysetColumn, Error> {
+        let column_not_found =
+            || Error::generate_error("TABLE_COLUMN_TYPE_NOT_FOUND", column.to_string());
+
+        let (sql_column, column_type, is_nullable) =
+            match ForeignKeyReference::find(fks, table, column) {
+                Some((fk_ref, fk_column)) => {
+                    let stat = fk_ref
+                        .foreign_key_table_stats
+                        .iter()
+                        .find(|stat| stat.column_name == fk_column)
+                        .ok_or_else(column_not_found)?;
+
+                    (
+                        [&fk_ref.foreign_key_table, ".", fk_column].join(""),
+                        stat.column_type,
+                        true,
+                    )
+                }
+                None => {
+                    let stat = stats
+                        .iter()
+                        .find(|stat| stat.column_name == column)
+                        .ok_or_else(column_not_found)?;
+
+                    (
+                        [table, ".", column].join(""),
+                        stat.column_type,
+                        stat.is_nullable,
+                    )
+                }
+            };
+
+        Ok(KeysetColumn {
+            column: column.to_string(),
+            sql_column,
+            column_type,
+            is_nullable,
+            is_descending,
+        })
+    }
+
+    /// The sort key columns that are selected (after the requested columns), so that the next
+    /// cursor can be generated from the last row.
+    pub(crate) fn select_str(&self) -> String {
+        self.columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| format!("{} AS \"{}{}\"", column.sql_column, CURSOR_COLUMN_ALIAS, i))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// The columns of the ORDER BY clause.
+    pub(crate) fn order_by_str(&self) -> String {
+        self.columns
+            .iter()
+            .map(|column| {
+                [
+                    column.sql_column.as_str(),
+                    if column.is_descending {
+                        " DESC"
+                    } else {
+                        " ASC"
+                    },
+                ]
+                .join("")
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// Generates the condition that only matches the rows after the cursor, along with its
+    /// prepared values (starting at `prepared_param_pos`, which is advanced past them). An empty
+    /// cursor starts from the first row, and has no condition.
+    ///
+    /// NULLs are sorted last in ascending order (and first in descending order), like postgres
+    /// does by default.
+    pub(crate) fn seek_conditions(
+        &self,
+        cursor: &str,
+        prepared_param_pos: &mut usize,
+    ) -> Result<Option<(String, Vec<TypedColumnValue>)>, Error> {
+        if cursor.is_empty() {
+            return Ok(None);
+        }
+
+        let values = self.decode_cursor(cursor)?;
+
+        // every non-NULL value becomes a prepared value, which can be referenced more than once
+        let mut placeholders = vec![];
+        let mut prepared_values = vec![];
+        for value in values {
+            placeholders.push(value.map(|value| {
+                prepared_values.push(value);
+                *prepared_param_pos += 1;
+                format!("${}", *prepared_param_pos - 1)
+            }));
+        }
+
+        // a row comparison can use a (multi-column) index, but only works if the columns are
+        // sorted in the same direction and have no NULLs
+        let is_row_comparison = self
+            .columns
+            .iter()
+            .all(|column| column.is_descending == self.columns[0].is_descending)
+            && self.columns.iter().all(|column| !column.is_nullable)
+            && placeholders.iter().all(Option::is_some);
+        if is_row_comparison {
+            let sql_columns: Vec<&str> = self
+                .columns
+                .iter()
+                .map(|column| column.sql_column.as_str())
+                .collect();
+            let placeholders: Vec<String> = placeholders.into_iter().flatten().collect();
+
+            let conditions = [
+                "(",
+                &sql_columns.join(", "),
+                if self.columns[0].is_descending {
+                    ") < ("
+                } else {
+                    ") > ("
+                },
+                &placeholders.join(", "),
+                ")",
+            ]
+            .join("");
+            return Ok(Some((conditions, prepared_values)));
+        }
+
+        // otherwise, a row comes after the cursor if its first column comes after the cursor’s,
+        // or if the first column is equal and the rest of the row comes after the cursor
+        let mut conditions: Option<String> = None;
+        for (column, placeholder) in self.columns.iter().zip(&placeholders).rev() {
+            let sql_column = &column.sql_column;
+            let (after, equal) = match (placeholder, column.is_descending) {
+                (Some(placeholder), false) if column.is_nullable => (
+                    Some(format!(
+                        "({0} > {1} OR {0} IS NULL)",
+                        sql_column, placeholder
+                    )),
+                    format!("{} = {}", sql_column, placeholder),
+                ),
+                (Some(placeholder), false) => (
+                    Some(format!("{} > {}", sql_column, placeholder)),
+                    format!("{} = {}", sql_column, placeholder),
+                ),
+                (Some(placeholder), true) => (
+                    Some(format!("{} < {}", sql_column, placeholder)),
+                    format!("{} = {}", sql_column, placeholder),
+                ),
+                // nothing comes after NULL in ascending order
+                (None, false) => (None, format!("{} IS NULL", sql_column)),
+                (None, true) => (
+                    Some(format!("{} IS NOT NULL", sql_column)),
+                    format!("{} IS NULL", sql_column),
+                ),
+            };
+
+            conditions = match (after, conditions) {
+                (Some(after), Some(rest)) => {
+                    Some(format!("{} OR ({} AND ({}))", after, equal, rest))
+                }
+                (None, Some(rest)) => Some(format!("{} AND ({})", equal, rest)),
+                (after, None) => Some(after.unwrap_or_else(|| "FALSE".to_string())),
+            };
+        }
+
+        Ok(conditions.map(|conditions| (conditions, prepared_values)))
+    }
+
+    /// Buffers the rows of a page, and removes the sort key columns from them. If the page is full,
+    /// the cursor of the next page is generated from the last row.
+    pub(crate) fn paginate(
+        self,
+        rows: RowValuesStream,
+        limit: usize,
+    ) -> impl Future<Item = RowValuesStream, Error = Error> {
+        let num_columns = rows.columns().len() - self.columns.len();
+        let columns = rows.columns()[..num_columns].to_vec();
+        let total_count = rows.total_count();
+
+        rows.collect().and_then(move |mut rows| {
+            let mut last_values = vec![];
+            for row in &mut rows {
+                last_values = (num_columns..row.len())
+                    .filter_map(|_| row.pop().map(|(_column, value)| value))
+                    .collect();
+            }
+            // the columns were removed from last to first
+            last_values.reverse();
+
+            let next_cursor = if limit > 0 && rows.len() == limit {
+                Some(self.encode_cursor(last_values)?)
+            } else {
+                None
+            };
+
+            Ok(RowValuesStream::from_rows(columns, rows)
+                .with_total_count(total_count)
+                .with_next_cursor(next_cursor))
+        })
+    }
+
+    /// The name of each sort key column, followed by its direction.
+    fn cursor_columns(&self) -> Vec<String> {
+        self.columns
+            .iter()
+            .map(|column| {
+                [
+                    column.column.as_str(),
+                    if column.is_descending {
+                        " desc"
+                    } else {
+                        " asc"
+                    },
+                ]
+                .join("")
+            })
+            .collect()
+    }
+
+    /// Cursors are hex-encoded JSON, so that they are URL-safe (and opaque).
+    fn encode_cursor(&self, values: Vec<TypedColumnValue>) -> Result<String, Error> {
+        let contents = CursorContents {
+            columns: self.cursor_columns(),
+            values: values
+                .iter()
+                .map(serde_json::to_value)
+                .collect::<Result<Vec<JsonValue>, serde_json::Error>>()?,
+        };
+
+        Ok(serde_json::to_vec(&contents)?
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect())
+    }
+
+    /// Returns the cursor’s values (`None` for NULL), converted to the types of the sort key
+    /// columns.
+    fn decode_cursor(&self, cursor: &str) -> Result<Vec<Option<TypedColumnValue>>, Error> {
+        let invalid = || Error::generate_error("INVALID_CURSOR", cursor.to_string());
+
+        if cursor.len() % 2 != 0 || !cursor.is_ascii() {
+            return Err(invalid());
+        }
+        let bytes = (0..cursor.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&cursor[i..i + 2], 16))
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+        let contents: CursorContents = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+
+        // cursors can’t be used with a different sort order
+        if contents.columns != self.cursor_columns() || contents.values.len() != self.columns.len()
+        {
+            return Err(invalid());
+        }
+
+        self.columns
+            .iter()
+            .zip(&contents.values)
+            .map(|(column, value)| match value {
+                JsonValue::Null => Ok(None),
+                value => TypedColumnValue::from_json(column.column_type, value)
+                    .map(Some)
+                    .map_err(|_| invalid()),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod keyset_tests {
+    use super::*;
+    use crate::queries::postgres_types::IsNullColumnValue;
+    use pretty_assertions::assert_eq;
+
+    fn stat(column_name: &str, column_type: &'static str, is_nullable: bool) -> TableColumnStat {
+        TableColumnStat {
+            column_name: column_name.to_string(),
+            column_type,
+            default_value: None,
+            is_nullable,
+            is_foreign_key: false,
+            foreign_key_table: None,
+            foreign_key_column: None,
+            foreign_key_column_type: None,
+            char_max_length: None,
+            char_octet_length: None,
+            enum_labels: None,
+            geometry_srid: None,
+            geometry_type: None,
+            composite_foreign_key: None,
+        }
+    }
+
+    fn keyset(order_by_columns: &[&str]) -> Keyset {
+        let order_by_columns: Vec<String> =
+            order_by_columns.iter().map(|c| c.to_string()).collect();
+        let stats = vec![
+            stat("id", "int8", false),
+            stat("name", "text", false),
+            stat("nickname", "text", true),
+        ];
+
+        Keyset::new(
+            &order_by_columns,
+            &["id".to_string()],
+            "a_table",
+            &stats,
+            &[],
+        )
+        .unwrap()
+    }
+
+    fn cursor(keyset: &Keyset, values: Vec<TypedColumnValue>) -> String {
+        keyset.encode_cursor(values).unwrap()
+    }
+
+    #[test]
+    fn sorted_by_primary_key() {
+        let keyset = keyset(&["name desc"]);
+        assert_eq!(keyset.order_by_str(), "a_table.name DESC, a_table.id ASC");
+        assert_eq!(
+            keyset.select_str(),
+            "a_table.name AS \"__cursor_0\", a_table.id AS \"__cursor_1\""
+        );
+
+        // the primary key isn’t sorted by twice
+        assert_eq!(keyset(&["id desc"]).order_by_str(), "a_table.id DESC");
+    }
+
+    #[test]
+    fn no_primary_key() {
+        match Keyset::new(&[], &[], "a_table", &[stat("id", "int8", false)], &[]) {
+            Err(Error::UserError { code, .. }) => assert_eq!(code, "PRIMARY_KEY_NOT_FOUND"),
+            _ => panic!("Tables without a primary key can’t be paginated with a cursor."),
+        }
+    }
+
+    #[test]
+    fn first_page() {
+        let mut prepared_param_pos = 1;
+        assert_eq!(
+            keyset(&["name"])
+                .seek_conditions("", &mut prepared_param_pos)
+                .unwrap(),
+            None
+        );
+        assert_eq!(prepared_param_pos, 1);
+    }
+
+    #[test]
+    fn row_comparison() {
+        let keyset = keyset(&["name"]);
+        let cursor = cursor(
+            &keyset,
+            vec![
+                TypedColumnValue::Text(IsNullColumnValue::NotNullable("Ned".to_string())),
+                TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(5)),
+            ],
+        );
+        let mut prepared_param_pos = 2;
+
+        assert_eq!(
+            keyset
+                .seek_conditions(&cursor, &mut prepared_param_pos)
+                .unwrap(),
+            Some((
+                "(a_table.name, a_table.id) > ($2, $3)".to_string(),
+                vec![
+                    TypedColumnValue::Text(IsNullColumnValue::NotNullable("Ned".to_string())),
+                    TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(5)),
+                ]
+            ))
+        );
+        assert_eq!(prepared_param_pos, 4);
+    }
+
+    #[test]
+    fn mixed_directions_and_nulls() {
+        let keyset = keyset(&["nickname", "name desc"]);
+        let cursor_values = vec![
+            TypedColumnValue::Text(IsNullColumnValue::NotNullable("Stark".to_string())),
+            TypedColumnValue::Text(IsNullColumnValue::NotNullable("Ned".to_string())),
+            TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(5)),
+        ];
+
+        assert_eq!(
+            keyset
+                .seek_conditions(&cursor(&keyset, cursor_values), &mut 1)
+                .unwrap()
+                .unwrap()
+                .0,
+            "(a_table.nickname > $1 OR a_table.nickname IS NULL) OR (a_table.nickname = $1 AND (a_table.name < $2 OR (a_table.name = $2 AND (a_table.id > $3))))"
+        );
+
+        let null_cursor_values = vec![
+            TypedColumnValue::Text(IsNullColumnValue::Nullable(None)),
+            TypedColumnValue::Text(IsNullColumnValue::NotNullable("Ned".to_string())),
+            TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(5)),
+        ];
+        let (conditions, prepared_values) = keyset
+            .seek_conditions(&cursor(&keyset, null_cursor_values), &mut 1)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            conditions,
+            "a_table.nickname IS NULL AND (a_table.name < $1 OR (a_table.name = $1 AND (a_table.id > $2)))"
+        );
+        assert_eq!(prepared_values.len(), 2);
+    }
+
+    #[test]
+    fn invalid_cursors() {
+        let other_keyset_cursor = cursor(
+            &keyset(&["id"]),
+            vec![TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(5))],
+        );
+        let wrong_type_cursor = cursor(
+            &keyset(&["name"]),
+            vec![
+                TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(5)),
+                TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(5)),
+            ],
+        );
+
+        for cursor in &[
+            "not a cursor",
+            "7b7d",
+            other_keyset_cursor.as_str(),
+            wrong_type_cursor.as_str(),
+        ] {
+            match keyset(&["name"]).seek_conditions(cursor, &mut 1) {
+                Err(Error::UserError { code, .. }) => assert_eq!(code, "INVALID_CURSOR"),
+                _ => panic!("`{}` should not be a valid cursor.", cursor),
+            }
+        }
+    }
+}
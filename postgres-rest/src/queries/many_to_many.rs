@@ -0,0 +1,412 @@
+use futures::future::{join_all, ok, Either, Future};
+use tokio_postgres::{tls::MakeTlsConnect, Socket};
+
+use super::{
+    select_table_stats::{select_table_stats, Constraint, TableColumnStat, TableReferencedBy},
+    utils::{split_table_name, validate_alias_identifier},
+    TableStats,
+};
+use crate::{Config, Error};
+
+/// A column of a table that is linked to the selected table through a junction table, like the
+/// `name` of the `tags` of a post (`tags.name`) through `post_tags`. Its values are returned as a
+/// JSON array.
+///
+/// A junction table has exactly two foreign keys: one referencing the selected table and one
+/// referencing the linked table. Each of its other columns must either be part of its primary key
+/// or have a default value (like a surrogate `id` or a `created_at` timestamp).
+#[derive(Debug, PartialEq)]
+pub(crate) struct ManyToManyReference {
+    /// The requested column (like `tags.name` or `tags.name AS tag_names`).
+    pub column: String,
+    /// The name the values are returned under.
+    pub output_name: String,
+    pub junction_table: String,
+    /// Pairs of junction table columns and the columns of the selected table they reference.
+    pub junction_columns: Vec<(String, String)>,
+    /// The linked table.
+    pub target_table: String,
+    /// Pairs of junction table columns and the columns of the linked table they reference.
+    pub target_columns: Vec<(String, String)>,
+    /// The column of the linked table whose values are returned.
+    pub target_column: String,
+}
+
+impl ManyToManyReference {
+    /// Finds the columns that go through a junction table (among the tables referencing the
+    /// selected `table`). Columns whose prefix is a column of the selected table (foreign key
+    /// references) are skipped, as are columns that don’t go through any junction table.
+    pub(crate) fn find_all(
+        table: &str,
+        columns: &[String],
+        stats: &[TableColumnStat],
+        junction_tables_stats: &[(String, TableStats)],
+    ) -> Result<Vec<Self>, Error> {
+        let mut references = vec![];
+
+        for column in columns {
+            let (column_ref, output_name) = match validate_alias_identifier(column)? {
+                Some((column_ref, alias)) => (column_ref, alias),
+                None => (column.as_str(), column.as_str()),
+            };
+            let (prefix, target_column) = match many_to_many_column_parts(column_ref, stats) {
+                Some(parts) => parts,
+                None => continue,
+            };
+
+            let mut matches = junction_tables_stats
+                .iter()
+                .filter_map(|(junction_table, junction_stats)| {
+                    Self::from_junction_table(table, prefix, junction_table, junction_stats)
+                })
+                .collect::<Vec<Self>>();
+
+            match matches.len() {
+                0 => continue,
+                1 => {
+                    let mut reference = matches.remove(0);
+                    reference.column = column.clone();
+                    reference.output_name = output_name.to_string();
+                    reference.target_column = target_column.to_string();
+                    references.push(reference);
+                }
+                _ => {
+                    return Err(Error::generate_error(
+                        "AMBIGUOUS_JUNCTION_TABLE",
+                        format!(
+                            "{} ({})",
+                            column,
+                            matches
+                                .iter()
+                                .map(|reference| reference.junction_table.as_str())
+                                .collect::<Vec<&str>>()
+                                .join(", ")
+                        ),
+                    ))
+                }
+            }
+        }
+
+        Ok(references)
+    }
+
+    /// Returns a (partial) reference if `junction_table` is a junction table between `table` and a
+    /// table named `target` (unqualified).
+    fn from_junction_table(
+        table: &str,
+        target: &str,
+        junction_table: &str,
+        junction_stats: &TableStats,
+    ) -> Option<Self> {
+        let foreign_keys: Vec<&Constraint> = junction_stats
+            .constraints
+            .iter()
+            .filter(|constraint| {
+                constraint.constraint_type == "foreign_key" && constraint.table == junction_table
+            })
+            .collect();
+        if foreign_keys.len() != 2 {
+            return None;
+        }
+
+        let references_table = |constraint: &Constraint| {
+            constraint.fk_table.as_ref().map(String::as_str) == Some(table)
+        };
+        let junction_fk = *foreign_keys
+            .iter()
+            .find(|constraint| references_table(constraint))?;
+        let target_fk = *foreign_keys.iter().find(|constraint| {
+            !references_table(constraint)
+                && match &constraint.fk_table {
+                    Some(fk_table) => split_table_name(fk_table).1 == target,
+                    None => false,
+                }
+        })?;
+
+        // the other columns can only be part of the primary key or have defaults
+        let primary_key = junction_stats.primary_key.clone().unwrap_or_default();
+        let is_junction_table = junction_stats.columns.iter().all(|column_stat| {
+            junction_fk.columns.contains(&column_stat.column_name)
+                || target_fk.columns.contains(&column_stat.column_name)
+                || primary_key.contains(&column_stat.column_name)
+                || column_stat.default_value.is_some()
+        });
+        if !is_junction_table {
+            return None;
+        }
+
+        let column_pairs = |constraint: &Constraint| {
+            constraint
+                .columns
+                .iter()
+                .cloned()
+                .zip(constraint.fk_columns.clone().unwrap_or_default())
+                .collect::<Vec<(String, String)>>()
+        };
+
+        Some(ManyToManyReference {
+            column: String::new(),
+            output_name: String::new(),
+            junction_table: junction_table.to_string(),
+            junction_columns: column_pairs(junction_fk),
+            target_table: target_fk.fk_table.clone().unwrap_or_default(),
+            target_columns: column_pairs(target_fk),
+            target_column: String::new(),
+        })
+    }
+
+    /// Generates the sub-query (correlated with the selected `table`) that aggregates the values
+    /// into a JSON array. The values are ordered by the linked table’s referenced columns, so that
+    /// the arrays of different columns of the same linked table line up.
+    pub(crate) fn to_sql(&self, table: &str) -> String {
+        let junction = split_table_name(&self.junction_table).1;
+        let target = split_table_name(&self.target_table).1;
+
+        let join_conditions = self
+            .target_columns
+            .iter()
+            .map(|(junction_column, target_column)| {
+                [
+                    target,
+                    ".",
+                    target_column,
+                    " = ",
+                    junction,
+                    ".",
+                    junction_column,
+                ]
+                .join("")
+            })
+            .collect::<Vec<String>>()
+            .join(" AND ");
+        let where_conditions = self
+            .junction_columns
+            .iter()
+            .map(|(junction_column, column)| {
+                [junction, ".", junction_column, " = ", table, ".", column].join("")
+            })
+            .collect::<Vec<String>>()
+            .join(" AND ");
+        let order_by = self
+            .target_columns
+            .iter()
+            .map(|(_junction_column, target_column)| [target, ".", target_column].join(""))
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        format!(
+            "(SELECT COALESCE(json_agg({target}.{target_column} ORDER BY {order_by}), '[]') FROM {junction_table} AS {junction} INNER JOIN {target_table} AS {target} ON {join_conditions} WHERE {where_conditions}) AS \"{output_name}\"",
+            target = target,
+            target_column = self.target_column,
+            order_by = order_by,
+            junction_table = self.junction_table,
+            junction = junction,
+            target_table = self.target_table,
+            join_conditions = join_conditions,
+            where_conditions = where_conditions,
+            output_name = self.output_name,
+        )
+    }
+}
+
+/// Splits a column like `tags.name` into the linked table name and the column, unless the prefix
+/// is a column of the selected table.
+fn many_to_many_column_parts<'a>(
+    column: &'a str,
+    stats: &[TableColumnStat],
+) -> Option<(&'a str, &'a str)> {
+    let mut parts = column.split('.');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(prefix), Some(target_column), None)
+            if !stats.iter().any(|stat| stat.column_name == prefix) =>
+        {
+            Some((prefix, target_column))
+        }
+        _ => None,
+    }
+}
+
+/// Finds the columns that go through junction tables. Only makes database calls if a column could
+/// go through one.
+pub(crate) fn select_many_to_many_references<T>(
+    config: &Config<T>,
+    table: &str,
+    columns: &[String],
+    stats: &[TableColumnStat],
+) -> impl Future<Item = Vec<ManyToManyReference>, Error = Error>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    let is_many_to_many_possible = columns.iter().any(|column| {
+        let column_ref = match validate_alias_identifier(column) {
+            Ok(Some((column_ref, _alias))) => column_ref,
+            _ => column,
+        };
+        many_to_many_column_parts(column_ref, stats).is_some()
+    });
+    if !is_many_to_many_possible {
+        return Either::A(ok(vec![]));
+    }
+
+    let config = config.clone();
+    let table = table.to_string();
+    let columns = columns.to_vec();
+    let stats = stats.to_vec();
+
+    let references_future =
+        select_table_stats(&config, table.clone()).and_then(move |table_stats| {
+            let mut junction_tables: Vec<String> = table_stats
+                .referenced_by
+                .into_iter()
+                .map(|reference: TableReferencedBy| reference.referencing_table)
+                .filter(|referencing_table| referencing_table != &table)
+                .collect();
+            junction_tables.sort_unstable();
+            junction_tables.dedup();
+
+            join_all(
+                junction_tables
+                    .iter()
+                    .map(|junction_table| select_table_stats(&config, junction_table.clone()))
+                    .collect::<Vec<_>>(),
+            )
+            .and_then(move |junction_tables_stats| {
+                let junction_tables_stats: Vec<(String, TableStats)> = junction_tables
+                    .into_iter()
+                    .zip(junction_tables_stats)
+                    .collect();
+
+                ManyToManyReference::find_all(&table, &columns, &stats, &junction_tables_stats)
+            })
+        });
+
+    Either::B(references_future)
+}
+
+#[cfg(test)]
+mod many_to_many_tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn column_stat(column_name: &str, default_value: Option<&str>) -> TableColumnStat {
+        TableColumnStat {
+            column_name: column_name.to_string(),
+            column_type: "int8",
+            default_value: default_value.map(str::to_string),
+            is_nullable: false,
+            is_foreign_key: false,
+            foreign_key_table: None,
+            foreign_key_column: None,
+            foreign_key_column_type: None,
+            char_max_length: None,
+            char_octet_length: None,
+            enum_labels: None,
+            geometry_srid: None,
+            geometry_type: None,
+            composite_foreign_key: None,
+        }
+    }
+
+    fn foreign_key(table: &str, column: &str, fk_table: &str) -> Constraint {
+        Constraint {
+            name: [table, "_", column].join(""),
+            table: table.to_string(),
+            columns: vec![column.to_string()],
+            constraint_type: "foreign_key",
+            definition: String::new(),
+            fk_table: Some(fk_table.to_string()),
+            fk_columns: Some(vec!["id".to_string()]),
+        }
+    }
+
+    fn post_tags_stats(extra_column: TableColumnStat) -> (String, TableStats) {
+        (
+            "post_tags".to_string(),
+            TableStats {
+                columns: vec![
+                    column_stat("post_id", None),
+                    column_stat("tag_id", None),
+                    extra_column,
+                ],
+                constraints: vec![
+                    foreign_key("post_tags", "post_id", "posts"),
+                    foreign_key("post_tags", "tag_id", "tags"),
+                ],
+                estimated_row_count: 0,
+                indexes: vec![],
+                primary_key: None,
+                referenced_by: vec![],
+                total_size_bytes: 0,
+            },
+        )
+    }
+
+    #[test]
+    fn junction_table() {
+        let posts_stats = vec![column_stat("id", None), column_stat("title", None)];
+        let references = ManyToManyReference::find_all(
+            "posts",
+            &[
+                "title".to_string(),
+                "tags.name as tag_names".to_string(),
+                "author_id.name".to_string(),
+            ],
+            &posts_stats,
+            &[post_tags_stats(column_stat("created_at", Some("now()")))],
+        )
+        .unwrap();
+
+        assert_eq!(
+            references,
+            vec![ManyToManyReference {
+                column: "tags.name as tag_names".to_string(),
+                output_name: "tag_names".to_string(),
+                junction_table: "post_tags".to_string(),
+                junction_columns: vec![("post_id".to_string(), "id".to_string())],
+                target_table: "tags".to_string(),
+                target_columns: vec![("tag_id".to_string(), "id".to_string())],
+                target_column: "name".to_string(),
+            }]
+        );
+        assert_eq!(
+            references[0].to_sql("posts"),
+            "(SELECT COALESCE(json_agg(tags.name ORDER BY tags.id), '[]') FROM post_tags AS post_tags INNER JOIN tags AS tags ON tags.id = post_tags.tag_id WHERE post_tags.post_id = posts.id) AS \"tag_names\""
+        );
+    }
+
+    #[test]
+    fn not_a_junction_table() {
+        // `note` has no default and isn’t part of the primary key
+        let references = ManyToManyReference::find_all(
+            "posts",
+            &["tags.name".to_string()],
+            &[column_stat("id", None)],
+            &[post_tags_stats(column_stat("note", None))],
+        )
+        .unwrap();
+
+        assert_eq!(references, vec![]);
+    }
+
+    #[test]
+    fn ambiguous_junction_tables() {
+        let mut other_junction_table = post_tags_stats(column_stat("id", Some("1")));
+        other_junction_table.0 = "post_tags_2".to_string();
+        for constraint in other_junction_table.1.constraints.iter_mut() {
+            constraint.table = "post_tags_2".to_string();
+        }
+
+        match ManyToManyReference::find_all(
+            "posts",
+            &["tags.name".to_string()],
+            &[column_stat("id", None)],
+            &[
+                post_tags_stats(column_stat("id", Some("1"))),
+                other_junction_table,
+            ],
+        ) {
+            Err(Error::UserError { code, .. }) => assert_eq!(code, "AMBIGUOUS_JUNCTION_TABLE"),
+            _ => panic!("More than one junction table should be an error."),
+        }
+    }
+}
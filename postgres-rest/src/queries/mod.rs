@@ -1,19 +1,30 @@
 mod foreign_keys;
+pub use self::foreign_keys::JoinType;
+
+mod keyset;
+
+mod many_to_many;
+
+mod batch;
+pub use self::batch::{execute_batch, BatchOperation, BatchResult};
 
 mod delete_table_rows;
 pub use self::delete_table_rows::{delete_table_rows, DeleteParams};
 
 mod execute_sql_query;
-pub use self::execute_sql_query::{execute_sql_query, ExecuteParams};
+pub use self::execute_sql_query::{execute_sql_query, ExecuteParams, SqlStatementKind};
 
 mod insert_into_table;
 pub use self::insert_into_table::{insert_into_table, InsertParams};
 
+mod primary_key;
+pub use self::primary_key::{primary_key_conditions, PrimaryKeyConditions, PRIMARY_KEY_DELIMITER};
+
 mod select_all_tables;
 pub use select_all_tables::select_all_tables;
 
 mod select_table_rows;
-pub use self::select_table_rows::{select_table_rows, SelectParams};
+pub use self::select_table_rows::{select_table_rows, CountMode, SelectParams};
 
 mod select_table_stats;
 pub use self::select_table_stats::select_table_stats;
@@ -22,22 +33,50 @@ pub(crate) use self::select_table_stats::TableStats;
 mod select_table_stats_cache;
 pub(crate) use self::select_table_stats_cache::select_all_table_stats;
 
+mod transaction;
+pub use self::transaction::{
+    begin_transaction, commit_transaction, create_savepoint, execute_in_transaction,
+    release_savepoint, rollback_to_savepoint, rollback_transaction, IsolationLevel,
+};
+
 mod update_table_rows;
 pub use update_table_rows::{update_table_rows, UpdateParams};
 
 mod postgres_types;
+pub use self::postgres_types::{RowValues, TypedColumnValue};
+
+mod row_values_stream;
+pub use self::row_values_stream::RowValuesStream;
+
+mod column_nesting;
+pub use self::column_nesting::{ColumnNesting, NestedRow};
+
+mod embedded_rows;
+pub use self::embedded_rows::EmbeddedRows;
+
+#[cfg(feature = "postgis")]
+mod postgis;
+
+#[cfg(feature = "arrow-export")]
+mod columnar;
+#[cfg(feature = "arrow-export")]
+pub use self::columnar::{write_parquet, ArrowIpcStream, RecordBatchStream, RECORD_BATCH_SIZE};
 
-mod utils;
+pub(crate) mod utils;
 
-use postgres_types::RowValues;
 use serde::Serialize;
 
 #[derive(Serialize)]
 #[serde(untagged)]
 /// Represents the response from sending a QueryTask to DbExecutor
 pub enum QueryResult {
-    QueryTableResult(Vec<RowValues>),
-    RowsAffected { num_rows: u64 },
+    /// Rows that are streamed from the database. Can’t be serialized directly; serialize each row
+    /// as it arrives instead.
+    #[serde(skip_serializing)]
+    QueryTableStream(RowValuesStream),
+    RowsAffected {
+        num_rows: u64,
+    },
 }
 
 impl QueryResult {
@@ -0,0 +1,535 @@
+//! Conversion between PostGIS `geometry`/`geography` values ([EWKB](https://postgis.net/docs/using_postgis_dbmanagement.html#EWKB_EWKT))
+//! and [GeoJSON](https://tools.ietf.org/html/rfc7946) geometry objects.
+
+use serde::{Serialize, Serializer};
+use serde_json::{json, Map as JsonMap, Value as JsonValue};
+use std::error::Error as StdError;
+use tokio_postgres::{
+    to_sql_checked,
+    types::{FromSql, IsNull, ToSql, Type},
+};
+
+/// The SRID assumed for GeoJSON objects without a `crs` member (WGS 84).
+const DEFAULT_SRID: i32 = 4326;
+
+const EWKB_Z_FLAG: u32 = 0x8000_0000;
+const EWKB_M_FLAG: u32 = 0x4000_0000;
+const EWKB_SRID_FLAG: u32 = 0x2000_0000;
+
+/// GeoJSON geometry types, in the order of their WKB type codes (starting at 1).
+static GEOMETRY_TYPES: [&str; 7] = [
+    "Point",
+    "LineString",
+    "Polygon",
+    "MultiPoint",
+    "MultiLineString",
+    "MultiPolygon",
+    "GeometryCollection",
+];
+
+/// A `geometry` or `geography` value. Serialized as a GeoJSON geometry object. Geometries whose
+/// SRID isn’t 4326 (or unknown) include a `crs` member, like
+/// `"crs": {"type": "name", "properties": {"name": "EPSG:3857"}}`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Geometry {
+    geojson: JsonValue,
+    ewkb: Vec<u8>,
+}
+
+impl Geometry {
+    /// Converts a GeoJSON geometry object. The SRID is taken from the object’s `crs` member if it
+    /// has one, and is 4326 otherwise.
+    pub fn from_geojson(geojson: &JsonValue) -> Result<Self, String> {
+        let srid = match geojson.get("crs") {
+            Some(crs) => parse_crs(crs)?,
+            None => DEFAULT_SRID,
+        };
+
+        let mut ewkb = vec![];
+        write_geometry(geojson, Some(srid), &mut ewkb)?;
+
+        Ok(Geometry {
+            geojson: geojson.clone(),
+            ewkb,
+        })
+    }
+
+    /// Same as `from_geojson()`, but for a GeoJSON string.
+    pub fn from_geojson_str(geojson_str: &str) -> Result<Self, String> {
+        let geojson: JsonValue = serde_json::from_str(geojson_str)
+            .map_err(|e| format!("Invalid GeoJSON: `{}`. Message: `{}`.", geojson_str, e))?;
+        Self::from_geojson(&geojson)
+    }
+
+    /// Returns the hex-encoded EWKB of the geometry, which postgres accepts as a `geometry` or
+    /// `geography` literal.
+    pub fn to_hex_ewkb(&self) -> String {
+        self.ewkb
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect()
+    }
+}
+
+impl<'a> FromSql<'a> for Geometry {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Geometry, Box<dyn StdError + Send + Sync>> {
+        let mut reader = EwkbReader { raw, pos: 0 };
+        let (mut geojson, srid_opt) = reader.read_geometry()?;
+
+        if let Some(srid) = srid_opt.filter(|srid| *srid != 0 && *srid != DEFAULT_SRID) {
+            geojson["crs"] = json!({
+                "type": "name",
+                "properties": { "name": format!("EPSG:{}", srid) }
+            });
+        }
+
+        Ok(Geometry {
+            geojson,
+            ewkb: raw.to_vec(),
+        })
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        match ty.name() {
+            "geography" | "geometry" => true,
+            _ => false,
+        }
+    }
+}
+
+impl ToSql for Geometry {
+    fn to_sql(&self, _: &Type, w: &mut Vec<u8>) -> Result<IsNull, Box<dyn StdError + Send + Sync>> {
+        w.extend_from_slice(&self.ewkb);
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        match ty.name() {
+            "geography" | "geometry" => true,
+            _ => false,
+        }
+    }
+
+    to_sql_checked!();
+}
+
+impl Serialize for Geometry {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.geojson.serialize(serializer)
+    }
+}
+
+/// Parses the SRID out of a (GeoJSON 2008) named `crs` member, like `EPSG:3857` or
+/// `urn:ogc:def:crs:EPSG::3857`.
+fn parse_crs(crs: &JsonValue) -> Result<i32, String> {
+    let invalid_err = || format!("Unsupported GeoJSON crs: `{}`.", crs);
+
+    let name = crs["properties"]["name"].as_str().ok_or_else(invalid_err)?;
+    if name == "urn:ogc:def:crs:OGC:1.3:CRS84" {
+        return Ok(DEFAULT_SRID);
+    }
+
+    name.rsplit(':')
+        .next()
+        .and_then(|srid_str| srid_str.parse().ok())
+        .ok_or_else(invalid_err)
+}
+
+/// Reads (E)WKB, keeping track of the current position.
+struct EwkbReader<'a> {
+    raw: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> EwkbReader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.pos + len > self.raw.len() {
+            return Err("Unexpected end of EWKB.".to_string());
+        }
+
+        let bytes = &self.raw[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self, is_little_endian: bool) -> Result<u32, String> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.read_bytes(4)?);
+
+        Ok(if is_little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+
+    fn read_f64(&mut self, is_little_endian: bool) -> Result<f64, String> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.read_bytes(8)?);
+
+        Ok(if is_little_endian {
+            f64::from_le_bytes(bytes)
+        } else {
+            f64::from_be_bytes(bytes)
+        })
+    }
+
+    /// Reads a geometry (including its header) as a GeoJSON object, and returns its SRID if it
+    /// has one.
+    fn read_geometry(&mut self) -> Result<(JsonValue, Option<i32>), String> {
+        let is_little_endian = self.read_bytes(1)?[0] == 1;
+        let type_int = self.read_u32(is_little_endian)?;
+
+        // EWKB flags dimensions in the high bits, while ISO WKB adds 1000 (Z), 2000 (M), or 3000
+        // (ZM) to the type code.
+        let iso_dimensions = (type_int & 0x0FFF_FFFF) / 1000;
+        let has_z = type_int & EWKB_Z_FLAG != 0 || iso_dimensions == 1 || iso_dimensions == 3;
+        let has_m = type_int & EWKB_M_FLAG != 0 || iso_dimensions == 2 || iso_dimensions == 3;
+        let srid_opt = if type_int & EWKB_SRID_FLAG != 0 {
+            Some(self.read_u32(is_little_endian)? as i32)
+        } else {
+            None
+        };
+
+        let type_code = (type_int & 0x0FFF_FFFF) % 1000;
+        let geometry_type = match type_code {
+            1..=7 => GEOMETRY_TYPES[type_code as usize - 1],
+            _ => return Err(format!("Unsupported geometry type code: {}.", type_code)),
+        };
+        let dims = Dims { has_z, has_m };
+
+        let mut geojson = JsonMap::new();
+        geojson.insert("type".to_string(), json!(geometry_type));
+
+        match geometry_type {
+            "Point" => {
+                let position = self.read_position(is_little_endian, dims)?;
+                // empty points are stored as NaN coordinates
+                let coordinates = if position.iter().all(|ordinate| ordinate.is_nan()) {
+                    json!([])
+                } else {
+                    json!(position)
+                };
+                geojson.insert("coordinates".to_string(), coordinates);
+            }
+            "LineString" => {
+                let line = self.read_positions(is_little_endian, dims)?;
+                geojson.insert("coordinates".to_string(), json!(line));
+            }
+            "Polygon" => {
+                let polygon = self.read_polygon(is_little_endian, dims)?;
+                geojson.insert("coordinates".to_string(), json!(polygon));
+            }
+            "GeometryCollection" => {
+                let num_geometries = self.read_u32(is_little_endian)?;
+                let geometries = (0..num_geometries)
+                    .map(|_| self.read_geometry().map(|(geometry, _)| geometry))
+                    .collect::<Result<Vec<JsonValue>, String>>()?;
+                geojson.insert("geometries".to_string(), JsonValue::Array(geometries));
+            }
+            // Multi* geometries contain complete (sub)geometries, whose coordinates are combined
+            _ => {
+                let num_geometries = self.read_u32(is_little_endian)?;
+                let coordinates = (0..num_geometries)
+                    .map(|_| {
+                        self.read_geometry()
+                            .map(|(mut geometry, _)| geometry["coordinates"].take())
+                    })
+                    .collect::<Result<Vec<JsonValue>, String>>()?;
+                geojson.insert("coordinates".to_string(), JsonValue::Array(coordinates));
+            }
+        }
+
+        Ok((JsonValue::Object(geojson), srid_opt))
+    }
+
+    /// Reads a single position. M values are dropped, since GeoJSON doesn’t support them.
+    fn read_position(&mut self, is_little_endian: bool, dims: Dims) -> Result<Vec<f64>, String> {
+        let mut position = vec![
+            self.read_f64(is_little_endian)?,
+            self.read_f64(is_little_endian)?,
+        ];
+        if dims.has_z {
+            position.push(self.read_f64(is_little_endian)?);
+        }
+        if dims.has_m {
+            self.read_f64(is_little_endian)?;
+        }
+
+        Ok(position)
+    }
+
+    fn read_positions(
+        &mut self,
+        is_little_endian: bool,
+        dims: Dims,
+    ) -> Result<Vec<Vec<f64>>, String> {
+        let num_positions = self.read_u32(is_little_endian)?;
+        (0..num_positions)
+            .map(|_| self.read_position(is_little_endian, dims))
+            .collect()
+    }
+
+    fn read_polygon(
+        &mut self,
+        is_little_endian: bool,
+        dims: Dims,
+    ) -> Result<Vec<Vec<Vec<f64>>>, String> {
+        let num_rings = self.read_u32(is_little_endian)?;
+        (0..num_rings)
+            .map(|_| self.read_positions(is_little_endian, dims))
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Dims {
+    has_z: bool,
+    has_m: bool,
+}
+
+/// Writes a GeoJSON geometry object as little-endian EWKB. Only the outermost geometry carries the
+/// SRID.
+fn write_geometry(
+    geojson: &JsonValue,
+    srid_opt: Option<i32>,
+    w: &mut Vec<u8>,
+) -> Result<(), String> {
+    let invalid_err = || format!("Invalid GeoJSON geometry: `{}`.", geojson);
+
+    let geometry_type = geojson["type"].as_str().ok_or_else(invalid_err)?;
+    let type_code = match GEOMETRY_TYPES.iter().position(|t| *t == geometry_type) {
+        Some(index) => index as u32 + 1,
+        None => {
+            return Err(format!(
+                "Unsupported GeoJSON geometry type: `{}`.",
+                geometry_type
+            ))
+        }
+    };
+
+    if geometry_type == "GeometryCollection" {
+        let geometries = geojson["geometries"].as_array().ok_or_else(invalid_err)?;
+        let has_z = geometries
+            .iter()
+            .filter_map(|geometry| first_position(&geometry["coordinates"]))
+            .next()
+            .map_or(false, |position| position.len() > 2);
+        write_header(type_code, has_z, srid_opt, w);
+        w.extend_from_slice(&(geometries.len() as u32).to_le_bytes());
+        for geometry in geometries {
+            write_geometry(geometry, None, w)?;
+        }
+
+        return Ok(());
+    }
+
+    let coordinates = &geojson["coordinates"];
+    let has_z = first_position(coordinates).map_or(false, |position| position.len() > 2);
+    write_header(type_code, has_z, srid_opt, w);
+
+    match geometry_type {
+        "Point" => match coordinates.as_array() {
+            // empty points are stored as NaN coordinates
+            Some(position) if position.is_empty() => {
+                let num_ordinates = if has_z { 3 } else { 2 };
+                for _ in 0..num_ordinates {
+                    w.extend_from_slice(&std::f64::NAN.to_le_bytes());
+                }
+            }
+            _ => write_position(coordinates, has_z, w)?,
+        },
+        "LineString" => write_positions(coordinates, has_z, w)?,
+        "Polygon" => write_polygon(coordinates, has_z, w)?,
+        _ => {
+            // Multi* geometries contain complete (sub)geometries
+            let sub_geometry_type = geometry_type.trim_start_matches("Multi");
+            let parts = coordinates.as_array().ok_or_else(invalid_err)?;
+            w.extend_from_slice(&(parts.len() as u32).to_le_bytes());
+            for part in parts {
+                write_geometry(
+                    &json!({ "type": sub_geometry_type, "coordinates": part }),
+                    None,
+                    w,
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_header(type_code: u32, has_z: bool, srid_opt: Option<i32>, w: &mut Vec<u8>) {
+    let mut type_int = type_code;
+    if has_z {
+        type_int |= EWKB_Z_FLAG;
+    }
+    if srid_opt.is_some() {
+        type_int |= EWKB_SRID_FLAG;
+    }
+
+    // little endian
+    w.push(1);
+    w.extend_from_slice(&type_int.to_le_bytes());
+    if let Some(srid) = srid_opt {
+        w.extend_from_slice(&srid.to_le_bytes());
+    }
+}
+
+fn write_position(position: &JsonValue, has_z: bool, w: &mut Vec<u8>) -> Result<(), String> {
+    let invalid_err = || format!("Invalid GeoJSON position: `{}`.", position);
+
+    let ordinates = position.as_array().ok_or_else(invalid_err)?;
+    let num_ordinates = if has_z { 3 } else { 2 };
+    if ordinates.len() != num_ordinates {
+        return Err(format!(
+            "All GeoJSON positions of a geometry must have {} ordinates: `{}`.",
+            num_ordinates, position
+        ));
+    }
+
+    for ordinate in ordinates {
+        let ordinate = ordinate.as_f64().ok_or_else(invalid_err)?;
+        w.extend_from_slice(&ordinate.to_le_bytes());
+    }
+
+    Ok(())
+}
+
+fn write_positions(positions: &JsonValue, has_z: bool, w: &mut Vec<u8>) -> Result<(), String> {
+    let positions = positions
+        .as_array()
+        .ok_or_else(|| format!("Invalid GeoJSON coordinates: `{}`.", positions))?;
+
+    w.extend_from_slice(&(positions.len() as u32).to_le_bytes());
+    for position in positions {
+        write_position(position, has_z, w)?;
+    }
+
+    Ok(())
+}
+
+fn write_polygon(rings: &JsonValue, has_z: bool, w: &mut Vec<u8>) -> Result<(), String> {
+    let rings = rings
+        .as_array()
+        .ok_or_else(|| format!("Invalid GeoJSON coordinates: `{}`.", rings))?;
+
+    w.extend_from_slice(&(rings.len() as u32).to_le_bytes());
+    for ring in rings {
+        write_positions(ring, has_z, w)?;
+    }
+
+    Ok(())
+}
+
+/// Returns the first position found in (possibly nested) GeoJSON coordinates.
+fn first_position(coordinates: &JsonValue) -> Option<&Vec<JsonValue>> {
+    let coordinates = coordinates.as_array()?;
+    match coordinates.first() {
+        Some(JsonValue::Array(_)) => first_position(&coordinates[0]),
+        Some(_) => Some(coordinates),
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod postgis_tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    /// Converts hex (as output by postgres) to bytes.
+    fn hex_to_bytes(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn point() {
+        // SELECT 'SRID=4326;POINT(1 2)'::geometry
+        let ewkb = hex_to_bytes("0101000020E6100000000000000000F03F0000000000000040");
+        let geometry = Geometry::from_sql(&Type::BYTEA, &ewkb).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&geometry).unwrap(),
+            json!({"type": "Point", "coordinates": [1.0, 2.0]})
+        );
+        assert_eq!(
+            Geometry::from_geojson(&json!({"type": "Point", "coordinates": [1, 2]}))
+                .unwrap()
+                .ewkb,
+            ewkb
+        );
+    }
+
+    #[test]
+    fn big_endian_polygon_with_z() {
+        // SELECT ST_AsEWKB('POLYGON Z((0 0 1,1 0 1,1 1 1,0 0 1))'::geometry, 'XDR')
+        let ewkb = hex_to_bytes(
+            "00800000030000000100000004\
+             00000000000000000000000000000000\
+             3FF0000000000000\
+             3FF000000000000000000000000000003FF0000000000000\
+             3FF00000000000003FF00000000000003FF0000000000000\
+             000000000000000000000000000000003FF0000000000000",
+        );
+        let geometry = Geometry::from_sql(&Type::BYTEA, &ewkb).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&geometry).unwrap(),
+            json!({
+                "type": "Polygon",
+                "coordinates": [[[0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [1.0, 1.0, 1.0], [0.0, 0.0, 1.0]]]
+            })
+        );
+    }
+
+    #[test]
+    fn round_trips() {
+        let geometries = vec![
+            json!({"type": "LineString", "coordinates": [[1.0, 2.0], [3.0, 4.0]]}),
+            json!({"type": "MultiPoint", "coordinates": [[1.0, 2.0, 3.0], [4.0, 5.0, 6.0]]}),
+            json!({"type": "MultiPolygon", "coordinates": [[[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 0.0]]]]}),
+            json!({
+                "type": "GeometryCollection",
+                "geometries": [
+                    {"type": "Point", "coordinates": []},
+                    {"type": "MultiLineString", "coordinates": [[[1.0, 2.0], [3.0, 4.0]]]}
+                ]
+            }),
+            json!({
+                "type": "Point",
+                "coordinates": [1.0, 2.0],
+                "crs": {"type": "name", "properties": {"name": "EPSG:3857"}}
+            }),
+        ];
+
+        for geojson in geometries {
+            let ewkb = Geometry::from_geojson(&geojson).unwrap().ewkb;
+            let geometry = Geometry::from_sql(&Type::BYTEA, &ewkb).unwrap();
+            assert_eq!(serde_json::to_value(&geometry).unwrap(), geojson);
+        }
+    }
+
+    #[test]
+    fn hex_ewkb() {
+        let geometry =
+            Geometry::from_geojson_str(r#"{"type": "Point", "coordinates": [1, 2]}"#).unwrap();
+        assert_eq!(
+            geometry.to_hex_ewkb(),
+            "0101000020E6100000000000000000F03F0000000000000040"
+        );
+    }
+
+    #[test]
+    fn invalid_geojson() {
+        assert!(Geometry::from_geojson(&json!({"type": "Circle", "coordinates": [1, 2]})).is_err());
+        assert!(Geometry::from_geojson(&json!({"type": "Point", "coordinates": [1]})).is_err());
+        assert!(Geometry::from_geojson(
+            &json!({"type": "LineString", "coordinates": [[1, 2], [1, 2, 3]]})
+        )
+        .is_err());
+        assert!(Geometry::from_geojson_str("POINT(1 2)").is_err());
+    }
+}
@@ -1,62 +1,1468 @@
-use crate::Error;
-use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
+#[cfg(feature = "postgis")]
+use super::postgis::Geometry;
+use super::utils::{replace_placeholders_with_operators, split_operator_placeholder};
+use crate::{encoding, Error};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Timelike, Utc};
 use eui48::MacAddress as Eui48MacAddress;
 use failure::Fail;
-use postgres_protocol::types::macaddr_to_sql;
+use fallible_iterator::FallibleIterator;
+use indexmap::IndexMap;
+use postgres_protocol::{
+    types::{
+        array_from_sql, array_to_sql, empty_range_to_sql, hstore_from_sql, hstore_to_sql,
+        inet_from_sql, inet_to_sql, macaddr_to_sql, range_from_sql, range_to_sql, text_from_sql,
+        text_to_sql, varbit_from_sql, varbit_to_sql, ArrayDimension, Range, RangeBound,
+    },
+    IsNull as ProtocolIsNull,
+};
 use rayon::prelude::*;
 use rust_decimal::Decimal;
-use serde::Serialize;
+use serde::{ser::SerializeMap, Serialize, Serializer};
 use serde_json::Value as JsonValue;
-use sqlparser::ast::{Expr, Function, UnaryOperator, Value as SqlValue};
+#[cfg(feature = "postgis")]
+use sqlparser::ast::Function;
+use sqlparser::ast::{Expr, UnaryOperator, Value as SqlValue};
 use std::{
     borrow::{Borrow, BorrowMut},
     collections::HashMap,
     error::Error as StdError,
-    fmt, mem,
-    str::FromStr,
+    fmt,
+    iter::Peekable,
+    mem,
+    net::IpAddr,
+    str::{Chars, FromStr},
 };
 use tokio_postgres::{
     accepts,
     row::Row,
     to_sql_checked,
-    types::{FromSql, IsNull, ToSql, Type},
+    types::{FromSql, IsNull, Kind, ToSql, Type},
 };
 use uuid::Uuid;
 
-/// we have to define our own MacAddress type in order for Serde to serialize it properly.
-#[derive(Debug, PartialEq, Serialize)]
-pub struct MacAddress(Eui48MacAddress);
+/// we have to define our own MacAddress type in order for Serde to serialize it properly.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct MacAddress(Eui48MacAddress);
+
+// mostly copied from the postgres-protocol and postgres-shared libraries
+impl<'a> FromSql<'a> for MacAddress {
+    fn from_sql(
+        typ: &Type,
+        raw: &[u8],
+    ) -> Result<MacAddress, Box<dyn StdError + Send + Sync + Send>> {
+        let mac = <Eui48MacAddress as FromSql>::from_sql(typ, raw)?;
+        Ok(MacAddress(mac))
+    }
+
+    accepts!(MACADDR);
+}
+
+impl ToSql for MacAddress {
+    fn to_sql(
+        &self,
+        _: &Type,
+        w: &mut Vec<u8>,
+    ) -> Result<IsNull, Box<dyn StdError + Send + Sync + Send>> {
+        let mut bytes = [0; 6];
+        bytes.copy_from_slice(self.0.as_bytes());
+        macaddr_to_sql(bytes, w);
+        Ok(IsNull::No)
+    }
+
+    accepts!(MACADDR);
+    to_sql_checked!();
+}
+
+/// An `inet` or `cidr` value. Serialized as a string, like `"192.168.0.1"` or `"10.0.0.0/8"`. The
+/// netmask is omitted for `inet` host addresses.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Inet {
+    addr: IpAddr,
+    netmask: u8,
+    is_cidr: bool,
+}
+
+impl Inet {
+    fn max_netmask(addr: &IpAddr) -> u8 {
+        match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        }
+    }
+}
+
+impl fmt::Display for Inet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_cidr || self.netmask != Self::max_netmask(&self.addr) {
+            write!(f, "{}/{}", self.addr, self.netmask)
+        } else {
+            write!(f, "{}", self.addr)
+        }
+    }
+}
+
+impl FromStr for Inet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr_str, netmask_str) = match s.find('/') {
+            Some(slash_pos) => (&s[..slash_pos], Some(&s[slash_pos + 1..])),
+            None => (s, None),
+        };
+
+        let addr = IpAddr::from_str(addr_str).map_err(|e| e.to_string())?;
+        let max_netmask = Self::max_netmask(&addr);
+        let netmask = match netmask_str {
+            Some(netmask_str) => match u8::from_str(netmask_str) {
+                Ok(netmask) if netmask <= max_netmask => netmask,
+                _ => return Err(format!("Invalid netmask: `{}`.", netmask_str)),
+            },
+            None => max_netmask,
+        };
+
+        Ok(Inet {
+            addr,
+            netmask,
+            is_cidr: netmask_str.is_some(),
+        })
+    }
+}
+
+impl Serialize for Inet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'a> FromSql<'a> for Inet {
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<Inet, Box<dyn StdError + Send + Sync>> {
+        let inet = inet_from_sql(raw)?;
+        Ok(Inet {
+            addr: inet.addr(),
+            netmask: inet.netmask(),
+            is_cidr: ty.name() == "cidr",
+        })
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        ty.name() == "inet" || ty.name() == "cidr"
+    }
+}
+
+impl ToSql for Inet {
+    fn to_sql(&self, _: &Type, w: &mut Vec<u8>) -> Result<IsNull, Box<dyn StdError + Send + Sync>> {
+        inet_to_sql(self.addr, self.netmask, w);
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        ty.name() == "inet" || ty.name() == "cidr"
+    }
+
+    to_sql_checked!();
+}
+
+/// An `interval` value. Serialized as an ISO 8601 duration string, like `"P1Y2M3DT4H5M6.5S"`.
+/// Parses both ISO 8601 durations and postgres’ own format (like `"1 year 2 mons 3 days
+/// 04:05:06"`).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Interval {
+    months: i32,
+    days: i32,
+    microseconds: i64,
+}
+
+impl Interval {
+    const MICROSECONDS_PER_SECOND: f64 = 1_000_000.0;
+    const MICROSECONDS_PER_DAY: f64 = 86_400_000_000.0;
+
+    /// Adds a (possibly fractional) amount of the given unit. Like postgres, fractional months
+    /// spill over into days (assuming 30-day months) and fractional days spill over into time.
+    fn add(&mut self, unit: &str, value: f64) -> Result<(), String> {
+        let (months, days, microseconds) = match unit {
+            "y" | "yr" | "yrs" | "year" | "years" => (value * 12.0, 0.0, 0.0),
+            "mon" | "mons" | "month" | "months" => (value, 0.0, 0.0),
+            "w" | "week" | "weeks" => (0.0, value * 7.0, 0.0),
+            "d" | "day" | "days" => (0.0, value, 0.0),
+            "h" | "hr" | "hrs" | "hour" | "hours" => {
+                (0.0, 0.0, value * 3600.0 * Self::MICROSECONDS_PER_SECOND)
+            }
+            "m" | "min" | "mins" | "minute" | "minutes" => {
+                (0.0, 0.0, value * 60.0 * Self::MICROSECONDS_PER_SECOND)
+            }
+            "s" | "sec" | "secs" | "second" | "seconds" => {
+                (0.0, 0.0, value * Self::MICROSECONDS_PER_SECOND)
+            }
+            "ms" | "millisecond" | "milliseconds" => (0.0, 0.0, value * 1000.0),
+            "us" | "microsecond" | "microseconds" => (0.0, 0.0, value),
+            _ => return Err(format!("Unknown interval unit: `{}`.", unit)),
+        };
+
+        let days = days + months.fract() * 30.0;
+        let microseconds = microseconds + days.fract() * Self::MICROSECONDS_PER_DAY;
+
+        let out_of_range = || "Interval out of range".to_string();
+        self.months = Self::to_i32(months.trunc())
+            .and_then(|months| self.months.checked_add(months))
+            .ok_or_else(out_of_range)?;
+        self.days = Self::to_i32(days.trunc())
+            .and_then(|days| self.days.checked_add(days))
+            .ok_or_else(out_of_range)?;
+        self.microseconds = Self::to_i64(microseconds.round())
+            .and_then(|microseconds| self.microseconds.checked_add(microseconds))
+            .ok_or_else(out_of_range)?;
+
+        Ok(())
+    }
+
+    /// Converts a whole number to an `i32`, if it is in range (casting floats that aren’t is
+    /// undefined behaviour).
+    fn to_i32(value: f64) -> Option<i32> {
+        if value >= f64::from(i32::min_value()) && value <= f64::from(i32::max_value()) {
+            Some(value as i32)
+        } else {
+            None
+        }
+    }
+
+    /// Converts a whole number to an `i64`, if it is in range. The largest `i64` can’t be
+    /// represented as a float, so the bounds are ±2⁶³.
+    fn to_i64(value: f64) -> Option<i64> {
+        if value >= -9_223_372_036_854_775_808.0 && value < 9_223_372_036_854_775_808.0 {
+            Some(value as i64)
+        } else {
+            None
+        }
+    }
+
+    /// Parses an ISO 8601 duration, like `P1Y2M3DT4H5M6S`.
+    fn from_iso_8601_str(s: &str) -> Result<Self, String> {
+        let invalid_err = || format!("Invalid ISO 8601 duration: `{}`.", s);
+        let mut interval = Interval::default();
+        let mut is_time = false;
+        let mut number = String::new();
+
+        for c in s.chars().skip(1) {
+            match c.to_ascii_lowercase() {
+                't' if number.is_empty() && !is_time => is_time = true,
+                '0'..='9' | '.' | '-' | '+' => number.push(c),
+                designator => {
+                    let value = f64::from_str(&number).map_err(|_| invalid_err())?;
+                    number.clear();
+
+                    let unit = match (designator, is_time) {
+                        ('y', false) => "years",
+                        ('m', false) => "months",
+                        ('w', false) => "weeks",
+                        ('d', false) => "days",
+                        ('h', true) => "hours",
+                        ('m', true) => "minutes",
+                        ('s', true) => "seconds",
+                        _ => return Err(invalid_err()),
+                    };
+                    interval.add(unit, value)?;
+                }
+            }
+        }
+
+        if !number.is_empty() {
+            return Err(invalid_err());
+        }
+
+        Ok(interval)
+    }
+
+    /// Parses postgres’ own interval format, like `1 year 2 mons -3 days 04:05:06 ago`.
+    fn from_postgres_str(s: &str) -> Result<Self, String> {
+        let invalid_err = || format!("Invalid interval: `{}`.", s);
+        let mut interval = Interval::default();
+        let mut is_ago = false;
+        let mut tokens = s
+            .split_whitespace()
+            .filter(|token| *token != "@")
+            .peekable();
+
+        while let Some(token) = tokens.next() {
+            let token = token.to_lowercase();
+
+            if token == "ago" {
+                is_ago = true;
+            } else if token.contains(':') {
+                // time, like `-04:05:06.789`
+                let (sign, time_str) = match token.chars().next() {
+                    Some('-') => (-1.0, &token[1..]),
+                    Some('+') => (1.0, &token[1..]),
+                    _ => (1.0, token.as_str()),
+                };
+
+                for (time_part, unit) in time_str.split(':').zip(&["hours", "minutes", "seconds"]) {
+                    let value = f64::from_str(time_part).map_err(|_| invalid_err())?;
+                    interval.add(unit, sign * value)?;
+                }
+            } else {
+                let value = f64::from_str(&token).map_err(|_| invalid_err())?;
+
+                // a number without a unit is a number of seconds
+                match tokens.peek().map(|unit| unit.to_lowercase()) {
+                    Some(ref unit) if f64::from_str(unit).is_err() && !unit.contains(':') => {
+                        tokens.next();
+                        interval.add(unit, value)?;
+                    }
+                    _ => interval.add("seconds", value)?,
+                }
+            }
+        }
+
+        if is_ago {
+            let out_of_range = || "Interval out of range".to_string();
+            interval.months = interval.months.checked_neg().ok_or_else(out_of_range)?;
+            interval.days = interval.days.checked_neg().ok_or_else(out_of_range)?;
+            interval.microseconds = interval
+                .microseconds
+                .checked_neg()
+                .ok_or_else(out_of_range)?;
+        }
+
+        Ok(interval)
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let years = self.months / 12;
+        let months = self.months % 12;
+        let hours = self.microseconds / 3_600_000_000;
+        let minutes = self.microseconds % 3_600_000_000 / 60_000_000;
+        let microseconds = self.microseconds % 60_000_000;
+
+        write!(f, "P")?;
+        if years != 0 {
+            write!(f, "{}Y", years)?;
+        }
+        if months != 0 {
+            write!(f, "{}M", months)?;
+        }
+        if self.days != 0 {
+            write!(f, "{}D", self.days)?;
+        }
+
+        if hours != 0 || minutes != 0 || microseconds != 0 {
+            write!(f, "T")?;
+            if hours != 0 {
+                write!(f, "{}H", hours)?;
+            }
+            if minutes != 0 {
+                write!(f, "{}M", minutes)?;
+            }
+            if microseconds != 0 {
+                let sign = if microseconds < 0 { "-" } else { "" };
+                let seconds = (microseconds / 1_000_000).abs();
+                let fraction = (microseconds % 1_000_000).abs();
+
+                if fraction == 0 {
+                    write!(f, "{}{}S", sign, seconds)?;
+                } else {
+                    let fraction_str = format!("{:06}", fraction);
+                    write!(
+                        f,
+                        "{}{}.{}S",
+                        sign,
+                        seconds,
+                        fraction_str.trim_end_matches('0')
+                    )?;
+                }
+            }
+        } else if years == 0 && months == 0 && self.days == 0 {
+            write!(f, "T0S")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s.starts_with('P') || s.starts_with('p') {
+            Self::from_iso_8601_str(s)
+        } else {
+            Self::from_postgres_str(s)
+        }
+    }
+}
+
+impl Serialize for Interval {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'a> FromSql<'a> for Interval {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Interval, Box<dyn StdError + Send + Sync>> {
+        if raw.len() != 16 {
+            return Err("invalid message length: interval".into());
+        }
+
+        let mut microseconds = [0; 8];
+        let mut days = [0; 4];
+        let mut months = [0; 4];
+        microseconds.copy_from_slice(&raw[0..8]);
+        days.copy_from_slice(&raw[8..12]);
+        months.copy_from_slice(&raw[12..16]);
+
+        Ok(Interval {
+            months: i32::from_be_bytes(months),
+            days: i32::from_be_bytes(days),
+            microseconds: i64::from_be_bytes(microseconds),
+        })
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        ty.name() == "interval"
+    }
+}
+
+impl ToSql for Interval {
+    fn to_sql(&self, _: &Type, w: &mut Vec<u8>) -> Result<IsNull, Box<dyn StdError + Send + Sync>> {
+        w.extend_from_slice(&self.microseconds.to_be_bytes());
+        w.extend_from_slice(&self.days.to_be_bytes());
+        w.extend_from_slice(&self.months.to_be_bytes());
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        ty.name() == "interval"
+    }
+
+    to_sql_checked!();
+}
+
+/// A `money` value, stored as a number of cents. Serialized as a decimal string, like
+/// `"-1234.56"`. Assumes that `lc_monetary` uses 2 fractional digits.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Money(i64);
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let cents = i128::from(self.0).abs();
+        write!(f, "{}{}.{:02}", sign, cents / 100, cents % 100)
+    }
+}
+
+impl FromStr for Money {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid_err = || format!("Invalid money amount: `{}`.", s);
+
+        let (is_negative, unsigned_str) = match s.chars().next() {
+            Some('-') => (true, &s[1..]),
+            Some('+') => (false, &s[1..]),
+            _ => (false, s),
+        };
+        let (whole_str, fraction_str) = match unsigned_str.find('.') {
+            Some(dot_pos) => (&unsigned_str[..dot_pos], &unsigned_str[dot_pos + 1..]),
+            None => (unsigned_str, ""),
+        };
+
+        if whole_str.is_empty()
+            || fraction_str.len() > 2
+            || !whole_str
+                .chars()
+                .chain(fraction_str.chars())
+                .all(|c| c.is_ascii_digit())
+        {
+            return Err(invalid_err());
+        }
+
+        let whole = i64::from_str(whole_str).map_err(|_| invalid_err())?;
+        let fraction = if fraction_str.is_empty() {
+            0
+        } else {
+            i64::from_str(&format!("{:0<2}", fraction_str)).map_err(|_| invalid_err())?
+        };
+        let cents = whole
+            .checked_mul(100)
+            .and_then(|cents| cents.checked_add(fraction))
+            .ok_or_else(invalid_err)?;
+
+        Ok(Money(if is_negative { -cents } else { cents }))
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'a> FromSql<'a> for Money {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Money, Box<dyn StdError + Send + Sync>> {
+        if raw.len() != 8 {
+            return Err("invalid message length: money".into());
+        }
+
+        let mut cents = [0; 8];
+        cents.copy_from_slice(raw);
+        Ok(Money(i64::from_be_bytes(cents)))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        ty.name() == "money"
+    }
+}
+
+impl ToSql for Money {
+    fn to_sql(&self, _: &Type, w: &mut Vec<u8>) -> Result<IsNull, Box<dyn StdError + Send + Sync>> {
+        w.extend_from_slice(&self.0.to_be_bytes());
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        ty.name() == "money"
+    }
+
+    to_sql_checked!();
+}
+
+/// A `timetz` value. Serialized as a string, like `"04:05:06.789-08:00"`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimeTz {
+    time: NaiveTime,
+    offset: FixedOffset,
+}
+
+impl fmt::Display for TimeTz {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.time, self.offset)
+    }
+}
+
+impl FromStr for TimeTz {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid_err = || {
+            format!(
+                "Invalid time with time zone (expected a format like `04:05:06-08:00`): `{}`.",
+                s
+            )
+        };
+
+        let offset_pos = s
+            .rfind(|c| c == '+' || c == '-' || c == 'Z' || c == 'z')
+            .ok_or_else(invalid_err)?;
+        let time = NaiveTime::from_str(&s[..offset_pos]).map_err(|e| e.to_string())?;
+
+        let offset_str = &s[offset_pos..];
+        let offset_seconds = if offset_str.eq_ignore_ascii_case("z") {
+            0
+        } else {
+            let sign = if offset_str.starts_with('-') { -1 } else { 1 };
+            let offset_parts: Vec<&str> = offset_str[1..].split(':').collect();
+            let offset_parts = if offset_parts.len() == 1 && offset_parts[0].len() == 4 {
+                // `+HHMM`
+                vec![&offset_parts[0][..2], &offset_parts[0][2..]]
+            } else {
+                offset_parts
+            };
+
+            if offset_parts.len() > 3 {
+                return Err(invalid_err());
+            }
+
+            let mut offset_seconds = 0;
+            for (offset_part, seconds_per_unit) in offset_parts.iter().zip(&[3600, 60, 1]) {
+                offset_seconds +=
+                    i32::from_str(offset_part).map_err(|_| invalid_err())? * seconds_per_unit;
+            }
+            sign * offset_seconds
+        };
+
+        Ok(TimeTz {
+            time,
+            offset: FixedOffset::east_opt(offset_seconds).ok_or_else(invalid_err)?,
+        })
+    }
+}
+
+impl Serialize for TimeTz {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'a> FromSql<'a> for TimeTz {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<TimeTz, Box<dyn StdError + Send + Sync>> {
+        if raw.len() != 12 {
+            return Err("invalid message length: timetz".into());
+        }
+
+        let mut microseconds = [0; 8];
+        let mut zone_seconds = [0; 4];
+        microseconds.copy_from_slice(&raw[0..8]);
+        zone_seconds.copy_from_slice(&raw[8..12]);
+        let microseconds = i64::from_be_bytes(microseconds);
+
+        let time = NaiveTime::from_num_seconds_from_midnight_opt(
+            (microseconds / 1_000_000) as u32,
+            (microseconds % 1_000_000 * 1000) as u32,
+        )
+        .ok_or("invalid timetz time")?;
+        // postgres stores the zone as seconds *west* of UTC
+        let offset = FixedOffset::east_opt(-i32::from_be_bytes(zone_seconds))
+            .ok_or("invalid timetz zone")?;
+
+        Ok(TimeTz { time, offset })
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        ty.name() == "timetz"
+    }
+}
+
+impl ToSql for TimeTz {
+    fn to_sql(&self, _: &Type, w: &mut Vec<u8>) -> Result<IsNull, Box<dyn StdError + Send + Sync>> {
+        let microseconds = i64::from(self.time.num_seconds_from_midnight()) * 1_000_000
+            + i64::from(self.time.nanosecond()) / 1000;
+
+        w.extend_from_slice(&microseconds.to_be_bytes());
+        w.extend_from_slice(&(-self.offset.local_minus_utc()).to_be_bytes());
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        ty.name() == "timetz"
+    }
+
+    to_sql_checked!();
+}
+
+/// An `xml` value. Serialized as a string.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Xml(String);
+
+impl<'a> FromSql<'a> for Xml {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Xml, Box<dyn StdError + Send + Sync>> {
+        Ok(Xml(text_from_sql(raw)?.to_string()))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        ty.name() == "xml"
+    }
+}
+
+impl ToSql for Xml {
+    fn to_sql(&self, _: &Type, w: &mut Vec<u8>) -> Result<IsNull, Box<dyn StdError + Send + Sync>> {
+        text_to_sql(&self.0, w);
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        ty.name() == "xml"
+    }
+
+    to_sql_checked!();
+}
+
+/// A `bit` or `varbit` value. Serialized as a string of 0s and 1s, like `"0101"`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BitString {
+    /// The number of bits.
+    len: usize,
+    /// The bits, packed into bytes (most significant bit first).
+    bytes: Vec<u8>,
+}
+
+impl fmt::Display for BitString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for i in 0..self.len {
+            let bit = (self.bytes[i / 8] >> (7 - i % 8)) & 1;
+            write!(f, "{}", bit)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for BitString {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes = vec![0; (s.len() + 7) / 8];
+        for (i, c) in s.chars().enumerate() {
+            match c {
+                '0' => (),
+                '1' => bytes[i / 8] |= 1 << (7 - i % 8),
+                _ => return Err(format!("Invalid bit string: `{}`.", s)),
+            }
+        }
+
+        Ok(BitString {
+            len: s.len(),
+            bytes,
+        })
+    }
+}
+
+impl<'a> FromSql<'a> for BitString {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<BitString, Box<dyn StdError + Send + Sync>> {
+        let varbit = varbit_from_sql(raw)?;
+
+        Ok(BitString {
+            len: varbit.len(),
+            bytes: varbit.bytes().to_vec(),
+        })
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        match ty.name() {
+            "bit" | "varbit" => true,
+            _ => false,
+        }
+    }
+}
+
+impl ToSql for BitString {
+    fn to_sql(&self, _: &Type, w: &mut Vec<u8>) -> Result<IsNull, Box<dyn StdError + Send + Sync>> {
+        varbit_to_sql(self.len, self.bytes.iter().cloned(), w)?;
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        match ty.name() {
+            "bit" | "varbit" => true,
+            _ => false,
+        }
+    }
+
+    to_sql_checked!();
+}
+
+impl Serialize for BitString {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// An `hstore` value. Serialized as an object of strings (values can be `null`).
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Hstore(HashMap<String, Option<String>>);
+
+impl Hstore {
+    fn skip_whitespace(chars: &mut Peekable<Chars>) {
+        while chars.peek().map_or(false, |c| c.is_whitespace()) {
+            chars.next();
+        }
+    }
+
+    /// Returns the next (optionally double-quoted) key or value, and whether it was quoted.
+    fn next_token(chars: &mut Peekable<Chars>) -> Option<(String, bool)> {
+        Self::skip_whitespace(chars);
+
+        let mut token = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            loop {
+                match chars.next()? {
+                    '\\' => token.push(chars.next()?),
+                    '"' => return Some((token, true)),
+                    c => token.push(c),
+                }
+            }
+        }
+
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || c == ',' || c == '=' {
+                break;
+            }
+            token.push(c);
+            chars.next();
+        }
+
+        if token.is_empty() {
+            None
+        } else {
+            Some((token, false))
+        }
+    }
+}
+
+impl FromStr for Hstore {
+    type Err = String;
+
+    /// Parses hstore’s text format, like `a=>1, "b c"=>"d", e=>NULL`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid_err = || format!("Invalid hstore: `{}`.", s);
+        let mut hstore = HashMap::new();
+        let mut chars = s.chars().peekable();
+
+        loop {
+            Self::skip_whitespace(&mut chars);
+            if chars.peek().is_none() {
+                break;
+            }
+
+            let (key, _) = Self::next_token(&mut chars).ok_or_else(invalid_err)?;
+
+            Self::skip_whitespace(&mut chars);
+            if chars.next() != Some('=') || chars.next() != Some('>') {
+                return Err(invalid_err());
+            }
+
+            let value = match Self::next_token(&mut chars).ok_or_else(invalid_err)? {
+                (value, false) if value.eq_ignore_ascii_case("null") => None,
+                (value, _) => Some(value),
+            };
+            hstore.insert(key, value);
+
+            Self::skip_whitespace(&mut chars);
+            match chars.next() {
+                Some(',') | None => (),
+                Some(_) => return Err(invalid_err()),
+            }
+        }
+
+        Ok(Hstore(hstore))
+    }
+}
+
+impl<'a> FromSql<'a> for Hstore {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<Hstore, Box<dyn StdError + Send + Sync>> {
+        let hstore = hstore_from_sql(raw)?
+            .map(|(key, value)| Ok((key.to_string(), value.map(str::to_string))))
+            .collect()?;
+
+        Ok(Hstore(hstore))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        ty.name() == "hstore"
+    }
+}
+
+impl ToSql for Hstore {
+    fn to_sql(&self, _: &Type, w: &mut Vec<u8>) -> Result<IsNull, Box<dyn StdError + Send + Sync>> {
+        hstore_to_sql(
+            self.0
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_ref().map(String::as_str))),
+            w,
+        )?;
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        ty.name() == "hstore"
+    }
+
+    to_sql_checked!();
+}
+
+/// A user-defined enum value. Serialized as its label.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct EnumLabel(String);
+
+impl<'a> FromSql<'a> for EnumLabel {
+    fn from_sql(_: &Type, raw: &'a [u8]) -> Result<EnumLabel, Box<dyn StdError + Send + Sync>> {
+        Ok(EnumLabel(text_from_sql(raw)?.to_string()))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        match ty.kind() {
+            Kind::Enum(_) => true,
+            _ => false,
+        }
+    }
+}
 
-// mostly copied from the postgres-protocol and postgres-shared libraries
-impl<'a> FromSql<'a> for MacAddress {
+impl ToSql for EnumLabel {
+    fn to_sql(
+        &self,
+        ty: &Type,
+        w: &mut Vec<u8>,
+    ) -> Result<IsNull, Box<dyn StdError + Send + Sync>> {
+        if let Kind::Enum(labels) = ty.kind() {
+            if !labels.contains(&self.0) {
+                return Err(Box::new(
+                    Error::generate_error(
+                        "INVALID_ENUM_LABEL",
+                        format!("{} (allowed labels: {})", self.0, labels.join(", ")),
+                    )
+                    .compat(),
+                ));
+            }
+        }
+
+        text_to_sql(&self.0, w);
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        match ty.kind() {
+            Kind::Enum(_) => true,
+            _ => false,
+        }
+    }
+
+    to_sql_checked!();
+}
+
+/// Returns the base type of a domain (following nested domains), or the type itself if it isn’t a
+/// domain.
+pub(crate) fn resolve_domain(ty: &Type) -> &Type {
+    match ty.kind() {
+        Kind::Domain(base_type) => resolve_domain(base_type),
+        _ => ty,
+    }
+}
+
+/// A (possibly multi-dimensional) postgres array. Elements are stored flattened, in row-major
+/// order, and are serialized as nested JSON arrays.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgArray {
+    /// The length of each dimension.
+    dimensions: Vec<i32>,
+    elements: Vec<TypedColumnValue>,
+}
+
+impl PgArray {
+    /// Returns the array type’s element type, or an error if the type is not an array.
+    fn member_type(ty: &Type) -> Result<&Type, Box<dyn StdError + Send + Sync>> {
+        match ty.kind() {
+            Kind::Array(member_type) => Ok(member_type),
+            _ => Err(Box::new(
+                Error::generate_error("UNSUPPORTED_DATA_TYPE", ty.name().to_string()).compat(),
+            )),
+        }
+    }
+}
+
+impl<'a> FromSql<'a> for PgArray {
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<PgArray, Box<dyn StdError + Send + Sync>> {
+        let member_type = Self::member_type(ty)?;
+        let array = array_from_sql(raw)?;
+
+        let dimensions = array
+            .dimensions()
+            .map(|dimension| Ok(dimension.len))
+            .collect()?;
+        let elements = array
+            .values()
+            .map(|raw_element| TypedColumnValue::from_sql_nullable(member_type, raw_element))
+            .collect()?;
+
+        Ok(PgArray {
+            dimensions,
+            elements,
+        })
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        match ty.kind() {
+            Kind::Array(member_type) => <TypedColumnValue as FromSql>::accepts(member_type),
+            _ => false,
+        }
+    }
+}
+
+impl ToSql for PgArray {
+    fn to_sql(
+        &self,
+        ty: &Type,
+        w: &mut Vec<u8>,
+    ) -> Result<IsNull, Box<dyn StdError + Send + Sync>> {
+        let member_type = Self::member_type(ty)?;
+        let dimensions = self.dimensions.iter().map(|len| ArrayDimension {
+            len: *len,
+            lower_bound: 1,
+        });
+
+        array_to_sql(
+            dimensions,
+            member_type.oid(),
+            self.elements.iter(),
+            |element, w| match element.to_sql(member_type, w)? {
+                IsNull::No => Ok(ProtocolIsNull::No),
+                IsNull::Yes => Ok(ProtocolIsNull::Yes),
+            },
+            w,
+        )?;
+
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        match ty.kind() {
+            Kind::Array(member_type) => <TypedColumnValue as ToSql>::accepts(member_type),
+            _ => false,
+        }
+    }
+
+    to_sql_checked!();
+}
+
+impl Serialize for PgArray {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        PgArraySlice {
+            dimensions: &self.dimensions,
+            elements: &self.elements,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// A sub-array of a `PgArray`, used to serialize each dimension as a nested JSON array.
+struct PgArraySlice<'a> {
+    dimensions: &'a [i32],
+    elements: &'a [TypedColumnValue],
+}
+
+impl<'a> Serialize for PgArraySlice<'a> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.dimensions.split_first() {
+            // empty arrays have no dimensions
+            None => serializer.collect_seq(self.elements),
+            Some((_, inner_dimensions)) if inner_dimensions.is_empty() => {
+                serializer.collect_seq(self.elements)
+            }
+            Some((len, inner_dimensions)) => {
+                let chunk_size = self.elements.len() / (*len).max(1) as usize;
+
+                serializer.collect_seq(self.elements.chunks(chunk_size.max(1)).map(|elements| {
+                    PgArraySlice {
+                        dimensions: inner_dimensions,
+                        elements,
+                    }
+                }))
+            }
+        }
+    }
+}
+
+/// Returns the element type of a built-in range or multirange type (for example, `int4` for
+/// `int4range` and `int4multirange`).
+pub fn range_subtype_name(column_type: &str) -> Option<&'static str> {
+    match column_type {
+        "daterange" | "datemultirange" => Some("date"),
+        "int4range" | "int4multirange" => Some("int4"),
+        "int8range" | "int8multirange" => Some("int8"),
+        "numrange" | "nummultirange" => Some("numeric"),
+        "tsrange" | "tsmultirange" => Some("timestamp"),
+        "tstzrange" | "tstzmultirange" => Some("timestamptz"),
+        _ => None,
+    }
+}
+
+/// Returns the built-in (range, multirange) types whose elements are of the given type.
+fn range_type_names(subtype: &str) -> Option<(&'static str, &'static str)> {
+    match subtype {
+        "date" => Some(("daterange", "datemultirange")),
+        "int4" => Some(("int4range", "int4multirange")),
+        "int8" => Some(("int8range", "int8multirange")),
+        "numeric" => Some(("numrange", "nummultirange")),
+        "timestamp" => Some(("tsrange", "tsmultirange")),
+        "timestamptz" => Some(("tstzrange", "tstzmultirange")),
+        _ => None,
+    }
+}
+
+/// Returns the element type of a range or multirange type.
+fn range_subtype(ty: &Type) -> Option<Type> {
+    match ty.kind() {
+        Kind::Range(subtype) => Some(subtype.clone()),
+        // multiranges are newer than our postgres driver, so they can only be identified by name
+        _ if ty.name().ends_with("multirange") => match range_subtype_name(ty.name()) {
+            Some("date") => Some(Type::DATE),
+            Some("int4") => Some(Type::INT4),
+            Some("int8") => Some(Type::INT8),
+            Some("numeric") => Some(Type::NUMERIC),
+            Some("timestamp") => Some(Type::TIMESTAMP),
+            Some("timestamptz") => Some(Type::TIMESTAMPTZ),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Same as `range_subtype()`, but returns an error if the type is not a range or multirange.
+fn range_subtype_or_err(ty: &Type) -> Result<Type, Box<dyn StdError + Send + Sync>> {
+    range_subtype(ty).ok_or_else(|| -> Box<dyn StdError + Send + Sync> {
+        Box::new(Error::generate_error("UNSUPPORTED_DATA_TYPE", ty.name().to_string()).compat())
+    })
+}
+
+/// One of the bounds of a `PgRange`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PgRangeBound {
+    Inclusive(Box<TypedColumnValue>),
+    Exclusive(Box<TypedColumnValue>),
+    Unbounded,
+}
+
+impl PgRangeBound {
     fn from_sql(
-        typ: &Type,
+        subtype: &Type,
+        bound: RangeBound<Option<&[u8]>>,
+    ) -> Result<Self, Box<dyn StdError + Send + Sync>> {
+        Ok(match bound {
+            RangeBound::Inclusive(raw) => PgRangeBound::Inclusive(Box::new(
+                TypedColumnValue::from_sql_nullable(subtype, raw)?,
+            )),
+            RangeBound::Exclusive(raw) => PgRangeBound::Exclusive(Box::new(
+                TypedColumnValue::from_sql_nullable(subtype, raw)?,
+            )),
+            RangeBound::Unbounded => PgRangeBound::Unbounded,
+        })
+    }
+
+    fn to_sql(
+        &self,
+        subtype: &Type,
+        w: &mut Vec<u8>,
+    ) -> Result<RangeBound<ProtocolIsNull>, Box<dyn StdError + Send + Sync>> {
+        let to_protocol_is_null = |is_null| match is_null {
+            IsNull::No => ProtocolIsNull::No,
+            IsNull::Yes => ProtocolIsNull::Yes,
+        };
+
+        Ok(match self {
+            PgRangeBound::Inclusive(value) => {
+                RangeBound::Inclusive(to_protocol_is_null(value.to_sql(subtype, w)?))
+            }
+            PgRangeBound::Exclusive(value) => {
+                RangeBound::Exclusive(to_protocol_is_null(value.to_sql(subtype, w)?))
+            }
+            PgRangeBound::Unbounded => RangeBound::Unbounded,
+        })
+    }
+
+    /// Returns the bound’s value, if bounded.
+    fn value(&self) -> Option<&TypedColumnValue> {
+        match self {
+            PgRangeBound::Inclusive(value) | PgRangeBound::Exclusive(value) => Some(value),
+            PgRangeBound::Unbounded => None,
+        }
+    }
+
+    fn is_inclusive(&self) -> bool {
+        match self {
+            PgRangeBound::Inclusive(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A postgres range (`int4range`, `tstzrange`, etc.). Serialized as an object with `lower`,
+/// `upper`, `lower_inclusive`, and `upper_inclusive` keys (unbounded bounds are `null`), or as
+/// `{"empty": true}` if the range is empty.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PgRange {
+    Empty,
+    Nonempty(PgRangeBound, PgRangeBound),
+}
+
+impl PgRange {
+    fn from_sql_with_subtype(
+        subtype: &Type,
         raw: &[u8],
-    ) -> Result<MacAddress, Box<dyn StdError + Send + Sync + Send>> {
-        let mac = <Eui48MacAddress as FromSql>::from_sql(typ, raw)?;
-        Ok(MacAddress(mac))
+    ) -> Result<Self, Box<dyn StdError + Send + Sync>> {
+        match range_from_sql(raw)? {
+            Range::Empty => Ok(PgRange::Empty),
+            Range::Nonempty(lower, upper) => Ok(PgRange::Nonempty(
+                PgRangeBound::from_sql(subtype, lower)?,
+                PgRangeBound::from_sql(subtype, upper)?,
+            )),
+        }
     }
 
-    accepts!(MACADDR);
+    fn to_sql_with_subtype(
+        &self,
+        subtype: &Type,
+        w: &mut Vec<u8>,
+    ) -> Result<(), Box<dyn StdError + Send + Sync>> {
+        match self {
+            PgRange::Empty => {
+                empty_range_to_sql(w);
+                Ok(())
+            }
+            PgRange::Nonempty(lower, upper) => range_to_sql(
+                |w| lower.to_sql(subtype, w),
+                |w| upper.to_sql(subtype, w),
+                w,
+            ),
+        }
+    }
 }
 
-impl ToSql for MacAddress {
+impl<'a> FromSql<'a> for PgRange {
+    fn from_sql(ty: &Type, raw: &'a [u8]) -> Result<PgRange, Box<dyn StdError + Send + Sync>> {
+        Self::from_sql_with_subtype(&range_subtype_or_err(ty)?, raw)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        match ty.kind() {
+            Kind::Range(subtype) => <TypedColumnValue as FromSql>::accepts(subtype),
+            _ => false,
+        }
+    }
+}
+
+impl ToSql for PgRange {
     fn to_sql(
         &self,
-        _: &Type,
+        ty: &Type,
         w: &mut Vec<u8>,
-    ) -> Result<IsNull, Box<dyn StdError + Send + Sync + Send>> {
-        let mut bytes = [0; 6];
-        bytes.copy_from_slice(self.0.as_bytes());
-        macaddr_to_sql(bytes, w);
+    ) -> Result<IsNull, Box<dyn StdError + Send + Sync>> {
+        self.to_sql_with_subtype(&range_subtype_or_err(ty)?, w)?;
         Ok(IsNull::No)
     }
 
-    accepts!(MACADDR);
+    fn accepts(ty: &Type) -> bool {
+        match ty.kind() {
+            Kind::Range(subtype) => <TypedColumnValue as ToSql>::accepts(subtype),
+            _ => false,
+        }
+    }
+
     to_sql_checked!();
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+impl Serialize for PgRange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(None)?;
+        match self {
+            PgRange::Empty => map.serialize_entry("empty", &true)?,
+            PgRange::Nonempty(lower, upper) => {
+                map.serialize_entry("lower", &lower.value())?;
+                map.serialize_entry("upper", &upper.value())?;
+                map.serialize_entry("lower_inclusive", &lower.is_inclusive())?;
+                map.serialize_entry("upper_inclusive", &upper.is_inclusive())?;
+            }
+        }
+        map.end()
+    }
+}
+
+impl PgRange {
+    /// Parses a range literal, like `[1,5)`, `(,"2019-01-01")`, or `empty`.
+    fn from_literal(subtype: &str, literal: &str) -> Result<Self, Error> {
+        let invalid_literal_err = || {
+            Error::generate_error(
+                "INVALID_SQL_VALUE",
+                format!("`{}` is not a valid range.", literal),
+            )
+        };
+
+        let literal = literal.trim();
+        if literal.eq_ignore_ascii_case("empty") {
+            return Ok(PgRange::Empty);
+        }
+
+        let lower_inclusive = match literal.chars().next() {
+            Some('[') => true,
+            Some('(') => false,
+            _ => return Err(invalid_literal_err()),
+        };
+        let upper_inclusive = match literal.chars().last() {
+            Some(']') if literal.len() > 1 => true,
+            Some(')') if literal.len() > 1 => false,
+            _ => return Err(invalid_literal_err()),
+        };
+
+        let bounds = split_outside_quotes(&literal[1..literal.len() - 1], ',');
+        if bounds.len() != 2 {
+            return Err(invalid_literal_err());
+        }
+
+        let parse_bound = |bound_str: &str, is_inclusive: bool| -> Result<PgRangeBound, Error> {
+            let bound_str = bound_str.trim();
+            if bound_str.is_empty() {
+                return Ok(PgRangeBound::Unbounded);
+            }
+
+            let bound_str =
+                if bound_str.len() > 1 && bound_str.starts_with('"') && bound_str.ends_with('"') {
+                    bound_str[1..bound_str.len() - 1].replace("\\\"", "\"")
+                } else {
+                    bound_str.to_string()
+                };
+
+            let value = match subtype {
+                "int4" | "int8" => match i64::from_str(&bound_str) {
+                    Ok(int_val) => TypedColumnValue::from_parsed_sql_value(
+                        subtype,
+                        ParsedSQLValue::Int8(int_val),
+                    )?,
+                    Err(_) => return Err(invalid_literal_err()),
+                },
+                _ => TypedColumnValue::from_parsed_sql_value(
+                    subtype,
+                    ParsedSQLValue::String(bound_str),
+                )?,
+            };
+
+            Ok(if is_inclusive {
+                PgRangeBound::Inclusive(Box::new(value))
+            } else {
+                PgRangeBound::Exclusive(Box::new(value))
+            })
+        };
+
+        Ok(PgRange::Nonempty(
+            parse_bound(&bounds[0], lower_inclusive)?,
+            parse_bound(&bounds[1], upper_inclusive)?,
+        ))
+    }
+}
+
+/// A postgres multirange (`int4multirange`, `tstzmultirange`, etc.). Serialized as an array of
+/// ranges.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PgMultirange(Vec<PgRange>);
+
+impl PgMultirange {
+    /// Parses a multirange literal, like `{[1,5), [7,9)}`.
+    fn from_literal(subtype: &str, literal: &str) -> Result<Self, Error> {
+        let literal = literal.trim();
+        if literal.len() < 2 || !literal.starts_with('{') || !literal.ends_with('}') {
+            return Err(Error::generate_error(
+                "INVALID_SQL_VALUE",
+                format!("`{}` is not a valid multirange.", literal),
+            ));
+        }
+
+        let inner = literal[1..literal.len() - 1].trim();
+        if inner.is_empty() {
+            return Ok(PgMultirange(vec![]));
+        }
+
+        let ranges = split_outside_quotes(inner, ',')
+            .into_iter()
+            .map(|range_str| PgRange::from_literal(subtype, &range_str))
+            .collect::<Result<Vec<PgRange>, Error>>()?;
+
+        Ok(PgMultirange(ranges))
+    }
+}
+
+impl<'a> FromSql<'a> for PgMultirange {
+    fn from_sql(
+        ty: &Type,
+        mut raw: &'a [u8],
+    ) -> Result<PgMultirange, Box<dyn StdError + Send + Sync>> {
+        let subtype = range_subtype_or_err(ty)?;
+
+        // a multirange is sent as the number of ranges, followed by each range prefixed by its
+        // length
+        let read_i32 = |raw: &mut &'a [u8]| -> Result<usize, Box<dyn StdError + Send + Sync>> {
+            if raw.len() < 4 {
+                return Err("invalid multirange message size".into());
+            }
+            let mut int_bytes = [0; 4];
+            int_bytes.copy_from_slice(&raw[..4]);
+            *raw = &raw[4..];
+            Ok(i32::from_be_bytes(int_bytes) as usize)
+        };
+
+        let num_ranges = read_i32(&mut raw)?;
+        let mut ranges = Vec::with_capacity(num_ranges);
+        for _ in 0..num_ranges {
+            let range_len = read_i32(&mut raw)?;
+            if raw.len() < range_len {
+                return Err("invalid multirange message size".into());
+            }
+            ranges.push(PgRange::from_sql_with_subtype(&subtype, &raw[..range_len])?);
+            raw = &raw[range_len..];
+        }
+
+        Ok(PgMultirange(ranges))
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        match ty.kind() {
+            Kind::Range(_) => false,
+            _ => match range_subtype(ty) {
+                Some(subtype) => <TypedColumnValue as FromSql>::accepts(&subtype),
+                None => false,
+            },
+        }
+    }
+}
+
+impl ToSql for PgMultirange {
+    fn to_sql(
+        &self,
+        ty: &Type,
+        w: &mut Vec<u8>,
+    ) -> Result<IsNull, Box<dyn StdError + Send + Sync>> {
+        let subtype = range_subtype_or_err(ty)?;
+
+        w.extend_from_slice(&(self.0.len() as i32).to_be_bytes());
+        for range in &self.0 {
+            let len_idx = w.len();
+            w.extend_from_slice(&[0; 4]);
+            range.to_sql_with_subtype(&subtype, w)?;
+
+            let range_len = (w.len() - len_idx - 4) as i32;
+            w[len_idx..len_idx + 4].copy_from_slice(&range_len.to_be_bytes());
+        }
+
+        Ok(IsNull::No)
+    }
+
+    fn accepts(ty: &Type) -> bool {
+        match ty.kind() {
+            Kind::Range(_) => false,
+            _ => match range_subtype(ty) {
+                Some(subtype) => <TypedColumnValue as ToSql>::accepts(&subtype),
+                None => false,
+            },
+        }
+    }
+
+    to_sql_checked!();
+}
+
+/// Splits a string by a delimiter, ignoring delimiters that are within double quotes, brackets, or
+/// parentheses.
+fn split_outside_quotes(s: &str, delimiter: char) -> Vec<String> {
+    let mut parts = vec![];
+    let mut current = String::new();
+    let mut is_in_quotes = false;
+    let mut depth = 0;
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if is_in_quotes => {
+                current.push(c);
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+                continue;
+            }
+            '"' => is_in_quotes = !is_in_quotes,
+            '[' | '(' if !is_in_quotes => depth += 1,
+            ']' | ')' if !is_in_quotes => depth -= 1,
+            _ if c == delimiter && !is_in_quotes && depth == 0 => {
+                parts.push(mem::replace(&mut current, String::new()));
+                continue;
+            }
+            _ => (),
+        }
+        current.push(c);
+    }
+    parts.push(current);
+
+    parts
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
 #[serde(untagged)]
 /// Represents a single column value for a returned row. We have to have an Enum describing column
 /// data that is non-nullable vs nullable
@@ -117,51 +1523,121 @@ where
     to_sql_checked!();
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+impl<T> IsNullColumnValue<T> {
+    /// Returns the value, or `None` if it’s NULL.
+    pub fn as_option(&self) -> Option<&T> {
+        match self {
+            IsNullColumnValue::Nullable(val_opt) => val_opt.as_ref(),
+            IsNullColumnValue::NotNullable(val) => Some(val),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
 #[serde(untagged)]
 /// Represents a postgres column's type
 pub enum TypedColumnValue {
+    Array(IsNullColumnValue<PgArray>),
     BigInt(IsNullColumnValue<i64>),
+    Bit(IsNullColumnValue<BitString>),
     Bool(IsNullColumnValue<bool>),
+    #[serde(serialize_with = "serialize_bytea")]
     ByteA(IsNullColumnValue<Vec<u8>>),
     Char(IsNullColumnValue<String>), // apparently it's a bad practice to use char(n)
+    Cidr(IsNullColumnValue<Inet>),
     Citext(IsNullColumnValue<String>),
     Date(IsNullColumnValue<NaiveDate>),
+    #[serde(serialize_with = "serialize_decimal")]
     Decimal(IsNullColumnValue<Decimal>),
+    Enum(IsNullColumnValue<EnumLabel>),
     Float8(IsNullColumnValue<f64>),
+    #[cfg(feature = "postgis")]
+    Geometry(IsNullColumnValue<Geometry>),
+    Hstore(IsNullColumnValue<Hstore>),
+    Inet(IsNullColumnValue<Inet>),
     Int(IsNullColumnValue<i32>),
+    Interval(IsNullColumnValue<Interval>),
     Json(IsNullColumnValue<JsonValue>),
     JsonB(IsNullColumnValue<JsonValue>),
     MacAddr(IsNullColumnValue<MacAddress>),
+    Money(IsNullColumnValue<Money>),
+    Multirange(IsNullColumnValue<PgMultirange>),
     Name(IsNullColumnValue<String>),
     Oid(IsNullColumnValue<u32>),
+    Range(IsNullColumnValue<PgRange>),
     Real(IsNullColumnValue<f32>),
     SmallInt(IsNullColumnValue<i16>),
     Text(IsNullColumnValue<String>),
     Time(IsNullColumnValue<NaiveTime>),
     Timestamp(IsNullColumnValue<NaiveDateTime>),
+    #[serde(serialize_with = "serialize_timestamptz")]
     TimestampTz(IsNullColumnValue<DateTime<Utc>>),
+    TimeTz(IsNullColumnValue<TimeTz>),
     // Unknown(IsNullColumnValue<String>),
     Uuid(IsNullColumnValue<Uuid>),
+    VarBit(IsNullColumnValue<BitString>),
     VarChar(IsNullColumnValue<String>),
+    Xml(IsNullColumnValue<Xml>),
+}
+
+/// Serializes `bytea` values as byte strings, which MessagePack and CBOR encode natively (JSON
+/// still gets an array of numbers).
+fn serialize_bytea<S: Serializer>(
+    value: &IsNullColumnValue<Vec<u8>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value.as_option() {
+        Some(bytes) => serializer.serialize_bytes(bytes),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn serialize_decimal<S: Serializer>(
+    value: &IsNullColumnValue<Decimal>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value.as_option() {
+        Some(decimal) => encoding::serialize_decimal(decimal, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn serialize_timestamptz<S: Serializer>(
+    value: &IsNullColumnValue<DateTime<Utc>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value.as_option() {
+        Some(timestamp) => encoding::serialize_timestamptz(timestamp, serializer),
+        None => serializer.serialize_none(),
+    }
 }
 
 impl<'a> FromSql<'a> for TypedColumnValue {
     fn accepts(ty: &Type) -> bool {
+        let ty = resolve_domain(ty);
+
         match ty.name() {
             "int8" => <IsNullColumnValue<i64> as FromSql>::accepts(ty),
+            "bit" => <IsNullColumnValue<BitString> as FromSql>::accepts(ty),
             "bool" => <IsNullColumnValue<bool> as FromSql>::accepts(ty),
             "bytea" => <IsNullColumnValue<Vec<u8>> as FromSql>::accepts(ty),
             "bpchar" => <IsNullColumnValue<String> as FromSql>::accepts(ty),
+            "cidr" => <IsNullColumnValue<Inet> as FromSql>::accepts(ty),
             "citext" => <IsNullColumnValue<String> as FromSql>::accepts(ty),
             "date" => <IsNullColumnValue<NaiveDate> as FromSql>::accepts(ty),
             "float4" => <IsNullColumnValue<f32> as FromSql>::accepts(ty),
             "float8" => <IsNullColumnValue<f64> as FromSql>::accepts(ty),
+            #[cfg(feature = "postgis")]
+            "geography" | "geometry" => <IsNullColumnValue<Geometry> as FromSql>::accepts(ty),
+            "hstore" => <IsNullColumnValue<Hstore> as FromSql>::accepts(ty),
+            "inet" => <IsNullColumnValue<Inet> as FromSql>::accepts(ty),
             "int2" => <IsNullColumnValue<i16> as FromSql>::accepts(ty),
             "int4" => <IsNullColumnValue<i32> as FromSql>::accepts(ty),
+            "interval" => <IsNullColumnValue<Interval> as FromSql>::accepts(ty),
             "json" => <IsNullColumnValue<JsonValue> as FromSql>::accepts(ty),
             "jsonb" => <IsNullColumnValue<JsonValue> as FromSql>::accepts(ty),
             "macaddr" => <IsNullColumnValue<MacAddress> as FromSql>::accepts(ty),
+            "money" => <IsNullColumnValue<Money> as FromSql>::accepts(ty),
             "name" => <IsNullColumnValue<String> as FromSql>::accepts(ty),
             "numeric" => <IsNullColumnValue<Decimal> as FromSql>::accepts(ty),
             "oid" => <IsNullColumnValue<u32> as FromSql>::accepts(ty),
@@ -169,8 +1645,16 @@ impl<'a> FromSql<'a> for TypedColumnValue {
             "time" => <IsNullColumnValue<NaiveTime> as FromSql>::accepts(ty),
             "timestamp" => <IsNullColumnValue<NaiveDateTime> as FromSql>::accepts(ty),
             "timestamptz" => <IsNullColumnValue<DateTime<Utc>> as FromSql>::accepts(ty),
+            "timetz" => <IsNullColumnValue<TimeTz> as FromSql>::accepts(ty),
             "uuid" => <IsNullColumnValue<Uuid> as FromSql>::accepts(ty),
+            "varbit" => <IsNullColumnValue<BitString> as FromSql>::accepts(ty),
             "varchar" => <IsNullColumnValue<String> as FromSql>::accepts(ty),
+            "xml" => <IsNullColumnValue<Xml> as FromSql>::accepts(ty),
+            _ if <IsNullColumnValue<EnumLabel> as FromSql>::accepts(ty) => true,
+            _ if <IsNullColumnValue<PgRange> as FromSql>::accepts(ty) => true,
+            _ if <IsNullColumnValue<PgMultirange> as FromSql>::accepts(ty) => true,
+            // array type names are prefixed with an underscore
+            name if name.starts_with('_') => <IsNullColumnValue<PgArray> as FromSql>::accepts(ty),
             &_ => false,
         }
     }
@@ -179,10 +1663,15 @@ impl<'a> FromSql<'a> for TypedColumnValue {
         ty: &Type,
         raw: &'a [u8],
     ) -> Result<Self, Box<dyn StdError + 'static + Send + Send + Sync>> {
+        let ty = resolve_domain(ty);
+
         match ty.name() {
             "int8" => Ok(Self::BigInt(<IsNullColumnValue<i64> as FromSql>::from_sql(
                 ty, raw,
             )?)),
+            "bit" => Ok(Self::Bit(
+                <IsNullColumnValue<BitString> as FromSql>::from_sql(ty, raw)?,
+            )),
             "bool" => Ok(Self::Bool(<IsNullColumnValue<bool> as FromSql>::from_sql(
                 ty, raw,
             )?)),
@@ -192,6 +1681,9 @@ impl<'a> FromSql<'a> for TypedColumnValue {
             "bpchar" => Ok(Self::Char(
                 <IsNullColumnValue<String> as FromSql>::from_sql(ty, raw)?,
             )),
+            "cidr" => Ok(Self::Cidr(<IsNullColumnValue<Inet> as FromSql>::from_sql(
+                ty, raw,
+            )?)),
             "citext" => Ok(Self::Citext(
                 <IsNullColumnValue<String> as FromSql>::from_sql(ty, raw)?,
             )),
@@ -204,12 +1696,25 @@ impl<'a> FromSql<'a> for TypedColumnValue {
             "float8" => Ok(Self::Float8(<IsNullColumnValue<f64> as FromSql>::from_sql(
                 ty, raw,
             )?)),
+            #[cfg(feature = "postgis")]
+            "geography" | "geometry" => Ok(Self::Geometry(
+                <IsNullColumnValue<Geometry> as FromSql>::from_sql(ty, raw)?,
+            )),
+            "hstore" => Ok(Self::Hstore(
+                <IsNullColumnValue<Hstore> as FromSql>::from_sql(ty, raw)?,
+            )),
+            "inet" => Ok(Self::Inet(<IsNullColumnValue<Inet> as FromSql>::from_sql(
+                ty, raw,
+            )?)),
             "int2" => Ok(Self::SmallInt(
                 <IsNullColumnValue<i16> as FromSql>::from_sql(ty, raw)?,
             )),
             "int4" => Ok(Self::Int(<IsNullColumnValue<i32> as FromSql>::from_sql(
                 ty, raw,
             )?)),
+            "interval" => Ok(Self::Interval(
+                <IsNullColumnValue<Interval> as FromSql>::from_sql(ty, raw)?,
+            )),
             "json" => Ok(Self::Json(
                 <IsNullColumnValue<JsonValue> as FromSql>::from_sql(ty, raw)?,
             )),
@@ -219,6 +1724,9 @@ impl<'a> FromSql<'a> for TypedColumnValue {
             "macaddr" => Ok(Self::MacAddr(
                 <IsNullColumnValue<MacAddress> as FromSql>::from_sql(ty, raw)?,
             )),
+            "money" => Ok(Self::Money(
+                <IsNullColumnValue<Money> as FromSql>::from_sql(ty, raw)?,
+            )),
             "name" => Ok(Self::Name(
                 <IsNullColumnValue<String> as FromSql>::from_sql(ty, raw)?,
             )),
@@ -240,12 +1748,33 @@ impl<'a> FromSql<'a> for TypedColumnValue {
             "timestamptz" => Ok(Self::TimestampTz(
                 <IsNullColumnValue<DateTime<Utc>> as FromSql>::from_sql(ty, raw)?,
             )),
+            "timetz" => Ok(Self::TimeTz(
+                <IsNullColumnValue<TimeTz> as FromSql>::from_sql(ty, raw)?,
+            )),
             "uuid" => Ok(Self::Uuid(<IsNullColumnValue<Uuid> as FromSql>::from_sql(
                 ty, raw,
             )?)),
+            "varbit" => Ok(Self::VarBit(
+                <IsNullColumnValue<BitString> as FromSql>::from_sql(ty, raw)?,
+            )),
             "varchar" => Ok(Self::VarChar(
                 <IsNullColumnValue<String> as FromSql>::from_sql(ty, raw)?,
             )),
+            "xml" => Ok(Self::Xml(<IsNullColumnValue<Xml> as FromSql>::from_sql(
+                ty, raw,
+            )?)),
+            _ if <IsNullColumnValue<EnumLabel> as FromSql>::accepts(ty) => Ok(Self::Enum(
+                <IsNullColumnValue<EnumLabel> as FromSql>::from_sql(ty, raw)?,
+            )),
+            _ if <IsNullColumnValue<PgRange> as FromSql>::accepts(ty) => Ok(Self::Range(
+                <IsNullColumnValue<PgRange> as FromSql>::from_sql(ty, raw)?,
+            )),
+            _ if <IsNullColumnValue<PgMultirange> as FromSql>::accepts(ty) => Ok(Self::Multirange(
+                <IsNullColumnValue<PgMultirange> as FromSql>::from_sql(ty, raw)?,
+            )),
+            name if name.starts_with('_') => Ok(Self::Array(
+                <IsNullColumnValue<PgArray> as FromSql>::from_sql(ty, raw)?,
+            )),
             &_ => Err(Box::new(
                 Error::generate_error("TABLE_COLUMN_TYPE_NOT_FOUND", ty.name().to_string())
                     .compat(),
@@ -274,47 +1803,74 @@ impl ToSql for TypedColumnValue {
         ty: &Type,
         out: &mut Vec<u8>,
     ) -> Result<IsNull, Box<dyn StdError + 'static + Send + Send + Sync>> {
+        let ty = resolve_domain(ty);
+
         match self {
+            Self::Array(col_val) => col_val.to_sql(ty, out),
             Self::BigInt(col_val) => col_val.to_sql(ty, out),
+            Self::Bit(col_val) => col_val.to_sql(ty, out),
             Self::Bool(col_val) => col_val.to_sql(ty, out),
             Self::ByteA(col_val) => col_val.to_sql(ty, out),
             Self::Char(col_val) => col_val.to_sql(ty, out),
+            Self::Cidr(col_val) => col_val.to_sql(ty, out),
             Self::Citext(col_val) => col_val.to_sql(ty, out),
             Self::Date(col_val) => col_val.to_sql(ty, out),
             Self::Decimal(col_val) => col_val.to_sql(ty, out),
+            Self::Enum(col_val) => col_val.to_sql(ty, out),
             Self::Float8(col_val) => col_val.to_sql(ty, out),
+            #[cfg(feature = "postgis")]
+            Self::Geometry(col_val) => col_val.to_sql(ty, out),
+            Self::Hstore(col_val) => col_val.to_sql(ty, out),
+            Self::Inet(col_val) => col_val.to_sql(ty, out),
             Self::Int(col_val) => col_val.to_sql(ty, out),
+            Self::Interval(col_val) => col_val.to_sql(ty, out),
             Self::Json(col_val) => col_val.to_sql(ty, out),
             Self::JsonB(col_val) => col_val.to_sql(ty, out),
             Self::MacAddr(col_val) => col_val.to_sql(ty, out),
+            Self::Money(col_val) => col_val.to_sql(ty, out),
+            Self::Multirange(col_val) => col_val.to_sql(ty, out),
             Self::Name(col_val) => col_val.to_sql(ty, out),
             Self::Oid(col_val) => col_val.to_sql(ty, out),
+            Self::Range(col_val) => col_val.to_sql(ty, out),
             Self::Real(col_val) => col_val.to_sql(ty, out),
             Self::SmallInt(col_val) => col_val.to_sql(ty, out),
             Self::Text(col_val) => col_val.to_sql(ty, out),
             Self::Time(col_val) => col_val.to_sql(ty, out),
             Self::Timestamp(col_val) => col_val.to_sql(ty, out),
             Self::TimestampTz(col_val) => col_val.to_sql(ty, out),
+            Self::TimeTz(col_val) => col_val.to_sql(ty, out),
             Self::Uuid(col_val) => col_val.to_sql(ty, out),
+            Self::VarBit(col_val) => col_val.to_sql(ty, out),
             Self::VarChar(col_val) => col_val.to_sql(ty, out),
+            Self::Xml(col_val) => col_val.to_sql(ty, out),
         }
     }
 
     fn accepts(ty: &Type) -> bool {
+        let ty = resolve_domain(ty);
+
         match ty.name() {
             "int8" => <IsNullColumnValue<i64> as ToSql>::accepts(ty),
+            "bit" => <IsNullColumnValue<BitString> as ToSql>::accepts(ty),
             "bool" => <IsNullColumnValue<bool> as ToSql>::accepts(ty),
             "bytea" => <IsNullColumnValue<Vec<u8>> as ToSql>::accepts(ty),
             "bpchar" => <IsNullColumnValue<String> as ToSql>::accepts(ty),
+            "cidr" => <IsNullColumnValue<Inet> as ToSql>::accepts(ty),
             "citext" => <IsNullColumnValue<String> as ToSql>::accepts(ty),
             "date" => <IsNullColumnValue<NaiveDate> as ToSql>::accepts(ty),
             "float4" => <IsNullColumnValue<f32> as ToSql>::accepts(ty),
             "float8" => <IsNullColumnValue<f64> as ToSql>::accepts(ty),
+            #[cfg(feature = "postgis")]
+            "geography" | "geometry" => <IsNullColumnValue<Geometry> as ToSql>::accepts(ty),
+            "hstore" => <IsNullColumnValue<Hstore> as ToSql>::accepts(ty),
+            "inet" => <IsNullColumnValue<Inet> as ToSql>::accepts(ty),
             "int2" => <IsNullColumnValue<i16> as ToSql>::accepts(ty),
             "int4" => <IsNullColumnValue<i32> as ToSql>::accepts(ty),
+            "interval" => <IsNullColumnValue<Interval> as ToSql>::accepts(ty),
             "json" => <IsNullColumnValue<JsonValue> as ToSql>::accepts(ty),
             "jsonb" => <IsNullColumnValue<JsonValue> as ToSql>::accepts(ty),
             "macaddr" => <IsNullColumnValue<MacAddress> as ToSql>::accepts(ty),
+            "money" => <IsNullColumnValue<Money> as ToSql>::accepts(ty),
             "name" => <IsNullColumnValue<String> as ToSql>::accepts(ty),
             "numeric" => <IsNullColumnValue<Decimal> as ToSql>::accepts(ty),
             "oid" => <IsNullColumnValue<u32> as ToSql>::accepts(ty),
@@ -322,8 +1878,16 @@ impl ToSql for TypedColumnValue {
             "time" => <IsNullColumnValue<NaiveTime> as ToSql>::accepts(ty),
             "timestamp" => <IsNullColumnValue<NaiveDateTime> as ToSql>::accepts(ty),
             "timestamptz" => <IsNullColumnValue<DateTime<Utc>> as ToSql>::accepts(ty),
+            "timetz" => <IsNullColumnValue<TimeTz> as ToSql>::accepts(ty),
             "uuid" => <IsNullColumnValue<Uuid> as ToSql>::accepts(ty),
+            "varbit" => <IsNullColumnValue<BitString> as ToSql>::accepts(ty),
             "varchar" => <IsNullColumnValue<String> as ToSql>::accepts(ty),
+            "xml" => <IsNullColumnValue<Xml> as ToSql>::accepts(ty),
+            _ if <IsNullColumnValue<EnumLabel> as ToSql>::accepts(ty) => true,
+            _ if <IsNullColumnValue<PgRange> as ToSql>::accepts(ty) => true,
+            _ if <IsNullColumnValue<PgMultirange> as ToSql>::accepts(ty) => true,
+            // array type names are prefixed with an underscore
+            name if name.starts_with('_') => <IsNullColumnValue<PgArray> as ToSql>::accepts(ty),
             &_ => false,
         }
     }
@@ -332,22 +1896,61 @@ impl ToSql for TypedColumnValue {
 }
 
 impl TypedColumnValue {
+    /// Formats the value as plain text (as used in CSV responses). Returns `None` for NULL.
+    ///
+    /// Strings, numbers, dates and timestamps are formatted like their JSON counterparts (without
+    /// the quotes, so timestamps are ISO 8601). `bytea` is formatted as hex (`\x0a1b`, like
+    /// postgres’ default output), and `json`/`jsonb` values, arrays, ranges, etc. are formatted as
+    /// JSON text.
+    pub fn to_text(&self) -> Result<Option<String>, Error> {
+        match self {
+            TypedColumnValue::ByteA(col_val) => Ok(col_val.as_option().map(|bytes| {
+                let mut hex = String::with_capacity(2 + bytes.len() * 2);
+                hex.push_str("\\x");
+                for byte in bytes {
+                    hex.push_str(&format!("{:02x}", byte));
+                }
+                hex
+            })),
+            TypedColumnValue::Json(col_val) | TypedColumnValue::JsonB(col_val) => {
+                match col_val.as_option() {
+                    Some(value) => Ok(Some(serde_json::to_string(value)?)),
+                    None => Ok(None),
+                }
+            }
+            _ => match serde_json::to_value(self)? {
+                JsonValue::Null => Ok(None),
+                JsonValue::String(s) => Ok(Some(s)),
+                value => Ok(Some(value.to_string())),
+            },
+        }
+    }
+
     /// Parses a Value and returns the Rust-Typed version.
     pub fn from_json(column_type: &str, value: &JsonValue) -> Result<Self, Error> {
         match column_type {
             "int8" => Self::convert_json_value_to_bigint(value),
+            "bit" | "varbit" => Self::convert_json_value_to_bit_string(column_type, value),
             "bool" => Self::convert_json_value_to_bool(value),
             "bytea" => Self::convert_json_value_to_bytea(value),
             "bpchar" => Self::convert_json_value_to_char(value),
+            "cidr" => Self::convert_json_value_to_cidr(value),
             "citext" => Self::convert_json_value_to_citext(value),
             "date" => Self::convert_json_value_to_date(value),
+            "enum" => Self::convert_json_value_to_enum(value),
             "float4" => Self::convert_json_value_to_real(value),
             "float8" => Self::convert_json_value_to_float8(value),
+            #[cfg(feature = "postgis")]
+            "geography" | "geometry" => Self::convert_json_value_to_geometry(value),
+            "hstore" => Self::convert_json_value_to_hstore(value),
+            "inet" => Self::convert_json_value_to_inet(value),
             "int2" => Self::convert_json_value_to_smallint(value),
             "int4" => Self::convert_json_value_to_int(value),
+            "interval" => Self::convert_json_value_to_interval(value),
             "json" => Self::convert_json_value_to_json(value),
             "jsonb" => Self::convert_json_value_to_jsonb(value),
             "macaddr" => Self::convert_json_value_to_macaddr(value),
+            "money" => Self::convert_json_value_to_money(value),
             "name" => Self::convert_json_value_to_name(value),
             "numeric" => Self::convert_json_value_to_decimal(value),
             "oid" => Self::convert_json_value_to_oid(value),
@@ -355,8 +1958,22 @@ impl TypedColumnValue {
             "time" => Self::convert_json_value_to_time(value),
             "timestamp" => Self::convert_json_value_to_timestamp(value),
             "timestamptz" => Self::convert_json_value_to_timestamptz(value),
+            "timetz" => Self::convert_json_value_to_timetz(value),
             "uuid" => Self::convert_json_value_to_uuid(value),
             "varchar" => Self::convert_json_value_to_varchar(value),
+            "xml" => Self::convert_json_value_to_xml(value),
+            _ if column_type.ends_with("multirange")
+                && range_subtype_name(column_type).is_some() =>
+            {
+                Self::convert_json_value_to_multirange(column_type, value)
+            }
+            _ if range_subtype_name(column_type).is_some() => {
+                Self::convert_json_value_to_range(column_type, value)
+            }
+            // array type names are prefixed with an underscore
+            _ if column_type.starts_with('_') => {
+                Self::convert_json_value_to_array(&column_type[1..], value)
+            }
             _ => Err(Error::generate_error(
                 "UNSUPPORTED_DATA_TYPE",
                 format!("Value {} has unsupported type: {}", value, column_type),
@@ -371,6 +1988,26 @@ impl TypedColumnValue {
                 ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
                 _ => unimplemented!("Cannot convert from ParsedSQLValue: `{}` to int8.", value),
             })),
+            "bit" | "varbit" => {
+                let bit_string_opt = match value {
+                    ParsedSQLValue::Null => None,
+                    ParsedSQLValue::String(val) => Some(
+                        BitString::from_str(&val)
+                            .map_err(|e| Error::generate_error("INVALID_SQL_VALUE", e))?,
+                    ),
+                    _ => return Err(Self::invalid_sql_value_error(&value, column_type)),
+                };
+                let col_val = match bit_string_opt {
+                    Some(bit_string) => IsNullColumnValue::NotNullable(bit_string),
+                    None => IsNullColumnValue::Nullable(None),
+                };
+
+                Ok(if column_type == "bit" {
+                    TypedColumnValue::Bit(col_val)
+                } else {
+                    TypedColumnValue::VarBit(col_val)
+                })
+            }
             "bool" => Ok(TypedColumnValue::Bool(match value {
                 ParsedSQLValue::Boolean(val) => IsNullColumnValue::NotNullable(val),
                 ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
@@ -386,6 +2023,14 @@ impl TypedColumnValue {
                 ParsedSQLValue::String(val) => IsNullColumnValue::NotNullable(val),
                 _ => unimplemented!("Cannot convert from ParsedSQLValue: `{}` to bpchar.", value),
             })),
+            "cidr" => Ok(TypedColumnValue::Cidr(match value {
+                ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
+                ParsedSQLValue::String(val) => IsNullColumnValue::NotNullable(
+                    Inet::from_str(&val)
+                        .map_err(|e| Error::generate_error("INVALID_SQL_VALUE", e))?,
+                ),
+                _ => return Err(Self::invalid_sql_value_error(&value, column_type)),
+            })),
             "citext" => Ok(TypedColumnValue::Citext(match value {
                 ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
                 ParsedSQLValue::String(val) => IsNullColumnValue::NotNullable(val),
@@ -398,6 +2043,11 @@ impl TypedColumnValue {
                 }
                 _ => unimplemented!("Cannot convert from ParsedSQLValue: `{}` to date.", value),
             })),
+            "enum" => Ok(TypedColumnValue::Enum(match value {
+                ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
+                ParsedSQLValue::String(val) => IsNullColumnValue::NotNullable(EnumLabel(val)),
+                _ => return Err(Self::invalid_sql_value_error(&value, column_type)),
+            })),
             "float4" => Ok(TypedColumnValue::Real(match value {
                 ParsedSQLValue::Float(val) => IsNullColumnValue::NotNullable(val as f32),
                 ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
@@ -408,6 +2058,35 @@ impl TypedColumnValue {
                 ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
                 _ => unimplemented!("Cannot convert from ParsedSQLValue: `{}` to float8.", value),
             })),
+            #[cfg(feature = "postgis")]
+            "geography" | "geometry" => Ok(TypedColumnValue::Geometry(match value {
+                ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
+                ParsedSQLValue::String(val) => IsNullColumnValue::NotNullable(
+                    Geometry::from_geojson_str(&val)
+                        .map_err(|e| Error::generate_error("INVALID_SQL_VALUE", e))?,
+                ),
+                _ => unimplemented!(
+                    "Cannot convert from ParsedSQLValue: `{}` to {}.",
+                    value,
+                    column_type
+                ),
+            })),
+            "hstore" => Ok(TypedColumnValue::Hstore(match value {
+                ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
+                ParsedSQLValue::String(val) => IsNullColumnValue::NotNullable(
+                    Hstore::from_str(&val)
+                        .map_err(|e| Error::generate_error("INVALID_SQL_VALUE", e))?,
+                ),
+                _ => return Err(Self::invalid_sql_value_error(&value, column_type)),
+            })),
+            "inet" => Ok(TypedColumnValue::Inet(match value {
+                ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
+                ParsedSQLValue::String(val) => IsNullColumnValue::NotNullable(
+                    Inet::from_str(&val)
+                        .map_err(|e| Error::generate_error("INVALID_SQL_VALUE", e))?,
+                ),
+                _ => return Err(Self::invalid_sql_value_error(&value, column_type)),
+            })),
             "int2" => Ok(TypedColumnValue::SmallInt(match value {
                 ParsedSQLValue::Int8(val) => IsNullColumnValue::NotNullable(val as i16),
                 ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
@@ -418,6 +2097,14 @@ impl TypedColumnValue {
                 ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
                 _ => unimplemented!("Cannot convert from ParsedSQLValue: `{}` to int4.", value),
             })),
+            "interval" => Ok(TypedColumnValue::Interval(match value {
+                ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
+                ParsedSQLValue::String(val) => IsNullColumnValue::NotNullable(
+                    Interval::from_str(&val)
+                        .map_err(|e| Error::generate_error("INVALID_SQL_VALUE", e))?,
+                ),
+                _ => return Err(Self::invalid_sql_value_error(&value, column_type)),
+            })),
             "json" => Ok(TypedColumnValue::Json(match value {
                 ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
                 ParsedSQLValue::String(val) => {
@@ -442,6 +2129,19 @@ impl TypedColumnValue {
                     value
                 ),
             })),
+            "money" => Ok(TypedColumnValue::Money(match value {
+                ParsedSQLValue::Float(val) => IsNullColumnValue::NotNullable(
+                    Money::from_str(&val.to_string())
+                        .map_err(|e| Error::generate_error("INVALID_SQL_VALUE", e))?,
+                ),
+                ParsedSQLValue::Int8(val) => IsNullColumnValue::NotNullable(Money(val * 100)),
+                ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
+                ParsedSQLValue::String(val) => IsNullColumnValue::NotNullable(
+                    Money::from_str(&val)
+                        .map_err(|e| Error::generate_error("INVALID_SQL_VALUE", e))?,
+                ),
+                _ => return Err(Self::invalid_sql_value_error(&value, column_type)),
+            })),
             "name" => Ok(TypedColumnValue::Name(match value {
                 ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
                 ParsedSQLValue::String(val) => IsNullColumnValue::NotNullable(val),
@@ -495,6 +2195,14 @@ impl TypedColumnValue {
                     value
                 ),
             })),
+            "timetz" => Ok(TypedColumnValue::TimeTz(match value {
+                ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
+                ParsedSQLValue::String(val) => IsNullColumnValue::NotNullable(
+                    TimeTz::from_str(&val)
+                        .map_err(|e| Error::generate_error("INVALID_SQL_VALUE", e))?,
+                ),
+                _ => return Err(Self::invalid_sql_value_error(&value, column_type)),
+            })),
             "uuid" => Ok(TypedColumnValue::Uuid(match value {
                 ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
                 ParsedSQLValue::String(val) => {
@@ -510,13 +2218,53 @@ impl TypedColumnValue {
                     value
                 ),
             })),
-            _ => Err(Error::generate_error(
-                "UNSUPPORTED_DATA_TYPE",
-                format!("Value {} has unsupported type: {}", value, column_type),
-            )),
+            "xml" => Ok(TypedColumnValue::Xml(match value {
+                ParsedSQLValue::Null => IsNullColumnValue::Nullable(None),
+                ParsedSQLValue::String(val) => IsNullColumnValue::NotNullable(Xml(val)),
+                _ => return Err(Self::invalid_sql_value_error(&value, column_type)),
+            })),
+            _ => {
+                if let Some(subtype) = range_subtype_name(column_type) {
+                    let literal = match value {
+                        ParsedSQLValue::Null => None,
+                        ParsedSQLValue::String(val) => Some(val),
+                        _ => return Err(Self::invalid_sql_value_error(&value, column_type)),
+                    };
+
+                    return Ok(if column_type.ends_with("multirange") {
+                        TypedColumnValue::Multirange(match literal {
+                            Some(val) => IsNullColumnValue::NotNullable(
+                                PgMultirange::from_literal(subtype, &val)?,
+                            ),
+                            None => IsNullColumnValue::Nullable(None),
+                        })
+                    } else {
+                        TypedColumnValue::Range(match literal {
+                            Some(val) => IsNullColumnValue::NotNullable(PgRange::from_literal(
+                                subtype, &val,
+                            )?),
+                            None => IsNullColumnValue::Nullable(None),
+                        })
+                    });
+                }
+
+                Err(Error::generate_error(
+                    "UNSUPPORTED_DATA_TYPE",
+                    format!("Value {} has unsupported type: {}", value, column_type),
+                ))
+            }
         }
     }
 
+    /// The error returned when an SQL literal is of a kind (like a number or boolean) that can’t be
+    /// converted to the column’s type.
+    fn invalid_sql_value_error(value: &ParsedSQLValue, column_type: &str) -> Error {
+        Error::generate_error(
+            "INVALID_SQL_VALUE",
+            format!("`{}` can’t be converted to {}.", value, column_type),
+        )
+    }
+
     /// Parses a given AST and returns a tuple: (String [the converted expression that uses PREPARE
     /// parameters], Vec<TypedColumnValue>).
     pub fn generate_prepared_statement_from_ast_expr(
@@ -530,7 +2278,10 @@ impl TypedColumnValue {
         let prepared_values =
             Self::generate_prepared_values(&mut ast, table, column_types, starting_pos)?;
 
-        Ok((ast.to_string(), prepared_values))
+        Ok((
+            replace_placeholders_with_operators(ast.to_string()),
+            prepared_values,
+        ))
     }
 
     /// Extracts the values being assigned and replaces them with prepared statement position
@@ -582,16 +2333,39 @@ impl TypedColumnValue {
                 right: bin_right_ast_box,
                 ..
             } => {
-                let column_name_opt = get_column_name(bin_left_ast_box.borrow_mut())?;
+                // operators that the SQL parser doesn’t support (like `@>`) are parsed as
+                // `(column = __op_contains__) = value`
+                let (column_name_opt, placeholder_operator_opt) =
+                    match split_operator_placeholder(bin_left_ast_box.borrow_mut()) {
+                        Some((operand_ast, operator)) => {
+                            (get_column_name(operand_ast)?, Some(operator))
+                        }
+                        None => (get_column_name(bin_left_ast_box.borrow_mut())?, None),
+                    };
+
                 let expr = bin_right_ast_box.borrow_mut();
-                if let Some(ast_replacement) = Self::attempt_prepared_value_extraction(
-                    table,
-                    column_types,
-                    prepared_param_pos,
-                    &column_name_opt,
-                    expr,
-                    &mut prepared_statement_values,
-                )? {
+                let ast_replacement_opt = if let Some(operator) = placeholder_operator_opt {
+                    Self::attempt_operator_prepared_value_extraction(
+                        table,
+                        column_types,
+                        prepared_param_pos,
+                        &column_name_opt,
+                        operator,
+                        expr,
+                        &mut prepared_statement_values,
+                    )?
+                } else {
+                    Self::attempt_prepared_value_extraction(
+                        table,
+                        column_types,
+                        prepared_param_pos,
+                        &column_name_opt,
+                        expr,
+                        &mut prepared_statement_values,
+                    )?
+                };
+
+                if let Some(ast_replacement) = ast_replacement_opt {
                     *expr = ast_replacement;
                 };
             }
@@ -708,10 +2482,11 @@ impl TypedColumnValue {
                     Some(prepared_param_pos),
                 )?);
             }
-            Expr::Function(Function {
-                args: args_ast_vec, ..
-            }) => {
-                for expr in args_ast_vec {
+            Expr::Function(function) => {
+                #[cfg(feature = "postgis")]
+                Self::convert_geojson_function_args(function)?;
+
+                for expr in &mut function.args {
                     prepared_statement_values.par_extend(Self::generate_prepared_values(
                         expr,
                         table,
@@ -773,6 +2548,27 @@ impl TypedColumnValue {
         Ok(prepared_statement_values)
     }
 
+    /// PostGIS functions accept geometries as hex-encoded EWKB (as well as WKT), so GeoJSON
+    /// arguments of `ST_*` functions are converted to it.
+    #[cfg(feature = "postgis")]
+    fn convert_geojson_function_args(function: &mut Function) -> Result<(), Error> {
+        if !function.name.to_string().to_lowercase().starts_with("st_") {
+            return Ok(());
+        }
+
+        for expr in &mut function.args {
+            if let Expr::Value(SqlValue::SingleQuotedString(val)) = expr {
+                if val.trim_start().starts_with('{') {
+                    *val = Geometry::from_geojson_str(val)
+                        .map_err(|e| Error::generate_error("INVALID_SQL_VALUE", e))?
+                        .to_hex_ewkb();
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Attempts to swap a Value with a prepared parameter string ($1, $2, etc.) and extract that
     /// value as a TypedColumnValue.
     fn attempt_prepared_value_extraction(
@@ -786,7 +2582,12 @@ impl TypedColumnValue {
         let val_opt = ParsedSQLValue::attempt_extract_prepared_value_from_expr(expr);
 
         if let (Some(column_name), true) = (column_name_opt, val_opt.is_some()) {
-            if let Some(column_type) = column_types.get(column_name) {
+            // Array literals (like `'{1,2}'`) can’t be converted to prepared values, so they are
+            // left in the statement as-is.
+            if let Some(column_type) = column_types
+                .get(column_name)
+                .filter(|column_type| !column_type.starts_with('_'))
+            {
                 prepared_statement_values
                     .push(Self::from_parsed_sql_value(column_type, val_opt.unwrap())?);
                 let new_node = Expr::Identifier(format!("${}", prepared_param_pos));
@@ -806,6 +2607,191 @@ impl TypedColumnValue {
         Ok(None)
     }
 
+    /// Same as `attempt_prepared_value_extraction()`, but for the right side of an operator that
+    /// the SQL parser doesn’t support (`@>`, `<@`, `&&`, `?`). Its type can differ from the
+    /// column’s type (like in `tstzrange_column @> timestamptz_value`), so it is inferred from the
+    /// operator and the value, and the prepared parameter is cast to it.
+    fn attempt_operator_prepared_value_extraction(
+        table: &str,
+        column_types: &HashMap<String, &'static str>,
+        prepared_param_pos: &mut usize,
+        column_name_opt: &Option<String>,
+        operator: &str,
+        expr: &mut Expr,
+        prepared_statement_values: &mut Vec<TypedColumnValue>,
+    ) -> Result<Option<Expr>, Error> {
+        let val_opt = ParsedSQLValue::attempt_extract_prepared_value_from_expr(expr);
+
+        let operand_type_opt = match (column_name_opt, &val_opt) {
+            (Some(column_name), Some(val)) => column_types
+                .get(column_name)
+                .and_then(|column_type| Self::operator_operand_type(column_type, operator, val)),
+            _ => None,
+        };
+
+        if let (Some(operand_type), Some(val)) = (operand_type_opt, val_opt) {
+            prepared_statement_values.push(Self::from_parsed_sql_value(operand_type, val)?);
+            let new_node = Expr::Identifier(format!("${}::{}", prepared_param_pos, operand_type));
+            *prepared_param_pos += 1;
+
+            return Ok(Some(new_node));
+        }
+
+        prepared_statement_values.par_extend(Self::generate_prepared_values(
+            expr,
+            table,
+            column_types,
+            Some(prepared_param_pos),
+        )?);
+
+        Ok(None)
+    }
+
+    /// Returns the type of the value on the right side of an operator that the SQL parser doesn’t
+    /// support, given the type of the column on its left side.
+    fn operator_operand_type(
+        column_type: &str,
+        operator: &str,
+        value: &ParsedSQLValue,
+    ) -> Option<&'static str> {
+        match (column_type, operator) {
+            // `hstore ? key`
+            ("hstore", "?") => Some("text"),
+            // `hstore @> hstore`, `hstore <@ hstore`
+            ("hstore", _) => Some("hstore"),
+            (_, "?") => None,
+            // `geometry && geometry` (bounding boxes intersect), etc.
+            #[cfg(feature = "postgis")]
+            ("geography", _) => Some("geography"),
+            #[cfg(feature = "postgis")]
+            ("geometry", _) => Some("geometry"),
+            _ => Self::range_operand_type(column_type, value),
+        }
+    }
+
+    /// Returns the type of the value on the right side of a range operator, given the type of the
+    /// column on its left side. Range literals (`[1,5)`) are ranges, multirange literals
+    /// (`{[1,5)}`) are multiranges, and anything else is a single element. Returns `None` for
+    /// columns that aren’t built-in ranges or range elements (like arrays).
+    fn range_operand_type(column_type: &str, value: &ParsedSQLValue) -> Option<&'static str> {
+        let column_subtype_opt = range_subtype_name(column_type);
+        let (range_type, multirange_type) =
+            range_type_names(column_subtype_opt.unwrap_or(column_type))?;
+
+        let literal = match value {
+            ParsedSQLValue::String(literal) => literal.trim(),
+            _ => "",
+        };
+
+        if literal.starts_with('{') {
+            Some(multirange_type)
+        } else if literal.starts_with('[')
+            || literal.starts_with('(')
+            || literal.eq_ignore_ascii_case("empty")
+        {
+            Some(range_type)
+        } else if let Some(column_subtype) = column_subtype_opt {
+            // `range @> element`
+            Some(column_subtype)
+        } else {
+            // `element @> element` is never valid
+            None
+        }
+    }
+
+    fn convert_json_value_to_array(element_type: &str, value: &JsonValue) -> Result<Self, Error> {
+        // `bytea`, `json`, and `jsonb` values can be JSON arrays themselves, so they are never
+        // treated as a nested dimension.
+        let is_nested_array = |json_val: &JsonValue| {
+            json_val.is_array() && !["bytea", "json", "jsonb"].contains(&element_type)
+        };
+
+        if !value.is_array() {
+            return Err(Error::generate_error(
+                "INVALID_JSON_TYPE_CONVERSION",
+                format!("Value must be an array: `{}`.", value),
+            ));
+        }
+
+        // the length of each dimension is determined by following the first element of each
+        // sub-array
+        let mut dimensions = vec![];
+        let mut current_val = value;
+        while let Some(json_vec) = current_val.as_array() {
+            dimensions.push(json_vec.len() as i32);
+
+            match json_vec.first() {
+                Some(first_val) if is_nested_array(first_val) => current_val = first_val,
+                _ => break,
+            }
+        }
+
+        let mut elements = vec![];
+        Self::flatten_json_array(
+            element_type,
+            value,
+            &dimensions,
+            &is_nested_array,
+            &mut elements,
+        )?;
+
+        Ok(TypedColumnValue::Array(IsNullColumnValue::NotNullable(
+            PgArray {
+                dimensions,
+                elements,
+            },
+        )))
+    }
+
+    /// Converts the elements of a (possibly nested) JSON array, appending them to `elements`.
+    /// Multi-dimensional arrays must be rectangular (sub-arrays of the same dimension must have
+    /// the same length).
+    fn flatten_json_array(
+        element_type: &str,
+        value: &JsonValue,
+        dimensions: &[i32],
+        is_nested_array: &dyn Fn(&JsonValue) -> bool,
+        elements: &mut Vec<TypedColumnValue>,
+    ) -> Result<(), Error> {
+        let mismatched_dimensions_error = || {
+            Error::generate_error(
+                "INVALID_JSON_TYPE_CONVERSION",
+                format!(
+                    "Multi-dimensional arrays must have sub-arrays with matching dimensions: `{}`.",
+                    value
+                ),
+            )
+        };
+
+        let json_vec = match (value.as_array(), dimensions.split_first()) {
+            (Some(json_vec), Some((len, _))) if json_vec.len() as i32 == *len => json_vec,
+            _ => return Err(mismatched_dimensions_error()),
+        };
+
+        for json_val in json_vec {
+            if dimensions.len() > 1 {
+                Self::flatten_json_array(
+                    element_type,
+                    json_val,
+                    &dimensions[1..],
+                    is_nested_array,
+                    elements,
+                )?;
+            } else if is_nested_array(json_val) {
+                return Err(mismatched_dimensions_error());
+            } else if json_val.is_null() {
+                elements.push(Self::from_parsed_sql_value(
+                    element_type,
+                    ParsedSQLValue::Null,
+                )?);
+            } else {
+                elements.push(Self::from_json(element_type, json_val)?);
+            }
+        }
+
+        Ok(())
+    }
+
     fn convert_json_value_to_bigint(value: &JsonValue) -> Result<Self, Error> {
         match value.as_i64() {
             Some(val) => Ok(TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(
@@ -818,6 +2804,38 @@ impl TypedColumnValue {
         }
     }
 
+    fn convert_json_value_to_bit_string(
+        column_type: &str,
+        value: &JsonValue,
+    ) -> Result<Self, Error> {
+        let bit_string = match value.as_str() {
+            Some(val) => match BitString::from_str(val) {
+                Ok(bit_string) => IsNullColumnValue::NotNullable(bit_string),
+                Err(e) => {
+                    return Err(Error::generate_error(
+                        "INVALID_JSON_TYPE_CONVERSION",
+                        format!(
+                            "Value must be a string of 0s and 1s: `{}`. Message: `{}`.",
+                            value, e
+                        ),
+                    ))
+                }
+            },
+            None => {
+                return Err(Error::generate_error(
+                    "INVALID_JSON_TYPE_CONVERSION",
+                    format!("Value must be a string: `{}`.", value),
+                ))
+            }
+        };
+
+        Ok(if column_type == "bit" {
+            TypedColumnValue::Bit(bit_string)
+        } else {
+            TypedColumnValue::VarBit(bit_string)
+        })
+    }
+
     fn convert_json_value_to_bool(value: &JsonValue) -> Result<Self, Error> {
         match value.as_bool() {
             Some(val) => Ok(TypedColumnValue::Bool(IsNullColumnValue::NotNullable(val))),
@@ -868,6 +2886,25 @@ impl TypedColumnValue {
         }
     }
 
+    fn convert_json_value_to_cidr(value: &JsonValue) -> Result<Self, Error> {
+        match value.as_str() {
+            Some(val) => match Inet::from_str(val) {
+                Ok(cidr) => Ok(TypedColumnValue::Cidr(IsNullColumnValue::NotNullable(cidr))),
+                Err(e) => Err(Error::generate_error(
+                    "INVALID_JSON_TYPE_CONVERSION",
+                    format!(
+                        "Value must be a valid network address: `{}`. Message: `{}`.",
+                        value, e
+                    ),
+                )),
+            },
+            None => Err(Error::generate_error(
+                "INVALID_JSON_TYPE_CONVERSION",
+                format!("Value must be a string: `{}`.", value),
+            )),
+        }
+    }
+
     fn convert_json_value_to_citext(value: &JsonValue) -> Result<Self, Error> {
         match value.as_str() {
             Some(val) => Ok(TypedColumnValue::Citext(IsNullColumnValue::NotNullable(
@@ -917,6 +2954,18 @@ impl TypedColumnValue {
         }
     }
 
+    fn convert_json_value_to_enum(value: &JsonValue) -> Result<Self, Error> {
+        match value.as_str() {
+            Some(val) => Ok(TypedColumnValue::Enum(IsNullColumnValue::NotNullable(
+                EnumLabel(val.to_string()),
+            ))),
+            None => Err(Error::generate_error(
+                "INVALID_JSON_TYPE_CONVERSION",
+                format!("Value must be a string: `{}`.", value),
+            )),
+        }
+    }
+
     fn convert_json_value_to_float8(value: &JsonValue) -> Result<Self, Error> {
         match value.as_f64() {
             Some(n) => Ok(TypedColumnValue::Float8(IsNullColumnValue::NotNullable(n))),
@@ -927,6 +2976,75 @@ impl TypedColumnValue {
         }
     }
 
+    #[cfg(feature = "postgis")]
+    fn convert_json_value_to_geometry(value: &JsonValue) -> Result<Self, Error> {
+        let geometry = match value {
+            JsonValue::Object(_) => Geometry::from_geojson(value),
+            JsonValue::String(geojson_str) => Geometry::from_geojson_str(geojson_str),
+            _ => Err(format!("Value must be a GeoJSON object: `{}`.", value)),
+        }
+        .map_err(|e| Error::generate_error("INVALID_JSON_TYPE_CONVERSION", e))?;
+
+        Ok(TypedColumnValue::Geometry(IsNullColumnValue::NotNullable(
+            geometry,
+        )))
+    }
+
+    fn convert_json_value_to_hstore(value: &JsonValue) -> Result<Self, Error> {
+        let json_obj = match value {
+            JsonValue::Object(json_obj) => json_obj,
+            // hstore literal, like `"a=>1, b=>NULL"`
+            JsonValue::String(literal) => {
+                let hstore = Hstore::from_str(literal)
+                    .map_err(|e| Error::generate_error("INVALID_JSON_TYPE_CONVERSION", e))?;
+                return Ok(TypedColumnValue::Hstore(IsNullColumnValue::NotNullable(
+                    hstore,
+                )));
+            }
+            _ => {
+                return Err(Error::generate_error(
+                    "INVALID_JSON_TYPE_CONVERSION",
+                    format!("Value must be an object or an hstore string: `{}`.", value),
+                ))
+            }
+        };
+
+        let hstore = json_obj
+            .iter()
+            .map(|(key, json_val)| match json_val {
+                JsonValue::Null => Ok((key.clone(), None)),
+                JsonValue::String(val) => Ok((key.clone(), Some(val.clone()))),
+                _ => Err(Error::generate_error(
+                    "INVALID_JSON_TYPE_CONVERSION",
+                    format!("hstore values must be strings or null: `{}`.", value),
+                )),
+            })
+            .collect::<Result<HashMap<String, Option<String>>, Error>>()?;
+
+        Ok(TypedColumnValue::Hstore(IsNullColumnValue::NotNullable(
+            Hstore(hstore),
+        )))
+    }
+
+    fn convert_json_value_to_inet(value: &JsonValue) -> Result<Self, Error> {
+        match value.as_str() {
+            Some(val) => match Inet::from_str(val) {
+                Ok(inet) => Ok(TypedColumnValue::Inet(IsNullColumnValue::NotNullable(inet))),
+                Err(e) => Err(Error::generate_error(
+                    "INVALID_JSON_TYPE_CONVERSION",
+                    format!(
+                        "Value must be a valid IP address: `{}`. Message: `{}`.",
+                        value, e
+                    ),
+                )),
+            },
+            None => Err(Error::generate_error(
+                "INVALID_JSON_TYPE_CONVERSION",
+                format!("Value must be a string: `{}`.", value),
+            )),
+        }
+    }
+
     fn convert_json_value_to_int(value: &JsonValue) -> Result<Self, Error> {
         match value.as_i64() {
             Some(n) => Ok(TypedColumnValue::Int(IsNullColumnValue::NotNullable(
@@ -939,6 +3057,27 @@ impl TypedColumnValue {
         }
     }
 
+    fn convert_json_value_to_interval(value: &JsonValue) -> Result<Self, Error> {
+        match value.as_str() {
+            Some(val) => match Interval::from_str(val) {
+                Ok(interval) => Ok(TypedColumnValue::Interval(IsNullColumnValue::NotNullable(
+                    interval,
+                ))),
+                Err(e) => Err(Error::generate_error(
+                    "INVALID_JSON_TYPE_CONVERSION",
+                    format!(
+                        "Value must be a valid interval: `{}`. Message: `{}`.",
+                        value, e
+                    ),
+                )),
+            },
+            None => Err(Error::generate_error(
+                "INVALID_JSON_TYPE_CONVERSION",
+                format!("Value must be a string: `{}`.", value),
+            )),
+        }
+    }
+
     fn convert_json_value_to_json(value: &JsonValue) -> Result<Self, Error> {
         Ok(TypedColumnValue::Json(IsNullColumnValue::NotNullable(
             value.clone(),
@@ -967,7 +3106,34 @@ impl TypedColumnValue {
             },
             None => Err(Error::generate_error(
                 "INVALID_JSON_TYPE_CONVERSION",
-                format!("Value must be a string: `{}`.", value),
+                format!("Value must be a string: `{}`.", value),
+            )),
+        }
+    }
+
+    fn convert_json_value_to_money(value: &JsonValue) -> Result<Self, Error> {
+        // numbers are accepted too, but strings avoid floating point rounding errors
+        let money_str = match value {
+            JsonValue::String(val) => val.clone(),
+            JsonValue::Number(val) => val.to_string(),
+            _ => {
+                return Err(Error::generate_error(
+                    "INVALID_JSON_TYPE_CONVERSION",
+                    format!("Value must be a string or number: `{}`.", value),
+                ))
+            }
+        };
+
+        match Money::from_str(&money_str) {
+            Ok(money) => Ok(TypedColumnValue::Money(IsNullColumnValue::NotNullable(
+                money,
+            ))),
+            Err(e) => Err(Error::generate_error(
+                "INVALID_JSON_TYPE_CONVERSION",
+                format!(
+                    "Value must be a valid money amount: `{}`. Message: `{}`.",
+                    value, e
+                ),
             )),
         }
     }
@@ -996,6 +3162,91 @@ impl TypedColumnValue {
         }
     }
 
+    fn convert_json_value_to_range(column_type: &str, value: &JsonValue) -> Result<Self, Error> {
+        let subtype = range_subtype_name(column_type).unwrap_or(column_type);
+
+        Ok(TypedColumnValue::Range(IsNullColumnValue::NotNullable(
+            Self::json_value_to_pg_range(subtype, value)?,
+        )))
+    }
+
+    fn convert_json_value_to_multirange(
+        column_type: &str,
+        value: &JsonValue,
+    ) -> Result<Self, Error> {
+        let subtype = range_subtype_name(column_type).unwrap_or(column_type);
+
+        let multirange = match value {
+            JsonValue::String(literal) => PgMultirange::from_literal(subtype, literal)?,
+            JsonValue::Array(json_ranges) => PgMultirange(
+                json_ranges
+                    .iter()
+                    .map(|json_range| Self::json_value_to_pg_range(subtype, json_range))
+                    .collect::<Result<Vec<PgRange>, Error>>()?,
+            ),
+            _ => {
+                return Err(Error::generate_error(
+                    "INVALID_JSON_TYPE_CONVERSION",
+                    format!("Value must be an array of ranges or a string: `{}`.", value),
+                ))
+            }
+        };
+
+        Ok(TypedColumnValue::Multirange(
+            IsNullColumnValue::NotNullable(multirange),
+        ))
+    }
+
+    /// Converts a range object (like `{"lower": 1, "upper": 5}`) or a range literal string (like
+    /// `"[1,5)"`) into a `PgRange`. Missing or `null` bounds are unbounded. Lower bounds are
+    /// inclusive and upper bounds are exclusive unless specified otherwise.
+    fn json_value_to_pg_range(subtype: &str, value: &JsonValue) -> Result<PgRange, Error> {
+        let range_obj = match value {
+            JsonValue::String(literal) => return PgRange::from_literal(subtype, literal),
+            JsonValue::Object(range_obj) => range_obj,
+            _ => {
+                return Err(Error::generate_error(
+                    "INVALID_JSON_TYPE_CONVERSION",
+                    format!("Value must be a range object or a string: `{}`.", value),
+                ))
+            }
+        };
+
+        if range_obj.get("empty") == Some(&JsonValue::Bool(true)) {
+            return Ok(PgRange::Empty);
+        }
+
+        let get_is_inclusive = |key: &str, default: bool| -> Result<bool, Error> {
+            match range_obj.get(key) {
+                None | Some(JsonValue::Null) => Ok(default),
+                Some(JsonValue::Bool(is_inclusive)) => Ok(*is_inclusive),
+                Some(_) => Err(Error::generate_error(
+                    "INVALID_JSON_TYPE_CONVERSION",
+                    format!("`{}` must be a boolean: `{}`.", key, value),
+                )),
+            }
+        };
+
+        let get_bound = |key: &str, is_inclusive: bool| -> Result<PgRangeBound, Error> {
+            match range_obj.get(key) {
+                None | Some(JsonValue::Null) => Ok(PgRangeBound::Unbounded),
+                Some(bound_value) => {
+                    let bound_value = Box::new(Self::from_json(subtype, bound_value)?);
+                    Ok(if is_inclusive {
+                        PgRangeBound::Inclusive(bound_value)
+                    } else {
+                        PgRangeBound::Exclusive(bound_value)
+                    })
+                }
+            }
+        };
+
+        Ok(PgRange::Nonempty(
+            get_bound("lower", get_is_inclusive("lower_inclusive", true)?)?,
+            get_bound("upper", get_is_inclusive("upper_inclusive", false)?)?,
+        ))
+    }
+
     fn convert_json_value_to_real(value: &JsonValue) -> Result<Self, Error> {
         match value.as_f64() {
             Some(n) => Ok(TypedColumnValue::Real(IsNullColumnValue::NotNullable(
@@ -1090,6 +3341,27 @@ impl TypedColumnValue {
         }
     }
 
+    fn convert_json_value_to_timetz(value: &JsonValue) -> Result<Self, Error> {
+        match value.as_str() {
+            Some(val) => match TimeTz::from_str(val) {
+                Ok(timetz) => Ok(TypedColumnValue::TimeTz(IsNullColumnValue::NotNullable(
+                    timetz,
+                ))),
+                Err(e) => Err(Error::generate_error(
+                    "INVALID_JSON_TYPE_CONVERSION",
+                    format!(
+                        "Value must be a valid time with time zone: `{}`. Message: `{}`.",
+                        value, e
+                    ),
+                )),
+            },
+            None => Err(Error::generate_error(
+                "INVALID_JSON_TYPE_CONVERSION",
+                format!("Value must be a string: `{}`.", value),
+            )),
+        }
+    }
+
     fn convert_json_value_to_uuid(value: &JsonValue) -> Result<Self, Error> {
         match value.as_str() {
             Some(val) => match Uuid::parse_str(val) {
@@ -1119,36 +3391,58 @@ impl TypedColumnValue {
             )),
         }
     }
+
+    fn convert_json_value_to_xml(value: &JsonValue) -> Result<Self, Error> {
+        match value.as_str() {
+            Some(val) => Ok(TypedColumnValue::Xml(IsNullColumnValue::NotNullable(Xml(
+                val.to_string(),
+            )))),
+            None => Err(Error::generate_error(
+                "INVALID_JSON_TYPE_CONVERSION",
+                format!("Value must be a string: `{}`.", value),
+            )),
+        }
+    }
 }
 
-/// A HashMap of column names and their values for a single table row.
-pub type RowValues = HashMap<String, TypedColumnValue>;
+/// A map of column names and their values for a single table row, in the order the columns were
+/// selected/returned.
+pub type RowValues = IndexMap<String, TypedColumnValue>;
 
 /// Analyzes a table postgres row and returns the Rust-equivalent value.
 pub fn row_to_row_values(row: &Row) -> Result<RowValues, Error> {
-    let mut row_values = HashMap::new();
+    let mut row_values = IndexMap::with_capacity(row.columns().len());
     for (i, column) in row.columns().iter().enumerate() {
-        let column_type_name = column.type_().name();
+        let column_type = column.type_();
+        let column_type_name = column_type.name();
 
         row_values.insert(
             column.name().to_string(),
             match column_type_name {
                 "int8" => TypedColumnValue::BigInt(row.get(i)),
+                "bit" => TypedColumnValue::Bit(row.get(i)),
                 "bool" => TypedColumnValue::Bool(row.get(i)),
                 "bytea" => {
                     // byte array (binary)
                     TypedColumnValue::ByteA(row.get(i))
                 }
                 "bpchar" => TypedColumnValue::Char(row.get(i)), // char
+                "cidr" => TypedColumnValue::Cidr(row.get(i)),
                 "citext" => TypedColumnValue::Citext(row.get(i)),
                 "date" => TypedColumnValue::Date(row.get(i)),
                 "float4" => TypedColumnValue::Real(row.get(i)),
                 "float8" => TypedColumnValue::Float8(row.get(i)),
+                #[cfg(feature = "postgis")]
+                "geography" | "geometry" => TypedColumnValue::Geometry(row.get(i)),
+                "hstore" => TypedColumnValue::Hstore(row.get(i)),
+                "inet" => TypedColumnValue::Inet(row.get(i)),
                 "int2" => TypedColumnValue::SmallInt(row.get(i)),
                 "int4" => TypedColumnValue::Int(row.get(i)), // int
+                "interval" => TypedColumnValue::Interval(row.get(i)),
                 "json" => TypedColumnValue::Json(row.get(i)),
                 "jsonb" => TypedColumnValue::JsonB(row.get(i)),
                 "macaddr" => TypedColumnValue::MacAddr(row.get(i)),
+                "money" => TypedColumnValue::Money(row.get(i)),
                 "name" => TypedColumnValue::Name(row.get(i)),
                 // using rust-decimal per discussion at https://www.reddit.com/r/rust/comments/a7frqj/have_anyone_reviewed_any_of_the_decimal_crates/.
                 // keep in mind that at the time of this writing, diesel uses bigdecimal
@@ -1158,11 +3452,29 @@ pub fn row_to_row_values(row: &Row) -> Result<RowValues, Error> {
                 "time" => TypedColumnValue::Time(row.get(i)),
                 "timestamp" => TypedColumnValue::Timestamp(row.get(i)),
                 "timestamptz" => TypedColumnValue::TimestampTz(row.get(i)),
+                "timetz" => TypedColumnValue::TimeTz(row.get(i)),
                 "uuid" => TypedColumnValue::Uuid(row.get(i)),
-                // "varbit" => {
-                //     TypedColumnValue::VarBit(row.get(i))
-                // }
+                "varbit" => TypedColumnValue::VarBit(row.get(i)),
                 "varchar" => TypedColumnValue::VarChar(row.get(i)),
+                "xml" => TypedColumnValue::Xml(row.get(i)),
+                _ if <EnumLabel as FromSql>::accepts(column_type) => {
+                    TypedColumnValue::Enum(row.get(i))
+                }
+                _ if <PgRange as FromSql>::accepts(column_type) => {
+                    TypedColumnValue::Range(row.get(i))
+                }
+                _ if <PgMultirange as FromSql>::accepts(column_type) => {
+                    TypedColumnValue::Multirange(row.get(i))
+                }
+                _ if <PgArray as FromSql>::accepts(column_type) => {
+                    TypedColumnValue::Array(row.get(i))
+                }
+                // domains are read as their base type
+                _ if column_type != resolve_domain(column_type)
+                    && <TypedColumnValue as FromSql>::accepts(column_type) =>
+                {
+                    row.get(i)
+                }
                 _ => {
                     return Err(Error::generate_error(
                         "UNSUPPORTED_DATA_TYPE",
@@ -1209,7 +3521,7 @@ impl From<SqlValue> for ParsedSQLValue {
             SqlValue::Date(v) => Self::String(v),
             SqlValue::Double(v) => Self::Float(v.into_inner()),
             SqlValue::HexStringLiteral(v) => Self::String(v),
-            SqlValue::Interval { .. } => unimplemented!("Interval type not supported"),
+            v @ SqlValue::Interval { .. } => Self::String(interval_literal_to_string(&v)),
             SqlValue::Long(v) => Self::Int8(v as i64),
             SqlValue::NationalStringLiteral(v) => Self::String(v),
             SqlValue::Null => Self::Null,
@@ -1220,6 +3532,30 @@ impl From<SqlValue> for ParsedSQLValue {
     }
 }
 
+/// Converts an `INTERVAL` literal (like `INTERVAL '1 day'` or `INTERVAL '2' HOUR`) to a string that
+/// can be parsed into an `Interval`.
+fn interval_literal_to_string(interval_literal: &SqlValue) -> String {
+    // the literal is displayed as `INTERVAL '<value>'`, optionally followed by qualifiers
+    let literal_str = interval_literal.to_string();
+    let (value, qualifiers) = match (literal_str.find('\''), literal_str.rfind('\'')) {
+        (Some(start), Some(end)) if start < end => (
+            literal_str[start + 1..end].replace("''", "'"),
+            &literal_str[end + 1..],
+        ),
+        _ => return literal_str,
+    };
+
+    // `INTERVAL '2' HOUR` means 2 hours, while `INTERVAL '1:30' HOUR TO MINUTE` is already a time
+    match qualifiers.split_whitespace().next() {
+        Some(leading_field) if !value.contains(':') && !value.trim().contains(' ') => {
+            // strip precisions, like the `(3)` in `SECOND(3)`
+            let leading_field = leading_field.split('(').next().unwrap_or(leading_field);
+            format!("{} {}", value, leading_field)
+        }
+        _ => value,
+    }
+}
+
 impl ParsedSQLValue {
     /// Tries to extract a prepared statement value from an Expr.
     pub fn attempt_extract_prepared_value_from_expr(expr: &Expr) -> Option<Self> {
@@ -1266,3 +3602,717 @@ impl ParsedSQLValue {
         };
     }
 }
+
+#[cfg(test)]
+mod from_json_array_tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn one_dimension() {
+        let value = TypedColumnValue::from_json("_text", &json!(["a", null, "b"])).unwrap();
+
+        assert_eq!(
+            value,
+            TypedColumnValue::Array(IsNullColumnValue::NotNullable(PgArray {
+                dimensions: vec![3],
+                elements: vec![
+                    TypedColumnValue::Text(IsNullColumnValue::NotNullable("a".to_string())),
+                    TypedColumnValue::Text(IsNullColumnValue::Nullable(None)),
+                    TypedColumnValue::Text(IsNullColumnValue::NotNullable("b".to_string())),
+                ],
+            }))
+        );
+        assert_eq!(
+            serde_json::to_value(&value).unwrap(),
+            json!(["a", null, "b"])
+        );
+    }
+
+    #[test]
+    fn multiple_dimensions() {
+        let json_val = json!([[[1, 2], [3, null]], [[5, 6], [7, 8]]]);
+        let value = TypedColumnValue::from_json("_int4", &json_val).unwrap();
+
+        match &value {
+            TypedColumnValue::Array(IsNullColumnValue::NotNullable(array)) => {
+                assert_eq!(array.dimensions, vec![2, 2, 2]);
+                assert_eq!(array.elements.len(), 8);
+            }
+            _ => panic!("Expected a non-null array."),
+        }
+        assert_eq!(serde_json::to_value(&value).unwrap(), json_val);
+    }
+
+    #[test]
+    fn empty() {
+        let value = TypedColumnValue::from_json("_int8", &json!([])).unwrap();
+
+        assert_eq!(serde_json::to_value(&value).unwrap(), json!([]));
+    }
+
+    #[test]
+    fn json_elements_are_not_nested() {
+        let json_val = json!([[1, 2], {"a": 1}]);
+        let value = TypedColumnValue::from_json("_jsonb", &json_val).unwrap();
+
+        assert_eq!(serde_json::to_value(&value).unwrap(), json_val);
+    }
+
+    #[test]
+    fn mismatched_dimensions() {
+        assert!(TypedColumnValue::from_json("_int4", &json!([[1, 2], [3]])).is_err());
+        assert!(TypedColumnValue::from_json("_int4", &json!([1, [2]])).is_err());
+        assert!(TypedColumnValue::from_json("_int4", &json!([[1], 2])).is_err());
+    }
+
+    #[test]
+    fn not_an_array() {
+        assert!(TypedColumnValue::from_json("_int4", &json!(1)).is_err());
+    }
+}
+
+#[cfg(test)]
+mod from_str_tests {
+    use super::*;
+    use crate::queries::utils::conditions_params_to_ast;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn inet() {
+        let inet = Inet::from_str("192.168.0.1").unwrap();
+        assert_eq!(inet.netmask, 32);
+        assert_eq!(inet.to_string(), "192.168.0.1");
+
+        let inet = Inet::from_str("192.168.0.0/24").unwrap();
+        assert_eq!(inet.netmask, 24);
+        assert_eq!(inet.to_string(), "192.168.0.0/24");
+
+        assert_eq!(Inet::from_str("::1").unwrap().to_string(), "::1");
+        assert!(Inet::from_str("192.168.0.1/33").is_err());
+        assert!(Inet::from_str("not an ip").is_err());
+    }
+
+    #[test]
+    fn interval_iso_8601() {
+        let interval = Interval::from_str("P1Y2M3DT4H5M6.5S").unwrap();
+        assert_eq!(
+            interval,
+            Interval {
+                months: 14,
+                days: 3,
+                microseconds: 14_706_500_000,
+            }
+        );
+        assert_eq!(interval.to_string(), "P1Y2M3DT4H5M6.5S");
+
+        assert_eq!(Interval::from_str("PT0S").unwrap().to_string(), "PT0S");
+        assert_eq!(Interval::from_str("P2W").unwrap().to_string(), "P14D");
+        assert_eq!(
+            Interval::from_str("PT-90M").unwrap().to_string(),
+            "PT-1H-30M"
+        );
+        assert!(Interval::from_str("P1H").is_err());
+        assert!(Interval::from_str("P1").is_err());
+    }
+
+    #[test]
+    fn interval_postgres_format() {
+        assert_eq!(
+            Interval::from_str("1 year 2 mons 3 days 04:05:06.5").unwrap(),
+            Interval::from_str("P1Y2M3DT4H5M6.5S").unwrap()
+        );
+        assert_eq!(
+            Interval::from_str("1.5 months").unwrap().to_string(),
+            "P1M15D"
+        );
+        assert_eq!(Interval::from_str("2 HOUR").unwrap().to_string(), "PT2H");
+        assert_eq!(Interval::from_str("1 day ago").unwrap().to_string(), "P-1D");
+        assert_eq!(Interval::from_str("30").unwrap().to_string(), "PT30S");
+        assert!(Interval::from_str("1 fortnight").is_err());
+    }
+
+    #[test]
+    fn mismatched_literal_kinds() {
+        for column_type in &["cidr", "enum", "inet", "interval", "money", "timetz", "xml"] {
+            match TypedColumnValue::from_parsed_sql_value(
+                column_type,
+                ParsedSQLValue::Boolean(true),
+            ) {
+                Err(Error::UserError { code, .. }) => assert_eq!(code, "INVALID_SQL_VALUE"),
+                _ => panic!("A boolean should not be converted to {}.", column_type),
+            }
+        }
+    }
+
+    #[test]
+    fn interval_out_of_range() {
+        for interval_str in &[
+            "P99999999999Y",
+            "P2147483647M1M",
+            "PT9999999999999999H",
+            "178956970 years 11 months",
+            "2147483647 days 1 day",
+        ] {
+            assert_eq!(
+                Interval::from_str(interval_str),
+                Err("Interval out of range".to_string()),
+                "{}",
+                interval_str
+            );
+        }
+    }
+
+    #[test]
+    fn interval_literal() {
+        let literal = |sql: &str| -> String {
+            match conditions_params_to_ast(&Some(["a = ", sql].join(""))).unwrap() {
+                Expr::BinaryOp { right, .. } => match *right {
+                    Expr::Value(v) => interval_literal_to_string(&v),
+                    _ => panic!("Expected an INTERVAL literal."),
+                },
+                _ => panic!("Expected a binary operation."),
+            }
+        };
+
+        assert_eq!(literal("INTERVAL '1 day'"), "1 day");
+        assert_eq!(literal("INTERVAL '2' HOUR"), "2 HOUR");
+        assert_eq!(literal("INTERVAL '1:30' HOUR TO MINUTE"), "1:30");
+    }
+
+    #[test]
+    fn money() {
+        assert_eq!(Money::from_str("-1234.56").unwrap(), Money(-123_456));
+        assert_eq!(Money::from_str("12.5").unwrap(), Money(1250));
+        assert_eq!(Money::from_str("7").unwrap(), Money(700));
+        assert_eq!(Money(-123_456).to_string(), "-1234.56");
+        assert_eq!(Money(5).to_string(), "0.05");
+        assert!(Money::from_str("1.234").is_err());
+        assert!(Money::from_str("$12").is_err());
+    }
+
+    #[test]
+    fn timetz() {
+        let timetz = TimeTz::from_str("04:05:06.789-08:00").unwrap();
+        assert_eq!(timetz.offset, FixedOffset::west(8 * 3600));
+        assert_eq!(timetz.to_string(), "04:05:06.789-08:00");
+
+        assert_eq!(
+            TimeTz::from_str("04:05:06+0530").unwrap().to_string(),
+            "04:05:06+05:30"
+        );
+        assert_eq!(
+            TimeTz::from_str("04:05:06Z").unwrap().to_string(),
+            "04:05:06+00:00"
+        );
+        assert!(TimeTz::from_str("04:05:06").is_err());
+    }
+}
+
+#[cfg(test)]
+mod range_tests {
+    use super::*;
+    use crate::queries::utils::conditions_params_to_ast;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn int4(val: i32) -> Box<TypedColumnValue> {
+        Box::new(TypedColumnValue::Int(IsNullColumnValue::NotNullable(val)))
+    }
+
+    #[test]
+    fn range_literal() {
+        assert_eq!(
+            PgRange::from_literal("int4", "[1,5)").unwrap(),
+            PgRange::Nonempty(
+                PgRangeBound::Inclusive(int4(1)),
+                PgRangeBound::Exclusive(int4(5))
+            )
+        );
+        assert_eq!(
+            PgRange::from_literal("int4", "(, 5]").unwrap(),
+            PgRange::Nonempty(PgRangeBound::Unbounded, PgRangeBound::Inclusive(int4(5)))
+        );
+        assert_eq!(
+            PgRange::from_literal("int4", "EMPTY").unwrap(),
+            PgRange::Empty
+        );
+        assert!(PgRange::from_literal("int4", "1,5").is_err());
+        assert!(PgRange::from_literal("int4", "[1,5,7)").is_err());
+        assert!(PgRange::from_literal("int4", "[a,5)").is_err());
+    }
+
+    #[test]
+    fn range_literal_quoted_bounds() {
+        let range = PgRange::from_literal(
+            "timestamptz",
+            r#"["2019-01-01T10:00:00Z","2019-01-01T11:00:00Z")"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&range).unwrap(),
+            json!({
+                "lower": "2019-01-01T10:00:00Z",
+                "upper": "2019-01-01T11:00:00Z",
+                "lower_inclusive": true,
+                "upper_inclusive": false
+            })
+        );
+    }
+
+    #[test]
+    fn range_sql_values() {
+        assert_eq!(
+            TypedColumnValue::from_parsed_sql_value(
+                "int4range",
+                ParsedSQLValue::String("[1,5)".to_string())
+            )
+            .unwrap(),
+            TypedColumnValue::Range(IsNullColumnValue::NotNullable(
+                PgRange::from_literal("int4", "[1,5)").unwrap()
+            ))
+        );
+        assert_eq!(
+            TypedColumnValue::from_parsed_sql_value("int4multirange", ParsedSQLValue::Null)
+                .unwrap(),
+            TypedColumnValue::Multirange(IsNullColumnValue::Nullable(None))
+        );
+
+        for column_type in &["int4range", "int4multirange"] {
+            match TypedColumnValue::from_parsed_sql_value(column_type, ParsedSQLValue::Int8(1)) {
+                Err(Error::UserError { code, .. }) => assert_eq!(code, "INVALID_SQL_VALUE"),
+                _ => panic!("A number should not be converted to {}.", column_type),
+            }
+        }
+    }
+
+    #[test]
+    fn multirange_literal() {
+        assert_eq!(
+            PgMultirange::from_literal("int4", "{[1,3), [5,7)}").unwrap(),
+            PgMultirange(vec![
+                PgRange::Nonempty(
+                    PgRangeBound::Inclusive(int4(1)),
+                    PgRangeBound::Exclusive(int4(3))
+                ),
+                PgRange::Nonempty(
+                    PgRangeBound::Inclusive(int4(5)),
+                    PgRangeBound::Exclusive(int4(7))
+                ),
+            ])
+        );
+        assert_eq!(
+            PgMultirange::from_literal("int4", "{}").unwrap(),
+            PgMultirange(vec![])
+        );
+        assert!(PgMultirange::from_literal("int4", "[1,3)").is_err());
+    }
+
+    #[test]
+    fn from_json() {
+        let value =
+            TypedColumnValue::from_json("int4range", &json!({"lower": 1, "upper": 5})).unwrap();
+        assert_eq!(
+            value,
+            TypedColumnValue::Range(IsNullColumnValue::NotNullable(PgRange::Nonempty(
+                PgRangeBound::Inclusive(int4(1)),
+                PgRangeBound::Exclusive(int4(5))
+            )))
+        );
+
+        let value = TypedColumnValue::from_json(
+            "int4range",
+            &json!({"lower": null, "upper": 5, "upper_inclusive": true}),
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&value).unwrap(),
+            json!({"lower": null, "upper": 5, "lower_inclusive": false, "upper_inclusive": true})
+        );
+
+        let value = TypedColumnValue::from_json("int4range", &json!({"empty": true})).unwrap();
+        assert_eq!(
+            serde_json::to_value(&value).unwrap(),
+            json!({"empty": true})
+        );
+
+        let value = TypedColumnValue::from_json(
+            "int4multirange",
+            &json!([{"lower": 1, "upper": 3}, "[5,7)"]),
+        )
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(&value).unwrap(),
+            json!([
+                {"lower": 1, "upper": 3, "lower_inclusive": true, "upper_inclusive": false},
+                {"lower": 5, "upper": 7, "lower_inclusive": true, "upper_inclusive": false}
+            ])
+        );
+
+        assert!(TypedColumnValue::from_json("int4range", &json!([1, 5])).is_err());
+        assert!(TypedColumnValue::from_json(
+            "int4range",
+            &json!({"lower": 1, "lower_inclusive": 1})
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn where_range_operators() {
+        let where_ast = conditions_params_to_ast(&Some(
+            "during @> '2019-01-01T10:00:00Z' AND during && '[2019-01-01T00:00:00Z,)' AND id <@ '{[1,3)}' AND tags @> '{a}'"
+                .to_string(),
+        ))
+        .unwrap();
+
+        let mut column_types = HashMap::new();
+        column_types.insert("during".to_string(), "tstzrange");
+        column_types.insert("id".to_string(), "int4");
+        column_types.insert("tags".to_string(), "_text");
+
+        let (where_str, prepared_values) =
+            TypedColumnValue::generate_prepared_statement_from_ast_expr(
+                &where_ast,
+                "a_table",
+                &column_types,
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(
+            where_str,
+            "a_table.during @> $1::timestamptz AND a_table.during && $2::tstzrange AND a_table.id <@ $3::int4multirange AND a_table.tags @> '{a}'"
+        );
+        assert_eq!(prepared_values.len(), 3);
+        match &prepared_values[1] {
+            TypedColumnValue::Range(IsNullColumnValue::NotNullable(PgRange::Nonempty(
+                PgRangeBound::Inclusive(_),
+                PgRangeBound::Unbounded,
+            ))) => (),
+            _ => panic!("Expected a range with an unbounded upper bound."),
+        }
+    }
+}
+
+#[cfg(test)]
+mod hstore_and_bit_string_tests {
+    use super::*;
+    use crate::queries::utils::conditions_params_to_ast;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn bit_string() {
+        let bit_string = BitString::from_str("0101100101").unwrap();
+        assert_eq!(bit_string.len, 10);
+        assert_eq!(bit_string.bytes, vec![0b0101_1001, 0b0100_0000]);
+        assert_eq!(bit_string.to_string(), "0101100101");
+
+        assert!(BitString::from_str("0121").is_err());
+    }
+
+    #[test]
+    fn hstore_literal() {
+        let hstore = Hstore::from_str(r#"a=>1, "b c" => "d \"e\"", f=>NULL, "g"=>"NULL""#).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&hstore).unwrap(),
+            json!({"a": "1", "b c": "d \"e\"", "f": null, "g": "NULL"})
+        );
+        assert_eq!(Hstore::from_str("  ").unwrap(), Hstore(HashMap::new()));
+
+        assert!(Hstore::from_str("a=>").is_err());
+        assert!(Hstore::from_str("a=1").is_err());
+        assert!(Hstore::from_str("a=>1 b=>2").is_err());
+    }
+
+    #[test]
+    fn from_json() {
+        let value = TypedColumnValue::from_json("hstore", &json!({"a": "1", "b": null})).unwrap();
+        assert_eq!(
+            serde_json::to_value(&value).unwrap(),
+            json!({"a": "1", "b": null})
+        );
+        assert!(TypedColumnValue::from_json("hstore", &json!({"a": 1})).is_err());
+
+        let value = TypedColumnValue::from_json("varbit", &json!("101")).unwrap();
+        assert_eq!(serde_json::to_value(&value).unwrap(), json!("101"));
+        assert!(TypedColumnValue::from_json("bit", &json!(101)).is_err());
+    }
+
+    #[test]
+    fn arrays() {
+        let value = TypedColumnValue::from_json("_hstore", &json!([{"a": "1"}, null, {}])).unwrap();
+        assert_eq!(
+            serde_json::to_value(&value).unwrap(),
+            json!([{"a": "1"}, null, {}])
+        );
+        assert!(TypedColumnValue::from_json("_hstore", &json!([{"a": 1}])).is_err());
+
+        let value =
+            TypedColumnValue::from_json("_bit", &json!([["01", "10"], ["11", null]])).unwrap();
+        match &value {
+            TypedColumnValue::Array(IsNullColumnValue::NotNullable(array)) => {
+                assert_eq!(array.dimensions, vec![2, 2]);
+                assert_eq!(
+                    array.elements[3],
+                    TypedColumnValue::Bit(IsNullColumnValue::Nullable(None))
+                );
+            }
+            _ => panic!("Expected an array."),
+        }
+        assert_eq!(
+            serde_json::to_value(&value).unwrap(),
+            json!([["01", "10"], ["11", null]])
+        );
+
+        let value = TypedColumnValue::from_json("_varbit", &json!(["1", "10110"])).unwrap();
+        assert_eq!(serde_json::to_value(&value).unwrap(), json!(["1", "10110"]));
+        assert!(TypedColumnValue::from_json("_varbit", &json!(["12"])).is_err());
+    }
+
+    #[test]
+    fn where_hstore_and_bit_strings() {
+        let where_ast = conditions_params_to_ast(&Some(
+            "tags ? 'sigil' AND tags @> 'color=>red' AND flags = B'0101'".to_string(),
+        ))
+        .unwrap();
+
+        let mut column_types = HashMap::new();
+        column_types.insert("tags".to_string(), "hstore");
+        column_types.insert("flags".to_string(), "bit");
+
+        let (where_str, prepared_values) =
+            TypedColumnValue::generate_prepared_statement_from_ast_expr(
+                &where_ast,
+                "a_table",
+                &column_types,
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(
+            where_str,
+            "a_table.tags ? $1::text AND a_table.tags @> $2::hstore AND a_table.flags = $3"
+        );
+        assert_eq!(prepared_values.len(), 3);
+        match &prepared_values[2] {
+            TypedColumnValue::Bit(IsNullColumnValue::NotNullable(bit_string)) => {
+                assert_eq!(bit_string.to_string(), "0101")
+            }
+            _ => panic!("Expected a bit string."),
+        }
+    }
+
+    #[test]
+    fn mismatched_literal_kinds() {
+        for column_type in &["bit", "varbit", "hstore"] {
+            match TypedColumnValue::from_parsed_sql_value(column_type, ParsedSQLValue::Int8(101)) {
+                Err(Error::UserError { code, .. }) => assert_eq!(code, "INVALID_SQL_VALUE"),
+                _ => panic!("A number should not be converted to {}.", column_type),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod to_text_tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn to_text() {
+        let text = |value: TypedColumnValue| value.to_text().unwrap();
+
+        assert_eq!(
+            text(TypedColumnValue::Text(IsNullColumnValue::NotNullable(
+                "a, \"b\"".to_string()
+            ))),
+            Some("a, \"b\"".to_string())
+        );
+        assert_eq!(
+            text(TypedColumnValue::Text(IsNullColumnValue::NotNullable(
+                "".to_string()
+            ))),
+            Some("".to_string())
+        );
+        assert_eq!(
+            text(TypedColumnValue::Text(IsNullColumnValue::Nullable(None))),
+            None
+        );
+        assert_eq!(
+            text(TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(42))),
+            Some("42".to_string())
+        );
+        assert_eq!(
+            text(TypedColumnValue::Bool(IsNullColumnValue::Nullable(Some(
+                true
+            )))),
+            Some("true".to_string())
+        );
+        assert_eq!(
+            text(TypedColumnValue::ByteA(IsNullColumnValue::NotNullable(
+                vec![0, 10, 255]
+            ))),
+            Some("\\x000aff".to_string())
+        );
+        assert_eq!(
+            text(TypedColumnValue::TimestampTz(
+                IsNullColumnValue::NotNullable(DateTime::from_utc(
+                    NaiveDate::from_ymd(2019, 1, 2).and_hms_milli(3, 4, 5, 600),
+                    Utc
+                ))
+            )),
+            Some("2019-01-02T03:04:05.600Z".to_string())
+        );
+        assert_eq!(
+            text(TypedColumnValue::JsonB(IsNullColumnValue::NotNullable(
+                json!({"a": [1, "b"]})
+            ))),
+            Some(r#"{"a":[1,"b"]}"#.to_string())
+        );
+        // a JSON string keeps its quotes, and JSON `null` isn't NULL
+        assert_eq!(
+            text(TypedColumnValue::Json(IsNullColumnValue::NotNullable(
+                json!("a")
+            ))),
+            Some(r#""a""#.to_string())
+        );
+        assert_eq!(
+            text(TypedColumnValue::Json(IsNullColumnValue::NotNullable(
+                json!(null)
+            ))),
+            Some("null".to_string())
+        );
+        assert_eq!(
+            text(TypedColumnValue::Json(IsNullColumnValue::Nullable(None))),
+            None
+        );
+    }
+}
+
+#[cfg(all(test, feature = "postgis"))]
+mod postgis_where_tests {
+    use super::*;
+    use crate::queries::utils::conditions_params_to_ast;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn geojson_function_args_and_operators() {
+        let where_ast = conditions_params_to_ast(&Some(
+            r#"ST_DWithin(location, '{"type": "Point", "coordinates": [1, 2]}', 1000) AND boundary && '{"type": "Point", "coordinates": [1, 2]}' AND ST_Intersects(boundary, 'POINT(1 2)')"#
+                .to_string(),
+        ))
+        .unwrap();
+
+        let mut column_types = HashMap::new();
+        column_types.insert("location".to_string(), "geography");
+        column_types.insert("boundary".to_string(), "geometry");
+
+        let (where_str, prepared_values) =
+            TypedColumnValue::generate_prepared_statement_from_ast_expr(
+                &where_ast,
+                "a_table",
+                &column_types,
+                None,
+            )
+            .unwrap();
+
+        assert_eq!(
+            where_str,
+            "ST_DWithin(location, '0101000020E6100000000000000000F03F0000000000000040', 1000) AND a_table.boundary && $1::geometry AND ST_Intersects(boundary, 'POINT(1 2)')"
+        );
+        assert_eq!(prepared_values.len(), 1);
+    }
+
+    #[test]
+    fn invalid_geojson_function_arg() {
+        let where_ast = conditions_params_to_ast(&Some(
+            "ST_Intersects(boundary, '{\"type\": \"Circle\"}')".to_string(),
+        ))
+        .unwrap();
+
+        assert!(TypedColumnValue::generate_prepared_statement_from_ast_expr(
+            &where_ast,
+            "a_table",
+            &HashMap::new(),
+            None,
+        )
+        .is_err());
+    }
+}
+
+#[cfg(test)]
+mod binary_serialization_tests {
+    use super::*;
+    use crate::encoding::{to_vec, BinaryFormat};
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    #[test]
+    fn bytea() {
+        let bytea = TypedColumnValue::ByteA(IsNullColumnValue::NotNullable(vec![0, 10]));
+
+        assert_eq!(serde_json::to_value(&bytea).unwrap(), json!([0, 10]));
+        assert_eq!(
+            to_vec(&bytea, BinaryFormat::Cbor).unwrap(),
+            vec![0x42, 0, 10]
+        );
+        assert_eq!(
+            to_vec(&bytea, BinaryFormat::MessagePack).unwrap(),
+            vec![0xc4, 2, 0, 10]
+        );
+    }
+
+    #[test]
+    fn decimal() {
+        let decimal =
+            TypedColumnValue::Decimal(IsNullColumnValue::Nullable(Some("100.01".parse().unwrap())));
+
+        assert_eq!(serde_json::to_value(&decimal).unwrap(), json!("100.01"));
+        // tag 4, [-2, 10001]
+        assert_eq!(
+            to_vec(&decimal, BinaryFormat::Cbor).unwrap(),
+            vec![0xc4, 0x82, 0x21, 0x19, 0x27, 0x11]
+        );
+        assert_eq!(
+            to_vec(&decimal, BinaryFormat::MessagePack).unwrap(),
+            b"\xa6100.01".to_vec()
+        );
+
+        let null_decimal = TypedColumnValue::Decimal(IsNullColumnValue::Nullable(None));
+        assert_eq!(
+            to_vec(&null_decimal, BinaryFormat::Cbor).unwrap(),
+            vec![0xf6]
+        );
+    }
+
+    #[test]
+    fn timestamptz() {
+        let timestamp = TypedColumnValue::TimestampTz(IsNullColumnValue::NotNullable(
+            Utc.ymd(2019, 1, 1).and_hms(0, 0, 0),
+        ));
+
+        assert_eq!(
+            serde_json::to_value(&timestamp).unwrap(),
+            json!("2019-01-01T00:00:00Z")
+        );
+
+        // tag 0, "2019-01-01T00:00:00Z"
+        let mut cbor = vec![0xc0, 0x74];
+        cbor.extend_from_slice(b"2019-01-01T00:00:00Z");
+        assert_eq!(to_vec(&timestamp, BinaryFormat::Cbor).unwrap(), cbor);
+
+        // ext 8 (12 bytes), type -1, nanoseconds, seconds
+        let mut msgpack = vec![0xc7, 12, 0xff, 0, 0, 0, 0];
+        msgpack.extend_from_slice(&1_546_300_800i64.to_be_bytes());
+        assert_eq!(
+            to_vec(&timestamp, BinaryFormat::MessagePack).unwrap(),
+            msgpack
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use super::select_table_stats::{select_table_stats, TableStats};
+use crate::{Config, Error};
+use futures::future::Future;
+use tokio_postgres::{tls::MakeTlsConnect, Socket};
+
+/// The values of a composite primary key are separated by this delimiter (`/table/1,2`), in the
+/// order of the primary key’s columns. Values containing the delimiter percent-encode it (`%2C`).
+pub static PRIMARY_KEY_DELIMITER: char = ',';
+
+/// Describes how to find a single table row by its primary key.
+#[derive(Debug, PartialEq)]
+pub struct PrimaryKeyConditions {
+    /// Every column of the table, for when no columns are requested.
+    pub columns: Vec<String>,
+    /// The WHERE clause matching the primary key columns to their values.
+    pub conditions: String,
+}
+
+/// Generates the WHERE clause that finds the row of `table` whose primary key is `pk_str`.
+/// Composite primary keys are given as values separated by `PRIMARY_KEY_DELIMITER`. Each value is
+/// percent-decoded after splitting, so `pk_str` should be taken from the URL as-is.
+pub fn primary_key_conditions<T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static>(
+    config: &Config<T>,
+    table: String,
+    pk_str: String,
+) -> impl Future<Item = PrimaryKeyConditions, Error = Error> {
+    select_table_stats(config, table.clone()).and_then(move |stats| {
+        let key_columns = primary_key_columns(&table, &stats)?;
+
+        Ok(PrimaryKeyConditions {
+            columns: stats
+                .columns
+                .iter()
+                .map(|stat| stat.column_name.clone())
+                .collect(),
+            conditions: conditions_from_primary_key(&key_columns, &pk_str)?,
+        })
+    })
+}
+
+/// Returns the name and type of each primary key column.
+fn primary_key_columns<'a>(
+    table: &str,
+    stats: &'a TableStats,
+) -> Result<Vec<(&'a str, &'static str)>, Error> {
+    let primary_key = match &stats.primary_key {
+        Some(primary_key) if !primary_key.is_empty() => primary_key,
+        _ => {
+            return Err(Error::generate_error(
+                "PRIMARY_KEY_NOT_FOUND",
+                table.to_string(),
+            ))
+        }
+    };
+
+    primary_key
+        .iter()
+        .map(|column| {
+            match stats
+                .columns
+                .iter()
+                .find(|stat| &stat.column_name == column)
+            {
+                Some(stat) => Ok((column.as_str(), stat.column_type)),
+                None => Err(Error::generate_error(
+                    "TABLE_COLUMN_TYPE_NOT_FOUND",
+                    column.to_string(),
+                )),
+            }
+        })
+        .collect()
+}
+
+/// Decodes the percent-encoded bytes (`%2C`) of a primary key value.
+fn percent_decode(value: &str) -> Result<String, Error> {
+    let invalid_encoding_error = || {
+        Error::generate_error(
+            "INVALID_PRIMARY_KEY",
+            format!("`{}` is not a valid percent-encoded value.", value),
+        )
+    };
+
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut encoded_bytes = value.bytes();
+    while let Some(byte) = encoded_bytes.next() {
+        if byte != b'%' {
+            bytes.push(byte);
+            continue;
+        }
+
+        let hex_digits = [
+            encoded_bytes.next().ok_or_else(invalid_encoding_error)?,
+            encoded_bytes.next().ok_or_else(invalid_encoding_error)?,
+        ];
+        let hex_str = std::str::from_utf8(&hex_digits).map_err(|_| invalid_encoding_error())?;
+        bytes.push(u8::from_str_radix(hex_str, 16).map_err(|_| invalid_encoding_error())?);
+    }
+
+    // null characters can’t be sent in a query
+    if bytes.contains(&0) {
+        return Err(invalid_encoding_error());
+    }
+
+    String::from_utf8(bytes).map_err(|_| invalid_encoding_error())
+}
+
+/// Matches each primary key column to its value. Numbers and booleans are checked and used as-is;
+/// other values are quoted, and converted to the column’s type along with the rest of the WHERE
+/// clause.
+fn conditions_from_primary_key(
+    key_columns: &[(&str, &str)],
+    pk_str: &str,
+) -> Result<String, Error> {
+    let values = pk_str
+        .split(PRIMARY_KEY_DELIMITER)
+        .map(percent_decode)
+        .collect::<Result<Vec<String>, Error>>()?;
+    if values.len() != key_columns.len() {
+        return Err(Error::generate_error(
+            "INVALID_PRIMARY_KEY",
+            format!(
+                "`{}` has {} value(s), but the primary key has {} column(s).",
+                pk_str,
+                values.len(),
+                key_columns.len()
+            ),
+        ));
+    }
+
+    let conditions = key_columns
+        .iter()
+        .zip(values)
+        .map(|(&(column, column_type), value)| {
+            let invalid_value_error = || {
+                Error::generate_error(
+                    "INVALID_PRIMARY_KEY",
+                    format!(
+                        "`{}` is not a valid `{}` value for `{}`.",
+                        value, column_type, column
+                    ),
+                )
+            };
+
+            let value_str = match column_type {
+                "int2" | "int4" | "int8" | "oid" => value
+                    .parse::<i64>()
+                    .map_err(|_| invalid_value_error())?
+                    .to_string(),
+                "float4" | "float8" => match value.parse::<f64>() {
+                    // keep the decimal point, so that the value is parsed as a float
+                    Ok(float) if float.is_finite() => format!("{:?}", float),
+                    _ => return Err(invalid_value_error()),
+                },
+                "bool" => value
+                    .parse::<bool>()
+                    .map_err(|_| invalid_value_error())?
+                    .to_string(),
+                _ => ["'", &value.replace('\'', "''"), "'"].join(""),
+            };
+
+            Ok([column, " = ", &value_str].join(""))
+        })
+        .collect::<Result<Vec<String>, Error>>()?;
+
+    Ok(conditions.join(" AND "))
+}
+
+#[cfg(test)]
+mod conditions_from_primary_key_tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn single_column() {
+        assert_eq!(
+            conditions_from_primary_key(&[("id", "int8")], "42").unwrap(),
+            "id = 42"
+        );
+    }
+
+    #[test]
+    fn composite() {
+        assert_eq!(
+            conditions_from_primary_key(&[("tenant_id", "int4"), ("id", "int4")], "1,2").unwrap(),
+            "tenant_id = 1 AND id = 2"
+        );
+    }
+
+    #[test]
+    fn quoted_values() {
+        assert_eq!(
+            conditions_from_primary_key(
+                &[("name", "text"), ("is_active", "bool"), ("score", "float8")],
+                "o'brien,true,3"
+            )
+            .unwrap(),
+            "name = 'o''brien' AND is_active = true AND score = 3.0"
+        );
+    }
+
+    #[test]
+    fn percent_encoded_values() {
+        assert_eq!(
+            conditions_from_primary_key(&[("name", "text"), ("id", "int4")], "Stark%2C%20Inc.,2")
+                .unwrap(),
+            "name = 'Stark, Inc.' AND id = 2"
+        );
+        assert_eq!(
+            conditions_from_primary_key(&[("name", "text")], "caf%C3%A9%2F100%25").unwrap(),
+            "name = 'café/100%'"
+        );
+        assert!(conditions_from_primary_key(&[("name", "text")], "100%").is_err());
+        assert!(conditions_from_primary_key(&[("name", "text")], "%zz").is_err());
+        assert!(conditions_from_primary_key(&[("name", "text")], "%FF").is_err());
+        assert!(conditions_from_primary_key(&[("name", "text")], "a%00").is_err());
+    }
+
+    #[test]
+    fn wrong_number_of_values() {
+        assert!(
+            conditions_from_primary_key(&[("tenant_id", "int4"), ("id", "int4")], "1").is_err()
+        );
+        assert!(conditions_from_primary_key(&[("id", "int4")], "1,2").is_err());
+    }
+
+    #[test]
+    fn invalid_number() {
+        assert!(conditions_from_primary_key(&[("id", "int8")], "1 OR 1 = 1").is_err());
+        assert!(conditions_from_primary_key(&[("score", "float8")], "NaN").is_err());
+    }
+}
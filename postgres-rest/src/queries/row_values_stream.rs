@@ -0,0 +1,156 @@
+#[cfg(feature = "arrow-export")]
+use super::columnar::RecordBatchStream;
+use super::{
+    column_nesting::ColumnNesting,
+    postgres_types::{row_to_row_values, RowValues},
+};
+use crate::{Error, PooledClient};
+use futures::{stream::Stream, Async, Poll};
+use std::vec::IntoIter;
+#[cfg(feature = "arrow-export")]
+use tokio_postgres::types::Type;
+use tokio_postgres::{impls::Query, Statement};
+
+/// A stream of query results, converted into `RowValues` one row at a time as they arrive from the
+/// database (instead of collecting the whole result set in memory first).
+///
+/// The stream holds on to its database connection, which is returned to the connection pool once
+/// the stream is dropped. If the query fails after some rows have already been yielded, the stream
+/// ends with the error.
+pub struct RowValuesStream {
+    columns: Vec<String>,
+    /// How foreign key columns are nested into objects, if requested.
+    nesting: Option<ColumnNesting>,
+    rows: RowsSource,
+    /// The total number of rows matching the query (ignoring `LIMIT` and `OFFSET`), if counted.
+    total_count: Option<i64>,
+    /// The cursor of the next page of rows, if paginated with a cursor.
+    next_cursor: Option<String>,
+}
+
+enum RowsSource {
+    Query {
+        rows: Query,
+        _client: PooledClient,
+        #[cfg(feature = "arrow-export")]
+        column_types: Vec<Type>,
+    },
+    /// Rows that have already been retrieved (like the `RETURNING` rows of batched INSERTs).
+    Buffered(IntoIter<RowValues>),
+}
+
+impl RowValuesStream {
+    pub(crate) fn new(statement: &Statement, rows: Query, client: PooledClient) -> Self {
+        RowValuesStream {
+            columns: column_names(statement),
+            nesting: None,
+            rows: RowsSource::Query {
+                rows,
+                _client: client,
+                #[cfg(feature = "arrow-export")]
+                column_types: statement
+                    .columns()
+                    .iter()
+                    .map(|column| column.type_().clone())
+                    .collect(),
+            },
+            total_count: None,
+            next_cursor: None,
+        }
+    }
+
+    pub(crate) fn from_rows(columns: Vec<String>, rows: Vec<RowValues>) -> Self {
+        RowValuesStream {
+            columns,
+            nesting: None,
+            rows: RowsSource::Buffered(rows.into_iter()),
+            total_count: None,
+            next_cursor: None,
+        }
+    }
+
+    pub(crate) fn with_nesting(mut self, nesting: Option<ColumnNesting>) -> Self {
+        self.nesting = nesting;
+        self
+    }
+
+    pub(crate) fn with_total_count(mut self, total_count: Option<i64>) -> Self {
+        self.total_count = total_count;
+        self
+    }
+
+    pub(crate) fn with_next_cursor(mut self, next_cursor: Option<String>) -> Self {
+        self.next_cursor = next_cursor;
+        self
+    }
+
+    /// The names of the returned columns, in the order they were selected.
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// How foreign key columns should be nested into objects when rows are serialized, if
+    /// requested (`None` means rows are flat).
+    pub fn nesting(&self) -> Option<&ColumnNesting> {
+        self.nesting.as_ref()
+    }
+
+    /// The total number of rows matching the query, ignoring `LIMIT` and `OFFSET` (`None` if the
+    /// rows weren’t counted).
+    pub fn total_count(&self) -> Option<i64> {
+        self.total_count
+    }
+
+    /// The cursor that continues after the returned rows, if they were paginated with a cursor.
+    /// `None` if there are no more rows.
+    pub fn next_cursor(&self) -> Option<&str> {
+        self.next_cursor.as_ref().map(String::as_str)
+    }
+
+    /// Converts the (not yet consumed) rows into a stream of Arrow record batches. Rows that have
+    /// already been retrieved (like the `RETURNING` rows of INSERTs) can’t be converted.
+    #[cfg(feature = "arrow-export")]
+    pub fn into_record_batches(self) -> Result<RecordBatchStream, Error> {
+        match self.rows {
+            RowsSource::Query {
+                rows,
+                _client: client,
+                column_types,
+            } => Ok(RecordBatchStream::new(
+                &self.columns,
+                &column_types,
+                rows,
+                client,
+            )),
+            RowsSource::Buffered(_) => Err(Error::generate_error(
+                "UNSUPPORTED_RESPONSE_FORMAT",
+                "Arrow/Parquet".to_string(),
+            )),
+        }
+    }
+}
+
+impl Stream for RowValuesStream {
+    type Item = RowValues;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Option<RowValues>, Error> {
+        match &mut self.rows {
+            RowsSource::Query { rows, .. } => match rows.poll().map_err(Error::from)? {
+                Async::Ready(Some(row)) => Ok(Async::Ready(Some(row_to_row_values(&row)?))),
+                Async::Ready(None) => Ok(Async::Ready(None)),
+                Async::NotReady => Ok(Async::NotReady),
+            },
+            RowsSource::Buffered(rows) => Ok(Async::Ready(rows.next())),
+        }
+    }
+}
+
+/// Returns the names of the columns returned by a prepared statement.
+pub(crate) fn column_names(statement: &Statement) -> Vec<String> {
+    statement
+        .columns()
+        .iter()
+        .map(|column| column.name().to_string())
+        .collect()
+}
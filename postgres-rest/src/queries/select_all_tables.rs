@@ -1,16 +1,33 @@
-use crate::error::Error;
-use future::Future;
-use futures::{future, stream::Stream};
-use tokio_postgres::Client;
+use super::utils::validate_table_name;
+use crate::{error::Error, PooledClient};
+use futures::{
+    future::{err, Either, Future},
+    stream::Stream,
+};
 
-/// Retrieves all user-created table names.
+/// Retrieves all user-created table names in the given schemas. Tables outside of the `public`
+/// schema are prefixed with their schema name (`schema.table`).
 pub fn select_all_tables(
-    mut client: Client,
-) -> impl Future<Item = (Vec<String>, Client), Error = Error> {
-    let statement_str = "SELECT DISTINCT table_name FROM information_schema.columns WHERE table_schema='public' ORDER BY table_name;";
+    mut client: PooledClient,
+    schemas: &[String],
+) -> impl Future<Item = (Vec<String>, PooledClient), Error = Error> {
+    let mut schemas_str_vec = vec![];
+    for schema in schemas {
+        if let Err(e) = validate_table_name(schema) {
+            return Either::A(err(e));
+        }
+        schemas_str_vec.push(["'", schema, "'"].join(""));
+    }
 
-    client
-        .prepare(statement_str)
+    let statement_str = [
+        "SELECT DISTINCT CASE WHEN table_schema = 'public' THEN table_name::text ELSE table_schema || '.' || table_name END AS table_name FROM information_schema.columns WHERE table_schema IN (",
+        &schemas_str_vec.join(", "),
+        ") ORDER BY table_name;",
+    ]
+    .join("");
+
+    let select_future = client
+        .prepare(&statement_str)
         .map_err(Error::from)
         .and_then(move |statement| {
             client
@@ -19,5 +36,7 @@ pub fn select_all_tables(
                 .map(|row| row.get(0))
                 .collect()
                 .map(move |tables| (tables, client))
-        })
+        });
+
+    Either::B(select_future)
 }
@@ -1,29 +1,119 @@
 use futures::{
-    future::{err, Either, Future},
+    future::{err, ok, Either, Future},
     stream::Stream,
 };
-use itertools::Itertools;
-use lazy_static::lazy_static;
 use rayon::prelude::*;
-use regex::Regex;
 use sqlparser::ast::Expr;
 use std::sync::Arc;
 use tokio_postgres::{
+    row::Row,
     tls::{MakeTlsConnect, TlsConnect},
     types::ToSql,
     Socket,
 };
 
 use super::{
-    foreign_keys::{fk_columns_from_where_ast, ForeignKeyReference},
-    postgres_types::{row_to_row_values, RowValues, TypedColumnValue},
-    select_table_stats::{select_column_stats, select_column_stats_statement, TableColumnStat},
+    column_nesting::ColumnNesting,
+    embedded_rows::{select_embedded_rows_stats, EmbeddedRows, EmbeddedRowsStats},
+    foreign_keys::{fk_columns_from_where_ast, ForeignKeyReference, JoinType},
+    keyset::Keyset,
+    many_to_many::{select_many_to_many_references, ManyToManyReference},
+    postgres_types::{IsNullColumnValue, TypedColumnValue},
+    select_table_stats::{
+        select_column_stats, select_column_stats_statement, select_table_stats, TableColumnStat,
+    },
     utils::{
-        conditions_params_to_ast, get_columns_str, get_where_string, validate_alias_identifier,
-        validate_table_name, validate_where_column,
+        conditions_params_to_ast, get_columns_str, get_db_column_str, get_order_by_str,
+        get_where_string, split_table_name, validate_alias_identifier, validate_table_name,
+        TableStatement,
     },
+    RowValuesStream,
 };
-use crate::{stats_cache::get_stats_cache_addr, Config, Error};
+use crate::{stats_cache::get_stats_cache_addr, Config, Error, PooledClient};
+
+/// How the total number of rows matching a SELECT query (ignoring `limit` and `offset`) is counted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CountMode {
+    /// Counts the matching rows with `COUNT(*)`. Accurate, but can be slow for large tables.
+    Exact,
+    /// Uses the number of rows that the query planner expects the query to return (`EXPLAIN`).
+    /// Takes the WHERE clause into account, but can be far off.
+    Planned,
+    /// Uses Postgres’ estimate of the number of rows in the whole table (`pg_class.reltuples`),
+    /// ignoring the WHERE clause. The fastest option.
+    Estimated,
+}
+
+impl CountMode {
+    /// Generates the statement that counts the rows returned by `statement` (a SELECT statement
+    /// without `LIMIT`, `OFFSET` or `ORDER BY`, whose prepared values are `prepared_values`). Only
+    /// `Estimated` ignores `statement`; its only prepared value is the quoted, schema-qualified
+    /// table name (so that it doesn’t depend on `search_path`).
+    fn count_statement(
+        self,
+        statement: &str,
+        prepared_values: &[TypedColumnValue],
+        table: &str,
+    ) -> CountStatement {
+        let (statement, prepared_values) = match self {
+            CountMode::Exact => (
+                ["SELECT COUNT(*) FROM (", statement, ") AS counted_rows;"].join(""),
+                prepared_values.to_vec(),
+            ),
+            CountMode::Planned => (
+                ["EXPLAIN (FORMAT JSON) ", statement, ";"].join(""),
+                prepared_values.to_vec(),
+            ),
+            // the table name is cast to `text` first, as strings can’t be sent as `regclass`
+            CountMode::Estimated => {
+                let (schema, table) = split_table_name(table);
+
+                (
+                    "SELECT GREATEST(c.reltuples, 0)::BIGINT FROM pg_class c WHERE c.oid = $1::text::regclass;".to_string(),
+                    vec![TypedColumnValue::Text(IsNullColumnValue::NotNullable(
+                        ["\"", schema, "\".\"", table, "\""].join(""),
+                    ))],
+                )
+            }
+        };
+
+        CountStatement {
+            count_mode: self,
+            statement,
+            prepared_values,
+        }
+    }
+
+    /// Reads the total number of rows from the result of the count statement.
+    fn total_count_from_row(self, row_opt: Option<Row>) -> Result<i64, Error> {
+        let row = match row_opt {
+            Some(row) => row,
+            None => return Ok(0),
+        };
+
+        match self {
+            CountMode::Exact | CountMode::Estimated => Ok(row.get(0)),
+            CountMode::Planned => {
+                let plan: serde_json::Value = row.get(0);
+                match plan[0]["Plan"]["Plan Rows"].as_f64() {
+                    Some(plan_rows) => Ok(plan_rows as i64),
+                    None => Err(Error::generate_error(
+                        "INVALID_QUERY_PLAN",
+                        plan.to_string(),
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// The statement that counts the rows matching a SELECT query, along with its own prepared values.
+#[derive(Debug, PartialEq)]
+pub(crate) struct CountStatement {
+    count_mode: CountMode,
+    statement: String,
+    prepared_values: Vec<TypedColumnValue>,
+}
 
 #[derive(Debug)]
 /// Options used to execute a SELECT query.
@@ -36,13 +126,73 @@ pub struct SelectParams {
     pub order_by: Option<Vec<String>>,
     pub limit: usize,
     pub offset: usize,
+    /// Whether foreign key columns (`parent_id.name`) are returned as nested objects.
+    pub is_nested: bool,
+    /// Rows of other tables referencing the selected table, returned as JSON arrays (after the
+    /// other columns).
+    pub embedded_rows: Vec<EmbeddedRows>,
+    /// How the tables of foreign key columns are joined. If `None`, nullable foreign key columns
+    /// use LEFT JOIN and the others use INNER JOIN.
+    pub join_type: Option<JoinType>,
+    /// If set, the total number of matching rows is counted, and available through the returned
+    /// stream’s `total_count()`. With a cursor, the rows before the cursor aren’t counted.
+    pub count: Option<CountMode>,
+    /// If set, rows are paginated with a cursor (keyset pagination) instead of `offset`: rows are
+    /// sorted by `order_by` and the primary key, and only the rows after the cursor are returned.
+    /// The cursor of the next page is available through the returned stream’s `next_cursor()`. An
+    /// empty cursor starts from the first row.
+    pub cursor: Option<String>,
 }
 
-/// Returns the results of a `SELECT /*..*/ FROM {TABLE}` query.
+/// Returns the results of a `SELECT /*..*/ FROM {TABLE}` query, as a stream of rows. If a count is
+/// requested, the rows are counted before the stream is returned. If a cursor is given, the rows of
+/// the page are retrieved before the stream is returned (so that the next cursor is known).
 pub fn select_table_rows<T>(
     config: Config<T>,
     params: SelectParams,
-) -> impl Future<Item = Vec<RowValues>, Error = Error>
+) -> impl Future<Item = RowValuesStream, Error = Error>
+where
+    <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
+    <T as MakeTlsConnect<Socket>>::Stream: Send,
+    <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    let config_clone = config.clone();
+    select_statement(config, params).and_then(move |(statement, count, keyset)| {
+        let TableStatement {
+            statement,
+            prepared_values,
+            nesting,
+            ..
+        } = statement;
+
+        // sending prepared statement to postgres
+        config_clone
+            .connect()
+            .map_err(Error::from)
+            .and_then(move |conn| query_rows(conn, statement, count, prepared_values))
+            .and_then(move |rows| match keyset {
+                Some((keyset, limit)) => Either::A(keyset.paginate(rows, limit)),
+                None => Either::B(ok(rows)),
+            })
+            .map(move |rows| rows.with_nesting(nesting))
+    })
+}
+
+/// Generates the SELECT statement (looking up the table’s column stats and foreign keys), without
+/// executing it. Also returns the count statement if a count is requested, and the keyset (along
+/// with the page size) if rows are paginated with a cursor.
+pub(crate) fn select_statement<T>(
+    config: Config<T>,
+    params: SelectParams,
+) -> impl Future<
+    Item = (
+        TableStatement,
+        Option<CountStatement>,
+        Option<(Keyset, usize)>,
+    ),
+    Error = Error,
+>
 where
     <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
     <T as MakeTlsConnect<Socket>>::Stream: Send,
@@ -52,6 +202,15 @@ where
     if let Err(e) = validate_table_name(&params.table) {
         return Either::A(err(e));
     }
+    if let (Some(cursor), true) = (
+        &params.cursor,
+        params.offset > 0 || params.group_by.is_some(),
+    ) {
+        return Either::A(err(Error::generate_error(
+            "INVALID_CURSOR",
+            cursor.to_string(),
+        )));
+    }
 
     // get list of every column being used in the query params (columns, where, distinct, group_by,
     // order_by). Used for finding all foreign key references
@@ -103,61 +262,151 @@ where
                 })
         });
 
+    // columns going through junction tables (like `tags.name`) can only be told apart from foreign
+    // key references once the table’s columns are known
+    let config_clone_2 = config.clone();
+    let table_clone_2 = params.table.clone();
+    let columns_clone = params.columns.clone();
+    let stats_future = stats_future.and_then(move |stats| {
+        select_many_to_many_references(&config_clone_2, &table_clone_2, &columns_clone, &stats)
+            .map(move |many_to_many| (stats, many_to_many))
+    });
+
+    // foreign keys referencing the table + column stats of the tables whose rows are embedded
+    let embedded_rows_stats_future =
+        select_embedded_rows_stats(&config, &params.table, &params.embedded_rows);
+
+    // the primary key is needed to sort rows that are paginated with a cursor
+    let primary_key_future = if params.cursor.is_some() {
+        Either::A(
+            select_table_stats(&config, params.table.clone())
+                .map(|stats| stats.primary_key.unwrap_or_default()),
+        )
+    } else {
+        Either::B(ok(vec![]))
+    };
+
     // parse columns for foreign key usage
-    let config_clone = config.clone();
     let fk_future = ForeignKeyReference::from_query_columns(
         config,
         Arc::new(get_stats_cache_addr()),
         params.table.clone(),
         columns,
     )
-    .join(stats_future)
-    .and_then(move |(fk_columns, stats)| {
-        let (statement_str, prepared_values) =
-            match build_select_statement(params, stats, fk_columns, where_ast) {
-                Ok((stmt, prep_vals)) => (stmt, prep_vals),
-                Err(e) => return Either::A(err(e)),
+    .join4(stats_future, embedded_rows_stats_future, primary_key_future)
+    .and_then(
+        move |(fk_columns, (stats, many_to_many), embedded_rows_stats, primary_key)| {
+            let nesting = if params.is_nested {
+                // embedded rows are returned under their keys, like other (non-nested) columns
+                let mut nesting_columns = params.columns.clone();
+                nesting_columns.extend(
+                    params
+                        .embedded_rows
+                        .iter()
+                        .map(|embedded_rows| embedded_rows.key().to_string()),
+                );
+
+                Some(ColumnNesting::new(
+                    &nesting_columns,
+                    &params.table,
+                    &fk_columns,
+                )?)
+            } else {
+                None
             };
 
-        // sending prepared statement to postgres
-        let select_rows_future = config_clone
-            .connect()
-            .map_err(Error::from)
-            .and_then(move |mut conn| {
-                conn.prepare(&statement_str)
-                    .map_err(Error::from)
-                    .and_then(move |statement| {
-                        let prep_values: Vec<&dyn ToSql> =
-                            prepared_values.iter().map(|v| v as _).collect();
-
-                        conn.query(&statement, &prep_values)
-                            .collect()
-                            .map_err(Error::from)
-                    })
-            })
-            .and_then(|rows| {
-                match rows
-                    .par_iter()
-                    .map(row_to_row_values)
-                    .collect::<Result<Vec<RowValues>, Error>>()
-                {
-                    Ok(row_values) => Ok(row_values),
-                    Err(e) => Err(e),
-                }
-            });
+            let keyset = match &params.cursor {
+                Some(_) => Some(Keyset::new(
+                    params.order_by.as_ref().map_or(&[][..], Vec::as_slice),
+                    &primary_key,
+                    &params.table,
+                    &stats,
+                    &fk_columns,
+                )?),
+                None => None,
+            };
 
-        Either::B(select_rows_future)
-    });
+            let limit = params.limit;
+            let (statement, count, prepared_values) = build_select_statement(
+                params,
+                stats,
+                fk_columns,
+                where_ast,
+                embedded_rows_stats,
+                many_to_many,
+                keyset.as_ref(),
+            )?;
+
+            Ok((
+                TableStatement {
+                    statement,
+                    prepared_values,
+                    is_return_rows: true,
+                    nesting,
+                },
+                count,
+                keyset.map(|keyset| (keyset, limit)),
+            ))
+        },
+    );
 
     Either::B(fk_future)
 }
 
+/// Prepares and executes the SELECT statement. If a count statement is given, the rows are
+/// counted first (the queries are pipelined).
+fn query_rows(
+    mut conn: PooledClient,
+    statement_str: String,
+    count: Option<CountStatement>,
+    prepared_values: Vec<TypedColumnValue>,
+) -> impl Future<Item = RowValuesStream, Error = Error> {
+    let count_statement_future = match &count {
+        Some(count) => Either::A(conn.prepare(&count.statement).map(Some)),
+        None => Either::B(ok(None)),
+    };
+
+    conn.prepare(&statement_str)
+        .join(count_statement_future)
+        .map_err(Error::from)
+        .and_then(move |(statement, count_statement_opt)| {
+            let prep_values: Vec<&dyn ToSql> = prepared_values.iter().map(|v| v as _).collect();
+
+            let total_count_future = match (count, count_statement_opt) {
+                (Some(count), Some(count_statement)) => {
+                    let count_mode = count.count_mode;
+                    let count_values: Vec<&dyn ToSql> =
+                        count.prepared_values.iter().map(|v| v as _).collect();
+
+                    Either::A(
+                        conn.query(&count_statement, &count_values)
+                            .into_future()
+                            .map_err(|(e, _rows)| Error::from(e))
+                            .and_then(move |(row_opt, _rows)| {
+                                count_mode.total_count_from_row(row_opt)
+                            })
+                            .map(Some),
+                    )
+                }
+                _ => Either::B(ok(None)),
+            };
+
+            let rows = conn.query(&statement, &prep_values);
+            total_count_future.map(move |total_count| {
+                RowValuesStream::new(&statement, rows, conn).with_total_count(total_count)
+            })
+        })
+}
+
 fn build_select_statement(
     params: SelectParams,
     stats: Vec<TableColumnStat>,
     fks: Vec<ForeignKeyReference>,
     mut where_ast: Expr,
-) -> Result<(String, Vec<TypedColumnValue>), Error> {
+    embedded_rows_stats: EmbeddedRowsStats,
+    many_to_many: Vec<ManyToManyReference>,
+    keyset: Option<&Keyset>,
+) -> Result<(String, Option<CountStatement>, Vec<TypedColumnValue>), Error> {
     let mut statement = vec!["SELECT "];
     let is_fks_exist = !fks.is_empty();
 
@@ -170,61 +419,77 @@ fn build_select_statement(
         statement.push(") ");
     }
 
-    // building column selection
-    statement.par_extend(get_columns_str(&params.columns, &params.table, &fks)?);
+    // building column selection. Columns going through junction tables are sub-queries
+    let many_to_many_strs: Vec<(&str, String)> = many_to_many
+        .iter()
+        .map(|reference| (reference.column.as_str(), reference.to_sql(&params.table)))
+        .collect();
+    for (i, column) in params.columns.iter().enumerate() {
+        if i > 0 {
+            statement.push(", ");
+        }
+
+        match many_to_many_strs
+            .iter()
+            .find(|(many_to_many_column, _)| many_to_many_column == column)
+        {
+            Some((_, many_to_many_str)) => statement.push(many_to_many_str),
+            None => {
+                statement.par_extend(get_db_column_str(column, &params.table, &fks, true, true)?)
+            }
+        }
+    }
+
+    // embedded rows are sub-queries, whose prepared values come before those of the WHERE clause
+    let mut prepared_param_pos = 1;
+    let mut prepared_values = vec![];
+    let mut embedded_rows_strs = vec![];
+    for (embedded_rows, embedded_table_stats) in params
+        .embedded_rows
+        .iter()
+        .zip(&embedded_rows_stats.columns)
+    {
+        let (embedded_rows_str, embedded_rows_values) = embedded_rows.to_sql(
+            &params.table,
+            &embedded_rows_stats.referenced_by,
+            embedded_table_stats,
+            &mut prepared_param_pos,
+        )?;
+        embedded_rows_strs.push(embedded_rows_str);
+        prepared_values.extend(embedded_rows_values);
+    }
+    for (i, embedded_rows_str) in embedded_rows_strs.iter().enumerate() {
+        if i > 0 || !params.columns.is_empty() {
+            statement.push(", ");
+        }
+        statement.push(embedded_rows_str);
+    }
+
+    // the sort key of rows paginated with a cursor is selected last (and removed from the rows)
+    let keyset_select_str;
+    if let Some(keyset) = keyset {
+        keyset_select_str = keyset.select_str();
+        if !params.columns.is_empty() || !embedded_rows_strs.is_empty() {
+            statement.push(", ");
+        }
+        statement.push(&keyset_select_str);
+    }
 
     statement.push(" FROM ");
     statement.push(&params.table);
 
-    // build inner join expression
-
-    let inner_join_str;
+    // build join expression
+    let join_str;
     if is_fks_exist {
-        // More than 1 fk that has the same referred_table and referring table should first be
-        // joined with "AND"
-        let fk_tables_columns =
-            ForeignKeyReference::fk_tables_columns_from_foreign_key_references(&fks);
-        let table_ref_groups = &fk_tables_columns.into_iter().group_by(
-            |(referring_table, _referring_column, referred_table, _referred_column)| {
-                [*referring_table, *referred_table]
-            },
-        );
-        inner_join_str = table_ref_groups
-            .into_iter()
-            .map(|(key, same_table_ref_group)| {
-                let referred_table = key[1];
-
-                let equals_str = same_table_ref_group
-                    .map(
-                        |(referring_table, referring_column, referred_table, referred_column)| {
-                            [
-                                referring_table,
-                                ".",
-                                referring_column,
-                                " = ",
-                                referred_table,
-                                ".",
-                                referred_column,
-                            ]
-                            .join("")
-                        },
-                    )
-                    .collect::<Vec<String>>()
-                    .join(" AND ");
-
-                [referred_table, " ON ", &equals_str].join("")
-            })
-            .collect::<Vec<String>>()
-            .join("\nINNER JOIN ");
+        join_str = ForeignKeyReference::join_clauses(&fks, params.join_type, &params.table, None);
 
-        statement.push(" INNER JOIN ");
-        statement.push(&inner_join_str);
+        statement.push(" ");
+        statement.push(&join_str);
     }
 
     // building WHERE string
     let (mut where_string, column_types) =
         get_where_string(&mut where_ast, &params.table, &stats, &fks);
-    let mut prepared_values = vec![];
     if &where_string != "" {
         statement.push(" WHERE (");
 
@@ -235,74 +500,54 @@ fn build_select_statement(
                 &where_ast,
                 &params.table,
                 &column_types,
-                None,
+                Some(&mut prepared_param_pos),
             )?;
         where_string = where_string_with_prepared_positions;
-        prepared_values = prepared_values_vec;
+        prepared_values.extend(prepared_values_vec);
 
         statement.push(&where_string);
         statement.push(")");
     }
 
+    // only the rows after the cursor
+    let seek_conditions_str;
+    let seek_conditions = match (keyset, &params.cursor) {
+        (Some(keyset), Some(cursor)) => keyset.seek_conditions(cursor, &mut prepared_param_pos)?,
+        _ => None,
+    };
+    if let Some((conditions_str, seek_prepared_values)) = seek_conditions {
+        seek_conditions_str = conditions_str;
+        prepared_values.extend(seek_prepared_values);
+
+        statement.push(if where_string.is_empty() {
+            " WHERE ("
+        } else {
+            " AND ("
+        });
+        statement.push(&seek_conditions_str);
+        statement.push(")");
+    }
+
     // GROUP BY statement
     if let Some(group_by_columns) = &params.group_by {
         statement.push(" GROUP BY ");
         statement.par_extend(get_columns_str(group_by_columns, &params.table, &fks)?);
     }
 
-    // Append ORDER BY if the param exists
-    if let Some(order_by_columns) = &params.order_by {
-        statement.push(" ORDER BY ");
-
-        lazy_static! {
-            // case-insensitive search for ORDER BY direction
-            static ref ORDER_BY_DIRECTION_RE: Regex = Regex::new(r"(?i) asc| desc").unwrap();
-        }
-
-        for (i, column) in order_by_columns.iter().enumerate() {
-            // using `is_match` first because it's faster than `find`
-            let (sql_column, order_by_direction): (&str, &str) =
-                if ORDER_BY_DIRECTION_RE.is_match(column) {
-                    // separate the column string from the direction string
-                    match ORDER_BY_DIRECTION_RE.find(column) {
-                        Some(order_direction_match) => {
-                            let order_by_column = &column[..order_direction_match.start()];
-                            validate_where_column(order_by_column)?;
-
-                            let order_by_direction = &column[order_direction_match.start()..];
-                            (order_by_column, order_by_direction)
-                        }
-                        None => {
-                            validate_where_column(column)?;
-                            (column, " asc")
-                        }
-                    }
-                } else {
-                    validate_where_column(column)?;
-                    (column, " asc")
-                };
-
-            if let (true, Some((fk_ref, fk_column))) = (
-                is_fks_exist,
-                ForeignKeyReference::find(&fks, &params.table, sql_column),
-            ) {
-                statement.push(fk_ref.foreign_key_table.as_str());
-                statement.push(".");
-                statement.push(fk_column);
-            } else {
-                statement.push(sql_column);
-            }
-
-            statement.push(if order_by_direction == " desc" {
-                " DESC"
-            } else {
-                " ASC"
-            });
+    // the rows are counted without ORDER BY, LIMIT and OFFSET
+    let count = params.count.map(|count_mode| {
+        count_mode.count_statement(&statement.join(""), &prepared_values, &params.table)
+    });
 
-            if i < order_by_columns.len() - 1 {
-                statement.push(", ");
-            }
-        }
+    // Append ORDER BY if the param exists. Rows paginated with a cursor are sorted by their key
+    let keyset_order_by_str;
+    if let Some(keyset) = keyset {
+        keyset_order_by_str = keyset.order_by_str();
+        statement.push(" ORDER BY ");
+        statement.push(&keyset_order_by_str);
+    } else if let Some(order_by_columns) = &params.order_by {
+        statement.push(" ORDER BY ");
+        statement.extend(get_order_by_str(order_by_columns, &params.table, &fks)?);
     }
 
     // LIMIT
@@ -319,13 +564,13 @@ fn build_select_statement(
 
     statement.push(";");
 
-    Ok((statement.join(""), prepared_values))
+    Ok((statement.join(""), count, prepared_values))
 }
 
 #[cfg(test)]
 mod build_select_statement_tests {
     use super::*;
-    use crate::queries::postgres_types::IsNullColumnValue;
+    use crate::queries::select_table_stats::TableReferencedBy;
     use pretty_assertions::assert_eq;
 
     #[test]
@@ -338,14 +583,22 @@ mod build_select_statement_tests {
                 group_by: None,
                 limit: 100,
                 offset: 0,
+                is_nested: false,
+                embedded_rows: vec![],
+                join_type: None,
+                count: None,
+                cursor: None,
                 order_by: None,
                 table: "a_table".to_string(),
             },
             vec![],
             vec![],
             Expr::Identifier("".to_string()),
+            EmbeddedRowsStats::default(),
+            vec![],
+            None,
         ) {
-            Ok((sql, _)) => {
+            Ok((sql, _, _)) => {
                 assert_eq!(&sql, "SELECT id FROM a_table LIMIT 100;");
             }
             Err(e) => {
@@ -364,14 +617,22 @@ mod build_select_statement_tests {
                 group_by: None,
                 limit: 100,
                 offset: 0,
+                is_nested: false,
+                embedded_rows: vec![],
+                join_type: None,
+                count: None,
+                cursor: None,
                 order_by: None,
                 table: "a_table".to_string(),
             },
             vec![],
             vec![],
             Expr::Identifier("".to_string()),
+            EmbeddedRowsStats::default(),
+            vec![],
+            None,
         ) {
-            Ok((sql, _)) => {
+            Ok((sql, _, _)) => {
                 assert_eq!(&sql, "SELECT id, name FROM a_table LIMIT 100;");
             }
             Err(e) => {
@@ -390,14 +651,22 @@ mod build_select_statement_tests {
                 group_by: None,
                 limit: 100,
                 offset: 0,
+                is_nested: false,
+                embedded_rows: vec![],
+                join_type: None,
+                count: None,
+                cursor: None,
                 order_by: None,
                 table: "a_table".to_string(),
             },
             vec![],
             vec![],
             Expr::Identifier("".to_string()),
+            EmbeddedRowsStats::default(),
+            vec![],
+            None,
         ) {
-            Ok((sql, _)) => {
+            Ok((sql, _, _)) => {
                 assert_eq!(
                     &sql,
                     "SELECT DISTINCT ON (name, blah) id FROM a_table LIMIT 100;"
@@ -419,14 +688,22 @@ mod build_select_statement_tests {
                 group_by: None,
                 limit: 1000,
                 offset: 100,
+                is_nested: false,
+                embedded_rows: vec![],
+                join_type: None,
+                count: None,
+                cursor: None,
                 order_by: None,
                 table: "a_table".to_string(),
             },
             vec![],
             vec![],
             Expr::Identifier("".to_string()),
+            EmbeddedRowsStats::default(),
+            vec![],
+            None,
         ) {
-            Ok((sql, _)) => {
+            Ok((sql, _, _)) => {
                 assert_eq!(&sql, "SELECT id FROM a_table LIMIT 1000 OFFSET 100;");
             }
             Err(e) => {
@@ -445,14 +722,22 @@ mod build_select_statement_tests {
                 group_by: None,
                 limit: 1000,
                 offset: 0,
+                is_nested: false,
+                embedded_rows: vec![],
+                join_type: None,
+                count: None,
+                cursor: None,
                 order_by: Some(vec!["name".to_string(), "test".to_string()]),
                 table: "a_table".to_string(),
             },
             vec![],
             vec![],
             Expr::Identifier("".to_string()),
+            EmbeddedRowsStats::default(),
+            vec![],
+            None,
         ) {
-            Ok((sql, _)) => {
+            Ok((sql, _, _)) => {
                 assert_eq!(
                     &sql,
                     "SELECT id FROM a_table ORDER BY name ASC, test ASC LIMIT 1000;"
@@ -474,14 +759,22 @@ mod build_select_statement_tests {
                 group_by: Some(vec!["name".to_string()]),
                 limit: 1000,
                 offset: 0,
+                is_nested: false,
+                embedded_rows: vec![],
+                join_type: None,
+                count: None,
+                cursor: None,
                 order_by: None,
                 table: "a_table".to_string(),
             },
             vec![],
             vec![],
             Expr::Identifier("".to_string()),
+            EmbeddedRowsStats::default(),
+            vec![],
+            None,
         ) {
-            Ok((sql, _)) => {
+            Ok((sql, _, _)) => {
                 assert_eq!(
                     &sql,
                     "SELECT COUNT(id) AS id_count, name FROM a_table GROUP BY name LIMIT 1000;"
@@ -506,6 +799,11 @@ mod build_select_statement_tests {
                 group_by: None,
                 limit: 10,
                 offset: 0,
+                is_nested: false,
+                embedded_rows: vec![],
+                join_type: None,
+                count: None,
+                cursor: None,
                 order_by: None,
                 table: "a_table".to_string(),
             },
@@ -521,6 +819,10 @@ mod build_select_statement_tests {
                     foreign_key_column_type: None,
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
                 TableColumnStat {
                     column_name: "name".to_string(),
@@ -533,12 +835,19 @@ mod build_select_statement_tests {
                     foreign_key_column_type: None,
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
             ],
             vec![],
             where_ast,
+            EmbeddedRowsStats::default(),
+            vec![],
+            None,
         ) {
-            Ok((sql, prepared_values)) => {
+            Ok((sql, _, prepared_values)) => {
                 assert_eq!(
                     &sql,
                     "SELECT id FROM a_table WHERE ((a_table.id > $1 OR a_table.id < $2) AND a_table.name = $3) LIMIT 10;"
@@ -578,6 +887,11 @@ mod build_select_statement_tests {
                 group_by: None,
                 limit: 10000,
                 offset: 2000,
+                is_nested: false,
+                embedded_rows: vec![],
+                join_type: None,
+                count: None,
+                cursor: None,
                 order_by: Some(vec!["due_date desc".to_string()]),
                 table: "a_table".to_string(),
             },
@@ -593,6 +907,10 @@ mod build_select_statement_tests {
                     foreign_key_column_type: None,
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
                 TableColumnStat {
                     column_name: "test_bigint".to_string(),
@@ -605,6 +923,10 @@ mod build_select_statement_tests {
                     foreign_key_column_type: None,
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
                 TableColumnStat {
                     column_name: "test_bigserial".to_string(),
@@ -617,6 +939,10 @@ mod build_select_statement_tests {
                     foreign_key_column_type: None,
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
                 TableColumnStat {
                     column_name: "test_name".to_string(),
@@ -629,6 +955,10 @@ mod build_select_statement_tests {
                     foreign_key_column_type: None,
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
                 TableColumnStat {
                     column_name: "test_date".to_string(),
@@ -641,6 +971,10 @@ mod build_select_statement_tests {
                     foreign_key_column_type: None,
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
                 TableColumnStat {
                     column_name: "test_timestamptz".to_string(),
@@ -653,6 +987,10 @@ mod build_select_statement_tests {
                     foreign_key_column_type: None,
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
                 TableColumnStat {
                     column_name: "due_date".to_string(),
@@ -665,12 +1003,19 @@ mod build_select_statement_tests {
                     foreign_key_column_type: None,
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
             ],
             vec![],
             where_ast,
+            EmbeddedRowsStats::default(),
+            vec![],
+            None,
         ) {
-            Ok((sql, prepared_values)) => {
+            Ok((sql, _, prepared_values)) => {
                 assert_eq!(
                     &sql,
                     "SELECT DISTINCT ON (test_date, test_timestamptz) id, test_bigint, test_bigserial FROM a_table WHERE (a_table.id = $1 AND a_table.test_name = $2) ORDER BY due_date DESC LIMIT 10000 OFFSET 2000;"
@@ -708,6 +1053,11 @@ mod build_select_statement_tests {
                 group_by: None,
                 limit: 100,
                 offset: 0,
+                is_nested: false,
+                embedded_rows: vec![],
+                join_type: None,
+                count: None,
+                cursor: None,
                 order_by: None,
                 table: "sibling".to_string(),
             },
@@ -723,6 +1073,10 @@ mod build_select_statement_tests {
                     foreign_key_column_type: None,
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
                 TableColumnStat {
                     column_name: "parent_id".to_string(),
@@ -735,6 +1089,10 @@ mod build_select_statement_tests {
                     foreign_key_column_type: Some("int8"),
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
                 TableColumnStat {
                     column_name: "sibling_id".to_string(),
@@ -747,6 +1105,10 @@ mod build_select_statement_tests {
                     foreign_key_column_type: Some("int8"),
                     char_max_length: None,
                     char_octet_length: None,
+                    enum_labels: None,
+                    geometry_srid: None,
+                    geometry_type: None,
+                    composite_foreign_key: None,
                 },
             ],
             vec![
@@ -755,6 +1117,7 @@ mod build_select_statement_tests {
                     referring_table: "sibling".to_string(),
                     referring_column: "sibling_id".to_string(),
                     referring_column_type: "int8",
+                    referring_column_is_nullable: false,
                     foreign_key_table: "child".to_string(),
                     foreign_key_table_stats: vec![
                         TableColumnStat {
@@ -768,6 +1131,10 @@ mod build_select_statement_tests {
                             foreign_key_column_type: Some("int8"),
                             char_max_length: None,
                             char_octet_length: None,
+                            enum_labels: None,
+                            geometry_srid: None,
+                            geometry_type: None,
+                            composite_foreign_key: None,
                         },
                         TableColumnStat {
                             column_name: "parent_id".to_string(),
@@ -780,6 +1147,10 @@ mod build_select_statement_tests {
                             foreign_key_column_type: Some("int8"),
                             char_max_length: None,
                             char_octet_length: None,
+                            enum_labels: None,
+                            geometry_srid: None,
+                            geometry_type: None,
+                            composite_foreign_key: None,
                         },
                         TableColumnStat {
                             column_name: "name".to_string(),
@@ -792,6 +1163,10 @@ mod build_select_statement_tests {
                             foreign_key_column_type: None,
                             char_max_length: None,
                             char_octet_length: None,
+                            enum_labels: None,
+                            geometry_srid: None,
+                            geometry_type: None,
+                            composite_foreign_key: None,
                         },
                         TableColumnStat {
                             column_name: "id".to_string(),
@@ -804,10 +1179,15 @@ mod build_select_statement_tests {
                             foreign_key_column_type: None,
                             char_max_length: None,
                             char_octet_length: None,
+                            enum_labels: None,
+                            geometry_srid: None,
+                            geometry_type: None,
+                            composite_foreign_key: None,
                         },
                     ],
                     foreign_key_column: "id".to_string(),
                     foreign_key_column_type: "int8",
+                    additional_columns: vec![],
                     nested_fks: vec![],
                 },
                 ForeignKeyReference {
@@ -815,6 +1195,7 @@ mod build_select_statement_tests {
                     referring_table: "sibling".to_string(),
                     referring_column: "parent_id".to_string(),
                     referring_column_type: "int8",
+                    referring_column_is_nullable: false,
                     foreign_key_table: "child".to_string(),
                     foreign_key_table_stats: vec![
                         TableColumnStat {
@@ -828,6 +1209,10 @@ mod build_select_statement_tests {
                             foreign_key_column_type: Some("int8"),
                             char_max_length: None,
                             char_octet_length: None,
+                            enum_labels: None,
+                            geometry_srid: None,
+                            geometry_type: None,
+                            composite_foreign_key: None,
                         },
                         TableColumnStat {
                             column_name: "name".to_string(),
@@ -840,6 +1225,10 @@ mod build_select_statement_tests {
                             foreign_key_column_type: None,
                             char_max_length: None,
                             char_octet_length: None,
+                            enum_labels: None,
+                            geometry_srid: None,
+                            geometry_type: None,
+                            composite_foreign_key: None,
                         },
                         TableColumnStat {
                             column_name: "id".to_string(),
@@ -852,15 +1241,21 @@ mod build_select_statement_tests {
                             foreign_key_column_type: None,
                             char_max_length: None,
                             char_octet_length: None,
+                            enum_labels: None,
+                            geometry_srid: None,
+                            geometry_type: None,
+                            composite_foreign_key: None,
                         },
                     ],
                     foreign_key_column: "parent_id".to_string(),
                     foreign_key_column_type: "int8",
+                    additional_columns: vec![],
                     nested_fks: vec![ForeignKeyReference {
                         original_refs: vec!["parent_id.name".to_string()],
                         referring_table: "child".to_string(),
                         referring_column: "parent_id".to_string(),
                         referring_column_type: "int8",
+                        referring_column_is_nullable: false,
                         foreign_key_table: "adult".to_string(),
                         foreign_key_table_stats: vec![
                             TableColumnStat {
@@ -874,6 +1269,10 @@ mod build_select_statement_tests {
                                 foreign_key_column_type: Some("int8"),
                                 char_max_length: None,
                                 char_octet_length: None,
+                                enum_labels: None,
+                                geometry_srid: None,
+                                geometry_type: None,
+                                composite_foreign_key: None,
                             },
                             TableColumnStat {
                                 column_name: "name".to_string(),
@@ -886,6 +1285,10 @@ mod build_select_statement_tests {
                                 foreign_key_column_type: None,
                                 char_max_length: None,
                                 char_octet_length: None,
+                                enum_labels: None,
+                                geometry_srid: None,
+                                geometry_type: None,
+                                composite_foreign_key: None,
                             },
                             TableColumnStat {
                                 column_name: "id".to_string(),
@@ -898,17 +1301,25 @@ mod build_select_statement_tests {
                                 foreign_key_column_type: None,
                                 char_max_length: None,
                                 char_octet_length: None,
+                                enum_labels: None,
+                                geometry_srid: None,
+                                geometry_type: None,
+                                composite_foreign_key: None,
                             },
                         ],
                         foreign_key_column: "id".to_string(),
                         foreign_key_column_type: "int8",
+                        additional_columns: vec![],
                         nested_fks: vec![],
                     }],
                 },
             ],
             Expr::Identifier("".to_string()),
+            EmbeddedRowsStats::default(),
+            vec![],
+            None,
         ) {
-            Ok((sql, _)) => {
+            Ok((sql, _, _)) => {
                 assert_eq!(
                     &sql,
                     "SELECT sibling.name AS \"name\", adult.name AS \"parent_name\", child.name AS \"sibling_name\" FROM sibling INNER JOIN child ON sibling.sibling_id = child.id AND sibling.parent_id = child.parent_id\nINNER JOIN adult ON child.parent_id = adult.id LIMIT 100;"
@@ -919,4 +1330,313 @@ mod build_select_statement_tests {
             }
         };
     }
+
+    #[test]
+    fn nullable_fks_are_left_joined() {
+        let fks = || {
+            vec![ForeignKeyReference {
+                original_refs: vec!["manager_id.company_id.name".to_string()],
+                referring_table: "employee".to_string(),
+                referring_column: "manager_id".to_string(),
+                referring_column_type: "int8",
+                referring_column_is_nullable: true,
+                foreign_key_table: "manager".to_string(),
+                foreign_key_table_stats: vec![],
+                foreign_key_column: "id".to_string(),
+                foreign_key_column_type: "int8",
+                additional_columns: vec![],
+                nested_fks: vec![ForeignKeyReference {
+                    original_refs: vec!["company_id.name".to_string()],
+                    referring_table: "manager".to_string(),
+                    referring_column: "company_id".to_string(),
+                    referring_column_type: "int8",
+                    referring_column_is_nullable: false,
+                    foreign_key_table: "company".to_string(),
+                    foreign_key_table_stats: vec![],
+                    foreign_key_column: "id".to_string(),
+                    foreign_key_column_type: "int8",
+                    additional_columns: vec![],
+                    nested_fks: vec![],
+                }],
+            }]
+        };
+        let params = |join_type| SelectParams {
+            columns: vec![
+                "name".to_string(),
+                "manager_id.company_id.name company_name".to_string(),
+            ],
+            conditions: None,
+            distinct: None,
+            group_by: None,
+            limit: 100,
+            offset: 0,
+            is_nested: false,
+            embedded_rows: vec![],
+            join_type,
+            count: None,
+            cursor: None,
+            order_by: None,
+            table: "employee".to_string(),
+        };
+
+        let (sql, _, _) = build_select_statement(
+            params(None),
+            vec![],
+            fks(),
+            Expr::Identifier("".to_string()),
+            EmbeddedRowsStats::default(),
+            vec![],
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            &sql,
+            "SELECT employee.name AS \"name\", company.name AS \"company_name\" FROM employee LEFT JOIN manager ON employee.manager_id = manager.id\nLEFT JOIN company ON manager.company_id = company.id LIMIT 100;"
+        );
+
+        let (sql, _, _) = build_select_statement(
+            params(Some(JoinType::Inner)),
+            vec![],
+            fks(),
+            Expr::Identifier("".to_string()),
+            EmbeddedRowsStats::default(),
+            vec![],
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            &sql,
+            "SELECT employee.name AS \"name\", company.name AS \"company_name\" FROM employee INNER JOIN manager ON employee.manager_id = manager.id\nINNER JOIN company ON manager.company_id = company.id LIMIT 100;"
+        );
+    }
+
+    #[test]
+    fn embedded_rows() {
+        let conditions = "id > 1";
+        let where_ast = conditions_params_to_ast(&Some(conditions.to_string())).unwrap();
+        let int_stat = |column_name: &str| TableColumnStat {
+            column_name: column_name.to_string(),
+            column_type: "int8",
+            default_value: None,
+            is_nullable: false,
+            is_foreign_key: false,
+            foreign_key_table: None,
+            foreign_key_column: None,
+            foreign_key_column_type: None,
+            char_max_length: None,
+            char_octet_length: None,
+            enum_labels: None,
+            geometry_srid: None,
+            geometry_type: None,
+            composite_foreign_key: None,
+        };
+
+        match build_select_statement(
+            SelectParams {
+                columns: vec!["id".to_string()],
+                conditions: Some(conditions.to_string()),
+                distinct: None,
+                group_by: None,
+                limit: 100,
+                offset: 0,
+                is_nested: false,
+                embedded_rows: vec![EmbeddedRows::from_column_str(
+                    "orders[id;where=total > 10;order_by=id desc]",
+                )
+                .unwrap()],
+                join_type: None,
+                count: None,
+                cursor: None,
+                order_by: None,
+                table: "customers".to_string(),
+            },
+            vec![int_stat("id")],
+            vec![],
+            where_ast,
+            EmbeddedRowsStats {
+                referenced_by: vec![TableReferencedBy {
+                    referencing_table: "orders".to_string(),
+                    referencing_columns: vec!["customer_id".to_string()],
+                    columns_referenced: vec!["id".to_string()],
+                }],
+                columns: vec![vec![int_stat("id"), int_stat("total")]],
+            },
+            vec![],
+            None,
+        ) {
+            Ok((sql, _, prepared_values)) => {
+                assert_eq!(
+                    &sql,
+                    "SELECT id, (SELECT COALESCE(json_agg(orders.*), '[]') FROM (SELECT id FROM orders AS orders WHERE orders.customer_id = customers.id AND (orders.total > $1) ORDER BY id DESC) AS orders) AS \"orders\" FROM customers WHERE (customers.id > $2) LIMIT 100;"
+                );
+                assert_eq!(
+                    prepared_values,
+                    vec![
+                        TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(10)),
+                        TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(1)),
+                    ]
+                );
+            }
+            Err(e) => {
+                panic!(e);
+            }
+        };
+    }
+
+    #[test]
+    fn count_statements() {
+        let conditions = "id > 10";
+        let params = |count| SelectParams {
+            columns: vec!["id".to_string()],
+            conditions: Some(conditions.to_string()),
+            distinct: None,
+            group_by: None,
+            limit: 10,
+            offset: 20,
+            is_nested: false,
+            embedded_rows: vec![],
+            join_type: None,
+            count,
+            cursor: None,
+            order_by: Some(vec!["id".to_string()]),
+            table: "a_table".to_string(),
+        };
+        let stats = || {
+            vec![TableColumnStat {
+                column_name: "id".to_string(),
+                column_type: "int8",
+                default_value: None,
+                is_nullable: false,
+                is_foreign_key: false,
+                foreign_key_table: None,
+                foreign_key_column: None,
+                foreign_key_column_type: None,
+                char_max_length: None,
+                char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
+            }]
+        };
+        let build = |count| {
+            build_select_statement(
+                params(count),
+                stats(),
+                vec![],
+                conditions_params_to_ast(&Some(conditions.to_string())).unwrap(),
+                EmbeddedRowsStats::default(),
+                vec![],
+                None,
+            )
+            .unwrap()
+        };
+
+        let (sql, count_sql, _) = build(None);
+        assert_eq!(
+            &sql,
+            "SELECT id FROM a_table WHERE (a_table.id > $1) ORDER BY id ASC LIMIT 10 OFFSET 20;"
+        );
+        assert_eq!(count_sql, None);
+
+        let (_, count_sql, prepared_values) = build(Some(CountMode::Exact));
+        assert_eq!(
+            count_sql,
+            Some(CountStatement {
+                count_mode: CountMode::Exact,
+                statement: "SELECT COUNT(*) FROM (SELECT id FROM a_table WHERE (a_table.id > $1)) AS counted_rows;".to_string(),
+                prepared_values: vec![TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(10))],
+            })
+        );
+        assert_eq!(
+            prepared_values,
+            vec![TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(10))]
+        );
+
+        let (_, count_sql, _) = build(Some(CountMode::Planned));
+        assert_eq!(
+            count_sql,
+            Some(CountStatement {
+                count_mode: CountMode::Planned,
+                statement: "EXPLAIN (FORMAT JSON) SELECT id FROM a_table WHERE (a_table.id > $1);"
+                    .to_string(),
+                prepared_values: vec![TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(10))],
+            })
+        );
+
+        // the table name is a prepared value, not pasted into the statement
+        let (_, count_sql, prepared_values) = build(Some(CountMode::Estimated));
+        assert_eq!(
+            count_sql,
+            Some(CountStatement {
+                count_mode: CountMode::Estimated,
+                statement: "SELECT GREATEST(c.reltuples, 0)::BIGINT FROM pg_class c WHERE c.oid = $1::text::regclass;".to_string(),
+                prepared_values: vec![TypedColumnValue::Text(IsNullColumnValue::NotNullable(
+                    "\"public\".\"a_table\"".to_string()
+                ))],
+            })
+        );
+        assert_eq!(
+            prepared_values,
+            vec![TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(10))]
+        );
+    }
+
+    #[test]
+    fn keyset_first_page() {
+        let int_stat = |column_name: &str| TableColumnStat {
+            column_name: column_name.to_string(),
+            column_type: "int8",
+            default_value: None,
+            is_nullable: false,
+            is_foreign_key: false,
+            foreign_key_table: None,
+            foreign_key_column: None,
+            foreign_key_column_type: None,
+            char_max_length: None,
+            char_octet_length: None,
+            enum_labels: None,
+            geometry_srid: None,
+            geometry_type: None,
+            composite_foreign_key: None,
+        };
+        let stats = vec![int_stat("id"), int_stat("team_id")];
+        let keyset = Keyset::new(
+            &["team_id desc".to_string()],
+            &["id".to_string()],
+            "player",
+            &stats,
+            &[],
+        )
+        .unwrap();
+
+        let (sql, _, _) = build_select_statement(
+            SelectParams {
+                columns: vec!["id".to_string()],
+                conditions: None,
+                distinct: None,
+                group_by: None,
+                limit: 2,
+                offset: 0,
+                is_nested: false,
+                embedded_rows: vec![],
+                join_type: None,
+                count: None,
+                cursor: Some("".to_string()),
+                order_by: Some(vec!["team_id desc".to_string()]),
+                table: "player".to_string(),
+            },
+            stats,
+            vec![],
+            Expr::Identifier("".to_string()),
+            EmbeddedRowsStats::default(),
+            vec![],
+            Some(&keyset),
+        )
+        .unwrap();
+        assert_eq!(
+            &sql,
+            "SELECT id, player.team_id AS \"__cursor_0\", player.id AS \"__cursor_1\" FROM player ORDER BY player.team_id DESC, player.id ASC LIMIT 2;"
+        );
+    }
 }
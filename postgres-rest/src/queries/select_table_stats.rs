@@ -1,4 +1,4 @@
-use super::utils::validate_table_name;
+use super::utils::{split_table_name, validate_table_name};
 use crate::{
     stats_cache::{get_stats_cache_addr, StatsCache, StatsCacheMessage, StatsCacheResponse},
     Config, Error,
@@ -20,33 +20,148 @@ use tokio_postgres::{
 };
 
 lazy_static! {
-    pub static ref COLUMN_TYPES: &'static [&'static str] = &[
-        "bit",
-        "bool",
-        "bytea",
-        "bpchar",
-        "citext",
-        "date",
-        "float4",
-        "float8",
-        "hstore",
-        "int2",
-        "int4",
-        "int8",
-        "json",
-        "jsonb",
-        "macaddr",
-        "name",
-        "numeric",
-        "oid",
-        "text",
-        "time",
-        "timestamp",
-        "timestamptz",
-        "uuid",
-        "varbit",
-        "varchar",
-    ];
+    pub static ref COLUMN_TYPES: Vec<&'static str> = {
+        let mut column_types = vec![
+            "_bit",
+            "_bool",
+            "_bpchar",
+            "_bytea",
+            "_cidr",
+            "_citext",
+            "_date",
+            "_float4",
+            "_float8",
+            "_hstore",
+            "_inet",
+            "_int2",
+            "_int4",
+            "_int8",
+            "_interval",
+            "_json",
+            "_jsonb",
+            "_macaddr",
+            "_money",
+            "_name",
+            "_numeric",
+            "_oid",
+            "_text",
+            "_time",
+            "_timestamp",
+            "_timestamptz",
+            "_timetz",
+            "_uuid",
+            "_varbit",
+            "_varchar",
+            "_xml",
+            "bit",
+            "bool",
+            "bpchar",
+            "bytea",
+            "cidr",
+            "citext",
+            "date",
+            "datemultirange",
+            "daterange",
+            "enum",
+            "float4",
+            "float8",
+            "hstore",
+            "inet",
+            "int2",
+            "int4",
+            "int4multirange",
+            "int4range",
+            "int8",
+            "int8multirange",
+            "int8range",
+            "interval",
+            "json",
+            "jsonb",
+            "macaddr",
+            "money",
+            "name",
+            "nummultirange",
+            "numeric",
+            "numrange",
+            "oid",
+            "text",
+            "time",
+            "timestamp",
+            "timestamptz",
+            "timetz",
+            "tsmultirange",
+            "tsrange",
+            "tstzmultirange",
+            "tstzrange",
+            "uuid",
+            "varbit",
+            "varchar",
+            "xml",
+        ];
+
+        if cfg!(feature = "postgis") {
+            column_types.extend_from_slice(&["geography", "geometry"]);
+            column_types.sort();
+        }
+
+        column_types
+    };
+}
+
+/// Selects `geometry_srid` and `geometry_type` for PostGIS columns (`c` being
+/// `information_schema.columns` and `a` being `pg_attribute`). Both are decoded from the column’s
+/// type modifier, so PostGIS’ own functions aren’t needed.
+pub(crate) static GEOMETRY_COLUMNS_SQL: &str = "
+    CASE WHEN c.udt_name IN ('geography', 'geometry') THEN COALESCE(
+        NULLIF((GREATEST(a.atttypmod, 0) & 268435200) >> 8, 0),
+        CASE WHEN c.udt_name = 'geography' THEN 4326 ELSE 0 END
+    ) END AS geometry_srid,
+    CASE WHEN c.udt_name IN ('geography', 'geometry') THEN
+        (ARRAY[
+            'Geometry', 'Point', 'LineString', 'Polygon', 'MultiPoint', 'MultiLineString',
+            'MultiPolygon', 'GeometryCollection'
+        ])[((GREATEST(a.atttypmod, 0) & 252) >> 2) + 1] ||
+        CASE WHEN a.atttypmod > 0 AND a.atttypmod & 2 = 2 THEN 'Z' ELSE '' END ||
+        CASE WHEN a.atttypmod > 0 AND a.atttypmod & 1 = 1 THEN 'M' ELSE '' END
+    END AS geometry_type";
+
+/// Selects `composite_fk_columns` and `composite_fk_columns_referenced`, the columns of
+/// multi-column foreign keys (`c` being the `pg_constraint` row). `NULL` for single-column foreign
+/// keys.
+pub(crate) static COMPOSITE_FK_COLUMNS_SQL: &str =
+    "CASE WHEN array_length(c.conkey, 1) > 1 THEN ARRAY(
+            SELECT k_col.attname::text
+            FROM UNNEST(c.conkey) WITH ORDINALITY AS k(attnum, i)
+            JOIN pg_attribute k_col ON (k_col.attrelid = c.conrelid AND k_col.attnum = k.attnum)
+            ORDER BY k.i
+        ) END AS composite_fk_columns,
+        CASE WHEN array_length(c.confkey, 1) > 1 THEN ARRAY(
+            SELECT k_col.attname::text
+            FROM UNNEST(c.confkey) WITH ORDINALITY AS k(attnum, i)
+            JOIN pg_attribute k_col ON (k_col.attrelid = c.confrelid AND k_col.attnum = k.attnum)
+            ORDER BY k.i
+        ) END AS composite_fk_columns_referenced";
+
+/// Selects the estimated number of rows of a table (`c` being its `pg_class` row). Postgres 14+
+/// uses `-1` for tables that haven’t been analyzed yet.
+pub(crate) static ESTIMATED_ROW_COUNT_SQL: &str =
+    "GREATEST(c.reltuples, 0)::BIGINT AS estimated_row_count";
+
+/// Selects the disk space used by a table, including its indexes and TOAST data (`c` being its
+/// `pg_class` row).
+pub(crate) static TOTAL_SIZE_BYTES_SQL: &str = "pg_total_relation_size(c.oid) AS total_size_bytes";
+
+/// Pairs the columns of a multi-column foreign key with the columns they reference.
+pub(crate) fn composite_foreign_key(
+    columns: Option<Vec<String>>,
+    columns_referenced: Option<Vec<String>>,
+) -> Option<Vec<(String, String)>> {
+    match (columns, columns_referenced) {
+        (Some(columns), Some(columns_referenced)) => {
+            Some(columns.into_iter().zip(columns_referenced).collect())
+        }
+        _ => None,
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -54,7 +169,7 @@ lazy_static! {
 pub struct TableColumnStat {
     /// Name of column.
     pub column_name: String,
-    /// Type of column.
+    /// Type of column. Domains are resolved to their base type, and enums are listed as `enum`.
     pub column_type: &'static str,
     /// Default value of column.
     pub default_value: Option<String>,
@@ -75,6 +190,20 @@ pub struct TableColumnStat {
     /// a datum; null for all other data types. The maximum octet length depends on the declared
     /// character maximum length (see above) and the server encoding.
     pub char_octet_length: Option<i32>,
+    /// The allowed labels, in order, if the column is an enum.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enum_labels: Option<Vec<String>>,
+    /// The SRID, if the column is a PostGIS `geometry` or `geography` (`0` if unknown).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geometry_srid: Option<i32>,
+    /// The geometry type (like `Point` or `MultiPolygonZ`), if the column is a PostGIS `geometry`
+    /// or `geography`. Unconstrained columns are `Geometry`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub geometry_type: Option<String>,
+    /// All (column, column referenced) pairs of the foreign key, if it has more than one column
+    /// (like `(tenant_id, order_id)`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub composite_foreign_key: Option<Vec<(String, String)>>,
 }
 
 impl TableColumnStat {
@@ -131,9 +260,14 @@ pub struct Constraint {
 pub struct TableStats {
     pub columns: Vec<TableColumnStat>,
     pub constraints: Vec<Constraint>,
+    /// The number of rows, as estimated by Postgres’ planner statistics (`pg_class.reltuples`).
+    /// `0` if the table hasn’t been analyzed yet.
+    pub estimated_row_count: i64,
     pub indexes: Vec<TableIndex>,
     pub primary_key: Option<Vec<String>>,
     pub referenced_by: Vec<TableReferencedBy>,
+    /// The disk space used by the table, including its indexes and TOAST data, in bytes.
+    pub total_size_bytes: i64,
 }
 
 /// Returns the requested table’s stats: number of rows, the foreign keys referring to the table,
@@ -197,6 +331,7 @@ fn select_table_stats_from_db<T: MakeTlsConnect<Socket> + Clone + Send + Sync +
                 select_constraints_statement(&mut conn, &table),
                 select_indexes_statement(&mut conn, &table),
                 select_column_stats_statement(&mut conn, &table),
+                select_size_estimates_statement(&mut conn, &table),
             ])
             .from_err()
             .and_then(move |statements| {
@@ -207,6 +342,7 @@ fn select_table_stats_from_db<T: MakeTlsConnect<Socket> + Clone + Send + Sync +
                 }
 
                 // compile the results of the sub-operations into final stats
+                let size_estimates_q = queries.pop().unwrap();
                 let column_stats_q = queries.pop().unwrap();
                 let indexes_q = queries.pop().unwrap();
                 let constraints_q = queries.pop().unwrap();
@@ -214,12 +350,21 @@ fn select_table_stats_from_db<T: MakeTlsConnect<Socket> + Clone + Send + Sync +
                 let constraints_f = select_constraints(constraints_q);
                 let indexes_f = select_indexes(indexes_q);
                 let column_stats_f = select_column_stats(column_stats_q);
-
-                constraints_f.join3(indexes_f, column_stats_f).map(
-                    move |(constraints, indexes, column_stats)| {
-                        compile_table_stats(&table, constraints, indexes, column_stats)
-                    },
-                )
+                let size_estimates_f = select_size_estimates(size_estimates_q);
+
+                constraints_f
+                    .join4(indexes_f, column_stats_f, size_estimates_f)
+                    .map(
+                        move |(constraints, indexes, column_stats, size_estimates)| {
+                            compile_table_stats(
+                                &table,
+                                constraints,
+                                indexes,
+                                column_stats,
+                                size_estimates,
+                            )
+                        },
+                    )
             })
             .map_err(Error::from)
         })
@@ -291,6 +436,10 @@ pub(crate) fn select_column_stats(
                     foreign_key_column_type,
                     char_max_length: row.get(3),
                     char_octet_length: row.get(4),
+                    enum_labels: row.get(10),
+                    geometry_srid: row.get(11),
+                    geometry_type: row.get(12),
+                    composite_foreign_key: composite_foreign_key(row.get(13), row.get(14)),
                 })
             })
             .collect::<Result<Vec<TableColumnStat>, Error>>()
@@ -298,91 +447,69 @@ pub(crate) fn select_column_stats(
 }
 
 pub(crate) fn select_column_stats_statement(conn: &mut Client, table: &str) -> Prepare {
+    let (schema, table) = split_table_name(table);
     let statement_str = &format!("
 WITH foreign_keys as (
     SELECT
         col.attname AS column_name,
-
-        substring(
-            pg_get_constraintdef(c.oid), position(' REFERENCES ' in pg_get_constraintdef(c.oid))+12, position('(' in substring(pg_get_constraintdef(c.oid), 14))-position(' REFERENCES ' in pg_get_constraintdef(c.oid))+1
-        ) AS fk_table,
-
-        (string_to_array(
-            substring( -- Referenced column names in parentheses
-                pg_get_constraintdef(c.oid),
-                position('(' in substring(pg_get_constraintdef(c.oid), 14)) + 14,
-                position(
-                    ')' in substring(
-                        pg_get_constraintdef(c.oid),
-                        position('(' in substring(pg_get_constraintdef(c.oid), 14)) + 14
-                    )
-                ) - 1
-            ),
-            ', '
-        ))[
-            array_position( -- index of matching referencing column, used to find the matching referenced column name
-            string_to_array(
-                substring( -- Just the referencing column names in parentheses
-                    pg_get_constraintdef(c.oid),
-                    position('(' in pg_get_constraintdef(c.oid)) + 1,
-                    position(' REFERENCES ' in pg_get_constraintdef(c.oid)) - 15
-                ),
-                ', '
-            ),
-            col.attname::text
-            )
-        ] AS fk_column
-
+        CASE
+            WHEN fk_sch.nspname = 'public' THEN fk_tbl.relname::text
+            ELSE fk_sch.nspname || '.' || fk_tbl.relname
+        END AS fk_table,
+        fk_col.attname::text AS fk_column,
+        CASE
+            WHEN COALESCE(fk_base_typ.typtype, fk_typ.typtype) = 'e' THEN 'enum'
+            ELSE COALESCE(fk_base_typ.typname, fk_typ.typname)::text
+        END AS fk_column_type,
+        {3}
     FROM
         pg_constraint c
-        JOIN LATERAL UNNEST(c.conkey) WITH ORDINALITY AS u(attnum, attposition) ON TRUE
+        JOIN LATERAL UNNEST(c.conkey, c.confkey) AS u(attnum, fk_attnum) ON TRUE
         JOIN pg_class tbl ON tbl.oid = c.conrelid
         JOIN pg_namespace sch ON sch.oid = tbl.relnamespace
         JOIN pg_attribute col ON (col.attrelid = tbl.oid AND col.attnum = u.attnum)
+        JOIN pg_class fk_tbl ON fk_tbl.oid = c.confrelid
+        JOIN pg_namespace fk_sch ON fk_sch.oid = fk_tbl.relnamespace
+        JOIN pg_attribute fk_col ON (fk_col.attrelid = fk_tbl.oid AND fk_col.attnum = u.fk_attnum)
+        JOIN pg_type fk_typ ON fk_typ.oid = fk_col.atttypid
+        LEFT JOIN pg_type fk_base_typ ON fk_base_typ.oid = fk_typ.typbasetype
     WHERE (
-        sch.nspname = 'public' AND
+        sch.nspname = '{0}' AND
         c.contype = 'f' AND
-        (tbl.relname = '{0}')
+        (tbl.relname = '{1}')
     )
-    GROUP BY c.oid, column_name
-    ORDER BY column_name
-),
-base_column_stats as (
-    SELECT
-        c.column_name,
-        c.udt_name as column_type,
-        c.column_default as default_value,
-        c.character_maximum_length,
-        c.character_octet_length,
-        c.is_nullable,
-        EXISTS(SELECT column_name from foreign_keys WHERE column_name = c.column_name) AS is_foreign_key,
-        f.fk_table,
-        f.fk_column
-    FROM
-        information_schema.columns c
-        LEFT JOIN foreign_keys f ON c.column_name = f.column_name
-    WHERE
-        table_schema = 'public' AND
-        table_name = '{0}'
     ORDER BY column_name
 )
 SELECT
-    base.column_name,
-    base.column_type,
-    base.default_value,
-    base.character_maximum_length,
-    base.character_octet_length,
-    base.is_nullable,
-    base.is_foreign_key,
-    base.fk_table,
-    base.fk_column,
-    fk.udt_name as fk_column_type
+    c.column_name,
+    CASE WHEN t.typtype = 'e' THEN 'enum' ELSE c.udt_name::text END as column_type,
+    c.column_default as default_value,
+    c.character_maximum_length,
+    c.character_octet_length,
+    c.is_nullable,
+    EXISTS(SELECT column_name from foreign_keys WHERE column_name = c.column_name) AS is_foreign_key,
+    f.fk_table,
+    f.fk_column,
+    f.fk_column_type,
+    CASE WHEN t.typtype = 'e' THEN ARRAY(
+        SELECT e.enumlabel::text FROM pg_enum e WHERE e.enumtypid = t.oid ORDER BY e.enumsortorder
+    ) END AS enum_labels,
+    {2},
+    f.composite_fk_columns,
+    f.composite_fk_columns_referenced
 FROM
-    base_column_stats base
-    LEFT JOIN information_schema.columns fk ON (
-        fk.column_name = base.fk_column AND
-        fk.table_name = base.fk_table
-    );", table);
+    information_schema.columns c
+    LEFT JOIN foreign_keys f ON c.column_name = f.column_name
+    LEFT JOIN pg_namespace t_sch ON t_sch.nspname = c.udt_schema
+    LEFT JOIN pg_type t ON (t.typnamespace = t_sch.oid AND t.typname = c.udt_name)
+    LEFT JOIN pg_attribute a ON (
+        a.attrelid = (quote_ident(c.table_schema) || '.' || quote_ident(c.table_name))::regclass AND
+        a.attname = c.column_name
+    )
+WHERE
+    table_schema = '{0}' AND
+    table_name = '{1}'
+ORDER BY column_name;", schema, table, GEOMETRY_COLUMNS_SQL, COMPOSITE_FK_COLUMNS_SQL);
 
     conn.prepare(&statement_str)
 }
@@ -393,6 +520,7 @@ fn compile_table_stats(
     constraints: Vec<Constraint>,
     indexes: Vec<TableIndex>,
     column_stats: Vec<TableColumnStat>,
+    (estimated_row_count, total_size_bytes): (i64, i64),
 ) -> TableStats {
     // calculate primary key + referenced_by by iterating constraints and trimming the pK_column
     let mut opt_primary_key = vec![];
@@ -427,12 +555,14 @@ fn compile_table_stats(
     TableStats {
         columns: column_stats,
         constraints,
+        estimated_row_count,
         indexes,
         primary_key: match opt_primary_key.len() {
             0 => None,
             _ => Some(opt_primary_key),
         },
         referenced_by,
+        total_size_bytes,
     }
 }
 
@@ -492,24 +622,31 @@ fn select_constraints(q: Query) -> impl Future<Item = Vec<Constraint>, Error = E
 }
 
 fn select_constraints_statement(conn: &mut Client, table: &str) -> Prepare {
+    let (schema, table) = split_table_name(table);
     let statement_str = format!(r#"
 SELECT
     c.conname                   AS name,
     c.contype                   AS constraint_type,
-    tbl.relname                 AS "table",
+    CASE
+        WHEN sch.nspname = 'public' THEN tbl.relname::text
+        ELSE sch.nspname || '.' || tbl.relname
+    END                         AS "table",
     ARRAY_AGG(
         col.attname ORDER BY u.attposition
      )                          AS columns,
     pg_get_constraintdef(c.oid) AS definition,
 
-    CASE WHEN pg_get_constraintdef(c.oid) LIKE 'FOREIGN KEY %'
-    THEN substring(
-        pg_get_constraintdef(c.oid), position(' REFERENCES ' in pg_get_constraintdef(c.oid))+12, position('(' in substring(pg_get_constraintdef(c.oid), 14))-position(' REFERENCES ' in pg_get_constraintdef(c.oid))+1
-    ) END AS "fk_table",
-
-    CASE WHEN pg_get_constraintdef(c.oid) LIKE 'FOREIGN KEY %'
-    THEN substring(
-        pg_get_constraintdef(c.oid), position('(' in substring(pg_get_constraintdef(c.oid), 14))+14, position(')' in substring(pg_get_constraintdef(c.oid), position('(' in substring(pg_get_constraintdef(c.oid), 14))+14))-1
+    CASE WHEN c.contype = 'f'
+    THEN CASE
+        WHEN fk_sch.nspname = 'public' THEN fk_tbl.relname::text
+        ELSE fk_sch.nspname || '.' || fk_tbl.relname
+    END END AS "fk_table",
+
+    CASE WHEN c.contype = 'f'
+    THEN (
+        SELECT string_agg(fk_col.attname, ',' ORDER BY fk_u.attposition)
+        FROM UNNEST(c.confkey) WITH ORDINALITY AS fk_u(attnum, attposition)
+            JOIN pg_attribute fk_col ON (fk_col.attrelid = c.confrelid AND fk_col.attnum = fk_u.attnum)
     ) END AS "fk_column"
 
 FROM pg_constraint c
@@ -517,12 +654,23 @@ FROM pg_constraint c
     JOIN pg_class tbl ON tbl.oid = c.conrelid
     JOIN pg_namespace sch ON sch.oid = tbl.relnamespace
     JOIN pg_attribute col ON (col.attrelid = tbl.oid AND col.attnum = u.attnum)
+    LEFT JOIN pg_class fk_tbl ON fk_tbl.oid = c.confrelid
+    LEFT JOIN pg_namespace fk_sch ON fk_sch.oid = fk_tbl.relnamespace
 WHERE (
-    sch.nspname = 'public' AND
-    (tbl.relname = '{0}' OR pg_get_constraintdef(c.oid) LIKE '%{0}(%')
+    (sch.nspname = '{0}' AND tbl.relname = '{1}') OR
+    (fk_sch.nspname = '{0}' AND fk_tbl.relname = '{1}')
 )
-GROUP BY name, constraint_type, "table", definition
-ORDER BY "table";"#, table);
+GROUP BY
+    c.oid,
+    c.conname,
+    c.contype,
+    c.confrelid,
+    c.confkey,
+    sch.nspname,
+    tbl.relname,
+    fk_sch.nspname,
+    fk_tbl.relname
+ORDER BY "table", name;"#, schema, table);
 
     conn.prepare(&statement_str)
 }
@@ -552,6 +700,8 @@ fn select_indexes(q: Query) -> impl Future<Item = Vec<TableIndex>, Error = Error
 }
 
 fn select_indexes_statement(conn: &mut Client, table: &str) -> Prepare {
+    let (schema, table) = split_table_name(table);
+
     // taken from https://stackoverflow.com/a/2213199
     let statement_str = format!(
         r#"
@@ -567,13 +717,16 @@ FROM
     pg_class i,
     pg_index ix,
     pg_attribute a,
-    pg_am am
+    pg_am am,
+    pg_namespace n
 WHERE
     t.oid = ix.indrelid
     and i.oid = ix.indexrelid
     and a.attrelid = t.oid
     and a.attnum = ANY(ix.indkey)
     and t.relkind = 'r'
+    and n.oid = t.relnamespace
+    and n.nspname = '{}'
     and t.relname = '{}'
     and i.relam = am.oid
 GROUP BY
@@ -590,7 +743,34 @@ ORDER BY
     ix.indisunique,
     ix.indisexclusion,
     ix.indisprimary;"#,
-        table
+        schema, table
+    );
+
+    conn.prepare(&statement_str)
+}
+
+/// Returns the table’s estimated number of rows and its total size in bytes.
+fn select_size_estimates(q: Query) -> impl Future<Item = (i64, i64), Error = Error> {
+    q.from_err()
+        .map(|row| (row.get(0), row.get(1)))
+        .collect()
+        .map(|rows: Vec<(i64, i64)>| rows.into_iter().next().unwrap_or((0, 0)))
+}
+
+fn select_size_estimates_statement(conn: &mut Client, table: &str) -> Prepare {
+    let (schema, table) = split_table_name(table);
+    let statement_str = format!(
+        "
+SELECT
+    {},
+    {}
+FROM
+    pg_class c
+    JOIN pg_namespace n ON n.oid = c.relnamespace
+WHERE
+    n.nspname = '{}' AND
+    c.relname = '{}';",
+        ESTIMATED_ROW_COUNT_SQL, TOTAL_SIZE_BYTES_SQL, schema, table
     );
 
     conn.prepare(&statement_str)
@@ -1,12 +1,14 @@
 use super::{
     select_table_stats::{
-        Constraint, TableColumnStat, TableIndex, TableReferencedBy, TableStats, COLUMN_TYPES,
+        composite_foreign_key, Constraint, TableColumnStat, TableIndex, TableReferencedBy,
+        TableStats, COLUMN_TYPES, COMPOSITE_FK_COLUMNS_SQL, ESTIMATED_ROW_COUNT_SQL,
+        GEOMETRY_COLUMNS_SQL, TOTAL_SIZE_BYTES_SQL,
     },
-    utils::validate_table_name,
+    utils::{split_table_name, validate_table_name},
 };
-use crate::Error;
+use crate::{Error, PooledClient};
 use futures::stream::Stream;
-use futures03::{compat::Future01CompatExt, future::try_join3};
+use futures03::{compat::Future01CompatExt, future::try_join4};
 use lazy_static::lazy_static;
 use rayon::prelude::*;
 use std::collections::HashMap;
@@ -16,14 +18,16 @@ use tokio_postgres::{impls::Prepare, row::Row, Client};
 /// Returns the requested tables’ stats: number of rows, the foreign keys referring to the table,
 /// and column names + types
 pub(crate) async fn select_all_table_stats(
-    mut conn: Client,
+    mut conn: PooledClient,
     tables: Vec<String>,
-) -> Result<(HashMap<String, TableStats>, Client), Error> {
+) -> Result<(HashMap<String, TableStats>, PooledClient), Error> {
+    // (schema, table) pairs, used in `(schema, table) IN (...)` conditions
     let tables_str: String = match tables
         .par_iter()
         .map(|table| -> Result<String, Error> {
             validate_table_name(table)?;
-            Ok(["'", table, "'"].join(""))
+            let (schema, table) = split_table_name(table);
+            Ok(["('", schema, "', '", table, "')"].join(""))
         })
         .collect::<Result<Vec<String>, Error>>()
     {
@@ -31,33 +35,50 @@ pub(crate) async fn select_all_table_stats(
         Err(e) => return Err(e),
     };
 
-    let (constraints_statement, indexes_statement, column_stats_statement) = match try_join3(
-        select_constraints_statement(&mut conn, &tables_str, &tables).compat(),
+    let (
+        constraints_statement,
+        indexes_statement,
+        column_stats_statement,
+        size_estimates_statement,
+    ) = match try_join4(
+        select_constraints_statement(&mut conn, &tables_str).compat(),
         select_indexes_statement(&mut conn, &tables_str).compat(),
         select_column_stats_statement(&mut conn, &tables_str).compat(),
+        select_size_estimates_statement(&mut conn, &tables_str).compat(),
     )
     .await
     {
-        Ok((constraints_statement, indexes_statement, column_stats_statement)) => (
+        Ok((
             constraints_statement,
             indexes_statement,
             column_stats_statement,
+            size_estimates_statement,
+        )) => (
+            constraints_statement,
+            indexes_statement,
+            column_stats_statement,
+            size_estimates_statement,
         ),
         Err(e) => return Err(Error::from(e)),
     };
 
-    let (constraints, indexes, column_stats) = match try_join3(
+    let (constraints, indexes, column_stats, size_estimates) = match try_join4(
         conn.query(&constraints_statement, &[]).collect().compat(),
         conn.query(&indexes_statement, &[]).collect().compat(),
         conn.query(&column_stats_statement, &[]).collect().compat(),
+        conn.query(&size_estimates_statement, &[])
+            .collect()
+            .compat(),
     )
     .await
     {
-        Ok((constraint_rows, index_rows, stat_rows)) => (constraint_rows, index_rows, stat_rows),
+        Ok((constraint_rows, index_rows, stat_rows, size_estimate_rows)) => {
+            (constraint_rows, index_rows, stat_rows, size_estimate_rows)
+        }
         Err(e) => return Err(Error::from(e)),
     };
 
-    match compile_table_stats(tables, constraints, indexes, column_stats) {
+    match compile_table_stats(tables, constraints, indexes, column_stats, size_estimates) {
         Ok(stats) => Ok((stats, conn)),
         Err(e) => Err(e),
     }
@@ -69,10 +90,12 @@ fn compile_table_stats(
     constraint_rows: Vec<Row>,
     index_rows: Vec<Row>,
     column_stat_rows: Vec<Row>,
+    size_estimate_rows: Vec<Row>,
 ) -> Result<HashMap<String, TableStats>, Error> {
     let mut constraints = process_constraints(constraint_rows);
     let mut indexes = process_indexes(index_rows);
     let mut column_stats = process_column_stats(column_stat_rows)?;
+    let mut size_estimates = process_size_estimates(size_estimate_rows);
 
     let mut table_stats: HashMap<String, TableStats> = HashMap::new();
 
@@ -89,6 +112,10 @@ fn compile_table_stats(
             Some((_t, stat_vec)) => stat_vec,
             None => vec![],
         };
+        let (estimated_row_count, total_size_bytes) = match size_estimates.remove_entry(&table) {
+            Some((_t, table_size_estimates)) => table_size_estimates,
+            None => (0, 0),
+        };
 
         // calculate primary key + referenced_by by iterating constraints and trimming the pK_column
         let mut opt_primary_key = vec![];
@@ -125,12 +152,14 @@ fn compile_table_stats(
             TableStats {
                 columns: table_column_stats,
                 constraints: table_constraints,
+                estimated_row_count,
                 indexes: table_indexes,
                 primary_key: match opt_primary_key.len() {
                     0 => None,
                     _ => Some(opt_primary_key),
                 },
                 referenced_by,
+                total_size_bytes,
             },
         );
     }
@@ -139,74 +168,68 @@ fn compile_table_stats(
 }
 
 fn select_column_stats_statement(conn: &mut Client, tables_str: &str) -> Prepare {
-    let statement_str = &format!("
+    let statement_str = &format!(
+        "
 WITH foreign_keys as (
     SELECT
-        tbl.relname AS table_name,
+        CASE
+            WHEN sch.nspname = 'public' THEN tbl.relname::text
+            ELSE sch.nspname || '.' || tbl.relname
+        END AS table_name,
         col.attname AS column_name,
-
-        substring(
-            pg_get_constraintdef(c.oid), position(' REFERENCES ' in pg_get_constraintdef(c.oid))+12, position('(' in substring(pg_get_constraintdef(c.oid), 14))-position(' REFERENCES ' in pg_get_constraintdef(c.oid))+1
-        ) AS fk_table,
-
-        (string_to_array(
-            substring( -- Referenced column names in parentheses
-                pg_get_constraintdef(c.oid),
-                position('(' in substring(pg_get_constraintdef(c.oid), 14)) + 14,
-                position(
-                    ')' in substring(
-                        pg_get_constraintdef(c.oid),
-                        position('(' in substring(pg_get_constraintdef(c.oid), 14)) + 14
-                    )
-                ) - 1
-            ),
-            ', '
-        ))[
-            array_position( -- index of matching referencing column, used to find the matching referenced column name
-            string_to_array(
-                substring( -- Just the referencing column names in parentheses
-                    pg_get_constraintdef(c.oid),
-                    position('(' in pg_get_constraintdef(c.oid)) + 1,
-                    position(' REFERENCES ' in pg_get_constraintdef(c.oid)) - 15
-                ),
-                ', '
-            ),
-            col.attname::text
-            )
-        ] AS fk_column
+        CASE
+            WHEN fk_sch.nspname = 'public' THEN fk_tbl.relname::text
+            ELSE fk_sch.nspname || '.' || fk_tbl.relname
+        END AS fk_table,
+        fk_col.attname::text AS fk_column,
+        CASE
+            WHEN COALESCE(fk_base_typ.typtype, fk_typ.typtype) = 'e' THEN 'enum'
+            ELSE COALESCE(fk_base_typ.typname, fk_typ.typname)::text
+        END AS fk_column_type,
+        {2}
     FROM
         pg_constraint c
-        JOIN LATERAL UNNEST(c.conkey) WITH ORDINALITY AS u(attnum, attposition) ON TRUE
+        JOIN LATERAL UNNEST(c.conkey, c.confkey) AS u(attnum, fk_attnum) ON TRUE
         JOIN pg_class tbl ON tbl.oid = c.conrelid
         JOIN pg_namespace sch ON sch.oid = tbl.relnamespace
         JOIN pg_attribute col ON (col.attrelid = tbl.oid AND col.attnum = u.attnum)
+        JOIN pg_class fk_tbl ON fk_tbl.oid = c.confrelid
+        JOIN pg_namespace fk_sch ON fk_sch.oid = fk_tbl.relnamespace
+        JOIN pg_attribute fk_col ON (fk_col.attrelid = fk_tbl.oid AND fk_col.attnum = u.fk_attnum)
+        JOIN pg_type fk_typ ON fk_typ.oid = fk_col.atttypid
+        LEFT JOIN pg_type fk_base_typ ON fk_base_typ.oid = fk_typ.typbasetype
     WHERE (
-        sch.nspname = 'public' AND
         c.contype = 'f' AND
-        (tbl.relname IN ({0}))
+        ((sch.nspname, tbl.relname) IN ({0}))
     )
-    GROUP BY c.oid, table_name, column_name
     ORDER BY table_name, column_name
 ),
 base_column_stats as (
     SELECT
-        c.table_name,
+        CASE
+            WHEN c.table_schema = 'public' THEN c.table_name::text
+            ELSE c.table_schema || '.' || c.table_name
+        END AS table_name,
         c.column_name,
-        c.udt_name as column_type,
+        CASE WHEN t.typtype = 'e' THEN 'enum' ELSE c.udt_name::text END as column_type,
         c.column_default as default_value,
         c.character_maximum_length,
         c.character_octet_length,
         c.is_nullable,
-        EXISTS(SELECT column_name from foreign_keys WHERE column_name = c.column_name) AS is_foreign_key,
-        f.fk_table,
-        f.fk_column
+        CASE WHEN t.typtype = 'e' THEN ARRAY(
+            SELECT e.enumlabel::text FROM pg_enum e WHERE e.enumtypid = t.oid ORDER BY e.enumsortorder
+        ) END AS enum_labels,
+        {1}
     FROM
         information_schema.columns c
-        LEFT JOIN foreign_keys f ON c.column_name = f.column_name AND c.table_name = f.table_name
+        LEFT JOIN pg_namespace t_sch ON t_sch.nspname = c.udt_schema
+        LEFT JOIN pg_type t ON (t.typnamespace = t_sch.oid AND t.typname = c.udt_name)
+        LEFT JOIN pg_attribute a ON (
+            a.attrelid = (quote_ident(c.table_schema) || '.' || quote_ident(c.table_name))::regclass AND
+            a.attname = c.column_name
+        )
     WHERE
-        table_schema = 'public' AND
-        c.table_name IN ({0})
-    ORDER BY c.table_name, column_name
+        (c.table_schema, c.table_name) IN ({0})
 )
 SELECT
     base.table_name,
@@ -216,16 +239,24 @@ SELECT
     base.character_maximum_length,
     base.character_octet_length,
     base.is_nullable,
-    base.is_foreign_key,
-    base.fk_table,
-    base.fk_column,
-    fk.udt_name as fk_column_type
+    EXISTS(
+        SELECT column_name from foreign_keys
+        WHERE table_name = base.table_name AND column_name = base.column_name
+    ) AS is_foreign_key,
+    f.fk_table,
+    f.fk_column,
+    f.fk_column_type,
+    base.enum_labels,
+    base.geometry_srid,
+    base.geometry_type,
+    f.composite_fk_columns,
+    f.composite_fk_columns_referenced
 FROM
     base_column_stats base
-    LEFT JOIN information_schema.columns fk ON (
-        fk.column_name = base.fk_column AND
-        fk.table_name = base.fk_table
-    )", tables_str);
+    LEFT JOIN foreign_keys f ON base.column_name = f.column_name AND base.table_name = f.table_name
+ORDER BY base.table_name, base.column_name",
+        tables_str, GEOMETRY_COLUMNS_SQL, COMPOSITE_FK_COLUMNS_SQL
+    );
 
     conn.prepare(&statement_str)
 }
@@ -293,6 +324,10 @@ fn process_column_stats(rows: Vec<Row>) -> Result<HashMap<String, Vec<TableColum
             foreign_key_column_type,
             char_max_length: row.get(4),
             char_octet_length: row.get(5),
+            enum_labels: row.get(11),
+            geometry_srid: row.get(12),
+            geometry_type: row.get(13),
+            composite_foreign_key: composite_foreign_key(row.get(14), row.get(15)),
         };
 
         table_column_stats
@@ -307,26 +342,31 @@ fn process_column_stats(rows: Vec<Row>) -> Result<HashMap<String, Vec<TableColum
     Ok(table_column_stats)
 }
 
-fn select_constraints_statement(conn: &mut Client, tables_str: &str, tables: &[String]) -> Prepare {
-    let mut statement_str_vec = vec![
-        r#"
+fn select_constraints_statement(conn: &mut Client, tables_str: &str) -> Prepare {
+    let statement_str = format!(r#"
 SELECT
     c.conname                   AS name,
     c.contype                   AS constraint_type,
-    tbl.relname                 AS "table",
+    CASE
+        WHEN sch.nspname = 'public' THEN tbl.relname::text
+        ELSE sch.nspname || '.' || tbl.relname
+    END                         AS "table",
     ARRAY_AGG(
         col.attname ORDER BY u.attposition
      )                          AS columns,
     pg_get_constraintdef(c.oid) AS definition,
 
-    CASE WHEN pg_get_constraintdef(c.oid) LIKE 'FOREIGN KEY %'
-    THEN substring(
-        pg_get_constraintdef(c.oid), position(' REFERENCES ' in pg_get_constraintdef(c.oid))+12, position('(' in substring(pg_get_constraintdef(c.oid), 14))-position(' REFERENCES ' in pg_get_constraintdef(c.oid))+1
-    ) END AS "fk_table",
-
-    CASE WHEN pg_get_constraintdef(c.oid) LIKE 'FOREIGN KEY %'
-    THEN substring(
-        pg_get_constraintdef(c.oid), position('(' in substring(pg_get_constraintdef(c.oid), 14))+14, position(')' in substring(pg_get_constraintdef(c.oid), position('(' in substring(pg_get_constraintdef(c.oid), 14))+14))-1
+    CASE WHEN c.contype = 'f'
+    THEN CASE
+        WHEN fk_sch.nspname = 'public' THEN fk_tbl.relname::text
+        ELSE fk_sch.nspname || '.' || fk_tbl.relname
+    END END AS "fk_table",
+
+    CASE WHEN c.contype = 'f'
+    THEN (
+        SELECT string_agg(fk_col.attname, ',' ORDER BY fk_u.attposition)
+        FROM UNNEST(c.confkey) WITH ORDINALITY AS fk_u(attnum, attposition)
+            JOIN pg_attribute fk_col ON (fk_col.attrelid = c.confrelid AND fk_col.attnum = fk_u.attnum)
     ) END AS "fk_column"
 
 FROM pg_constraint c
@@ -334,31 +374,23 @@ FROM pg_constraint c
     JOIN pg_class tbl ON tbl.oid = c.conrelid
     JOIN pg_namespace sch ON sch.oid = tbl.relnamespace
     JOIN pg_attribute col ON (col.attrelid = tbl.oid AND col.attnum = u.attnum)
+    LEFT JOIN pg_class fk_tbl ON fk_tbl.oid = c.confrelid
+    LEFT JOIN pg_namespace fk_sch ON fk_sch.oid = fk_tbl.relnamespace
 WHERE (
-    sch.nspname = 'public' AND
-    (tbl.relname IN ("#,
-    tables_str,
-    ") OR (",
-    ];
-
-    let tables_where_vec = &tables
-        .par_iter()
-        .map(|table| ["pg_get_constraintdef(c.oid) LIKE '%", table, "(%'"])
-        .collect::<Vec<[&str; 3]>>();
-    for (i, tables_where_arr) in tables_where_vec.iter().enumerate() {
-        statement_str_vec.extend(tables_where_arr);
-        if i < tables_where_vec.len() - 1 {
-            statement_str_vec.push(" OR ");
-        }
-    }
-
-    statement_str_vec.push(
-        r#")
-))
-GROUP BY name, constraint_type, "table", definition
-ORDER BY "table";"#,
-    );
-    let statement_str = statement_str_vec.join("");
+    (sch.nspname, tbl.relname) IN ({0}) OR
+    (fk_sch.nspname, fk_tbl.relname) IN ({0})
+)
+GROUP BY
+    c.oid,
+    c.conname,
+    c.contype,
+    c.confrelid,
+    c.confkey,
+    sch.nspname,
+    tbl.relname,
+    fk_sch.nspname,
+    fk_tbl.relname
+ORDER BY "table", name;"#, tables_str);
 
     conn.prepare(&statement_str)
 }
@@ -415,6 +447,20 @@ fn process_constraints(rows: Vec<Row>) -> HashMap<String, Vec<Constraint>> {
             },
         };
 
+        // foreign keys are also listed under the table they reference, so that its `referenced_by`
+        // can be calculated
+        if let Some(fk_table) = &constraint.fk_table {
+            if fk_table != &table {
+                table_constraints
+                    .entry(fk_table.clone())
+                    .or_insert_with(|| vec![]);
+                table_constraints
+                    .get_mut(fk_table)
+                    .unwrap()
+                    .push(constraint.clone());
+            }
+        }
+
         table_constraints
             .entry(table.clone())
             .or_insert_with(|| vec![]);
@@ -429,7 +475,10 @@ fn select_indexes_statement(conn: &mut Client, tables_str: &str) -> Prepare {
     let statement_str = [
         "
 SELECT
-    t.relname as table_name,
+    CASE
+        WHEN n.nspname = 'public' THEN t.relname::text
+        ELSE n.nspname || '.' || t.relname
+    END as table_name,
     i.relname as name,
     am.amname as access_method,
     array_to_string(array_agg(a.attname), ',') as columns,
@@ -441,18 +490,21 @@ FROM
     pg_class i,
     pg_index ix,
     pg_attribute a,
-    pg_am am
+    pg_am am,
+    pg_namespace n
 WHERE
     t.oid = ix.indrelid
     and i.oid = ix.indexrelid
     and a.attrelid = t.oid
     and a.attnum = ANY(ix.indkey)
     and t.relkind = 'r'
-    and t.relname IN (",
+    and n.oid = t.relnamespace
+    and (n.nspname, t.relname) IN (",
         tables_str,
         ")
     and i.relam = am.oid
 GROUP BY
+    n.nspname,
     t.relname,
     i.relname,
     am.amname,
@@ -460,6 +512,7 @@ GROUP BY
     ix.indisexclusion,
     ix.indisprimary
 ORDER BY
+    n.nspname,
     t.relname,
     i.relname,
     am.amname,
@@ -496,3 +549,31 @@ fn process_indexes(rows: Vec<Row>) -> HashMap<String, Vec<TableIndex>> {
 
     table_indexes
 }
+
+fn select_size_estimates_statement(conn: &mut Client, tables_str: &str) -> Prepare {
+    let statement_str = format!(
+        "
+SELECT
+    CASE
+        WHEN n.nspname = 'public' THEN c.relname::text
+        ELSE n.nspname || '.' || c.relname
+    END AS table_name,
+    {},
+    {}
+FROM
+    pg_class c
+    JOIN pg_namespace n ON n.oid = c.relnamespace
+WHERE
+    (n.nspname, c.relname) IN ({});",
+        ESTIMATED_ROW_COUNT_SQL, TOTAL_SIZE_BYTES_SQL, tables_str
+    );
+
+    conn.prepare(&statement_str)
+}
+
+// returns the estimated row count and total size (in bytes) of each table
+fn process_size_estimates(rows: Vec<Row>) -> HashMap<String, (i64, i64)> {
+    rows.into_iter()
+        .map(|row| (row.get(0), (row.get(1), row.get(2))))
+        .collect()
+}
@@ -0,0 +1,299 @@
+use futures::{
+    future::{err, Either, Future},
+    stream::Stream,
+};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::str::FromStr;
+use tokio_postgres::{
+    tls::{MakeTlsConnect, TlsConnect},
+    Socket,
+};
+
+use super::{
+    batch::{execute_operation, BatchOperation},
+    QueryResult,
+};
+use crate::{Config, Error};
+
+/// The isolation levels that a long-lived transaction can be started with. Transactions use the
+/// database’s default isolation level (usually `READ COMMITTED`) if none is given.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum IsolationLevel {
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl IsolationLevel {
+    fn to_sql(self) -> &'static str {
+        match self {
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        }
+    }
+}
+
+/// Parses `read_committed`, `repeatable_read` or `serializable` (case-insensitive, with either
+/// underscores or spaces).
+impl FromStr for IsolationLevel {
+    type Err = Error;
+
+    fn from_str(isolation_level_str: &str) -> Result<Self, Error> {
+        match isolation_level_str
+            .trim()
+            .to_lowercase()
+            .replace(' ', "_")
+            .as_str()
+        {
+            "read_committed" => Ok(IsolationLevel::ReadCommitted),
+            "repeatable_read" => Ok(IsolationLevel::RepeatableRead),
+            "serializable" => Ok(IsolationLevel::Serializable),
+            _ => Err(Error::generate_error(
+                "INVALID_ISOLATION_LEVEL",
+                isolation_level_str.to_string(),
+            )),
+        }
+    }
+}
+
+/// Starts a long-lived transaction and returns its ID. The transaction’s connection stays checked
+/// out of the connection pool; use the ID to run queries on it (`execute_in_transaction()`) until
+/// it is committed or rolled back. Transactions that aren’t used for longer than the config’s
+/// `transaction_idle_timeout_seconds` are rolled back.
+///
+/// At most `pool_max_size - 1` transactions can be open at a time, so that there is always a
+/// connection left for requests outside of transactions.
+pub fn begin_transaction<T>(
+    config: &Config<T>,
+    isolation_level: Option<IsolationLevel>,
+) -> impl Future<Item = String, Error = Error>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    let reserved_transaction = match config
+        .transactions
+        .reserve(config.pool_max_size.saturating_sub(1))
+    {
+        Ok(reserved_transaction) => reserved_transaction,
+        Err(e) => return Either::A(err(e)),
+    };
+    let idle_timeout_seconds = config.transaction_idle_timeout_seconds;
+    let begin_statement = match isolation_level {
+        Some(isolation_level) => ["BEGIN ISOLATION LEVEL ", isolation_level.to_sql()].join(""),
+        None => "BEGIN".to_string(),
+    };
+
+    let begin_future = config.connect().and_then(move |mut conn| {
+        conn.simple_query(&begin_statement)
+            .for_each(|_| Ok(()))
+            .map_err(Error::from)
+            .map(move |_| reserved_transaction.open(conn, idle_timeout_seconds))
+    });
+
+    Either::B(begin_future)
+}
+
+/// Commits a long-lived transaction, and returns its connection to the pool.
+///
+/// If one of the transaction’s statements has failed (and the transaction wasn’t rolled back to a
+/// savepoint since), the transaction is rolled back instead and an error is returned.
+pub fn commit_transaction<T>(
+    config: &Config<T>,
+    transaction_id: &str,
+) -> impl Future<Item = (), Error = Error>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    let mut conn = match config.transactions.close(transaction_id) {
+        Ok(conn) => conn,
+        Err(e) => return Either::A(err(e)),
+    };
+    let transaction_id = transaction_id.to_string();
+
+    // Postgres rolls back a failed transaction on COMMIT without raising an error, so check whether
+    // the transaction can still run statements first
+    let commit_future = conn
+        .simple_query("SELECT 1")
+        .for_each(|_| Ok(()))
+        .then(move |result| match result {
+            Ok(_) => Either::A(conn.simple_query("COMMIT").for_each(|_| Ok(())).then(
+                move |result| {
+                    drop(conn);
+                    result.map_err(Error::from)
+                },
+            )),
+            Err(_) => Either::B(conn.simple_query("ROLLBACK").for_each(|_| Ok(())).then(
+                move |_| {
+                    drop(conn);
+                    Err(Error::generate_error("TRANSACTION_ABORTED", transaction_id))
+                },
+            )),
+        });
+
+    Either::B(commit_future)
+}
+
+/// Rolls back a long-lived transaction, and returns its connection to the pool.
+pub fn rollback_transaction<T>(
+    config: &Config<T>,
+    transaction_id: &str,
+) -> impl Future<Item = (), Error = Error>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    let mut conn = match config.transactions.close(transaction_id) {
+        Ok(conn) => conn,
+        Err(e) => return Either::A(err(e)),
+    };
+
+    // keep the connection checked out until the rollback finishes, so that it isn't returned to
+    // the pool mid-transaction
+    let rollback_future = conn
+        .simple_query("ROLLBACK")
+        .for_each(|_| Ok(()))
+        .then(move |result| {
+            drop(conn);
+            result.map_err(Error::from)
+        });
+
+    Either::B(rollback_future)
+}
+
+/// Creates a savepoint in a long-lived transaction. Rolling back to a savepoint undoes the
+/// statements run after it was created, and recovers the transaction from a failed statement.
+pub fn create_savepoint<T>(
+    config: &Config<T>,
+    transaction_id: &str,
+    savepoint: &str,
+) -> impl Future<Item = (), Error = Error>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    execute_savepoint_statement(config, transaction_id, "SAVEPOINT ", savepoint)
+}
+
+/// Destroys a savepoint of a long-lived transaction, keeping the statements run after it was
+/// created.
+pub fn release_savepoint<T>(
+    config: &Config<T>,
+    transaction_id: &str,
+    savepoint: &str,
+) -> impl Future<Item = (), Error = Error>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    execute_savepoint_statement(config, transaction_id, "RELEASE SAVEPOINT ", savepoint)
+}
+
+/// Rolls back a long-lived transaction to a savepoint. The savepoint can be rolled back to again.
+pub fn rollback_to_savepoint<T>(
+    config: &Config<T>,
+    transaction_id: &str,
+    savepoint: &str,
+) -> impl Future<Item = (), Error = Error>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    execute_savepoint_statement(config, transaction_id, "ROLLBACK TO SAVEPOINT ", savepoint)
+}
+
+fn execute_savepoint_statement<T>(
+    config: &Config<T>,
+    transaction_id: &str,
+    command: &str,
+    savepoint: &str,
+) -> impl Future<Item = (), Error = Error>
+where
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    lazy_static! {
+        static ref SAVEPOINT_RE: Regex = Regex::new(r"^[a-z_][a-z0-9_]*$").unwrap();
+    }
+
+    if !SAVEPOINT_RE.is_match(savepoint) {
+        return Either::A(err(Error::generate_error(
+            "INVALID_SQL_IDENTIFIER",
+            savepoint.to_string(),
+        )));
+    }
+
+    let mut conn = match config.transactions.check_out(transaction_id) {
+        Ok(conn) => conn,
+        Err(e) => return Either::A(err(e)),
+    };
+
+    // the connection is returned to the transaction once it is dropped
+    let savepoint_future = conn
+        .simple_query(&[command, savepoint].join(""))
+        .for_each(|_| Ok(()))
+        .then(move |result| {
+            drop(conn);
+            result.map_err(Error::from)
+        });
+
+    Either::B(savepoint_future)
+}
+
+/// Runs an INSERT, UPDATE, DELETE or SELECT on the connection of a long-lived transaction (which is
+/// used to look up table stats as well). Rows are retrieved all at once, and then streamed from
+/// memory.
+pub fn execute_in_transaction<T>(
+    config: Config<T>,
+    transaction_id: &str,
+    operation: BatchOperation,
+) -> impl Future<Item = QueryResult, Error = Error>
+where
+    <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
+    <T as MakeTlsConnect<Socket>>::Stream: Send,
+    <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    // counting and cursor pagination run more than one statement per SELECT
+    if let BatchOperation::Select(params) = &operation {
+        if params.count.is_some() || params.cursor.is_some() {
+            return Either::A(err(Error::generate_error(
+                "UNSUPPORTED_TRANSACTION_OPERATION",
+                params.table.clone(),
+            )));
+        }
+    }
+
+    let conn = match config.transactions.check_out(transaction_id) {
+        Ok(conn) => conn,
+        Err(e) => return Either::A(err(e)),
+    };
+
+    // the connection is returned to the transaction once it is dropped
+    let operation_future =
+        execute_operation(&config, conn, operation).then(|result| match result {
+            Ok((result, _conn)) => Ok(QueryResult::from(result)),
+            Err((e, _conn)) => Err(e),
+        });
+
+    Either::B(operation_future)
+}
+
+#[cfg(test)]
+mod isolation_level_tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn from_str() {
+        assert_eq!(
+            "serializable".parse::<IsolationLevel>().unwrap(),
+            IsolationLevel::Serializable
+        );
+        assert_eq!(
+            "Repeatable Read".parse::<IsolationLevel>().unwrap(),
+            IsolationLevel::RepeatableRead
+        );
+        assert_eq!(
+            "read_committed".parse::<IsolationLevel>().unwrap(),
+            IsolationLevel::ReadCommitted
+        );
+        assert!("read_uncommitted".parse::<IsolationLevel>().is_err());
+    }
+}
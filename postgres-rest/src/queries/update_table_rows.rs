@@ -1,11 +1,12 @@
 use super::{
-    foreign_keys::{fk_columns_from_where_ast, ForeignKeyReference},
+    column_nesting::ColumnNesting,
+    foreign_keys::{fk_columns_from_where_ast, ForeignKeyReference, JoinType, JOINED_TABLE_ALIAS},
     postgres_types::TypedColumnValue,
     select_table_stats::{select_column_stats, select_column_stats_statement, TableColumnStat},
     utils::{
         conditions_params_to_ast, generate_query_result_from_db, get_columns_str,
         get_db_column_str, get_where_string, validate_alias_identifier, validate_table_name,
-        validate_where_column,
+        validate_where_column, TableStatement,
     },
     QueryResult,
 };
@@ -34,6 +35,11 @@ pub struct UpdateParams {
     pub column_values: Map<String, JsonValue>,
     /// WHERE expression.
     pub conditions: Option<String>,
+    /// Whether foreign key columns in `returning_columns` are returned as nested objects.
+    pub is_nested: bool,
+    /// How the tables of foreign key columns are joined. If `None`, nullable foreign key columns
+    /// use LEFT JOIN and the others use INNER JOIN.
+    pub join_type: Option<JoinType>,
     /// List of (foreign key) columns whose values are returned.
     pub returning_columns: Option<Vec<String>>,
     // Name of table to update.
@@ -45,6 +51,30 @@ pub fn update_table_rows<T>(
     config: &Config<T>,
     params: UpdateParams,
 ) -> impl Future<Item = QueryResult, Error = Error>
+where
+    <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
+    <T as MakeTlsConnect<Socket>>::Stream: Send,
+    <<T as MakeTlsConnect<Socket>>::TlsConnect as TlsConnect<Socket>>::Future: Send,
+    T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static,
+{
+    let config_clone = config.clone();
+    update_statement(config, params).and_then(move |statement| {
+        generate_query_result_from_db(
+            config_clone,
+            statement.statement,
+            statement.prepared_values,
+            statement.is_return_rows,
+            statement.nesting,
+        )
+    })
+}
+
+/// Generates the UPDATE statement (looking up the table’s column stats and foreign keys), without
+/// executing it.
+pub(crate) fn update_statement<T>(
+    config: &Config<T>,
+    params: UpdateParams,
+) -> impl Future<Item = TableStatement, Error = Error>
 where
     <T as MakeTlsConnect<Socket>>::TlsConnect: Send,
     <T as MakeTlsConnect<Socket>>::Stream: Send,
@@ -135,7 +165,6 @@ where
 
     // parse column_expr_strings for foreign key usage
     let config_clone = config.clone();
-    let config_clone_2 = config.clone();
 
     let fk_future = stats_future
         .join(ForeignKeyReference::from_query_columns(
@@ -145,20 +174,22 @@ where
             column_expr_strings,
         ))
         .and_then(move |(stats, fk_columns)| {
-            let (statement_str, prepared_values) =
-                match build_update_statement(params, stats, fk_columns, where_ast) {
-                    Ok((stmt, prep_vals)) => (stmt, prep_vals),
-                    Err(e) => return Either::A(err(e)),
-                };
-
-            let update_rows_future = generate_query_result_from_db(
-                config_clone_2,
-                statement_str,
+            let nesting = match (&params.returning_columns, params.is_nested) {
+                (Some(columns), true) => {
+                    Some(ColumnNesting::new(columns, &params.table, &fk_columns)?)
+                }
+                _ => None,
+            };
+
+            let (statement, prepared_values) =
+                build_update_statement(params, stats, fk_columns, where_ast)?;
+
+            Ok(TableStatement {
+                statement,
                 prepared_values,
                 is_return_rows,
-            );
-
-            Either::B(update_rows_future)
+                nesting,
+            })
         });
 
     Either::B(fk_future)
@@ -238,9 +269,28 @@ fn build_update_statement(
         }
     }
 
-    // FROM string
+    // FROM string. LEFT JOINs need the updated table to be joined again (by `ctid`), because the
+    // tables listed in FROM can’t refer to it
+    let is_left_join = ForeignKeyReference::has_left_join(&fks, params.join_type);
     let from_tables_str;
-    if !fks.is_empty() {
+    if is_left_join {
+        from_tables_str = [
+            params.table.as_str(),
+            " AS ",
+            JOINED_TABLE_ALIAS,
+            " ",
+            &ForeignKeyReference::join_clauses(
+                &fks,
+                params.join_type,
+                &params.table,
+                Some(JOINED_TABLE_ALIAS),
+            ),
+        ]
+        .join("");
+
+        query_str_arr.push(" FROM ");
+        query_str_arr.push(&from_tables_str);
+    } else if !fks.is_empty() {
         from_tables_str = ForeignKeyReference::join_foreign_key_references(
             &fks,
             |(_, _, referred_table, _)| referred_table.to_string(),
@@ -252,7 +302,15 @@ fn build_update_statement(
     }
 
     // building WHERE string
-    let fk_where_filter = if !fks.is_empty() {
+    let fk_where_filter = if is_left_join {
+        [
+            params.table.as_str(),
+            ".ctid = ",
+            JOINED_TABLE_ALIAS,
+            ".ctid",
+        ]
+        .join("")
+    } else if !fks.is_empty() {
         ForeignKeyReference::join_foreign_key_references(
             &fks,
             |(referring_table, referring_column, fk_table, fk_column)| {
@@ -325,6 +383,8 @@ mod build_update_statement_tests {
             column_values: json!({"name": "'test'"}).as_object().unwrap().clone(),
             conditions: None,
             returning_columns: None,
+            is_nested: false,
+            join_type: None,
             table: "a_table".to_string(),
         };
         let stats = vec![TableColumnStat {
@@ -338,6 +398,10 @@ mod build_update_statement_tests {
             foreign_key_column_type: None,
             char_max_length: None,
             char_octet_length: None,
+            enum_labels: None,
+            geometry_srid: None,
+            geometry_type: None,
+            composite_foreign_key: None,
         }];
         let fks = vec![];
 
@@ -364,6 +428,8 @@ mod build_update_statement_tests {
                 .clone(),
             conditions: Some(conditions.to_string()),
             returning_columns: Some(vec!["id".to_string(), "nemesis_name".to_string()]),
+            is_nested: false,
+            join_type: None,
             table: "throne".to_string(),
         };
         let stats = vec![
@@ -378,6 +444,10 @@ mod build_update_statement_tests {
                 foreign_key_column_type: None,
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
             TableColumnStat {
                 column_name: "nemesis_id".to_string(),
@@ -390,6 +460,10 @@ mod build_update_statement_tests {
                 foreign_key_column_type: Some("int8"),
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
             TableColumnStat {
                 column_name: "nemesis_name".to_string(),
@@ -402,6 +476,10 @@ mod build_update_statement_tests {
                 foreign_key_column_type: None,
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
             TableColumnStat {
                 column_name: "house".to_string(),
@@ -414,6 +492,10 @@ mod build_update_statement_tests {
                 foreign_key_column_type: None,
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
             TableColumnStat {
                 column_name: "ruler".to_string(),
@@ -426,6 +508,10 @@ mod build_update_statement_tests {
                 foreign_key_column_type: None,
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
         ];
         let fks = vec![ForeignKeyReference {
@@ -433,10 +519,12 @@ mod build_update_statement_tests {
             referring_table: "throne".to_string(),
             referring_column: "nemesis_id".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "adult".to_string(),
             foreign_key_table_stats: vec![],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![],
         }];
 
@@ -462,6 +550,8 @@ mod build_update_statement_tests {
                 .clone(),
             conditions: None,
             returning_columns: Some(vec!["id".to_string(), "nemesis_name".to_string()]),
+            is_nested: false,
+            join_type: None,
             table: "throne".to_string(),
         };
         let stats = vec![
@@ -476,6 +566,10 @@ mod build_update_statement_tests {
                 foreign_key_column_type: None,
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
             TableColumnStat {
                 column_name: "nemesis_id".to_string(),
@@ -488,6 +582,10 @@ mod build_update_statement_tests {
                 foreign_key_column_type: Some("int8"),
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
             TableColumnStat {
                 column_name: "nemesis_name".to_string(),
@@ -500,6 +598,10 @@ mod build_update_statement_tests {
                 foreign_key_column_type: None,
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
             TableColumnStat {
                 column_name: "house".to_string(),
@@ -512,6 +614,10 @@ mod build_update_statement_tests {
                 foreign_key_column_type: None,
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
             TableColumnStat {
                 column_name: "ruler".to_string(),
@@ -524,6 +630,10 @@ mod build_update_statement_tests {
                 foreign_key_column_type: None,
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
         ];
         let fks = vec![ForeignKeyReference {
@@ -531,10 +641,12 @@ mod build_update_statement_tests {
             referring_table: "throne".to_string(),
             referring_column: "nemesis_id".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "adult".to_string(),
             foreign_key_table_stats: vec![],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![],
         }];
 
@@ -559,6 +671,8 @@ mod build_update_statement_tests {
                 .clone(),
             conditions: Some(conditions.to_string()),
             returning_columns: Some(vec!["id".to_string(), "team_id.coach_id.name".to_string()]),
+            is_nested: false,
+            join_type: None,
             table: "player".to_string(),
         };
         let stats = vec![
@@ -573,6 +687,10 @@ mod build_update_statement_tests {
                 foreign_key_column_type: None,
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
             TableColumnStat {
                 column_name: "team_id".to_string(),
@@ -585,6 +703,10 @@ mod build_update_statement_tests {
                 foreign_key_column_type: Some("int8"),
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
             TableColumnStat {
                 column_name: "name".to_string(),
@@ -597,6 +719,10 @@ mod build_update_statement_tests {
                 foreign_key_column_type: None,
                 char_max_length: None,
                 char_octet_length: None,
+                enum_labels: None,
+                geometry_srid: None,
+                geometry_type: None,
+                composite_foreign_key: None,
             },
         ];
         let fks = vec![ForeignKeyReference {
@@ -604,19 +730,23 @@ mod build_update_statement_tests {
             referring_table: "player".to_string(),
             referring_column: "team_id".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "team".to_string(),
             foreign_key_table_stats: vec![],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![ForeignKeyReference {
                 original_refs: vec!["coach_id.name".to_string()],
                 referring_table: "team".to_string(),
                 referring_column: "coach_id".to_string(),
                 referring_column_type: "int8",
+                referring_column_is_nullable: false,
                 foreign_key_table: "coach".to_string(),
                 foreign_key_table_stats: vec![],
                 foreign_key_column: "id".to_string(),
                 foreign_key_column_type: "int8",
+                additional_columns: vec![],
                 nested_fks: vec![],
             }],
         }];
@@ -633,4 +763,82 @@ mod build_update_statement_tests {
             vec![TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(1))]
         );
     }
+
+    #[test]
+    fn nullable_fk_left_join() {
+        let conditions = "id = 2";
+        let where_ast = conditions_params_to_ast(&Some(conditions.to_string())).unwrap();
+        let stat = |column_name: &str, column_type: &'static str| TableColumnStat {
+            column_name: column_name.to_string(),
+            column_type,
+            default_value: None,
+            is_nullable: true,
+            is_foreign_key: false,
+            foreign_key_table: None,
+            foreign_key_column: None,
+            foreign_key_column_type: None,
+            char_max_length: None,
+            char_octet_length: None,
+            enum_labels: None,
+            geometry_srid: None,
+            geometry_type: None,
+            composite_foreign_key: None,
+        };
+        let fks = || {
+            vec![ForeignKeyReference {
+                original_refs: vec!["nemesis_id.name".to_string()],
+                referring_table: "throne".to_string(),
+                referring_column: "nemesis_id".to_string(),
+                referring_column_type: "int8",
+                referring_column_is_nullable: true,
+                foreign_key_table: "adult".to_string(),
+                foreign_key_table_stats: vec![],
+                foreign_key_column: "id".to_string(),
+                foreign_key_column_type: "int8",
+                additional_columns: vec![],
+                nested_fks: vec![],
+            }]
+        };
+        let params = |join_type| UpdateParams {
+            column_values: json!({"nemesis_name": "nemesis_id.name"})
+                .as_object()
+                .unwrap()
+                .clone(),
+            conditions: Some(conditions.to_string()),
+            returning_columns: None,
+            is_nested: false,
+            join_type,
+            table: "throne".to_string(),
+        };
+
+        let (sql_str, prepared_values) = build_update_statement(
+            params(None),
+            vec![stat("id", "int8"), stat("nemesis_name", "text")],
+            fks(),
+            where_ast.clone(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            &sql_str,
+            "UPDATE throne SET nemesis_name = adult.name FROM throne AS joined_table LEFT JOIN adult ON joined_table.nemesis_id = adult.id\nWHERE (\n  throne.id = $1 AND\n  throne.ctid = joined_table.ctid\n);"
+        );
+        assert_eq!(
+            prepared_values,
+            vec![TypedColumnValue::BigInt(IsNullColumnValue::NotNullable(2))]
+        );
+
+        let (sql_str, _) = build_update_statement(
+            params(Some(JoinType::Inner)),
+            vec![stat("id", "int8"), stat("nemesis_name", "text")],
+            fks(),
+            where_ast,
+        )
+        .unwrap();
+
+        assert_eq!(
+            &sql_str,
+            "UPDATE throne SET nemesis_name = adult.name FROM adult\nWHERE (\n  throne.id = $1 AND\n  throne.nemesis_id = adult.id\n);"
+        );
+    }
 }
@@ -1,19 +1,17 @@
 use super::{
+    column_nesting::ColumnNesting,
     foreign_keys::{fk_ast_nodes_from_where_ast, ForeignKeyReference},
-    postgres_types::{row_to_row_values, RowValues, TypedColumnValue},
+    postgres_types::TypedColumnValue,
     select_table_stats::TableColumnStat,
-    QueryResult,
+    QueryResult, RowValuesStream,
 };
 use crate::{Config, Error};
-use futures::{
-    future::{Either, Future},
-    stream::Stream,
-};
+use futures::future::{ok, Either, Future};
 use lazy_static::lazy_static;
 use rayon::prelude::*;
 use regex::{Regex, RegexSet};
 use sqlparser::{
-    ast::{Expr, SetExpr, Statement},
+    ast::{BinaryOperator, Expr, SetExpr, Statement},
     dialect::PostgreSqlDialect,
     parser::Parser,
 };
@@ -26,11 +24,25 @@ static ALIAS_RE_STR: &str = r"(?i) AS ";
 // followed by an string containing only alphanumeric characters or underscore.
 static SHORTENED_ALIAS_RE_STR: &str = r"^[A-Za-z_][A-Za-z0-9_\(\)\.\*]*[^\.\*\s]?\s+\w+$";
 
+/// Postgres operators that the SQL parser doesn’t support, and the placeholder identifiers that
+/// stand in for them. `a @> b` is parsed as `a = __op_contains__ = b`, which has the same
+/// precedence relative to `AND`/`OR`.
+static OPERATOR_PLACEHOLDERS: [(&str, &str); 4] = [
+    ("@>", "__op_contains__"),
+    ("<@", "__op_contained_by__"),
+    ("&&", "__op_overlaps__"),
+    ("?", "__op_has_key__"),
+];
+
 /// Converts a WHERE clause string into an Expr.
 pub fn conditions_params_to_ast(clause_opt: &Option<String>) -> Result<Expr, Error> {
     match clause_opt {
         Some(clause) => {
-            let full_statement = ["SELECT * FROM a_table WHERE ", clause].join("");
+            let full_statement = [
+                "SELECT * FROM a_table WHERE ",
+                &replace_operators_with_placeholders(clause),
+            ]
+            .join("");
             let dialect = PostgreSqlDialect {};
 
             // convert the statement into an AST, and then extract the "WHERE" portion of the AST
@@ -59,6 +71,92 @@ pub fn conditions_params_to_ast(clause_opt: &Option<String>) -> Result<Expr, Err
     }
 }
 
+/// Replaces the operators in `OPERATOR_PLACEHOLDERS` (outside of quotes) with their placeholders.
+/// Also strips the `B` prefix from bit string literals (`B'0101'` becomes `'0101'`), which the
+/// SQL parser can’t tokenize; the value is converted to the bit column’s type later anyway.
+pub(crate) fn replace_operators_with_placeholders(clause: &str) -> String {
+    let mut replaced = String::with_capacity(clause.len());
+    let mut quote_opt: Option<char> = None;
+    let mut prev_c_opt: Option<char> = None;
+    let mut char_indices = clause.char_indices().peekable();
+
+    while let Some((i, c)) = char_indices.next() {
+        match quote_opt {
+            Some(quote) => {
+                if c == quote {
+                    quote_opt = None;
+                }
+            }
+            None if c == '\'' || c == '"' => quote_opt = Some(c),
+            None => {
+                let is_bit_string_prefix = (c == 'B' || c == 'b')
+                    && char_indices.peek().map(|(_, next_c)| *next_c) == Some('\'')
+                    && !prev_c_opt
+                        .map_or(false, |prev_c| prev_c.is_alphanumeric() || prev_c == '_');
+                if is_bit_string_prefix {
+                    prev_c_opt = Some(c);
+                    continue;
+                }
+
+                let placeholder_opt = OPERATOR_PLACEHOLDERS
+                    .iter()
+                    .find(|(operator, _)| clause[i..].starts_with(operator));
+
+                if let Some((operator, placeholder)) = placeholder_opt {
+                    for _ in 1..operator.len() {
+                        char_indices.next();
+                    }
+                    replaced.push_str(" = ");
+                    replaced.push_str(placeholder);
+                    replaced.push_str(" = ");
+                    prev_c_opt = Some(' ');
+                    continue;
+                }
+            }
+        }
+
+        replaced.push(c);
+        prev_c_opt = Some(c);
+    }
+
+    replaced
+}
+
+/// Reverses `replace_operators_with_placeholders()` for a stringified AST.
+pub(crate) fn replace_placeholders_with_operators(sql: String) -> String {
+    OPERATOR_PLACEHOLDERS
+        .iter()
+        .fold(sql, |sql, (operator, placeholder)| {
+            sql.replace(
+                &[" = ", placeholder, " = "].join(""),
+                &[" ", operator, " "].join(""),
+            )
+        })
+}
+
+/// If the given Expr is the left side of a placeholder operator (`a = __op_contains__` in
+/// `a = __op_contains__ = b`), returns the operator’s left operand and the operator.
+pub(crate) fn split_operator_placeholder(expr: &mut Expr) -> Option<(&mut Expr, &'static str)> {
+    match expr {
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::Eq,
+            right,
+        } => {
+            let operator_opt = match right.as_ref() {
+                Expr::Identifier(identifier) => OPERATOR_PLACEHOLDERS
+                    .iter()
+                    .find(|(_, placeholder)| placeholder == identifier)
+                    .map(|(operator, _)| *operator),
+                _ => None,
+            };
+
+            operator_opt.map(move |operator| (left.as_mut(), operator))
+        }
+        _ => None,
+    }
+}
+
 /// Finds and returns the Expr that represents the WHERE clause of a SELECT statement
 fn extract_where_ast_from_setexpr(expr: SetExpr) -> Option<Expr> {
     match expr {
@@ -69,12 +167,25 @@ fn extract_where_ast_from_setexpr(expr: SetExpr) -> Option<Expr> {
     }
 }
 
-/// Returns a Future resolving to a QueryResult.
+/// A statement generated from the params of an UPDATE, DELETE or SELECT query, ready to be
+/// executed.
+pub(crate) struct TableStatement {
+    pub(crate) statement: String,
+    pub(crate) prepared_values: Vec<TypedColumnValue>,
+    /// Whether the statement returns rows (instead of the number of rows affected).
+    pub(crate) is_return_rows: bool,
+    /// How foreign key columns in the returned rows are nested, if requested.
+    pub(crate) nesting: Option<ColumnNesting>,
+}
+
+/// Returns a Future resolving to a QueryResult. Returned rows are nested by foreign key if a
+/// `ColumnNesting` is given.
 pub fn generate_query_result_from_db<T: MakeTlsConnect<Socket> + Clone + Send + Sync + 'static>(
     config: Config<T>,
     statement_str: String,
     prepared_values: Vec<TypedColumnValue>,
     is_return_rows: bool,
+    nesting: Option<ColumnNesting>,
 ) -> impl Future<Item = QueryResult, Error = Error> {
     config
         .connect()
@@ -87,22 +198,11 @@ pub fn generate_query_result_from_db<T: MakeTlsConnect<Socket> + Clone + Send +
                         prepared_values.iter().map(|v| v as _).collect();
 
                     if is_return_rows {
-                        let return_rows_future = conn
-                            .query(&statement, &prep_values)
-                            .collect()
-                            .map_err(Error::from)
-                            .and_then(|rows| {
-                                match rows
-                                    .par_iter()
-                                    .map(|row| row_to_row_values(&row))
-                                    .collect::<Result<Vec<RowValues>, Error>>()
-                                {
-                                    Ok(row_values) => Ok(QueryResult::QueryTableResult(row_values)),
-                                    Err(e) => Err(e),
-                                }
-                            });
-
-                        Either::A(return_rows_future)
+                        let rows = conn.query(&statement, &prep_values);
+
+                        Either::A(ok(QueryResult::QueryTableStream(
+                            RowValuesStream::new(&statement, rows, conn).with_nesting(nesting),
+                        )))
                     } else {
                         let return_row_count_future = conn.execute(&statement, &prep_values).then(
                             move |result| match result {
@@ -255,18 +355,95 @@ pub(crate) fn get_columns_str<'a>(
     Ok(statement)
 }
 
+/// Separates an ORDER BY column (like `name desc`) into the column and whether it’s sorted in
+/// descending order.
+pub(crate) fn split_order_by_column(column: &str) -> Result<(&str, bool), Error> {
+    lazy_static! {
+        // case-insensitive search for ORDER BY direction
+        static ref ORDER_BY_DIRECTION_RE: Regex = Regex::new(r"(?i) asc| desc").unwrap();
+    }
+
+    // using `is_match` first because it's faster than `find`
+    let (sql_column, order_by_direction): (&str, &str) = if ORDER_BY_DIRECTION_RE.is_match(column) {
+        // separate the column string from the direction string
+        match ORDER_BY_DIRECTION_RE.find(column) {
+            Some(order_direction_match) => {
+                let order_by_column = &column[..order_direction_match.start()];
+                validate_where_column(order_by_column)?;
+
+                let order_by_direction = &column[order_direction_match.start()..];
+                (order_by_column, order_by_direction)
+            }
+            None => {
+                validate_where_column(column)?;
+                (column, " asc")
+            }
+        }
+    } else {
+        validate_where_column(column)?;
+        (column, " asc")
+    };
+
+    Ok((sql_column, order_by_direction == " desc"))
+}
+
+/// Generates the columns of an ORDER BY clause (like `name ASC, id DESC`). Foreign keys are
+/// correctly accounted for.
+pub(crate) fn get_order_by_str<'a>(
+    order_by_columns: &'a [String],
+    table: &'a str,
+    fks: &'a [ForeignKeyReference],
+) -> Result<Vec<&'a str>, Error> {
+    let mut statement: Vec<&str> = vec![];
+
+    for (i, column) in order_by_columns.iter().enumerate() {
+        let (sql_column, is_descending) = split_order_by_column(column)?;
+
+        if let (true, Some((fk_ref, fk_column))) = (
+            !fks.is_empty(),
+            ForeignKeyReference::find(fks, table, sql_column),
+        ) {
+            statement.push(fk_ref.foreign_key_table.as_str());
+            statement.push(".");
+            statement.push(fk_column);
+        } else {
+            statement.push(sql_column);
+        }
+
+        statement.push(if is_descending { " DESC" } else { " ASC" });
+
+        if i < order_by_columns.len() - 1 {
+            statement.push(", ");
+        }
+    }
+
+    Ok(statement)
+}
+
+/// Splits a table name into its schema and unqualified table name. Table names without a schema
+/// prefix (`schema.table`) are in the `public` schema.
+pub fn split_table_name(name: &str) -> (&str, &str) {
+    match name.find('.') {
+        Some(i) => (&name[..i], &name[i + 1..]),
+        None => ("public", name),
+    }
+}
+
 /// Checks a table name and returns true if it is valid (false otherwise).
 /// The identifier must start with a lower-case letter or underscore, and only contain
 /// alphanumeric or underscore characters. (Sorry, I don’t have time or energy for UTF-8
-/// shenanigans)
+/// shenanigans). The table name may be prefixed with a schema name that follows the same rules
+/// (`schema.table`).
 pub fn validate_table_name(name: &str) -> Result<(), Error> {
     // Using lazy_static so that VALID_REGEX is only compiled once total (versus compiling the regex
     // every time this function is called)
     lazy_static! {
-        static ref VALID_REGEX: Regex = Regex::new(r"^[a-z_][a-z0-9_]*$").unwrap();
+        static ref VALID_REGEX: Regex =
+            Regex::new(r"^([a-z_][a-z0-9_]*\.)?[a-z_][a-z0-9_]*$").unwrap();
     }
 
-    if name == "table" {
+    let (schema, table) = split_table_name(name);
+    if schema == "table" || table == "table" {
         return Err(Error::generate_error(
             "SQL_IDENTIFIER_KEYWORD",
             name.to_string(),
@@ -378,19 +555,23 @@ mod get_db_column_str_tests {
             referring_table: "child".to_string(),
             referring_column: "parent_id".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "adult".to_string(),
             foreign_key_table_stats: vec![],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![ForeignKeyReference {
                 original_refs: vec!["company_id.name".to_string()],
                 referring_table: "adult".to_string(),
                 referring_column: "company_id".to_string(),
                 referring_column_type: "int8",
+                referring_column_is_nullable: false,
                 foreign_key_table: "company".to_string(),
                 foreign_key_table_stats: vec![],
                 foreign_key_column: "id".to_string(),
                 foreign_key_column_type: "int8",
+                additional_columns: vec![],
                 nested_fks: vec![],
             }],
         }];
@@ -410,19 +591,23 @@ mod get_db_column_str_tests {
             referring_table: "child".to_string(),
             referring_column: "parent_id".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "adult".to_string(),
             foreign_key_table_stats: vec![],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![ForeignKeyReference {
                 original_refs: vec!["company_id.name".to_string()],
                 referring_table: "adult".to_string(),
                 referring_column: "company_id".to_string(),
                 referring_column_type: "int8",
+                referring_column_is_nullable: false,
                 foreign_key_table: "company".to_string(),
                 foreign_key_table_stats: vec![],
                 foreign_key_column: "id".to_string(),
                 foreign_key_column_type: "int8",
+                additional_columns: vec![],
                 nested_fks: vec![],
             }],
         }];
@@ -442,19 +627,23 @@ mod get_db_column_str_tests {
             referring_table: "child".to_string(),
             referring_column: "parent_id".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "adult".to_string(),
             foreign_key_table_stats: vec![],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![ForeignKeyReference {
                 original_refs: vec!["company_id.name".to_string()],
                 referring_table: "adult".to_string(),
                 referring_column: "company_id".to_string(),
                 referring_column_type: "int8",
+                referring_column_is_nullable: false,
                 foreign_key_table: "company".to_string(),
                 foreign_key_table_stats: vec![],
                 foreign_key_column: "id".to_string(),
                 foreign_key_column_type: "int8",
+                additional_columns: vec![],
                 nested_fks: vec![],
             }],
         }];
@@ -474,19 +663,23 @@ mod get_db_column_str_tests {
             referring_table: "child".to_string(),
             referring_column: "parent_id".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "adult".to_string(),
             foreign_key_table_stats: vec![],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![ForeignKeyReference {
                 original_refs: vec!["company_id.name".to_string()],
                 referring_table: "adult".to_string(),
                 referring_column: "company_id".to_string(),
                 referring_column_type: "int8",
+                referring_column_is_nullable: false,
                 foreign_key_table: "company".to_string(),
                 foreign_key_table_stats: vec![],
                 foreign_key_column: "id".to_string(),
                 foreign_key_column_type: "int8",
+                additional_columns: vec![],
                 nested_fks: vec![],
             }],
         }];
@@ -506,19 +699,23 @@ mod get_db_column_str_tests {
             referring_table: "child".to_string(),
             referring_column: "parent_id".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "adult".to_string(),
             foreign_key_table_stats: vec![],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![ForeignKeyReference {
                 original_refs: vec!["company_id.name".to_string()],
                 referring_table: "adult".to_string(),
                 referring_column: "company_id".to_string(),
                 referring_column_type: "int8",
+                referring_column_is_nullable: false,
                 foreign_key_table: "company".to_string(),
                 foreign_key_table_stats: vec![],
                 foreign_key_column: "id".to_string(),
                 foreign_key_column_type: "int8",
+                additional_columns: vec![],
                 nested_fks: vec![],
             }],
         }];
@@ -538,19 +735,23 @@ mod get_db_column_str_tests {
             referring_table: "child".to_string(),
             referring_column: "parent_id".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "adult".to_string(),
             foreign_key_table_stats: vec![],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![ForeignKeyReference {
                 original_refs: vec!["company_id.name".to_string()],
                 referring_table: "adult".to_string(),
                 referring_column: "company_id".to_string(),
                 referring_column_type: "int8",
+                referring_column_is_nullable: false,
                 foreign_key_table: "company".to_string(),
                 foreign_key_table_stats: vec![],
                 foreign_key_column: "id".to_string(),
                 foreign_key_column_type: "int8",
+                additional_columns: vec![],
                 nested_fks: vec![],
             }],
         }];
@@ -576,19 +777,23 @@ mod get_column_str_tests {
             referring_table: "child".to_string(),
             referring_column: "parent_id".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "adult".to_string(),
             foreign_key_table_stats: vec![],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![ForeignKeyReference {
                 original_refs: vec!["company_id.name".to_string()],
                 referring_table: "adult".to_string(),
                 referring_column: "company_id".to_string(),
                 referring_column_type: "int8",
+                referring_column_is_nullable: false,
                 foreign_key_table: "company".to_string(),
                 foreign_key_table_stats: vec![],
                 foreign_key_column: "id".to_string(),
                 foreign_key_column_type: "int8",
+                additional_columns: vec![],
                 nested_fks: vec![],
             }],
         }];
@@ -615,19 +820,23 @@ mod get_column_str_tests {
             referring_table: "child".to_string(),
             referring_column: "parent_id".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "adult".to_string(),
             foreign_key_table_stats: vec![],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![ForeignKeyReference {
                 original_refs: vec!["company_id.name".to_string()],
                 referring_table: "adult".to_string(),
                 referring_column: "company_id".to_string(),
                 referring_column_type: "int8",
+                referring_column_is_nullable: false,
                 foreign_key_table: "company".to_string(),
                 foreign_key_table_stats: vec![],
                 foreign_key_column: "id".to_string(),
                 foreign_key_column_type: "int8",
+                additional_columns: vec![],
                 nested_fks: vec![],
             }],
         }];
@@ -651,19 +860,23 @@ mod get_column_str_tests {
             referring_table: "child".to_string(),
             referring_column: "parent_id".to_string(),
             referring_column_type: "int8",
+            referring_column_is_nullable: false,
             foreign_key_table: "adult".to_string(),
             foreign_key_table_stats: vec![],
             foreign_key_column: "id".to_string(),
             foreign_key_column_type: "int8",
+            additional_columns: vec![],
             nested_fks: vec![ForeignKeyReference {
                 original_refs: vec!["company_id.name".to_string()],
                 referring_table: "adult".to_string(),
                 referring_column: "company_id".to_string(),
                 referring_column_type: "int8",
+                referring_column_is_nullable: false,
                 foreign_key_table: "company".to_string(),
                 foreign_key_table_stats: vec![],
                 foreign_key_column: "id".to_string(),
                 foreign_key_column_type: "int8",
+                additional_columns: vec![],
                 nested_fks: vec![],
             }],
         }];
@@ -724,6 +937,38 @@ mod validate_table_name_tests {
         assert!(validate_table_name("\n").is_err());
         assert!(validate_table_name("\t").is_err());
     }
+
+    #[test]
+    fn schema_prefix() {
+        assert!(validate_table_name("billing.invoice").is_ok());
+        assert!(validate_table_name("_billing._invoice").is_ok());
+    }
+
+    #[test]
+    fn invalid_schema_prefix() {
+        assert!(validate_table_name("table.invoice").is_err());
+        assert!(validate_table_name("billing.table").is_err());
+        assert!(validate_table_name("Billing.invoice").is_err());
+        assert!(validate_table_name("a.billing.invoice").is_err());
+        assert!(validate_table_name(".invoice").is_err());
+        assert!(validate_table_name("billing.").is_err());
+    }
+}
+
+#[cfg(test)]
+mod split_table_name_tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn unqualified() {
+        assert_eq!(split_table_name("invoice"), ("public", "invoice"));
+    }
+
+    #[test]
+    fn qualified() {
+        assert_eq!(split_table_name("billing.invoice"), ("billing", "invoice"));
+    }
 }
 
 #[cfg(test)]
@@ -823,6 +1068,50 @@ mod conditions_params_to_ast_tests {
         assert_eq!(conditions_params_to_ast(&Some(clause)).unwrap(), expected);
     }
 
+    #[test]
+    fn range_operators() {
+        let clause = "during @> '2019-01-01' AND span && '[1,5)'".to_string();
+        let ast = conditions_params_to_ast(&Some(clause)).unwrap();
+
+        assert_eq!(
+            ast.to_string(),
+            "during = __op_contains__ = '2019-01-01' AND span = __op_overlaps__ = '[1,5)'"
+        );
+        assert_eq!(
+            replace_placeholders_with_operators(ast.to_string()),
+            "during @> '2019-01-01' AND span && '[1,5)'"
+        );
+    }
+
+    #[test]
+    fn operators_in_quotes_are_not_replaced() {
+        let clause = "name = 'a <@ b' OR \"x&&y\" <@ '[1,2]'".to_string();
+        assert_eq!(
+            replace_operators_with_placeholders(&clause),
+            "name = 'a <@ b' OR \"x&&y\"  = __op_contained_by__ =  '[1,2]'"
+        );
+    }
+
+    #[test]
+    fn hstore_operators() {
+        let clause = "tags ? 'color' AND tags @> 'color=>red'".to_string();
+        let ast = conditions_params_to_ast(&Some(clause)).unwrap();
+
+        assert_eq!(
+            replace_placeholders_with_operators(ast.to_string()),
+            "tags ? 'color' AND tags @> 'color=>red'"
+        );
+    }
+
+    #[test]
+    fn bit_string_prefixes_are_removed() {
+        let clause = "flags = B'0101' OR sub='b' OR \"b\"=b'1'".to_string();
+        assert_eq!(
+            replace_operators_with_placeholders(&clause),
+            "flags = '0101' OR sub='b' OR \"b\"='1'"
+        );
+    }
+
     #[test]
     fn empty_string_returns_error() {
         let clause = "".to_string();
@@ -1,6 +1,6 @@
 use crate::{
     queries::{select_all_table_stats, select_all_tables, TableStats},
-    Config, Error,
+    Config, Error, PooledClient,
 };
 use actix::{spawn, Actor, Addr, Context, Handler, Message, ResponseFuture};
 use clokwerk::{Scheduler, TimeUnits};
@@ -20,7 +20,7 @@ use std::{
     time::{Duration, Instant},
 };
 use tokio::timer::Interval;
-use tokio_postgres::{tls::MakeTlsConnect, Client, Socket};
+use tokio_postgres::{tls::MakeTlsConnect, Socket};
 
 #[derive(Debug)]
 pub(crate) enum StatsCacheMessage {
@@ -44,6 +44,8 @@ pub(crate) struct StatsCache {
     cache: Arc<RwLock<Option<HashMap<String, TableStats>>>>,
     /// Whether the cache is currently being fetched/reset.
     is_fetching: Arc<AtomicBool>,
+    /// The schemas whose tables are cached.
+    schemas: Vec<String>,
 }
 
 impl Actor for StatsCache {
@@ -89,10 +91,11 @@ impl Handler<StatsCacheMessage> for StatsCache {
 
 impl StatsCache {
     /// Creates a new instance of `StatsCache`.
-    pub fn new() -> Self {
+    pub fn new(schemas: Vec<String>) -> Self {
         StatsCache {
             cache: Arc::new(RwLock::new(None)),
             is_fetching: Arc::new(AtomicBool::new(false)),
+            schemas,
         }
     }
 
@@ -122,7 +125,7 @@ impl StatsCache {
             // will not get to this point.
             let mut persistent_db_conn_opt = PERSISTENT_DB_CONNECTION.lock().unwrap();
             let client = persistent_db_conn_opt.take().unwrap();
-            let f = select_all_tables(client)
+            let f = select_all_tables(client, &self.schemas)
                 .and_then(|(tables, client)| {
                     select_all_table_stats(client, tables).boxed().compat()
                 })
@@ -152,8 +155,8 @@ lazy_static! {
     static ref IS_STATS_CACHE_INIT: AtomicBool = AtomicBool::new(false);
     /// Stores the actor address containing the cache data.
     static ref STATS_CACHE_MUTEX: Mutex<Option<Addr<StatsCache>>> = Mutex::new(None);
-    /// Active connection to Postgres.
-    static ref PERSISTENT_DB_CONNECTION: Mutex<Option<Client>> = Mutex::new(None);
+    /// Connection to Postgres that stays checked out of the pool for the lifetime of the cache.
+    static ref PERSISTENT_DB_CONNECTION: Mutex<Option<PooledClient>> = Mutex::new(None);
 }
 
 // todo, replace config.stats_cache_addr with a static variable + function here
@@ -170,6 +173,7 @@ pub fn initialize_stats_cache<T: MakeTlsConnect<Socket> + Clone + Send + Sync>(
         //     config.stats_cache_addr = Some(static_addr_opt.as_ref().unwrap().clone());
         // } else {
         let cache_reset_interval_seconds = config.cache_reset_interval_seconds;
+        let schemas = config.schemas.clone();
         INIT_STATS_CACHE.call_once(|| {
             spawn(
                 config
@@ -180,7 +184,7 @@ pub fn initialize_stats_cache<T: MakeTlsConnect<Socket> + Clone + Send + Sync>(
                         let mut persistent_db_conn_opt = PERSISTENT_DB_CONNECTION.lock().unwrap();
                         persistent_db_conn_opt.get_or_insert(client);
 
-                        let addr = StatsCache::new().start();
+                        let addr = StatsCache::new(schemas).start();
                         let addr_clone = addr.clone();
 
                         static_addr_opt.get_or_insert(addr);